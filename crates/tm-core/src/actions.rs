@@ -1,20 +1,149 @@
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
-    MoveDown,          // j / Down
-    MoveUp,            // k / Up
-    HalfPageDown,      // Ctrl-d
-    HalfPageUp,        // Ctrl-u
-    GoTop,             // gg
-    GoBottom,          // G
-    FocusFilter,       // /
-    Quit,              // q  (TUI only, GUI ignores)
+    MoveDown,     // j / Down
+    MoveUp,       // k / Up
+    HalfPageDown, // Ctrl-d
+    HalfPageUp,   // Ctrl-u
+    GoTop,        // gg
+    GoBottom,     // G
+    ScrollTop,    // zt  (keep selection, scroll it to the top)
+    ScrollCenter, // zz
+    ScrollBottom, // zb
+    FocusFilter,  // /
+    EnterCommand, // :
+    Quit,         // q  (TUI only, GUI ignores)
 
     // Status management
-    StatusNext,    // cycle forward
-    StatusPrev,    // cycle backward
-    SetTodo,       // force todo
-    SetDoing,      // force in-progress
-    SetDone,       // force done
+    StatusNext,   // cycle forward
+    StatusPrev,   // cycle backward
+    SetTodo,      // force the first open status (todo)
+    SetDoing,     // force the first active status (doing)
+    SetDone,      // force the first closed status (done)
+    SetCancelled, // X: cancelled
 
+    // Projects
+    PickProject, // O
+    NextProject, // ]
+    PrevProject, // [
+    NewProject,  // P
+
+    // Views
+    NextView,    // gt
+    PrevView,    // gT
+    SortNext,    // o   (lead with the next sort key)
+    SortReverse, // ~   (flip the leading sort key)
+    ToggleFold,  // za  (collapse / expand the group under the cursor)
+    OpenFolds,   // zR
+    CloseFolds,  // zM
+
+    // Edit panels
+    EditDue,   // D
+    EditTitle, // R
+    EditTags,  // T
+    Snooze,    // s  (defer: 1d, mon, next week)
+
+    OpenPalette,  // <leader>p
+    QuickActions, // <leader>a
+    OpenInEditor, // E  ($EDITOR on the task file)
+    ToggleTimer,  // t  (start / stop tracking time)
+
+    // Checklist in the detail pane
+    FocusDetail, // Tab   (j/k move through the checklist; Tab again goes back to the list)
+    ToggleCheck, // Space (tick the checklist item under the cursor; 3 Space: the third)
+
+    // Inputs (insert / command / search / picker modes)
+    CursorLeft,      // Left / Ctrl-b
+    CursorRight,     // Right / Ctrl-f
+    CursorWordLeft,  // Alt-b
+    CursorWordRight, // Alt-f
+    CursorStart,     // Home / Ctrl-a
+    CursorEnd,       // End / Ctrl-e
+    DeleteBack,      // Backspace
+    DeleteForward,   // Delete
+    DeleteWordBack,  // Ctrl-w
+    DeleteToStart,   // Ctrl-u
+    DeleteToEnd,     // Ctrl-k
+    HistoryPrev,     // Up
+    HistoryNext,     // Down
+    Complete,        // Tab (accept completion)
+    Submit,          // Enter
+    Cancel,          // Esc
 }
 
+/// Config names (`keymaps.<mode>` values) for every action
+const NAMES: &[(&str, Action)] = {
+    use Action::*;
+    &[
+        ("move_down", MoveDown),
+        ("move_up", MoveUp),
+        ("half_page_down", HalfPageDown),
+        ("half_page_up", HalfPageUp),
+        ("go_top", GoTop),
+        ("go_bottom", GoBottom),
+        ("scroll_top", ScrollTop),
+        ("scroll_center", ScrollCenter),
+        ("scroll_bottom", ScrollBottom),
+        ("focus_filter", FocusFilter),
+        ("enter_command", EnterCommand),
+        ("quit", Quit),
+        ("status_next", StatusNext),
+        ("status_prev", StatusPrev),
+        ("set_todo", SetTodo),
+        ("set_doing", SetDoing),
+        ("set_done", SetDone),
+        ("set_cancelled", SetCancelled),
+        ("pick_project", PickProject),
+        ("next_project", NextProject),
+        ("prev_project", PrevProject),
+        ("new_project", NewProject),
+        ("next_view", NextView),
+        ("prev_view", PrevView),
+        ("sort_next", SortNext),
+        ("sort_reverse", SortReverse),
+        ("toggle_fold", ToggleFold),
+        ("open_folds", OpenFolds),
+        ("close_folds", CloseFolds),
+        ("edit_due", EditDue),
+        ("edit_title", EditTitle),
+        ("edit_tags", EditTags),
+        ("snooze", Snooze),
+        ("open_palette", OpenPalette),
+        ("quick_actions", QuickActions),
+        ("open_in_editor", OpenInEditor),
+        ("toggle_timer", ToggleTimer),
+        ("focus_detail", FocusDetail),
+        ("toggle_check", ToggleCheck),
+        ("cursor_left", CursorLeft),
+        ("cursor_right", CursorRight),
+        ("cursor_word_left", CursorWordLeft),
+        ("cursor_word_right", CursorWordRight),
+        ("cursor_start", CursorStart),
+        ("cursor_end", CursorEnd),
+        ("delete_back", DeleteBack),
+        ("delete_forward", DeleteForward),
+        ("delete_word_back", DeleteWordBack),
+        ("delete_to_start", DeleteToStart),
+        ("delete_to_end", DeleteToEnd),
+        ("history_prev", HistoryPrev),
+        ("history_next", HistoryNext),
+        ("complete", Complete),
+        ("submit", Submit),
+        ("cancel", Cancel),
+    ]
+};
+
+impl Action {
+    /// Parse a config name like `move_down`
+    pub fn from_name(s: &str) -> Option<Action> {
+        NAMES.iter().find(|(n, _)| *n == s).map(|(_, a)| *a)
+    }
+
+    /// The config name of this action
+    pub fn name(self) -> &'static str {
+        NAMES
+            .iter()
+            .find(|(_, a)| *a == self)
+            .map(|(n, _)| *n)
+            .unwrap_or("")
+    }
+}
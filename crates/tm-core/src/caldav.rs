@@ -0,0 +1,954 @@
+//! CalDAV (RFC 4791): `tm sync caldav` keeps projects and server calendars (Nextcloud,
+//! Radicale, Fastmail, ...) in step both ways, one VTODO per task:
+//!
+//! ```lua
+//! caldav = {
+//!   url = 'https://cloud.example.com/remote.php/dav/calendars/alice/',
+//!   user = 'alice',                 -- the password comes from $TM_CALDAV_PASSWORD
+//!   calendars = { work = 'work-tasks', home = 'https://dav.example.org/home/' },
+//!   conflict = 'newest-wins',       -- or local-wins / remote-wins
+//! },
+//! ```
+//!
+//! `calendars` maps project keys to calendars: a collection name under `url`, or a full URL.
+//! `.tm/caldav.json` keeps each task's href, ETag and `updated` from the last sync, so only
+//! objects whose ETag changed are fetched and only tasks edited since are uploaded. Uploads
+//! are conditional (`If-Match`): an object changed on the server in the meantime is left for
+//! the next sync rather than overwritten. A task changed on both sides goes by `conflict`
+//! (newest compares `updated` with LAST-MODIFIED); one deleted on the server is closed here,
+//! one deleted here is deleted there.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{
+    date,
+    export::{self, Conflict, TaskRecord},
+    ics::{self, Prop},
+    sync::SyncReport,
+    track, Category, Frontmatter, Vault, Workflow,
+};
+
+/// The default variable holding the password
+pub const ENV_PASSWORD: &str = "TM_CALDAV_PASSWORD";
+
+/// `caldav = { url, user, password_env, calendars, conflict }`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalDav {
+    /// the calendar home, which calendar names are resolved against
+    pub url: String,
+    pub user: String,
+    /// the environment variable holding the password (default `TM_CALDAV_PASSWORD`)
+    pub password_env: String,
+    /// project key → calendar name under `url`, or a calendar URL
+    pub calendars: BTreeMap<String, String>,
+    /// `newest-wins` (default), `local-wins` or `remote-wins`
+    pub conflict: String,
+}
+
+impl CalDav {
+    /// A calendar's collection URL, with the trailing slash
+    fn collection(&self, calendar: &str) -> String {
+        let url = if calendar.contains("://") {
+            calendar.to_string()
+        } else {
+            format!(
+                "{}/{}",
+                self.url.trim_end_matches('/'),
+                calendar.trim_matches('/')
+            )
+        };
+        format!("{}/", url.trim_end_matches('/'))
+    }
+}
+
+/// What `.tm/caldav.json` keeps per task id
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Synced {
+    /// the project it was synced under
+    project: String,
+    /// empty once the object was deleted on the server
+    href: String,
+    etag: String,
+    uid: String,
+    /// the task's `updated` as of the sync
+    updated: Option<String>,
+}
+
+/// One `<response>` of a multistatus
+struct Object {
+    href: String,
+    etag: String,
+    data: String,
+}
+
+enum Put {
+    Done(Option<String>),
+    /// the object changed on the server since `If-Match`'s ETag
+    Conflict,
+}
+
+const PROPFIND: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getetag/><d:resourcetype/></d:prop></d:propfind>"#;
+
+/// A WebDAV client for the few requests a sync needs
+struct Client {
+    agent: ureq::Agent,
+    auth: Option<String>,
+}
+
+fn http_error(method: &str, url: &str, e: ureq::Error) -> anyhow::Error {
+    match e {
+        ureq::Error::Status(code, resp) => {
+            anyhow!("caldav: {method} {url}: {code} {}", resp.status_text())
+        }
+        e => anyhow!("caldav: {method} {url}: {e}"),
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// `https://host:port` of a URL
+fn origin(url: &str) -> &str {
+    let start = url.find("://").map_or(0, |i| i + 3);
+    url[start..].find('/').map_or(url, |i| &url[..start + i])
+}
+
+/// An href (usually an absolute path) as a URL
+fn resolve(base: &str, href: &str) -> String {
+    if href.contains("://") {
+        href.to_string()
+    } else {
+        format!("{}{href}", origin(base))
+    }
+}
+
+/// The `<response>`s of a multistatus body; namespaces are ignored, missing objects skipped
+fn responses(xml: &str) -> Result<Vec<Object>> {
+    let doc = roxmltree::Document::parse(xml).context("caldav: the server sent malformed XML")?;
+    let text = |n: roxmltree::Node, name: &str| {
+        n.descendants()
+            .find(|d| d.tag_name().name() == name)
+            .and_then(|d| d.text())
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+    Ok(doc
+        .descendants()
+        .filter(|n| n.tag_name().name() == "response")
+        .filter(|n| {
+            !n.children()
+                .any(|c| c.tag_name().name() == "status" && text(c, "status").contains(" 404"))
+        })
+        .map(|n| Object {
+            href: text(n, "href"),
+            etag: text(n, "getetag"),
+            data: text(n, "calendar-data"),
+        })
+        .collect())
+}
+
+impl Client {
+    fn new(cfg: &CalDav) -> Result<Self> {
+        let auth = if cfg.user.is_empty() {
+            None
+        } else {
+            let var = if cfg.password_env.is_empty() {
+                ENV_PASSWORD
+            } else {
+                &cfg.password_env
+            };
+            let password = env::var(var)
+                .ok()
+                .with_context(|| format!("caldav: set ${var} to the password of {}", cfg.user))?;
+            let token = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{password}", cfg.user));
+            Some(format!("Basic {token}"))
+        };
+        Ok(Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+            auth,
+        })
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let req = self.agent.request(method, url);
+        match &self.auth {
+            Some(auth) => req.set("Authorization", auth),
+            None => req,
+        }
+    }
+
+    fn xml(&self, method: &str, url: &str, body: &str) -> Result<Vec<Object>> {
+        let resp = self
+            .request(method, url)
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(body)
+            .map_err(|e| http_error(method, url, e))?;
+        responses(&resp.into_string()?)
+    }
+
+    /// href → ETag of each object in a calendar
+    fn etags(&self, coll: &str) -> Result<BTreeMap<String, String>> {
+        Ok(self
+            .xml("PROPFIND", coll, PROPFIND)?
+            .into_iter()
+            .filter(|o| !o.href.ends_with('/'))
+            .map(|o| (o.href, o.etag))
+            .collect())
+    }
+
+    /// The given objects, with their data
+    fn multiget(&self, coll: &str, hrefs: &[String]) -> Result<Vec<Object>> {
+        if hrefs.is_empty() {
+            return Ok(vec![]);
+        }
+        let hrefs: String = hrefs
+            .iter()
+            .map(|h| format!("<d:href>{}</d:href>", xml_escape(h)))
+            .collect();
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop><d:getetag/><c:calendar-data/></d:prop>{hrefs}</c:calendar-multiget>"#
+        );
+        self.xml("REPORT", coll, &body)
+    }
+
+    /// Create (no `etag`) or replace an object
+    fn put(&self, url: &str, data: &str, etag: Option<&str>) -> Result<Put> {
+        let req = self
+            .request("PUT", url)
+            .set("Content-Type", "text/calendar; charset=utf-8");
+        let req = match etag {
+            Some(e) => req.set("If-Match", e),
+            None => req.set("If-None-Match", "*"),
+        };
+        match req.send_string(data) {
+            Ok(resp) => Ok(Put::Done(resp.header("ETag").map(str::to_string))),
+            Err(ureq::Error::Status(412, _)) => Ok(Put::Conflict),
+            Err(e) => Err(http_error("PUT", url, e)),
+        }
+    }
+
+    /// False when the object changed on the server since `etag`
+    fn delete(&self, url: &str, etag: &str) -> Result<bool> {
+        let req = self.request("DELETE", url);
+        let req = if etag.is_empty() {
+            req
+        } else {
+            req.set("If-Match", etag)
+        };
+        match req.call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(true),
+            Err(ureq::Error::Status(412, _)) => Ok(false),
+            Err(e) => Err(http_error("DELETE", url, e)),
+        }
+    }
+}
+
+fn value<'a>(todo: &'a [Prop], name: &str) -> Option<&'a str> {
+    todo.iter()
+        .find(|p| p.name == name)
+        .map(|p| p.value.as_str())
+}
+
+/// `a,b\,c` → `["a", "b,c"]`
+fn split_list(value: &str) -> Vec<String> {
+    let mut out = vec![String::new()];
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            ',' if !escaped => out.push(String::new()),
+            _ => out.last_mut().unwrap().push(c),
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    out.iter()
+        .map(|s| ics::unescape(s.trim()))
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// A `RELATED-TO` UID pointing at a task (`<ULID>@tm`)
+fn related_id(uid: &str) -> Option<String> {
+    uid.strip_suffix("@tm")
+        .filter(|id| Ulid::from_string(id).is_ok())
+        .map(str::to_string)
+}
+
+/// Put what a VTODO says into `fm` / `body`; fields it doesn't carry stay as they are
+fn apply(todo: &[Prop], fm: &mut Frontmatter, body: &mut String, wf: &Workflow) -> Result<()> {
+    let day = |name: &str| value(todo, name).and_then(ics::parse_ics_date);
+    if let Some(s) = value(todo, "SUMMARY") {
+        fm.title = ics::unescape(s);
+    }
+    if fm.key.is_empty() {
+        fm.key = slug::slugify(&fm.title);
+    }
+    let text = value(todo, "DESCRIPTION")
+        .map(ics::unescape)
+        .unwrap_or_default();
+    let text = text.trim_matches('\n');
+    if text != body.trim_matches('\n') {
+        *body = if text.is_empty() {
+            String::new()
+        } else {
+            format!("\n{text}\n")
+        };
+    }
+
+    let due = day("DUE");
+    fm.due = date::same_day(&fm.due, due);
+    // tm writes the due date as DTSTART for an RRULE, and leaves out a start after the due date
+    let start = day("DTSTART").filter(|s| value(todo, "RRULE").is_none() || Some(*s) != due);
+    let late_start = |old: &Option<String>| {
+        old.as_deref()
+            .and_then(date::parse_date)
+            .zip(due)
+            .is_some_and(|(s, d)| s > d)
+    };
+    if start.is_some() || !late_start(&fm.start) {
+        fm.start = date::same_day(&fm.start, start);
+    }
+
+    let status = value(todo, "STATUS")
+        .unwrap_or("NEEDS-ACTION")
+        .to_uppercase();
+    if ics::vtodo_status(&fm.status, wf) != status {
+        let was_closed = wf.category(&fm.status) == Some(Category::Closed);
+        fm.status = match status.as_str() {
+            "CANCELLED" => wf.cancel_status()?.to_string(),
+            "COMPLETED" => wf.first(Category::Closed)?.to_string(),
+            "IN-PROCESS" => wf.first(Category::Active)?.to_string(),
+            _ => wf.first(Category::Open)?.to_string(),
+        };
+        match wf.category(&fm.status) {
+            Some(Category::Closed) if !was_closed => {
+                track::stop(&mut fm.time);
+                fm.done_at = value(todo, "COMPLETED")
+                    .and_then(ics::parse_ics_time)
+                    .or_else(|| Some(track::now_rfc3339()));
+            }
+            Some(Category::Closed) => {}
+            _ => fm.done_at = None,
+        }
+    }
+
+    fm.priority = match value(todo, "PRIORITY").and_then(|p| p.trim().parse::<u8>().ok()) {
+        Some(1..=4) => "high",
+        Some(5) => "med",
+        Some(6..=9) => "low",
+        _ => "none",
+    }
+    .into();
+    let tags: Vec<String> = todo
+        .iter()
+        .filter(|p| p.name == "CATEGORIES")
+        .flat_map(|p| split_list(&p.value))
+        .collect();
+    if tags.iter().collect::<BTreeSet<_>>() != fm.tags.iter().collect::<BTreeSet<_>>() {
+        fm.tags = tags;
+    }
+    let related = |kind: &str| -> Vec<String> {
+        todo.iter()
+            .filter(|p| p.name == "RELATED-TO")
+            .filter(|p| {
+                p.params
+                    .iter()
+                    .find(|(k, _)| k == "RELTYPE")
+                    .map_or("PARENT", |(_, v)| v.as_str())
+                    .eq_ignore_ascii_case(kind)
+            })
+            .filter_map(|p| related_id(&p.value))
+            .collect()
+    };
+    fm.parent = related("PARENT").into_iter().next();
+    fm.depends_on = related("DEPENDS-ON");
+    Ok(())
+}
+
+impl Vault {
+    /// Two-way sync of the mapped projects with their CalDAV calendars. The state is saved
+    /// after each calendar is pulled and after each object is pushed or deleted, so a sync
+    /// that fails part way picks up where it stopped.
+    pub fn sync_caldav(&self) -> Result<SyncReport> {
+        let cfg = self.cfg.caldav.as_ref().context(
+            "caldav: not configured (add `caldav = { url, user, calendars }` to config.lua)",
+        )?;
+        let policy = if cfg.conflict.is_empty() {
+            Conflict::Newest
+        } else {
+            Conflict::parse(&cfg.conflict)?
+        };
+        let client = Client::new(cfg)?;
+        let wf = &self.cfg.workflow;
+        let mut state: BTreeMap<String, Synced> = self.load_sync_state("caldav")?;
+        let mut local = self.records_by_id()?;
+        let mut report = SyncReport::default();
+
+        for (project, calendar) in &cfg.calendars {
+            let mut pulled: Vec<TaskRecord> = vec![];
+            let coll = cfg.collection(calendar);
+            let remote = client.etags(&coll)?;
+            let known: HashMap<String, String> = state
+                .iter()
+                .filter(|(_, s)| s.project == *project)
+                .map(|(id, s)| (s.href.clone(), id.clone()))
+                .collect();
+            let changed: Vec<String> = remote
+                .iter()
+                .filter(|(href, etag)| known.get(*href).is_none_or(|id| state[id].etag != **etag))
+                .map(|(href, _)| href.clone())
+                .collect();
+            for obj in client.multiget(&coll, &changed)? {
+                // events and journals in the same calendar aren't tasks
+                let Some(todo) = ics::components(&obj.data, "VTODO").into_iter().next() else {
+                    continue;
+                };
+                let uid = value(&todo, "UID").unwrap_or_default().to_string();
+                let id = known
+                    .get(&obj.href)
+                    .cloned()
+                    .or_else(|| related_id(&uid))
+                    .unwrap_or_else(|| Ulid::new().to_string());
+                let current = local.get(&id);
+                let last = state.get(&id);
+                if let Some(r) =
+                    current.filter(|r| last.is_none_or(|s| s.updated != r.frontmatter.updated))
+                {
+                    let theirs = value(&todo, "LAST-MODIFIED").and_then(ics::parse_ics_time);
+                    if !export::incoming_wins(
+                        policy,
+                        r.frontmatter.updated.as_deref(),
+                        theirs.as_deref(),
+                    ) {
+                        report
+                            .conflicts
+                            .push(format!("{}: kept the local version", r.frontmatter.title));
+                        // no `updated`, so it is uploaded over the server's version below
+                        state.insert(
+                            id,
+                            Synced {
+                                project: project.clone(),
+                                href: obj.href,
+                                etag: obj.etag,
+                                uid,
+                                updated: None,
+                            },
+                        );
+                        continue;
+                    }
+                    report.conflicts.push(format!(
+                        "{}: took the server's version",
+                        r.frontmatter.title
+                    ));
+                }
+                let (mut fm, mut body) = match current {
+                    Some(r) => (r.frontmatter.clone(), r.body.clone()),
+                    None => (export::new_task(id.clone(), &self.cfg)?, String::new()),
+                };
+                let before = (fm.clone(), body.clone());
+                apply(&todo, &mut fm, &mut body, wf)?;
+                fm.project = project.clone();
+                if (&fm, &body) != (&before.0, &before.1) || current.is_none() {
+                    fm.updated = Some(track::now_rfc3339());
+                    report.pulled.push(fm.title.clone());
+                    let record = TaskRecord {
+                        frontmatter: fm.clone(),
+                        body,
+                    };
+                    local.insert(id.clone(), record.clone());
+                    pulled.push(record);
+                }
+                state.insert(
+                    id,
+                    Synced {
+                        project: project.clone(),
+                        href: obj.href,
+                        etag: obj.etag,
+                        uid,
+                        updated: fm.updated,
+                    },
+                );
+            }
+
+            // deleted on the server: close the task, and keep it off the server until it is edited again
+            for (id, s) in state.iter_mut().filter(|(_, s)| {
+                s.project == *project && !s.href.is_empty() && !remote.contains_key(&s.href)
+            }) {
+                s.href.clear();
+                s.etag.clear();
+                let Some(r) = local.get_mut(id) else { continue };
+                let edited = s.updated != r.frontmatter.updated;
+                if edited && policy == Conflict::Skip {
+                    report.conflicts.push(format!(
+                        "{}: deleted on the server, uploaded again",
+                        r.frontmatter.title
+                    ));
+                    continue;
+                }
+                let fm = &mut r.frontmatter;
+                if wf.category(&fm.status) != Some(Category::Closed) && fm.project == *project {
+                    fm.status = wf.cancel_status()?.to_string();
+                    track::stop(&mut fm.time);
+                    fm.done_at = Some(track::now_rfc3339());
+                    fm.updated = fm.done_at.clone();
+                    report.closed.push(fm.title.clone());
+                    pulled.push(r.clone());
+                }
+                s.updated = r.frontmatter.updated.clone();
+            }
+            // the calendar's changes are in the vault before its state says so
+            if !pulled.is_empty() {
+                self.import_json(&export::as_export(pulled), Conflict::Overwrite, false)?;
+            }
+            self.save_sync_state("caldav", &state)?;
+        }
+
+        // deleted here with `tm rm`, or moved to a project that isn't synced (or to another
+        // calendar); a task file removed by hand loses its state and is pulled again
+        let mut ids: Vec<&String> = local.keys().collect();
+        ids.sort();
+        let tombstones = self.deleted_tasks()?;
+        let gone: Vec<String> = state
+            .iter()
+            .filter(|(id, s)| {
+                local
+                    .get(*id)
+                    .is_none_or(|r| r.frontmatter.project != s.project)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in gone {
+            let s = &state[&id];
+            let deleted = local.contains_key(&id) || tombstones.contains_key(&id);
+            if let Some(calendar) = cfg
+                .calendars
+                .get(&s.project)
+                .filter(|_| deleted && !s.href.is_empty())
+            {
+                let title = local
+                    .get(&id)
+                    .map_or(id.clone(), |r| r.frontmatter.title.clone());
+                if client.delete(&resolve(&cfg.collection(calendar), &s.href), &s.etag)? {
+                    report.deleted.push(title);
+                } else {
+                    report
+                        .conflicts
+                        .push(format!("{title}: changed on the server, not deleted there"));
+                }
+            }
+            state.remove(&id);
+            self.save_sync_state("caldav", &state)?;
+        }
+
+        let mut refresh: BTreeSet<String> = BTreeSet::new();
+        for id in ids {
+            let r = &local[id];
+            let fm = &r.frontmatter;
+            let Some(calendar) = cfg.calendars.get(&fm.project) else {
+                continue;
+            };
+            let last = state.get(id);
+            if last.is_some_and(|s| s.updated == fm.updated) {
+                continue;
+            }
+            let coll = cfg.collection(calendar);
+            let (url, href, uid, etag) = match last.filter(|s| !s.href.is_empty()) {
+                Some(s) => (
+                    resolve(&coll, &s.href),
+                    s.href.clone(),
+                    s.uid.clone(),
+                    Some(s.etag.clone()),
+                ),
+                None => {
+                    let url = format!("{coll}{id}.ics");
+                    let href = url[origin(&url).len()..].to_string();
+                    let uid = last.map_or_else(|| ics::uid(id), |s| s.uid.clone());
+                    (url, href, uid, None)
+                }
+            };
+            match client.put(
+                &url,
+                &ics::todo_object(r, &uid, wf),
+                etag.as_deref().filter(|e| !e.is_empty()),
+            )? {
+                Put::Done(etag) => {
+                    if etag.is_none() {
+                        refresh.insert(fm.project.clone());
+                    }
+                    report.pushed.push(fm.title.clone());
+                    let etag = etag.unwrap_or_default();
+                    state.insert(
+                        id.clone(),
+                        Synced {
+                            project: fm.project.clone(),
+                            href,
+                            etag,
+                            uid,
+                            updated: fm.updated.clone(),
+                        },
+                    );
+                    self.save_sync_state("caldav", &state)?;
+                }
+                Put::Conflict => report.conflicts.push(format!(
+                    "{}: changed on the server meanwhile, left for the next sync",
+                    fm.title
+                )),
+            }
+        }
+        // servers may leave the ETag out of a PUT response when they changed the object
+        for project in refresh {
+            let remote = client.etags(&cfg.collection(&cfg.calendars[&project]))?;
+            for s in state
+                .values_mut()
+                .filter(|s| s.project == project && s.etag.is_empty())
+            {
+                s.etag = remote.get(&s.href).cloned().unwrap_or_default();
+            }
+        }
+        self.save_sync_state("caldav", &state)?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::{
+        tests::{FakeServer, Hit, Reply},
+        TaskNew,
+    };
+
+    /// href → (ETag, calendar object)
+    type Objects = Arc<Mutex<BTreeMap<String, (String, String)>>>;
+
+    /// A calendar at `/cal/work/`: PROPFIND, multiget REPORT and conditional PUT / DELETE.
+    /// `fail_puts` makes every PUT after that many fail with a 500.
+    struct Dav {
+        server: FakeServer,
+        objects: Objects,
+        /// `METHOD path body` of each request
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Dav {
+        fn start(fail_puts: Option<usize>) -> Self {
+            let objects: Objects = Arc::default();
+            let log: Arc<Mutex<Vec<String>>> = Arc::default();
+            let (objs, hits) = (objects.clone(), log.clone());
+            let mut etag = 0;
+            let mut puts = 0;
+            let server = FakeServer::start(move |hit: &Hit| -> Reply {
+                hits.lock()
+                    .unwrap()
+                    .push(format!("{} {} {}", hit.method, hit.url, hit.body));
+                let mut objs = objs.lock().unwrap();
+                let current = objs.get(&hit.url).map(|(e, _)| e.clone());
+                let precondition_failed =
+                    match (hit.header("If-Match"), hit.header("If-None-Match")) {
+                        (Some(m), _) => current.as_deref() != Some(m),
+                        (_, Some("*")) => current.is_some(),
+                        _ => false,
+                    };
+                let multistatus = |objects: Vec<(&String, &(String, String))>, data: bool| {
+                    let responses: String = objects
+                        .into_iter()
+                        .map(|(href, (etag, ics))| {
+                            let ics = if data { format!("<c:calendar-data>{}</c:calendar-data>", xml_escape(ics)) } else { String::new() };
+                            format!("<d:response><d:href>{href}</d:href><d:propstat><d:prop><d:getetag>{}</d:getetag>{ics}</d:prop></d:propstat></d:response>", xml_escape(etag))
+                        })
+                        .collect();
+                    let body = format!(
+                        r#"<d:multistatus xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:response><d:href>/cal/work/</d:href></d:response>{responses}</d:multistatus>"#
+                    );
+                    (207, vec![], body)
+                };
+                match hit.method.as_str() {
+                    "PROPFIND" => multistatus(objs.iter().collect(), false),
+                    "REPORT" => {
+                        let wanted: Vec<String> = hit
+                            .body
+                            .split("<d:href>")
+                            .skip(1)
+                            .filter_map(|h| h.split_once("</d:href>"))
+                            .map(|(h, _)| h.to_string())
+                            .collect();
+                        multistatus(
+                            objs.iter().filter(|(h, _)| wanted.contains(h)).collect(),
+                            true,
+                        )
+                    }
+                    "PUT" if fail_puts.is_some_and(|n| puts >= n) => (500, vec![], String::new()),
+                    "PUT" if precondition_failed => (412, vec![], String::new()),
+                    "PUT" => {
+                        puts += 1;
+                        etag += 1;
+                        let tag = format!("\"put-{etag}\"");
+                        objs.insert(hit.url.clone(), (tag.clone(), hit.body.clone()));
+                        (201, vec![("ETag", tag)], String::new())
+                    }
+                    "DELETE" if current.is_none() => (404, vec![], String::new()),
+                    "DELETE" if precondition_failed => (412, vec![], String::new()),
+                    "DELETE" => {
+                        objs.remove(&hit.url);
+                        (204, vec![], String::new())
+                    }
+                    _ => (405, vec![], String::new()),
+                }
+            });
+            Self {
+                server,
+                objects,
+                log,
+            }
+        }
+
+        /// Add or replace an object as another client would
+        fn put(&self, name: &str, etag: &str, todo: &str) {
+            let ics = format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\n{todo}END:VTODO\r\nEND:VCALENDAR\r\n");
+            self.objects
+                .lock()
+                .unwrap()
+                .insert(format!("/cal/work/{name}"), (etag.into(), ics));
+        }
+
+        fn object(&self, name: &str) -> Option<String> {
+            self.objects
+                .lock()
+                .unwrap()
+                .get(&format!("/cal/work/{name}"))
+                .map(|(_, ics)| ics.clone())
+        }
+
+        fn count(&self, method: &str) -> usize {
+            self.log
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|l| l.starts_with(method))
+                .count()
+        }
+    }
+
+    fn setup(dav: &Dav, conflict: &str) -> (tempfile::TempDir, Vault) {
+        let (dir, mut vault) = crate::tests::vault();
+        vault.cfg.caldav = Some(CalDav {
+            url: format!("{}/cal/", dav.server.url),
+            calendars: [("work".to_string(), "work".to_string())].into(),
+            conflict: conflict.into(),
+            ..Default::default()
+        });
+        (dir, vault)
+    }
+
+    fn work_task(vault: &Vault, title: &str) -> String {
+        vault
+            .create_task(TaskNew {
+                title: title.into(),
+                project: "work".into(),
+                due: None,
+                tags: vec![],
+                parent: None,
+            })
+            .unwrap()
+    }
+
+    fn by_title(vault: &Vault, title: &str) -> Option<crate::Task> {
+        vault
+            .list_tasks(None)
+            .unwrap()
+            .into_iter()
+            .find(|t| t.title == title)
+    }
+
+    #[test]
+    fn new_tasks_are_created_on_both_sides() {
+        let dav = Dav::start(None);
+        let (_dir, vault) = setup(&dav, "");
+        let id = work_task(&vault, "Write report");
+        vault
+            .create_task(TaskNew {
+                title: "Not synced".into(),
+                project: "inbox".into(),
+                due: None,
+                tags: vec![],
+                parent: None,
+            })
+            .unwrap();
+        dav.put(
+            "phone.ics",
+            "\"1\"",
+            "UID:from-phone\r\nSUMMARY:Call the bank\r\nPRIORITY:1\r\n",
+        );
+
+        let report = vault.sync_caldav().unwrap();
+        assert_eq!(
+            (report.pulled.as_slice(), report.pushed.as_slice()),
+            (
+                &["Call the bank".to_string()][..],
+                &["Write report".to_string()][..]
+            )
+        );
+        assert!(dav
+            .object(&format!("{id}.ics"))
+            .unwrap()
+            .contains("SUMMARY:Write report"));
+        let pulled = by_title(&vault, "Call the bank").unwrap();
+        assert_eq!(
+            (pulled.project.as_str(), pulled.priority.as_str()),
+            ("work", "high")
+        );
+        assert_eq!(dav.count("PUT"), 1);
+
+        // nothing changed: no uploads, no downloads
+        assert!(vault.sync_caldav().unwrap().is_empty());
+        assert_eq!((dav.count("PUT"), dav.count("REPORT")), (1, 1));
+    }
+
+    #[test]
+    fn only_objects_whose_etag_changed_are_fetched() {
+        let dav = Dav::start(None);
+        let (_dir, vault) = setup(&dav, "");
+        dav.put("a.ics", "\"1\"", "UID:a\r\nSUMMARY:First\r\n");
+        dav.put("b.ics", "\"1\"", "UID:b\r\nSUMMARY:Second\r\n");
+        vault.sync_caldav().unwrap();
+
+        dav.put(
+            "b.ics",
+            "\"2\"",
+            "UID:b\r\nSUMMARY:Second, renamed\r\nSTATUS:COMPLETED\r\n",
+        );
+        let report = vault.sync_caldav().unwrap();
+        assert_eq!(report.pulled, ["Second, renamed"]);
+        let last = dav
+            .log
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|l| l.starts_with("REPORT"))
+            .cloned()
+            .unwrap();
+        assert!(
+            last.contains("/cal/work/b.ics") && !last.contains("/cal/work/a.ics"),
+            "{last}"
+        );
+        let t = by_title(&vault, "Second, renamed").unwrap();
+        assert_eq!(t.status, "done");
+        assert!(by_title(&vault, "First").is_some());
+    }
+
+    #[test]
+    fn conflicts_follow_the_policy() {
+        for (policy, winner) in [
+            ("newest-wins", "Local edit"),
+            ("remote-wins", "Server edit"),
+        ] {
+            let dav = Dav::start(None);
+            let (_dir, vault) = setup(&dav, policy);
+            let id = work_task(&vault, "Original");
+            vault.sync_caldav().unwrap();
+            let name = format!("{id}.ics");
+
+            vault.rename_title(&id, "Local edit").unwrap();
+            dav.put(
+                &name,
+                "\"other\"",
+                &format!(
+                    "UID:{id}@tm\r\nSUMMARY:Server edit\r\nLAST-MODIFIED:20000101T000000Z\r\n"
+                ),
+            );
+            let report = vault.sync_caldav().unwrap();
+            assert_eq!(
+                report.conflicts.len(),
+                1,
+                "{policy}: {:?}",
+                report.conflicts
+            );
+            assert!(by_title(&vault, winner).is_some(), "{policy}");
+            assert!(
+                dav.object(&name)
+                    .unwrap()
+                    .contains(&format!("SUMMARY:{winner}")),
+                "{policy}"
+            );
+        }
+    }
+
+    #[test]
+    fn deletions_go_both_ways() {
+        let dav = Dav::start(None);
+        let (_dir, vault) = setup(&dav, "");
+        let gone_there = work_task(&vault, "Deleted on the server");
+        let gone_here = work_task(&vault, "Deleted here");
+        vault.sync_caldav().unwrap();
+
+        dav.objects
+            .lock()
+            .unwrap()
+            .remove(&format!("/cal/work/{gone_there}.ics"));
+        vault.delete_task(&gone_here).unwrap();
+        let report = vault.sync_caldav().unwrap();
+        assert_eq!((report.closed.len(), report.deleted.len()), (1, 1));
+        assert_eq!(
+            by_title(&vault, "Deleted on the server").unwrap().status,
+            "cancelled"
+        );
+        assert!(dav.object(&format!("{gone_here}.ics")).is_none());
+        // the closed task stays off the server until it is edited again
+        assert!(vault.sync_caldav().unwrap().is_empty());
+        assert!(dav.object(&format!("{gone_there}.ics")).is_none());
+    }
+
+    #[test]
+    fn a_task_file_removed_by_hand_stays_on_the_server() {
+        let dav = Dav::start(None);
+        let (_dir, vault) = setup(&dav, "");
+        let id = work_task(&vault, "Removed by hand");
+        vault.sync_caldav().unwrap();
+
+        fs::remove_file(vault.task_path(&id).unwrap()).unwrap();
+        let report = vault.sync_caldav().unwrap();
+        assert!(report.deleted.is_empty());
+        assert!(dav.object(&format!("{id}.ics")).is_some());
+        // without its state the object counts as new and comes back
+        let report = vault.sync_caldav().unwrap();
+        assert_eq!(report.pulled, ["Removed by hand"]);
+        assert!(vault.task_path(&id).is_ok());
+    }
+
+    #[test]
+    fn a_failed_upload_keeps_what_was_done_before_it() {
+        let dav = Dav::start(Some(1));
+        let (_dir, vault) = setup(&dav, "");
+        work_task(&vault, "One");
+        work_task(&vault, "Two");
+        assert!(vault.sync_caldav().is_err());
+        let state: BTreeMap<String, Synced> = vault.load_sync_state("caldav").unwrap();
+        assert_eq!(state.len(), 1);
+        assert_eq!(dav.objects.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,176 @@
+//! Checklists in task bodies: `- [ ] step` / `- [x] step` lines (code blocks skipped) are
+//! the task's lightweight subtasks. The list shows `done/total`; toggling or adding an item
+//! touches only that one line of the file.
+
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+
+use crate::{find_task_file_by_id, notes, Vault};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckItem {
+    /// line in the body (0-based)
+    pub line: usize,
+    pub done: bool,
+    pub text: String,
+}
+
+/// The checklist items of a body, in order
+pub fn parse(body: &str) -> Vec<CheckItem> {
+    let mut out = vec![];
+    let mut in_code = false;
+    for (line, s) in body.lines().enumerate() {
+        if s.trim_start().starts_with("```") || s.trim_start().starts_with("~~~") {
+            in_code = !in_code;
+        }
+        if in_code {
+            continue;
+        }
+        if let Some(caps) = notes::checkbox_re().captures(s) {
+            let done = matches!(&caps[2], "x" | "X" | "-");
+            out.push(CheckItem {
+                line,
+                done,
+                text: caps[4].trim().to_string(),
+            });
+        }
+    }
+    out
+}
+
+/// `(done, total)`
+pub fn progress(items: &[CheckItem]) -> (usize, usize) {
+    (items.iter().filter(|i| i.done).count(), items.len())
+}
+
+/// The line where the body starts in a task file (after the closing `---`)
+fn body_offset(file: &str) -> Result<usize> {
+    let mut lines = file.lines();
+    if lines.next() != Some("---") {
+        bail!("no frontmatter");
+    }
+    let end = lines
+        .position(|l| l == "---")
+        .context("unterminated frontmatter")?;
+    Ok(end + 2)
+}
+
+impl Vault {
+    fn task_file(&self, id: &str) -> Result<(std::path::PathBuf, String)> {
+        if notes::is_note_id(id) {
+            bail!("a note checkbox has no checklist (`:promote` it to a task file first)");
+        }
+        let path = find_task_file_by_id(&self.cfg.vault_path, id)
+            .with_context(|| format!("task {id} not found"))?;
+        let text = fs::read_to_string(&path)?;
+        Ok((path, text))
+    }
+
+    /// Tick or untick checklist item `index` (0-based); returns it as it is now
+    pub fn toggle_check(&self, id: &str, index: usize) -> Result<CheckItem> {
+        let (path, text) = self.task_file(id)?;
+        let offset = body_offset(&text)?;
+        let body: String = text
+            .lines()
+            .skip(offset)
+            .map(|l| format!("{l}\n"))
+            .collect();
+        let items = parse(&body);
+        let Some(item) = items.get(index) else {
+            bail!(
+                "no checklist item {} (the task has {})",
+                index + 1,
+                items.len()
+            );
+        };
+        let mark = if item.done { ' ' } else { 'x' };
+        notes::rewrite_line(&path, offset + item.line, |l| {
+            let caps = notes::checkbox_re().captures(l).expect("a checklist line");
+            format!("{}{mark}{}{}", &caps[1], &caps[3], &caps[4])
+        })?;
+        Ok(CheckItem {
+            done: !item.done,
+            ..item.clone()
+        })
+    }
+
+    /// Add `- [ ] text` after the last checklist item, or at the end of the body
+    pub fn add_check(&self, id: &str, text: &str) -> Result<()> {
+        let text = text.trim();
+        if text.is_empty() {
+            bail!("the checklist item needs some text");
+        }
+        let (path, file) = self.task_file(id)?;
+        let offset = body_offset(&file)?;
+        let body: String = file
+            .lines()
+            .skip(offset)
+            .map(|l| format!("{l}\n"))
+            .collect();
+        let new = format!("- [ ] {text}");
+        match parse(&body).last() {
+            Some(last) => notes::rewrite_line(&path, offset + last.line, |l| format!("{l}\n{new}")),
+            None => {
+                // a blank line before the new list
+                let sep = match file.as_str() {
+                    f if f.ends_with("\n\n") => "",
+                    f if f.ends_with('\n') => "\n",
+                    _ => "\n\n",
+                };
+                fs::write(&path, format!("{file}{sep}{new}\n"))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{task, vault};
+
+    #[test]
+    fn items_outside_code_blocks_are_parsed() {
+        let body =
+            "Intro\n- [ ] one\n```\n- [ ] not this\n```\n  * [x] two\n1. [-] three\n- plain\n";
+        let items = parse(body);
+        let got: Vec<(usize, bool, &str)> = items
+            .iter()
+            .map(|i| (i.line, i.done, i.text.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            [(1, false, "one"), (5, true, "two"), (6, true, "three")]
+        );
+        assert_eq!(progress(&items), (2, 3));
+    }
+
+    #[test]
+    fn adding_and_toggling_touch_one_line() {
+        let (_dir, vault) = vault();
+        let id = task(&vault, "Move house");
+        vault.add_check(&id, "  pack  ").unwrap();
+        vault.add_check(&id, "label boxes").unwrap();
+        assert!(vault.add_check(&id, " ").is_err());
+
+        assert!(vault.toggle_check(&id, 1).unwrap().done);
+        assert!(vault.toggle_check(&id, 5).is_err());
+        let before = fs::read_to_string(vault.task_path(&id).unwrap()).unwrap();
+        assert!(
+            before.ends_with("\n\n- [ ] pack\n- [x] label boxes\n"),
+            "{before}"
+        );
+
+        assert!(!vault.toggle_check(&id, 1).unwrap().done);
+        let after = fs::read_to_string(vault.task_path(&id).unwrap()).unwrap();
+        assert_eq!(after, before.replace("[x]", "[ ]"));
+        let t = vault
+            .list_tasks(None)
+            .unwrap()
+            .into_iter()
+            .find(|t| t.id == id)
+            .unwrap();
+        assert_eq!(progress(&t.checklist), (0, 2));
+    }
+}
@@ -0,0 +1,271 @@
+//! Tab completion and signature hints for the ex bar, driven by `CommandDef` signatures
+
+use crate::{
+    date,
+    ex::{tokenize, ArgKind, CommandDef, Param},
+};
+
+/// What the UI knows about the vault, offered as argument values
+#[derive(Clone, Debug, Default)]
+pub struct CompletionData {
+    pub projects: Vec<String>,
+    pub tags: Vec<String>,
+    /// task handles (ULIDs)
+    pub tasks: Vec<String>,
+    pub views: Vec<String>,
+    /// workflow status names
+    pub statuses: Vec<String>,
+}
+
+/// Replace `line[start..end]` with one of `candidates`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Completion {
+    pub start: usize,
+    pub end: usize,
+    pub candidates: Vec<String>,
+}
+
+impl Completion {
+    /// Longest prefix shared by all candidates
+    pub fn common_prefix(&self) -> String {
+        let Some(first) = self.candidates.first() else {
+            return String::new();
+        };
+        let mut common = first.clone();
+        for c in &self.candidates[1..] {
+            while !c.starts_with(&common) {
+                common.pop();
+            }
+        }
+        common
+    }
+}
+
+/// Byte offset where the word under the cursor starts (spaces inside quotes don't split)
+fn word_start(before: &str) -> usize {
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in before.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ' ' if !in_quotes => start = i + 1,
+            _ => {}
+        }
+    }
+    start
+}
+
+fn values(kind: &ArgKind, data: &CompletionData) -> Vec<String> {
+    match kind {
+        ArgKind::Project => data.projects.clone(),
+        ArgKind::Tag => data.tags.clone(),
+        ArgKind::Task => data.tasks.clone(),
+        ArgKind::View => data.views.clone(),
+        ArgKind::Status => data
+            .statuses
+            .iter()
+            .cloned()
+            .chain(["next".into(), "prev".into()])
+            .collect(),
+        ArgKind::Choice(c) => c.clone(),
+        ArgKind::Date => {
+            let today = date::today();
+            [
+                Some(today),
+                today.next_day(),
+                today.checked_add(time::Duration::days(7)),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|d| date::format_date("YYYY-MM-DD", d))
+            .collect()
+        }
+        ArgKind::Text => vec![],
+    }
+}
+
+fn matching(values: Vec<String>, partial: &str, prefix: &str) -> Vec<String> {
+    let p = partial.to_lowercase();
+    values
+        .into_iter()
+        .filter(|v| v.to_lowercase().starts_with(&p))
+        .map(|v| format!("{prefix}{v}"))
+        .collect()
+}
+
+/// Candidates for the word under `cursor`: command names for the first word, then
+/// argument values by the command's signature (`project:` → projects, `+` → tags, ...)
+pub fn complete(
+    line: &str,
+    cursor: usize,
+    defs: &[CommandDef],
+    data: &CompletionData,
+) -> Completion {
+    let before = &line[..cursor.min(line.len())];
+    let start = word_start(before);
+    let word = &before[start..];
+    let mut out = Completion {
+        start,
+        end: before.len(),
+        candidates: vec![],
+    };
+
+    let done = tokenize(&before[..start]);
+    let Some(name) = done.first() else {
+        let mut names: Vec<String> = defs
+            .iter()
+            .filter(|d| d.name.starts_with(word))
+            .map(|d| d.name.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+        out.candidates = names;
+        return out;
+    };
+    let Some(def) = defs.iter().find(|d| d.name == *name) else {
+        return out;
+    };
+    let params = def.params();
+    let keyed: Vec<&Param> = params.iter().filter(|p| p.prefix.is_some()).collect();
+    let has_prefix = |w: &str| {
+        keyed
+            .iter()
+            .any(|p| w.starts_with(p.prefix.as_deref().unwrap_or_default()))
+    };
+
+    // `project:wo` → project keys
+    for p in &keyed {
+        let prefix = p.prefix.as_deref().unwrap_or_default();
+        if let Some(rest) = word.strip_prefix(prefix) {
+            out.candidates = matching(values(&p.kind, data), rest, prefix);
+            return out;
+        }
+    }
+
+    // positional: the n-th argument without a key; an optional one may be skipped
+    let n = done[1..].iter().filter(|w| !has_prefix(w)).count();
+    for p in params.iter().filter(|p| p.prefix.is_none()).skip(n) {
+        out.candidates
+            .extend(matching(values(&p.kind, data), word, ""));
+        if !p.optional {
+            break;
+        }
+    }
+    // keys not used yet (tags repeat)
+    for p in keyed {
+        let prefix = p.prefix.as_deref().unwrap_or_default();
+        let used = prefix != "+" && done.iter().any(|w| w.starts_with(prefix));
+        if !used && prefix.starts_with(word) {
+            out.candidates.push(prefix.to_string());
+        }
+    }
+    out.candidates.dedup();
+    out
+}
+
+/// Inline hint for the ex bar: the signature of the command being typed, or the
+/// commands its first word could still become
+pub fn signature_hint(line: &str, defs: &[CommandDef]) -> Option<String> {
+    let first = line.split_whitespace().next()?;
+    if let Some(def) = defs.iter().find(|d| d.name == first) {
+        return Some(def.signature.clone());
+    }
+    if line.ends_with(' ') {
+        return None;
+    }
+    let names: Vec<&str> = defs
+        .iter()
+        .filter(|d| d.name.starts_with(first))
+        .map(|d| d.name.as_str())
+        .collect();
+    (!names.is_empty()).then(|| names.join(" · "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defs() -> Vec<CommandDef> {
+        vec![
+            CommandDef::new(
+                r#":new "title" project:<project> +tag due:<date>"#,
+                "create a task",
+            ),
+            CommandDef::new(":status [<task>] (todo|doing|done)", "set the status"),
+            CommandDef::new(":open project:<project>", "show one project"),
+        ]
+    }
+
+    fn data() -> CompletionData {
+        CompletionData {
+            projects: vec!["work".into(), "home".into(), "Wolf".into()],
+            tags: vec!["q4".into(), "home".into()],
+            tasks: vec!["01J9ZQ".into(), "01J9ZR".into()],
+            views: vec!["today".into()],
+            statuses: vec!["todo".into(), "doing".into(), "done".into()],
+        }
+    }
+
+    fn candidates(line: &str) -> Vec<String> {
+        complete(line, line.len(), &defs(), &data()).candidates
+    }
+
+    #[test]
+    fn the_first_word_completes_to_command_names() {
+        assert_eq!(candidates(""), ["new", "open", "status"]);
+        assert_eq!(candidates("st"), ["status"]);
+        assert!(candidates("zz").is_empty());
+        assert!(candidates("frob x").is_empty());
+    }
+
+    #[test]
+    fn keyed_arguments_complete_from_the_vault() {
+        let c = complete(r#"new "x" project:wo"#, 18, &defs(), &data());
+        assert_eq!((c.start, c.end), (8, 18));
+        assert_eq!(c.candidates, ["project:work", "project:Wolf"]);
+        assert_eq!(c.common_prefix(), "project:");
+        assert_eq!(candidates(r#"new "x" +"#), ["+q4", "+home"]);
+        let dates = candidates(r#"new "x" due:"#);
+        assert_eq!(dates.len(), 3);
+        assert_eq!(
+            dates[0],
+            format!("due:{}", date::format_date("YYYY-MM-DD", date::today()))
+        );
+    }
+
+    #[test]
+    fn unused_keys_are_offered_after_the_positional_arguments() {
+        assert_eq!(candidates(r#"new "Buy milk" "#), ["project:", "+", "due:"]);
+        // a key is offered once, tags can repeat
+        assert_eq!(candidates(r#"new "x" project:work +q4 "#), ["+", "due:"]);
+        assert!(candidates(r#"new "x" project:work pro"#).is_empty());
+    }
+
+    #[test]
+    fn optional_positionals_may_be_skipped() {
+        assert_eq!(candidates("status 01J9ZR d"), ["doing", "done"]);
+        assert_eq!(candidates("status d"), ["doing", "done"]);
+        assert_eq!(
+            candidates("status "),
+            ["01J9ZQ", "01J9ZR", "todo", "doing", "done"]
+        );
+    }
+
+    #[test]
+    fn hints_show_the_signature_or_the_possible_commands() {
+        let defs = defs();
+        assert_eq!(
+            signature_hint("open pro", &defs).as_deref(),
+            Some(":open project:<project>")
+        );
+        assert_eq!(signature_hint("n", &defs).as_deref(), Some("new"));
+        assert_eq!(signature_hint("", &defs), None);
+        assert_eq!(signature_hint("xyz", &defs), None);
+        assert_eq!(signature_hint("ne ", &defs), None);
+        let all = CommandDef::new(":no", "");
+        assert_eq!(
+            signature_hint("n", &[defs[0].clone(), all]).as_deref(),
+            Some("new · no")
+        );
+    }
+}
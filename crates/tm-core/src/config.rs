@@ -0,0 +1,907 @@
+//! User configuration: `config.lua` (or `config.toml` / `config.yaml`) plus env overrides
+//!
+//! ```lua
+//! -- ~/.config/tm/config.lua
+//! use({ 'tm-plugins/todoist', version = '>=1.0.0' })
+//! set('date_format', 'DD.MM.YYYY')
+//!
+//! return {
+//!   vault_path = '~/TasksVault',
+//!   default_project = 'inbox',
+//!   sort = 'due, priority desc',
+//!   theme = 'gruvbox-dark',
+//!   themes = { mine = { base = 'gruvbox-dark', overdue = '#ff5f5f bold' } },
+//!   layout = { pane = 'right', pane_size = 40 },
+//!   workflow = { statuses = { todo = 'open', doing = 'active', done = 'closed', cancelled = 'closed' } },
+//!   leader = ' ',
+//!   todotxt_mirror = '~/Dropbox/todo/todo.txt',
+//!   notes = { folders = { '~/Notes' } },
+//!   ics = { path = '~/Calendars/tm.ics', events = true, query = 'is:open' },
+//!   caldav = { url = 'https://cloud.example.com/remote.php/dav/calendars/alice/', user = 'alice', calendars = { work = 'work-tasks' } },
+//!   issues = { labels = { 'tm' }, repos = { { repo = 'acme/api', project = 'api' } } },
+//!   keymaps = {
+//!     normal = { ['j'] = 'move_down', ['<leader>g'] = 'go_top', ['x'] = 'nop' },
+//!     command = { ['<C-j>'] = 'history_next' },
+//!   },
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use directories::ProjectDirs;
+use mlua::{DeserializeOptions, Lua, LuaSerdeExt, Table, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+
+use crate::{
+    caldav::CalDav,
+    date,
+    ics::IcsFeed,
+    issues::Issues,
+    keymap,
+    notes::Notes,
+    plugin::spec_from_json,
+    sort,
+    theme::{Theme, ThemeSpec},
+    view::{ColumnSpec, GroupBy, View},
+    workflow::Workflow,
+    PluginSpec, ViewSpec,
+};
+
+/// Overrides the vault path from any config file
+pub const ENV_VAULT: &str = "TM_VAULT";
+/// Points at a specific config file instead of the XDG location
+pub const ENV_CONFIG: &str = "TM_CONFIG";
+
+/// Registry slot for the table `set(key, value)` fills while config.lua runs
+const SETS_KEY: &str = "tm.config.sets";
+
+pub fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("io.github", "josephbwalters", "tm")
+}
+
+/// XDG: ~/.config/tm/config.lua (also config.toml, config.yaml/.yml, or a plain `config` in Lua)
+pub fn default_config_path() -> PathBuf {
+    let base = project_dirs()
+        .map(|p| p.config_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(".tm"));
+    [
+        "config.lua",
+        "config.toml",
+        "config.yaml",
+        "config.yml",
+        "config",
+    ]
+    .iter()
+    .map(|f| base.join(f))
+    .find(|p| p.exists())
+    .unwrap_or_else(|| base.join("config.lua"))
+}
+
+/// Where an effective config value came from (`tm config show`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Env(&'static str),
+    Cli,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(p) => write!(f, "{}", p.display()),
+            Origin::Env(var) => write!(f, "env ${var}"),
+            Origin::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// Where the side pane (task detail / help) sits next to the task list
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PanePosition {
+    Right,
+    Bottom,
+    Hidden,
+}
+
+/// `layout = { pane = "right", pane_size = 40 }` (size in percent)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    pub pane: PanePosition,
+    pub pane_size: u16,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            pane: PanePosition::Right,
+            pane_size: 40,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub vault_path: PathBuf,
+    /// project for `tm add` / `:new` without `project:`
+    pub default_project: String,
+    /// display pattern for dates, e.g. `YYYY-MM-DD`
+    pub date_format: String,
+    /// list order, e.g. `updated desc` or `due, priority desc`
+    pub sort: String,
+    /// built-in (`gruvbox-dark`, `gruvbox-light`, `mono`) or one of `themes`
+    pub theme: String,
+    /// user themes: name → `base` plus role → style
+    pub themes: BTreeMap<String, ThemeSpec>,
+    /// terminal colour depth: auto, truecolor, 256, 16 or none
+    pub colors: String,
+    /// prefix for `<leader>` bindings, e.g. `\`, `,` or `<Space>`
+    pub leader: String,
+    /// how long a pending key sequence (`g` of `gg`) waits for the next key
+    pub key_timeout_ms: u64,
+    pub editor: String,
+    pub layout: Layout,
+    pub plugins: Vec<PluginSpec>,
+    /// raw `keymaps.<mode>` tables (normal, insert, command, search, picker): keys → action name
+    pub keymaps: BTreeMap<String, BTreeMap<String, String>>,
+    /// list columns without a view: `status`, `title:40`, ... (empty: status, title, project)
+    pub columns: Vec<String>,
+    /// list grouping without a view: none, project, status, due or tag
+    pub group: String,
+    /// named views: name → query string or `{ query, columns, sort, group }`
+    pub views: BTreeMap<String, ViewSpec>,
+    /// task statuses: `{ statuses = { name = category }, order, transitions }`
+    pub workflow: Workflow,
+    /// a todo.txt kept in sync with the vault both ways (`tm mirror`, and while the TUI/GUI run)
+    pub todotxt_mirror: Option<PathBuf>,
+    /// note folders whose Obsidian-Tasks checkboxes are listed as tasks
+    pub notes: Notes,
+    /// an iCalendar file rewritten when the vault changes (`tm feed`, and while the TUI/GUI run)
+    pub ics: Option<IcsFeed>,
+    /// projects synced with CalDAV calendars (`tm sync caldav`)
+    pub caldav: Option<CalDav>,
+    /// repositories whose issues are synced into projects (`tm sync issues`)
+    pub issues: Option<Issues>,
+    /// config file this was loaded from (may not exist)
+    #[serde(skip)]
+    pub config_path: PathBuf,
+    #[serde(skip)]
+    pub origins: BTreeMap<&'static str, Origin>,
+}
+
+/// Shape shared by all config formats; every field optional so files only override
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FileConfig {
+    vault_path: Option<String>,
+    #[serde(alias = "inbox_project")]
+    default_project: Option<String>,
+    date_format: Option<String>,
+    sort: Option<String>,
+    #[serde(alias = "color_scheme")]
+    theme: Option<String>,
+    themes: Option<BTreeMap<String, ThemeSpec>>,
+    colors: Option<String>,
+    leader: Option<String>,
+    key_timeout_ms: Option<u64>,
+    editor: Option<String>,
+    layout: Option<Layout>,
+    plugins: Option<Vec<Json>>,
+    keymaps: Option<BTreeMap<String, Json>>,
+    columns: Option<Vec<String>>,
+    group: Option<String>,
+    views: Option<BTreeMap<String, ViewSpec>>,
+    workflow: Option<Workflow>,
+    todotxt_mirror: Option<String>,
+    notes: Option<Notes>,
+    ics: Option<IcsFeed>,
+    caldav: Option<CalDav>,
+    issues: Option<Issues>,
+}
+
+impl Config {
+    /// Built-in defaults, before any file or env override
+    pub fn defaults() -> Self {
+        let vault_path = directories::UserDirs::new()
+            .map(|u| u.home_dir().to_path_buf().join("TasksVault"))
+            .unwrap_or_else(|| PathBuf::from("TasksVault"));
+        Self {
+            vault_path,
+            default_project: "inbox".into(),
+            date_format: "YYYY-MM-DD".into(),
+            sort: "updated desc".into(),
+            theme: crate::theme::DEFAULT_THEME.into(),
+            themes: BTreeMap::new(),
+            colors: "auto".into(),
+            leader: keymap::DEFAULT_LEADER.into(),
+            key_timeout_ms: keymap::DEFAULT_TIMEOUT_MS,
+            editor: "vi".into(),
+            layout: Layout::default(),
+            plugins: vec![],
+            keymaps: BTreeMap::new(),
+            columns: vec![],
+            group: "none".into(),
+            views: BTreeMap::new(),
+            workflow: Workflow::default(),
+            todotxt_mirror: None,
+            notes: Notes::default(),
+            ics: None,
+            caldav: None,
+            issues: None,
+            config_path: default_config_path(),
+            origins: BTreeMap::new(),
+        }
+    }
+
+    /// `$TM_CONFIG` or the XDG config file, then `$TM_VAULT`
+    pub fn load_default() -> Result<Self> {
+        match env::var_os(ENV_CONFIG) {
+            Some(p) => Self::load_from(Path::new(&p), Origin::Env(ENV_CONFIG)),
+            None => Self::load_from(&default_config_path(), Origin::Default),
+        }
+    }
+
+    /// Defaults ← config file (if it exists) ← environment. Fails on unreadable or invalid files.
+    pub fn load_from(path: &Path, path_origin: Origin) -> Result<Self> {
+        let mut cfg = Self::defaults();
+        cfg.config_path = path.to_path_buf();
+        cfg.origins.insert("config", path_origin);
+
+        if path.exists() {
+            let (file, uses) = read_file_config(path)?;
+            cfg.apply(file, uses, Origin::File(path.to_path_buf()))?;
+        }
+
+        if let Some(v) = env::var_os(ENV_VAULT) {
+            cfg.vault_path = expand_home(&v.to_string_lossy());
+            cfg.origins.insert("vault_path", Origin::Env(ENV_VAULT));
+        }
+        if !cfg.origins.contains_key("editor") {
+            for var in ["VISUAL", "EDITOR"] {
+                if let Some(e) = env::var(var).ok().filter(|e| !e.is_empty()) {
+                    cfg.editor = e;
+                    cfg.origins.insert("editor", Origin::Env(var));
+                    break;
+                }
+            }
+        }
+
+        cfg.validate()
+            .with_context(|| format!("invalid config {}", path.display()))?;
+        Ok(cfg)
+    }
+
+    /// Re-read the same config file for hot reload, keeping command-line overrides.
+    /// On error the caller keeps using `self`.
+    pub fn reload(&self) -> Result<Self> {
+        let mut cfg = Self::load_from(&self.config_path, self.origin("config"))?;
+        if self.origin("vault_path") == Origin::Cli {
+            cfg.override_vault(self.vault_path.clone());
+        }
+        Ok(cfg)
+    }
+
+    /// `--vault` on the command line wins over everything
+    pub fn override_vault(&mut self, path: PathBuf) {
+        self.vault_path = path;
+        self.origins.insert("vault_path", Origin::Cli);
+    }
+
+    fn apply(&mut self, f: FileConfig, uses: Vec<PluginSpec>, origin: Origin) -> Result<()> {
+        macro_rules! take {
+            ($field:ident) => {
+                if let Some(v) = f.$field {
+                    self.$field = v;
+                    self.origins.insert(stringify!($field), origin.clone());
+                }
+            };
+        }
+        if let Some(v) = f.vault_path {
+            self.vault_path = expand_home(&v);
+            self.origins.insert("vault_path", origin.clone());
+        }
+        take!(default_project);
+        take!(date_format);
+        take!(sort);
+        take!(theme);
+        take!(themes);
+        take!(colors);
+        take!(leader);
+        take!(key_timeout_ms);
+        take!(editor);
+        take!(layout);
+        take!(columns);
+        take!(group);
+        take!(views);
+        take!(workflow);
+        if let Some(v) = f.todotxt_mirror {
+            self.todotxt_mirror = Some(expand_home(&v));
+            self.origins.insert("todotxt_mirror", origin.clone());
+        }
+        if let Some(mut notes) = f.notes {
+            for dir in &mut notes.folders {
+                *dir = expand_home(&dir.to_string_lossy());
+            }
+            self.notes = notes;
+            self.origins.insert("notes", origin.clone());
+        }
+        if let Some(mut ics) = f.ics {
+            if ics.path.as_os_str().is_empty() {
+                bail!("ics: `path` is required");
+            }
+            ics.path = expand_home(&ics.path.to_string_lossy());
+            self.ics = Some(ics);
+            self.origins.insert("ics", origin.clone());
+        }
+        if let Some(caldav) = f.caldav {
+            if caldav.url.is_empty() && caldav.calendars.values().any(|c| !c.contains("://")) {
+                bail!("caldav: `url` is required for calendars given by name");
+            }
+            self.caldav = Some(caldav);
+            self.origins.insert("caldav", origin.clone());
+        }
+        if let Some(issues) = f.issues {
+            issues.validate()?;
+            self.issues = Some(issues);
+            self.origins.insert("issues", origin.clone());
+        }
+
+        let mut plugins = uses;
+        for v in f.plugins.unwrap_or_default() {
+            plugins.push(spec_from_json(&v).ok_or_else(|| anyhow!("invalid plugin entry: {v}"))?);
+        }
+        if !plugins.is_empty() {
+            self.plugins = plugins;
+            self.origins.insert("plugins", origin.clone());
+        }
+
+        if let Some(maps) = f.keymaps {
+            for (mode, tbl) in maps {
+                self.keymaps.insert(mode, string_map(&tbl)?);
+            }
+            self.origins.insert("keymaps", origin);
+        }
+        Ok(())
+    }
+
+    /// Reject values the rest of tm would choke on
+    pub fn validate(&self) -> Result<()> {
+        sort::parse_sort(&self.sort).context("sort")?;
+        if !date::is_valid_pattern(&self.date_format) {
+            bail!(
+                "date_format '{}' needs YYYY (or YY), MM and DD",
+                self.date_format
+            );
+        }
+        if self.leader.is_empty() {
+            bail!("leader must not be empty");
+        }
+        if !(50..=10_000).contains(&self.key_timeout_ms) {
+            bail!("key_timeout_ms must be between 50 and 10000");
+        }
+        if !(10..=90).contains(&self.layout.pane_size) {
+            bail!("layout.pane_size must be between 10 and 90 (percent)");
+        }
+        Theme::resolve(&self.theme, &self.themes).context("theme")?;
+        for name in self.themes.keys() {
+            Theme::resolve(name, &self.themes)?;
+        }
+        if !["auto", "truecolor", "256", "16", "none"].contains(&self.colors.as_str()) {
+            bail!(
+                "colors '{}' must be auto, truecolor, 256, 16 or none",
+                self.colors
+            );
+        }
+        if self.default_project.trim().is_empty() {
+            bail!("default_project must not be empty");
+        }
+        for (mode, tbl) in &self.keymaps {
+            if keymap::Mode::parse(mode).is_none() {
+                bail!("keymaps.{mode}: unknown mode (normal, insert, command, search, picker)");
+            }
+            for (token, action) in tbl {
+                if action != "nop" && keymap::parse_action_name(action).is_none() {
+                    bail!("keymaps.{mode}['{token}']: unknown action '{action}'");
+                }
+            }
+        }
+        if !self.columns.is_empty() {
+            ColumnSpec::parse_list(&self.columns).context("columns")?;
+        }
+        GroupBy::parse(&self.group).context("group")?;
+        self.workflow.validate().context("workflow")?;
+        for (name, spec) in &self.views {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                bail!("views: '{name}' is not a one-word name");
+            }
+            View::from_spec(name, spec, false)?;
+        }
+        Ok(())
+    }
+
+    pub fn origin(&self, key: &str) -> Origin {
+        self.origins.get(key).cloned().unwrap_or(Origin::Default)
+    }
+
+    /// Effective settings as (key, value, origin) rows for `tm config show`
+    pub fn entries(&self) -> Vec<(&'static str, String, Origin)> {
+        let plugins = self
+            .plugins
+            .iter()
+            .map(|p| match &p.version {
+                Some(v) => format!("{}@{v}", p.source),
+                None => p.source.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let keymaps = self
+            .keymaps
+            .iter()
+            .map(|(mode, t)| format!("{mode}: {} bindings", t.len()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let columns = self
+            .list_columns()
+            .iter()
+            .map(|c| c.to_config())
+            .collect::<Vec<_>>()
+            .join(", ");
+        vec![
+            (
+                "config",
+                self.config_path.display().to_string(),
+                self.origin("config"),
+            ),
+            (
+                "vault_path",
+                self.vault_path.display().to_string(),
+                self.origin("vault_path"),
+            ),
+            (
+                "default_project",
+                self.default_project.clone(),
+                self.origin("default_project"),
+            ),
+            (
+                "date_format",
+                self.date_format.clone(),
+                self.origin("date_format"),
+            ),
+            ("sort", self.sort.clone(), self.origin("sort")),
+            ("theme", self.theme.clone(), self.origin("theme")),
+            (
+                "themes",
+                self.themes.keys().cloned().collect::<Vec<_>>().join(", "),
+                self.origin("themes"),
+            ),
+            ("colors", self.colors.clone(), self.origin("colors")),
+            ("leader", self.leader.clone(), self.origin("leader")),
+            (
+                "key_timeout_ms",
+                self.key_timeout_ms.to_string(),
+                self.origin("key_timeout_ms"),
+            ),
+            ("editor", self.editor.clone(), self.origin("editor")),
+            (
+                "layout",
+                format!("{:?} {}%", self.layout.pane, self.layout.pane_size).to_lowercase(),
+                self.origin("layout"),
+            ),
+            ("plugins", plugins, self.origin("plugins")),
+            ("keymaps", keymaps, self.origin("keymaps")),
+            ("columns", columns, self.origin("columns")),
+            ("group", self.group.clone(), self.origin("group")),
+            ("workflow", self.workflow.summary(), self.origin("workflow")),
+            (
+                "views",
+                self.views.keys().cloned().collect::<Vec<_>>().join(", "),
+                self.origin("views"),
+            ),
+            (
+                "todotxt_mirror",
+                self.todotxt_mirror
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+                self.origin("todotxt_mirror"),
+            ),
+            (
+                "notes",
+                self.notes
+                    .folders
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.origin("notes"),
+            ),
+            (
+                "ics",
+                self.ics
+                    .as_ref()
+                    .map(|f| f.path.display().to_string())
+                    .unwrap_or_default(),
+                self.origin("ics"),
+            ),
+            (
+                "caldav",
+                self.caldav
+                    .as_ref()
+                    .map(|c| c.calendars.keys().cloned().collect::<Vec<_>>().join(", "))
+                    .unwrap_or_default(),
+                self.origin("caldav"),
+            ),
+            (
+                "issues",
+                self.issues
+                    .as_ref()
+                    .map(|i| {
+                        i.repos
+                            .iter()
+                            .map(|r| format!("{}:{} → {}", r.forge.as_str(), r.repo, r.project))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    })
+                    .unwrap_or_default(),
+                self.origin("issues"),
+            ),
+        ]
+    }
+
+    /// Render a stored date (`YYYY-MM-DD` or RFC 3339) with `date_format`
+    pub fn display_date(&self, s: &str) -> String {
+        date::display_date(&self.date_format, s)
+    }
+
+    /// `columns`, or the classic `[status] title · project`
+    pub fn list_columns(&self) -> Vec<ColumnSpec> {
+        ColumnSpec::parse_list(&self.columns).unwrap_or_else(|_| ColumnSpec::defaults())
+    }
+
+    /// The configured theme (the default if it doesn't resolve)
+    pub fn theme(&self) -> Theme {
+        Theme::resolve(&self.theme, &self.themes).unwrap_or_default()
+    }
+
+    pub fn list_group(&self) -> GroupBy {
+        GroupBy::parse(&self.group).unwrap_or(GroupBy::None)
+    }
+
+    /// `editor` (may carry arguments, e.g. `code -w`) opening `path`
+    pub fn editor_command(&self, path: &Path) -> std::process::Command {
+        let mut words = self.editor.split_whitespace();
+        let mut cmd = std::process::Command::new(words.next().unwrap_or("vi"));
+        cmd.args(words).arg(path);
+        cmd
+    }
+}
+
+fn expand_home(s: &str) -> PathBuf {
+    match s.strip_prefix("~/") {
+        Some(rest) => directories::UserDirs::new()
+            .map(|u| u.home_dir().join(rest))
+            .unwrap_or_else(|| PathBuf::from(s)),
+        None => PathBuf::from(s),
+    }
+}
+
+/// `{ j = "move_down" }` (or a Lua sequence, keyed "1", "2", ...) → token → action name
+fn string_map(v: &Json) -> Result<BTreeMap<String, String>> {
+    let pairs: Vec<(String, &Json)> = match v {
+        Json::Object(m) => m.iter().map(|(k, v)| (k.clone(), v)).collect(),
+        Json::Array(a) => a
+            .iter()
+            .enumerate()
+            .map(|(i, v)| ((i + 1).to_string(), v))
+            .collect(),
+        _ => bail!("keymaps: expected a table, got {v}"),
+    };
+    Ok(pairs
+        .into_iter()
+        .filter_map(|(k, v)| v.as_str().map(|s| (k, s.to_string())))
+        .collect())
+}
+
+fn read_file_config(path: &Path) -> Result<(FileConfig, Vec<PluginSpec>)> {
+    let src = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("lua");
+    let json = match ext {
+        "toml" => toml::from_str::<Json>(&src)?,
+        "yaml" | "yml" => serde_yaml::from_str::<Json>(&src)?,
+        _ => return eval_lua_config(path, &src),
+    };
+    let file = match json {
+        Json::Null => FileConfig::default(),
+        j => serde_json::from_value(j).with_context(|| format!("in {}", path.display()))?,
+    };
+    Ok((file, vec![]))
+}
+
+/// Run config.lua with the `use(spec)` and `set(key, value)` helpers; the returned table
+/// (if any) is merged over the `set` calls. Values are deserialized straight from Lua so
+/// an empty table fits both list fields (`columns = {}`) and map fields (`themes = {}`).
+fn eval_lua_config(path: &Path, src: &str) -> Result<(FileConfig, Vec<PluginSpec>)> {
+    let lua = Lua::new();
+    let uses: Arc<Mutex<Vec<PluginSpec>>> = Arc::default();
+    let sets = lua.create_table().map_err(|e| anyhow!(e.to_string()))?;
+    lua.set_named_registry_value(SETS_KEY, &sets)
+        .map_err(|e| anyhow!(e.to_string()))?;
+
+    let collected = uses.clone();
+    let use_fn = lua
+        .create_function(move |_, v: Value| {
+            let j = lua_to_json(&v).map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+            let spec = spec_from_json(&j)
+                .ok_or_else(|| mlua::Error::RuntimeError("use(): missing plugin source".into()))?;
+            collected.lock().unwrap().push(spec);
+            Ok(())
+        })
+        .map_err(|e| anyhow!(e.to_string()))?;
+    let set_fn = lua
+        .create_function(|lua, (k, v): (String, Value)| {
+            lua.named_registry_value::<Table>(SETS_KEY)?.raw_set(k, v)
+        })
+        .map_err(|e| anyhow!(e.to_string()))?;
+    let globals = lua.globals();
+    globals
+        .set("use", use_fn)
+        .map_err(|e| anyhow!(e.to_string()))?;
+    globals
+        .set("set", set_fn)
+        .map_err(|e| anyhow!(e.to_string()))?;
+
+    // IMPORTANT: never bubble mlua::Error with `?` directly; map to string.
+    let ret = lua
+        .load(src)
+        .set_name(path.to_string_lossy())
+        .eval::<Value>()
+        .map_err(|e| anyhow!(e.to_string()))?;
+    if let Value::Table(t) = ret {
+        for pair in t.pairs::<Value, Value>() {
+            let (k, v) = pair.map_err(|e| anyhow!(e.to_string()))?;
+            sets.raw_set(k, v).map_err(|e| anyhow!(e.to_string()))?;
+        }
+    }
+
+    // plugin entries mix a positional source with named fields (`{ 'a/b', version = .. }`),
+    // which only the JSON conversion keeps
+    let plugins: Value = sets
+        .raw_get("plugins")
+        .map_err(|e| anyhow!(e.to_string()))?;
+    sets.raw_set("plugins", Value::Nil)
+        .map_err(|e| anyhow!(e.to_string()))?;
+    let opts = DeserializeOptions::new().deny_unsupported_types(false);
+    let mut file: FileConfig = lua
+        .from_value_with(Value::Table(sets), opts)
+        .map_err(|e| anyhow!("in {}: {e}", path.display()))?;
+    file.plugins = match lua_to_json(&plugins)? {
+        Json::Null => None,
+        Json::Array(list) => Some(list),
+        Json::Object(m) if m.is_empty() => Some(vec![]),
+        j => bail!("in {}: plugins must be a list, got {j}", path.display()),
+    };
+    let uses = uses.lock().unwrap().clone();
+    Ok((file, uses))
+}
+
+/// Lua value → JSON. Sequences become arrays; other tables become objects with
+/// stringified keys. Functions and userdata are dropped (null).
+pub(crate) fn lua_to_json(v: &Value) -> Result<Json> {
+    Ok(match v {
+        Value::Boolean(b) => Json::Bool(*b),
+        Value::Integer(n) => Json::from(*n),
+        Value::Number(n) => Json::from(*n),
+        Value::String(s) => {
+            Json::String(s.to_str().map_err(|e| anyhow!(e.to_string()))?.to_string())
+        }
+        Value::Table(t) => {
+            let mut pairs = Vec::new();
+            for pair in t.pairs::<Value, Value>() {
+                pairs.push(pair.map_err(|e| anyhow!(e.to_string()))?);
+            }
+            let len = t.raw_len();
+            if len > 0 && pairs.len() == len {
+                let mut arr = Vec::with_capacity(len);
+                for i in 1..=len {
+                    let item: Value = t.raw_get(i).map_err(|e| anyhow!(e.to_string()))?;
+                    arr.push(lua_to_json(&item)?);
+                }
+                Json::Array(arr)
+            } else {
+                let mut obj = serde_json::Map::new();
+                for (k, v) in pairs {
+                    let key = match k {
+                        Value::String(s) => {
+                            s.to_str().map_err(|e| anyhow!(e.to_string()))?.to_string()
+                        }
+                        Value::Integer(n) => n.to_string(),
+                        Value::Number(n) => n.to_string(),
+                        _ => continue,
+                    };
+                    obj.insert(key, lua_to_json(&v)?);
+                }
+                Json::Object(obj)
+            }
+        }
+        _ => Json::Null,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loading reads TM_VAULT, VISUAL and EDITOR; tests that load or touch them take this
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Set (Some) or clear (None) env vars, returning what they were before
+    fn swap_env<const N: usize>(
+        vars: [(&'static str, Option<std::ffi::OsString>); N],
+    ) -> [(&'static str, Option<std::ffi::OsString>); N] {
+        vars.map(|(k, v)| {
+            let old = env::var_os(k);
+            match v {
+                Some(v) => env::set_var(k, v),
+                None => env::remove_var(k),
+            }
+            (k, old)
+        })
+    }
+
+    fn load(dir: &Path, name: &str, src: &str) -> Result<Config> {
+        let path = dir.join(name);
+        fs::write(&path, src).unwrap();
+        Config::load_from(&path, Origin::Default)
+    }
+
+    #[test]
+    fn lua_config_merges_the_returned_table_over_set_calls() {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = load(
+            dir.path(),
+            "config.lua",
+            r#"
+            use({ 'tm-plugins/todoist', version = '>=1.0.0' })
+            set('date_format', 'DD.MM.YYYY')
+            set('sort', 'due')
+            return {
+              sort = 'priority desc',
+              editor = 'nvim',
+              plugins = { 'acme/tm-extra' },
+              keymaps = { normal = { ['<leader>g'] = 'go_top' } },
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.date_format, "DD.MM.YYYY");
+        assert_eq!(cfg.sort, "priority desc");
+        let sources: Vec<_> = cfg.plugins.iter().map(|p| p.source.as_str()).collect();
+        assert_eq!(sources, ["tm-plugins/todoist", "acme/tm-extra"]);
+        assert_eq!(cfg.plugins[0].version.as_deref(), Some(">=1.0.0"));
+        assert_eq!(cfg.keymaps["normal"]["<leader>g"], "go_top");
+        let file = Origin::File(dir.path().join("config.lua"));
+        assert_eq!(cfg.origin("date_format"), file);
+        assert_eq!(cfg.origin("sort"), file);
+        assert_eq!(cfg.origin("theme"), Origin::Default);
+    }
+
+    #[test]
+    fn empty_lua_tables_fit_list_and_map_fields() {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = load(
+            dir.path(),
+            "config.lua",
+            "return { plugins = {}, columns = {}, views = {}, keymaps = { insert = {} } }",
+        )
+        .unwrap();
+        assert!(cfg.plugins.is_empty());
+        assert!(cfg.columns.is_empty());
+        assert!(cfg.views.is_empty());
+        assert!(cfg.keymaps["insert"].is_empty());
+    }
+
+    #[test]
+    fn toml_and_yaml_configs_set_the_same_fields() {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let toml = load(
+            dir.path(),
+            "config.toml",
+            "default_project = \"work\"\ndate_format = \"DD/MM/YYYY\"\nplugins = [\"acme/tm-extra\"]\n\n[keymaps.normal]\nj = \"move_up\"\n",
+        )
+        .unwrap();
+        let yaml = load(
+            dir.path(),
+            "config.yaml",
+            "default_project: work\ndate_format: DD/MM/YYYY\nplugins: [acme/tm-extra]\nkeymaps:\n  normal:\n    j: move_up\n",
+        )
+        .unwrap();
+        for cfg in [toml, yaml] {
+            assert_eq!(cfg.default_project, "work");
+            assert_eq!(cfg.date_format, "DD/MM/YYYY");
+            assert_eq!(cfg.plugins[0].source, "acme/tm-extra");
+            assert_eq!(cfg.keymaps["normal"]["j"], "move_up");
+        }
+    }
+
+    #[test]
+    fn env_overrides_the_file_and_the_command_line_overrides_env() {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let saved = swap_env([
+            (ENV_VAULT, Some(dir.path().join("from-env").into())),
+            ("VISUAL", None),
+            ("EDITOR", Some("nano".into())),
+        ]);
+
+        let path = dir.path().join("config.lua");
+        let file_only = load(
+            dir.path(),
+            "config.lua",
+            "return { vault_path = '/tmp/from-file' }",
+        );
+        let with_editor = load(dir.path(), "config.lua", "return { editor = 'hx' }");
+        swap_env(saved);
+
+        let mut cfg = file_only.unwrap();
+        assert_eq!(cfg.vault_path, dir.path().join("from-env"));
+        assert_eq!(cfg.origin("vault_path"), Origin::Env(ENV_VAULT));
+        assert_eq!(cfg.editor, "nano");
+        assert_eq!(cfg.origin("editor"), Origin::Env("EDITOR"));
+        // $EDITOR only fills in when the file has no editor
+        let with_editor = with_editor.unwrap();
+        assert_eq!(with_editor.editor, "hx");
+        assert_eq!(with_editor.origin("editor"), Origin::File(path));
+
+        cfg.override_vault(PathBuf::from("/tmp/from-cli"));
+        let shown: BTreeMap<_, _> = cfg
+            .entries()
+            .into_iter()
+            .map(|(k, v, o)| (k, (v, o.to_string())))
+            .collect();
+        assert_eq!(
+            shown["vault_path"],
+            ("/tmp/from-cli".into(), "command line".into())
+        );
+        assert_eq!(shown["editor"], ("nano".into(), "env $EDITOR".into()));
+        assert_eq!(shown["sort"], ("updated desc".into(), "default".into()));
+    }
+
+    #[test]
+    fn invalid_values_name_the_config_file() {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let err = load(dir.path(), "config.lua", "return { date_format = 'DD.MM' }").unwrap_err();
+        assert!(format!("{err:#}").contains("config.lua"), "{err:#}");
+        assert!(format!("{err:#}").contains("date_format"), "{err:#}");
+        let err = load(dir.path(), "config.lua", "return { plugins = 'acme/x' }").unwrap_err();
+        assert!(err.to_string().contains("plugins must be a list"), "{err}");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn loading_the_default_config_does_not_create_its_directory() {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let saved = swap_env([
+            (ENV_CONFIG, None),
+            ("XDG_CONFIG_HOME", Some(dir.path().join("xdg").into())),
+        ]);
+        let cfg = Config::load_default();
+        swap_env(saved);
+        assert!(cfg.unwrap().config_path.starts_with(dir.path()));
+        assert!(!dir.path().join("xdg").exists());
+    }
+}
@@ -0,0 +1,271 @@
+//! Date helpers: parse the dates stored in frontmatter, render them with `date_format`
+
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, Duration,
+    OffsetDateTime, Time, UtcOffset, Weekday,
+};
+
+/// Accepts `YYYY-MM-DD` or a full RFC 3339 timestamp (as written in `created`/`updated`)
+pub fn parse_date(s: &str) -> Option<Date> {
+    let s = s.trim();
+    if let Ok(d) = Date::parse(s, format_description!("[year]-[month]-[day]")) {
+        return Some(d);
+    }
+    OffsetDateTime::parse(s, &Rfc3339).ok().map(|dt| dt.date())
+}
+
+/// Render a date using a `YYYY-MM-DD`-style pattern (tokens: YYYY YY MM DD).
+/// Anything else in the pattern is copied through literally.
+pub fn format_date(pattern: &str, d: Date) -> String {
+    let mut out = String::new();
+    let mut rest = pattern;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix("YYYY") {
+            out.push_str(&format!("{:04}", d.year()));
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("YY") {
+            out.push_str(&format!("{:02}", d.year().rem_euclid(100)));
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("MM") {
+            out.push_str(&format!("{:02}", u8::from(d.month())));
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("DD") {
+            out.push_str(&format!("{:02}", d.day()));
+            rest = r;
+        } else {
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// True if the pattern contains at least a day, month and year token
+pub fn is_valid_pattern(pattern: &str) -> bool {
+    pattern.contains("YY") && pattern.contains("MM") && pattern.contains("DD")
+}
+
+/// Reformat a stored date string for display; unparseable input is shown unchanged
+pub fn display_date(pattern: &str, s: &str) -> String {
+    parse_date(s)
+        .map(|d| format_date(pattern, d))
+        .unwrap_or_else(|| s.to_string())
+}
+
+/// The start of a local day as an RFC 3339 timestamp (a `done_at` known only by its date)
+pub(crate) fn local_midnight(d: Date) -> String {
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    d.with_time(Time::MIDNIGHT)
+        .assume_offset(offset)
+        .format(&Rfc3339)
+        .unwrap()
+}
+
+/// `d` for a stored date string, keeping the stored one (a time of day, say) when it is
+/// the same day
+pub(crate) fn same_day(old: &Option<String>, d: Option<Date>) -> Option<String> {
+    match d {
+        Some(d) if old.as_deref().and_then(parse_date) == Some(d) => old.clone(),
+        Some(d) => Some(format_date("YYYY-MM-DD", d)),
+        None => None,
+    }
+}
+
+/// Today's date in local time (UTC if the local offset can't be determined)
+pub fn today() -> Date {
+    OffsetDateTime::now_local()
+        .unwrap_or_else(|_| OffsetDateTime::now_utc())
+        .date()
+}
+
+/// The Monday of `d`'s week
+pub fn week_start(d: Date) -> Date {
+    d - Duration::days(d.weekday().number_days_from_monday() as i64)
+}
+
+/// A date typed by the user: `today`, `tomorrow`, `yesterday`, `+3d` (or `3d`), `-2w`,
+/// `next week` (its Monday), `next month` (the 1st), a weekday (`mon`, `friday`: the next
+/// one after today) or a stored date
+pub fn parse_relative(s: &str, today: Date) -> Option<Date> {
+    let s = s.trim().to_lowercase();
+    match s.as_str() {
+        "today" => return Some(today),
+        "tomorrow" => return today.next_day(),
+        "yesterday" => return today.previous_day(),
+        "next week" | "nextweek" => return week_start(today).checked_add(Duration::weeks(1)),
+        "next month" | "nextmonth" => {
+            let (y, m) = match today.month() {
+                time::Month::December => (today.year() + 1, time::Month::January),
+                m => (today.year(), m.next()),
+            };
+            return Date::from_calendar_date(y, m, 1).ok();
+        }
+        _ => {}
+    }
+    if let Some(body) = s.strip_prefix('+') {
+        return today.checked_add(Duration::days(offset_days(body)?));
+    }
+    if let Some(body) = s.strip_prefix('-') {
+        return today.checked_sub(Duration::days(offset_days(body)?));
+    }
+    // bare `3d` / `2w`: forward, like `+3d`
+    if let Some(days) = offset_days(&s) {
+        return today.checked_add(Duration::days(days));
+    }
+    if let Some(wd) = parse_weekday(&s) {
+        let ahead = (wd.number_days_from_monday() as i64
+            - today.weekday().number_days_from_monday() as i64
+            + 6)
+        .rem_euclid(7)
+            + 1;
+        return today.checked_add(Duration::days(ahead));
+    }
+    parse_date(&s)
+}
+
+/// Offsets past this many days (about a century) are typos, not dates
+const MAX_OFFSET_DAYS: i64 = 36_600;
+
+/// `3d` → 3, `2w` → 14; `None` for anything else or offsets beyond [`MAX_OFFSET_DAYS`]
+fn offset_days(body: &str) -> Option<i64> {
+    let (n, per) = match body.strip_suffix('d') {
+        Some(n) => (n, 1),
+        None => (body.strip_suffix('w')?, 7),
+    };
+    if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let days = n.parse::<i64>().ok()?.checked_mul(per)?;
+    (days <= MAX_OFFSET_DAYS).then_some(days)
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    const DAYS: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Monday),
+        ("tuesday", Weekday::Tuesday),
+        ("wednesday", Weekday::Wednesday),
+        ("thursday", Weekday::Thursday),
+        ("friday", Weekday::Friday),
+        ("saturday", Weekday::Saturday),
+        ("sunday", Weekday::Sunday),
+    ];
+    if s.len() < 3 {
+        return None;
+    }
+    DAYS.iter()
+        .find(|(name, _)| name.starts_with(s))
+        .map(|(_, d)| *d)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use super::*;
+
+    #[test]
+    fn dates_parse_plain_or_from_a_timestamp() {
+        assert_eq!(parse_date(" 2026-10-14 "), Some(date!(2026 - 10 - 14)));
+        assert_eq!(
+            parse_date("2026-10-14T23:30:00+02:00"),
+            Some(date!(2026 - 10 - 14))
+        );
+        assert_eq!(
+            parse_date("2026-10-14T09:00:00Z"),
+            Some(date!(2026 - 10 - 14))
+        );
+        assert_eq!(parse_date("2026-02-30"), None);
+        assert_eq!(parse_date("14/10/2026"), None);
+        assert_eq!(parse_date(""), None);
+    }
+
+    #[test]
+    fn patterns_render_tokens_and_copy_the_rest() {
+        let d = date!(2026 - 03 - 07);
+        assert_eq!(format_date("YYYY-MM-DD", d), "2026-03-07");
+        assert_eq!(format_date("DD/MM/YY", d), "07/03/26");
+        assert_eq!(format_date("DD. MM. YYYY – ok", d), "07. 03. 2026 – ok");
+        assert_eq!(display_date("DD/MM/YYYY", "2026-03-07"), "07/03/2026");
+        assert_eq!(display_date("DD/MM/YYYY", "someday"), "someday");
+    }
+
+    #[test]
+    fn a_pattern_needs_day_month_and_year() {
+        assert!(is_valid_pattern("YYYY-MM-DD"));
+        assert!(is_valid_pattern("DD.MM.YY"));
+        assert!(!is_valid_pattern("DD.MM"));
+        assert!(!is_valid_pattern("MM/YYYY"));
+        assert!(!is_valid_pattern(""));
+    }
+
+    #[test]
+    fn relative_dates_count_from_today() {
+        let today = date!(2026 - 10 - 14); // a Wednesday
+        assert_eq!(parse_relative("Today", today), Some(today));
+        assert_eq!(
+            parse_relative("tomorrow", today),
+            Some(date!(2026 - 10 - 15))
+        );
+        assert_eq!(parse_relative(" +3d ", today), Some(date!(2026 - 10 - 17)));
+        assert_eq!(parse_relative("-2w", today), Some(date!(2026 - 09 - 30)));
+        assert_eq!(parse_relative("+0d", today), Some(today));
+        assert_eq!(parse_relative("3d", today), Some(date!(2026 - 10 - 17)));
+        assert_eq!(parse_relative("1w", today), Some(date!(2026 - 10 - 21)));
+        assert_eq!(
+            parse_relative("next week", today),
+            Some(date!(2026 - 10 - 19))
+        );
+        assert_eq!(
+            parse_relative("nextmonth", today),
+            Some(date!(2026 - 11 - 01))
+        );
+        assert_eq!(
+            parse_relative("next month", date!(2026 - 12 - 31)),
+            Some(date!(2027 - 01 - 01))
+        );
+        assert_eq!(parse_relative("fri", today), Some(date!(2026 - 10 - 16)));
+        assert_eq!(parse_relative("wed", today), Some(date!(2026 - 10 - 21)));
+        assert_eq!(
+            parse_relative("wednesday", today),
+            Some(date!(2026 - 10 - 21))
+        );
+        assert_eq!(
+            parse_relative("2026-12-01", today),
+            Some(date!(2026 - 12 - 01))
+        );
+    }
+
+    #[test]
+    fn bad_relative_dates_are_rejected_not_panics() {
+        let today = date!(2026 - 10 - 14);
+        for s in [
+            "+é",
+            "-ü",
+            "+",
+            "-",
+            "+d",
+            "+3x",
+            "+-3d",
+            "-+3d",
+            "+3ddd",
+            "+200000000000000d",
+            "-200000000000000d",
+            "+2000000000000000000w",
+            "+9223372036854775807d",
+            "+36601d",
+            "é",
+            "3é",
+            "d",
+            "3",
+            "-3",
+            "200000000000000d",
+            "2000000000000000000w",
+            "fr",
+            "someday",
+        ] {
+            assert_eq!(parse_relative(s, today), None, "{s}");
+        }
+        assert_eq!(parse_relative("+5000w", today), Some(date!(2122 - 08 - 12)));
+    }
+}
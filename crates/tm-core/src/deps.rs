@@ -0,0 +1,314 @@
+//! Task dependencies: `depends_on: [ids]` in the frontmatter. A task is blocked while any
+//! task it depends on is not closed; dependencies may not form a cycle. `tm graph` draws
+//! them as Graphviz DOT or Mermaid, an arrow from each task to the ones it unblocks.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::Task;
+
+/// Tasks by id
+fn index(tasks: &[Task]) -> HashMap<&str, &Task> {
+    tasks.iter().map(|t| (t.id.as_str(), t)).collect()
+}
+
+/// Fill in `blocked` for every task (after loading; closed tasks are never blocked)
+pub fn mark_blocked(tasks: &mut [Task]) {
+    let open: HashSet<String> = tasks
+        .iter()
+        .filter(|t| !t.is_closed())
+        .map(|t| t.id.clone())
+        .collect();
+    for t in tasks.iter_mut() {
+        t.blocked = !t.is_closed() && t.depends_on.iter().any(|d| open.contains(d));
+    }
+}
+
+/// The tasks `t` waits for that are not closed yet
+pub fn blocked_by<'a>(tasks: &'a [Task], t: &Task) -> Vec<&'a Task> {
+    let by_id = index(tasks);
+    t.depends_on
+        .iter()
+        .filter_map(|d| by_id.get(d.as_str()).copied())
+        .filter(|d| !d.is_closed())
+        .collect()
+}
+
+/// The tasks that depend on `t`
+pub fn dependents<'a>(tasks: &'a [Task], t: &Task) -> Vec<&'a Task> {
+    tasks
+        .iter()
+        .filter(|x| x.depends_on.contains(&t.id))
+        .collect()
+}
+
+/// The path `to → … → from` if `from` depending on `to` would close a cycle
+pub fn cycle_path(tasks: &[Task], from: &str, to: &str) -> Option<Vec<String>> {
+    if from == to {
+        return Some(vec![from.to_string(), to.to_string()]);
+    }
+    let by_id = index(tasks);
+    // depth-first from `to` along depends_on, looking for `from`
+    let mut stack = vec![vec![to.to_string()]];
+    let mut seen = HashSet::new();
+    while let Some(path) = stack.pop() {
+        let last = path.last().cloned().unwrap_or_default();
+        if last == from {
+            let mut cycle = vec![from.to_string()];
+            cycle.extend(path);
+            return Some(cycle);
+        }
+        if !seen.insert(last.clone()) {
+            continue;
+        }
+        if let Some(t) = by_id.get(last.as_str()) {
+            for d in &t.depends_on {
+                let mut next = path.clone();
+                next.push(d.clone());
+                stack.push(next);
+            }
+        }
+    }
+    None
+}
+
+/// Cycles already in the vault (hand-edited files), one path per cycle found
+pub fn find_cycles(tasks: &[Task]) -> Vec<Vec<String>> {
+    let mut out: Vec<Vec<String>> = vec![];
+    for t in tasks {
+        for d in &t.depends_on {
+            if let Some(c) = cycle_path(tasks, &t.id, d) {
+                let members: HashSet<&String> = c.iter().collect();
+                let dup = out
+                    .iter()
+                    .any(|o| o.iter().collect::<HashSet<_>>() == members);
+                if !dup {
+                    out.push(c);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `depends_on` entries that name no task
+pub fn dangling(tasks: &[Task]) -> Vec<(String, String)> {
+    let by_id = index(tasks);
+    tasks
+        .iter()
+        .flat_map(|t| t.depends_on.iter().map(move |d| (t.id.clone(), d.clone())))
+        .filter(|(_, d)| !by_id.contains_key(d.as_str()))
+        .collect()
+}
+
+/// Tasks with dependencies or dependents (the ones worth drawing)
+fn graph_tasks(tasks: &[Task]) -> Vec<&Task> {
+    let targets: HashSet<&str> = tasks
+        .iter()
+        .flat_map(|t| t.depends_on.iter().map(String::as_str))
+        .collect();
+    tasks
+        .iter()
+        .filter(|t| !t.depends_on.is_empty() || targets.contains(t.id.as_str()))
+        .collect()
+}
+
+fn short(id: &str) -> String {
+    id.chars().take(8).collect()
+}
+
+/// Graphviz DOT: closed tasks dashed and grey, blocked ones red
+pub fn to_dot(tasks: &[Task]) -> String {
+    let esc = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let nodes = graph_tasks(tasks);
+    let ids: HashSet<&str> = nodes.iter().map(|t| t.id.as_str()).collect();
+    let mut out = String::from("digraph tm {\n  rankdir=LR;\n  node [shape=box];\n");
+    for t in &nodes {
+        let style = if t.is_closed() {
+            ", style=dashed, color=gray, fontcolor=gray"
+        } else if t.blocked {
+            ", color=red"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "  \"{}\" [label=\"{}\\n[{}] {}\"{style}];\n",
+            t.id,
+            esc(&t.title),
+            esc(&t.status),
+            short(&t.id)
+        ));
+    }
+    for t in &nodes {
+        for d in t.depends_on.iter().filter(|d| ids.contains(d.as_str())) {
+            out.push_str(&format!("  \"{d}\" -> \"{}\";\n", t.id));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Mermaid flowchart, same shape as the DOT output
+pub fn to_mermaid(tasks: &[Task]) -> String {
+    let esc = |s: &str| s.replace('"', "#quot;");
+    let nodes = graph_tasks(tasks);
+    let ids: HashSet<&str> = nodes.iter().map(|t| t.id.as_str()).collect();
+    let mut out = String::from("graph LR\n");
+    for t in &nodes {
+        out.push_str(&format!(
+            "  t{}[\"{}<br/>[{}] {}\"]\n",
+            t.id,
+            esc(&t.title),
+            esc(&t.status),
+            short(&t.id)
+        ));
+    }
+    for t in &nodes {
+        for d in t.depends_on.iter().filter(|d| ids.contains(d.as_str())) {
+            out.push_str(&format!("  t{d} --> t{}\n", t.id));
+        }
+    }
+    let class = |pred: &dyn Fn(&Task) -> bool| -> Vec<String> {
+        nodes
+            .iter()
+            .filter(|t| pred(t))
+            .map(|t| format!("t{}", t.id))
+            .collect()
+    };
+    for (name, def, members) in [
+        (
+            "closed",
+            "stroke-dasharray: 4 4, color: gray",
+            class(&|t| t.is_closed()),
+        ),
+        ("blocked", "stroke: red", class(&|t| t.blocked)),
+    ] {
+        if !members.is_empty() {
+            out.push_str(&format!(
+                "  classDef {name} {def}\n  class {} {name}\n",
+                members.join(",")
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Workflow;
+
+    fn task(id: &str, status: &str, depends_on: &[&str]) -> Task {
+        Task {
+            id: id.into(),
+            title: format!("Task {id}"),
+            status: status.into(),
+            category: Workflow::default().category(status),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ..Task::default()
+        }
+    }
+
+    fn ids(tasks: &[&Task]) -> Vec<String> {
+        tasks.iter().map(|t| t.id.clone()).collect()
+    }
+
+    #[test]
+    fn tasks_are_blocked_until_their_dependencies_close() {
+        let mut tasks = vec![
+            task("a", "todo", &[]),
+            task("b", "done", &[]),
+            task("c", "todo", &["a", "b"]),
+            task("d", "todo", &["b"]),
+            task("e", "done", &["a"]),
+            task("f", "todo", &["gone"]),
+        ];
+        mark_blocked(&mut tasks);
+        let blocked: Vec<&str> = tasks
+            .iter()
+            .filter(|t| t.blocked)
+            .map(|t| t.id.as_str())
+            .collect();
+        assert_eq!(
+            blocked,
+            ["c"],
+            "closed and missing dependencies don't block"
+        );
+        assert_eq!(ids(&blocked_by(&tasks, &tasks[2])), ["a"]);
+        assert!(blocked_by(&tasks, &tasks[3]).is_empty());
+        assert_eq!(ids(&dependents(&tasks, &tasks[0])), ["c", "e"]);
+
+        tasks[0].status = "done".into();
+        tasks[0].category = Workflow::default().category("done");
+        mark_blocked(&mut tasks);
+        assert!(tasks.iter().all(|t| !t.blocked));
+    }
+
+    #[test]
+    fn cycles_are_found_before_and_after_they_exist() {
+        let tasks = vec![
+            task("a", "todo", &["b"]),
+            task("b", "todo", &["c"]),
+            task("c", "todo", &[]),
+        ];
+        assert_eq!(cycle_path(&tasks, "a", "a").unwrap(), ["a", "a"]);
+        assert_eq!(cycle_path(&tasks, "c", "a").unwrap(), ["c", "a", "b", "c"]);
+        assert_eq!(cycle_path(&tasks, "a", "c"), None);
+        assert!(find_cycles(&tasks).is_empty());
+
+        let tasks = vec![
+            task("a", "todo", &["b"]),
+            task("b", "todo", &["c"]),
+            task("c", "todo", &["a"]),
+            task("s", "todo", &["s"]),
+            task("x", "todo", &["a"]),
+        ];
+        let cycles = find_cycles(&tasks);
+        assert_eq!(cycles.len(), 2, "{cycles:?}");
+        assert_eq!(cycles[0], ["a", "b", "c", "a"]);
+        assert_eq!(cycles[1], ["s", "s"]);
+    }
+
+    #[test]
+    fn dangling_references_name_the_task_and_the_missing_id() {
+        let tasks = vec![task("a", "todo", &["b", "nope"]), task("b", "todo", &[])];
+        assert_eq!(dangling(&tasks), [("a".to_string(), "nope".to_string())]);
+    }
+
+    #[test]
+    fn graphs_draw_only_linked_tasks_with_arrows_to_what_they_unblock() {
+        let mut tasks = vec![
+            task("a", "done", &[]),
+            task("b", "todo", &["a", "c"]),
+            task("c", "todo", &[]),
+            task("lonely", "todo", &[]),
+        ];
+        tasks[1].title = "Say \"hi\"".into();
+        mark_blocked(&mut tasks);
+
+        let dot = to_dot(&tasks);
+        assert!(dot.starts_with("digraph tm {\n"), "{dot}");
+        assert!(dot.contains("  \"a\" -> \"b\";\n"), "{dot}");
+        assert!(dot.contains("  \"c\" -> \"b\";\n"), "{dot}");
+        assert!(
+            dot.contains("label=\"Say \\\"hi\\\"\\n[todo] b\", color=red"),
+            "{dot}"
+        );
+        assert!(
+            dot.contains("\"a\" [label=\"Task a\\n[done] a\", style=dashed"),
+            "{dot}"
+        );
+        assert!(!dot.contains("lonely"), "{dot}");
+        assert!(dot.ends_with("}\n"));
+
+        let mermaid = to_mermaid(&tasks);
+        assert!(mermaid.starts_with("graph LR\n"), "{mermaid}");
+        assert!(
+            mermaid.contains("  tb[\"Say #quot;hi#quot;<br/>[todo] b\"]\n"),
+            "{mermaid}"
+        );
+        assert!(mermaid.contains("  ta --> tb\n  tc --> tb\n"), "{mermaid}");
+        assert!(mermaid.contains("  class ta closed\n"), "{mermaid}");
+        assert!(mermaid.contains("  class tb blocked\n"), "{mermaid}");
+        assert!(!mermaid.contains("lonely"), "{mermaid}");
+    }
+}
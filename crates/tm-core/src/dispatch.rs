@@ -0,0 +1,428 @@
+//! Key sequence state machine shared by the TUI and GUI: feeds key tokens through the
+//! keymap trie of the current mode, collects counts (`5j`, `3x`, normal mode only) and
+//! resolves ambiguous prefixes on timeout.
+
+use std::time::Instant;
+
+use crate::{
+    keymap::{Keymap, Lookup, Mode},
+    Action,
+};
+
+/// An action to run, with the count typed before it (if any)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dispatch {
+    pub action: Action,
+    pub count: Option<u32>,
+}
+
+impl Dispatch {
+    /// How many times to repeat the action (count defaults to 1)
+    pub fn times(&self) -> usize {
+        self.count.unwrap_or(1).max(1) as usize
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyOutcome {
+    /// waiting for more keys; show `pending_display()`
+    Pending,
+    Action(Dispatch),
+    /// no binding; the frontend may still handle these keys itself
+    Unbound(Vec<String>),
+    /// Esc dropped a pending sequence/count
+    Cancelled,
+}
+
+/// Pending keys and count between key presses
+#[derive(Clone, Debug, Default)]
+pub struct KeyDispatcher {
+    keys: Vec<String>,
+    count: String,
+    deadline: Option<Instant>,
+    /// mode the pending keys were typed in
+    mode: Mode,
+}
+
+const MAX_COUNT: u32 = 99_999;
+
+impl KeyDispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one key token. Usually yields one outcome; a timed-out-style resolution
+    /// (`g` bound, `gg` bound, then `j`) yields the shorter action followed by the next key's.
+    pub fn feed(&mut self, km: &Keymap, mode: Mode, token: &str, now: Instant) -> Vec<KeyOutcome> {
+        if mode != self.mode {
+            self.reset();
+            self.mode = mode;
+        }
+        if token == "Esc" && self.is_pending() {
+            self.reset();
+            return vec![KeyOutcome::Cancelled];
+        }
+
+        if let Some(d) = digit(token).filter(|_| mode == Mode::Normal) {
+            // digits extend a running count; `0` only counts after another digit
+            if self.keys.is_empty() && (!self.count.is_empty() || d != 0) {
+                if !self.count.is_empty() || km.lookup(mode, &[token.to_string()]) == Lookup::None {
+                    self.push_count(token);
+                    return vec![KeyOutcome::Pending];
+                }
+                // a bound digit (`3` = set done) might still start a count (`3x`):
+                // wait like an ambiguous prefix, its own binding runs on timeout
+                self.keys.push(token.to_string());
+                self.deadline = Some(now + km.timeout);
+                return vec![KeyOutcome::Pending];
+            }
+            if self.is_count_candidate() {
+                let first = self.keys.pop().unwrap_or_default();
+                self.push_count(&first);
+                self.push_count(token);
+                self.deadline = None;
+                return vec![KeyOutcome::Pending];
+            }
+        }
+
+        self.keys.push(token.to_string());
+        match km.lookup(self.mode, &self.keys) {
+            Lookup::Exact(action) => vec![KeyOutcome::Action(self.finish(action))],
+            Lookup::Ambiguous(_) | Lookup::Prefix => {
+                self.deadline = Some(now + km.timeout);
+                vec![KeyOutcome::Pending]
+            }
+            Lookup::None => {
+                let tok = self.keys.pop().unwrap_or_default();
+                if self.keys.is_empty() {
+                    self.count.clear();
+                    self.deadline = None;
+                    return vec![KeyOutcome::Unbound(vec![tok])];
+                }
+                // `3` followed by a non-digit: the `3` was a count after all
+                if self.is_count_candidate() {
+                    let first = self.keys.pop().unwrap_or_default();
+                    self.push_count(&first);
+                    self.deadline = None;
+                    return self.feed(km, mode, &tok, now);
+                }
+                match km.lookup(self.mode, &self.keys) {
+                    Lookup::Ambiguous(action) => {
+                        let mut out = vec![KeyOutcome::Action(self.finish(action))];
+                        out.extend(self.feed(km, mode, &tok, now));
+                        out
+                    }
+                    _ => {
+                        let mut keys = std::mem::take(&mut self.keys);
+                        keys.push(tok);
+                        self.reset();
+                        vec![KeyOutcome::Unbound(keys)]
+                    }
+                }
+            }
+        }
+    }
+
+    /// Call regularly (e.g. before polling for input). Runs the shorter binding of an
+    /// ambiguous sequence once the timeout passed; a dangling prefix comes back as
+    /// `Unbound` (text modes type it). A bare count never times out.
+    pub fn tick(&mut self, km: &Keymap, now: Instant) -> Option<KeyOutcome> {
+        let deadline = self.deadline?;
+        if now < deadline {
+            return None;
+        }
+        match km.lookup(self.mode, &self.keys) {
+            Lookup::Ambiguous(action) | Lookup::Exact(action) => {
+                Some(KeyOutcome::Action(self.finish(action)))
+            }
+            _ => {
+                let keys = std::mem::take(&mut self.keys);
+                self.reset();
+                Some(KeyOutcome::Unbound(keys))
+            }
+        }
+    }
+
+    /// When `tick` next needs to run (for frontends that sleep between events)
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.keys.is_empty() || !self.count.is_empty()
+    }
+
+    /// Count and keys typed so far, e.g. `5g` or `\p`
+    pub fn pending_display(&self) -> String {
+        let mut s = self.count.clone();
+        for k in &self.keys {
+            if k.chars().count() > 1 {
+                s.push_str(&format!("<{k}>"));
+            } else {
+                s.push_str(k);
+            }
+        }
+        s
+    }
+
+    pub fn reset(&mut self) {
+        self.keys.clear();
+        self.count.clear();
+        self.deadline = None;
+    }
+
+    fn finish(&mut self, action: Action) -> Dispatch {
+        let count = self.count.parse::<u32>().ok().map(|n| n.min(MAX_COUNT));
+        self.reset();
+        Dispatch { action, count }
+    }
+
+    fn push_count(&mut self, digit: &str) {
+        if self.count.len() < 5 {
+            self.count.push_str(digit);
+        }
+    }
+
+    /// A single bound non-zero digit waiting to see whether a count follows
+    fn is_count_candidate(&self) -> bool {
+        self.mode == Mode::Normal
+            && self.count.is_empty()
+            && self.keys.len() == 1
+            && digit(&self.keys[0]).map(|d| d != 0).unwrap_or(false)
+    }
+}
+
+fn digit(token: &str) -> Option<u32> {
+    let mut chars = token.chars();
+    let c = chars.next()?;
+    if chars.next().is_some() {
+        return None;
+    }
+    c.to_digit(10)
+}
+
+/// New selection for a navigation action over a list of `len` rows with `page` visible rows,
+/// or None if `action` does not move the selection. `5G`/`5gg` jump to row 5.
+pub fn motion_target(d: Dispatch, selected: usize, len: usize, page: usize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    let last = len - 1;
+    let n = d.times();
+    let half = (page.max(2) / 2).max(1);
+    let target = match d.action {
+        Action::MoveDown => selected.saturating_add(n),
+        Action::MoveUp => selected.saturating_sub(n),
+        Action::HalfPageDown => selected.saturating_add(half.saturating_mul(n)),
+        Action::HalfPageUp => selected.saturating_sub(half.saturating_mul(n)),
+        Action::GoTop => d.count.map(|c| c.max(1) as usize - 1).unwrap_or(0),
+        Action::GoBottom => d.count.map(|c| c.max(1) as usize - 1).unwrap_or(last),
+        _ => return None,
+    };
+    Some(target.min(last))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::keymap::default_keymap;
+
+    fn act(action: Action, count: Option<u32>) -> KeyOutcome {
+        KeyOutcome::Action(Dispatch { action, count })
+    }
+
+    /// Feed keys one after another at `now`, collecting every outcome
+    fn feed(d: &mut KeyDispatcher, km: &Keymap, keys: &[&str], now: Instant) -> Vec<KeyOutcome> {
+        keys.iter()
+            .flat_map(|k| d.feed(km, Mode::Normal, k, now))
+            .collect()
+    }
+
+    #[test]
+    fn counts_repeat_the_action_they_precede() {
+        let km = default_keymap();
+        let mut d = KeyDispatcher::new();
+        let now = Instant::now();
+        let out = feed(&mut d, &km, &["5", "j"], now);
+        assert_eq!(out, [KeyOutcome::Pending, act(Action::MoveDown, Some(5))]);
+        assert!(!d.is_pending());
+
+        assert_eq!(
+            feed(&mut d, &km, &["4", "0"], now).last(),
+            Some(&KeyOutcome::Pending)
+        );
+        assert_eq!(d.pending_display(), "40");
+        assert_eq!(
+            d.feed(&km, Mode::Normal, "G", now),
+            [act(Action::GoBottom, Some(40))]
+        );
+
+        // `0` only counts after another digit
+        assert_eq!(
+            d.feed(&km, Mode::Normal, "0", now),
+            [KeyOutcome::Unbound(vec!["0".into()])]
+        );
+        let out = feed(&mut d, &km, &["9", "9", "9", "9", "9", "9", "k"], now);
+        assert_eq!(out.last(), Some(&act(Action::MoveUp, Some(MAX_COUNT))));
+    }
+
+    #[test]
+    fn bound_digits_wait_to_see_whether_a_count_follows() {
+        let km = default_keymap();
+        let mut d = KeyDispatcher::new();
+        let now = Instant::now();
+        // `1`, `2`, `3` set a status on their own but also start counts
+        assert_eq!(d.feed(&km, Mode::Normal, "3", now), [KeyOutcome::Pending]);
+        assert_eq!(
+            d.feed(&km, Mode::Normal, "x", now),
+            [act(Action::StatusNext, Some(3))]
+        );
+
+        let out = feed(&mut d, &km, &["1", "2", "j"], now);
+        assert_eq!(out.last(), Some(&act(Action::MoveDown, Some(12))));
+
+        // nothing follows: the digit's own binding runs once the timeout passes
+        assert_eq!(d.feed(&km, Mode::Normal, "2", now), [KeyOutcome::Pending]);
+        assert_eq!(d.tick(&km, now + km.timeout / 2), None);
+        assert_eq!(d.deadline(), Some(now + km.timeout));
+        let done = d.tick(&km, now + km.timeout);
+        assert_eq!(done, Some(act(Action::SetDoing, None)));
+        assert!(!d.is_pending());
+    }
+
+    #[test]
+    fn a_bare_count_never_times_out() {
+        let km = default_keymap();
+        let mut d = KeyDispatcher::new();
+        let now = Instant::now();
+        feed(&mut d, &km, &["7", "5"], now);
+        assert_eq!(d.deadline(), None);
+        assert_eq!(d.tick(&km, now + Duration::from_secs(60)), None);
+        assert_eq!(
+            d.feed(&km, Mode::Normal, "j", now),
+            [act(Action::MoveDown, Some(75))]
+        );
+    }
+
+    #[test]
+    fn esc_cancels_a_pending_sequence_or_count() {
+        let km = default_keymap();
+        let mut d = KeyDispatcher::new();
+        let now = Instant::now();
+        for keys in [&["g"][..], &["5"], &["5", "z"], &["3"]] {
+            feed(&mut d, &km, keys, now);
+            assert!(d.is_pending(), "{keys:?}");
+            assert_eq!(
+                d.feed(&km, Mode::Normal, "Esc", now),
+                [KeyOutcome::Cancelled]
+            );
+            assert!(!d.is_pending());
+            assert_eq!(d.deadline(), None);
+        }
+        // with nothing pending Esc is an ordinary key
+        assert_eq!(
+            d.feed(&km, Mode::Normal, "Esc", now),
+            [KeyOutcome::Unbound(vec!["Esc".into()])]
+        );
+    }
+
+    #[test]
+    fn ambiguous_prefixes_resolve_on_the_next_key_or_timeout() {
+        let mut km = default_keymap();
+        km.bind(Mode::Normal, "g", Action::GoBottom);
+        let mut d = KeyDispatcher::new();
+        let now = Instant::now();
+
+        assert_eq!(d.feed(&km, Mode::Normal, "g", now), [KeyOutcome::Pending]);
+        assert_eq!(
+            d.feed(&km, Mode::Normal, "g", now),
+            [act(Action::GoTop, None)]
+        );
+
+        // `g` then an unrelated key runs `g` and then handles the key
+        let out = feed(&mut d, &km, &["g", "j"], now);
+        assert_eq!(
+            out,
+            [
+                KeyOutcome::Pending,
+                act(Action::GoBottom, None),
+                act(Action::MoveDown, None)
+            ]
+        );
+
+        feed(&mut d, &km, &["2", "g"], now);
+        assert_eq!(d.pending_display(), "2g");
+        let out = d.tick(&km, now + km.timeout);
+        assert_eq!(out, Some(act(Action::GoBottom, Some(2))));
+
+        // a prefix with no binding of its own comes back unbound on timeout or a wrong key
+        assert_eq!(d.feed(&km, Mode::Normal, "z", now), [KeyOutcome::Pending]);
+        let out = d.tick(&km, now + km.timeout);
+        assert_eq!(out, Some(KeyOutcome::Unbound(vec!["z".into()])));
+        assert!(!d.is_pending());
+        let out = feed(&mut d, &km, &["z", "q"], now);
+        assert_eq!(
+            out.last(),
+            Some(&KeyOutcome::Unbound(vec!["z".into(), "q".into()]))
+        );
+    }
+
+    #[test]
+    fn text_modes_type_digits_and_a_mode_switch_drops_pending_keys() {
+        let km = default_keymap();
+        let mut d = KeyDispatcher::new();
+        let now = Instant::now();
+        let typed = d.feed(&km, Mode::Insert, "5", now);
+        assert_eq!(typed, [KeyOutcome::Unbound(vec!["5".into()])]);
+        assert_eq!(
+            d.feed(&km, Mode::Insert, "Ctrl-a", now),
+            [act(Action::CursorStart, None)]
+        );
+
+        assert_eq!(d.feed(&km, Mode::Normal, "g", now), [KeyOutcome::Pending]);
+        let out = d.feed(&km, Mode::Command, "g", now);
+        assert_eq!(out, [KeyOutcome::Unbound(vec!["g".into()])]);
+        assert!(!d.is_pending());
+    }
+
+    #[test]
+    fn leader_sequences_dispatch_through_the_configured_leader() {
+        let mut km = default_keymap();
+        km.bind(Mode::Normal, "<leader>g", Action::GoTop);
+        let mut d = KeyDispatcher::new();
+        let now = Instant::now();
+        assert_eq!(d.feed(&km, Mode::Normal, "\\", now), [KeyOutcome::Pending]);
+        assert_eq!(d.pending_display(), "\\");
+        assert_eq!(
+            d.feed(&km, Mode::Normal, "g", now),
+            [act(Action::GoTop, None)]
+        );
+    }
+
+    #[test]
+    fn motions_jump_to_counted_rows_and_stay_in_bounds() {
+        let d = |action, count| Dispatch { action, count };
+        assert_eq!(
+            motion_target(d(Action::MoveDown, Some(3)), 1, 10, 20),
+            Some(4)
+        );
+        assert_eq!(
+            motion_target(d(Action::MoveDown, Some(30)), 1, 10, 20),
+            Some(9)
+        );
+        assert_eq!(motion_target(d(Action::MoveUp, None), 0, 10, 20), Some(0));
+        assert_eq!(
+            motion_target(d(Action::GoBottom, Some(5)), 0, 10, 20),
+            Some(4)
+        );
+        assert_eq!(motion_target(d(Action::GoTop, None), 7, 10, 20), Some(0));
+        assert_eq!(
+            motion_target(d(Action::HalfPageDown, None), 0, 100, 20),
+            Some(10)
+        );
+        assert_eq!(motion_target(d(Action::Quit, None), 0, 10, 20), None);
+        assert_eq!(motion_target(d(Action::MoveDown, None), 0, 0, 20), None);
+    }
+}
@@ -1,7 +1,168 @@
+use crate::{date, sort::apply_sort_arg, GroupBy, ProjectNew, TaskNew, Vault};
 use anyhow::{bail, Result};
 use std::str::FromStr;
 
-use crate::{Status};
+/// An ex command as shown to the user: the signature drives Tab completion and the inline
+/// hint. Built-ins come from `builtin_commands`; plugins add more via `host.register_command`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandDef {
+    pub name: String,
+    /// e.g. `:new "title" project:<project> +tag due:<date>`
+    pub signature: String,
+    pub summary: String,
+}
+
+/// What an argument holds (from its placeholder name); decides what Tab offers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    Text,
+    Project,
+    Tag,
+    Date,
+    Task,
+    View,
+    /// workflow statuses, `next` and `prev`
+    Status,
+    Choice(Vec<String>),
+}
+
+/// One argument of a signature: positional (`"title"`, `<task>`, `(a|b)`) or keyed
+/// (`project:<project>`, `+tag`); `[...]` marks it optional
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Param {
+    pub prefix: Option<String>,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+impl CommandDef {
+    /// The name is the first word of the signature (without `:`)
+    pub fn new(signature: &str, summary: &str) -> Self {
+        let signature = signature.trim();
+        let name = signature
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches(':')
+            .to_string();
+        Self {
+            name,
+            signature: signature.to_string(),
+            summary: summary.to_string(),
+        }
+    }
+
+    pub fn params(&self) -> Vec<Param> {
+        split_signature(&self.signature)
+            .into_iter()
+            .skip(1)
+            .map(|t| parse_param(&t))
+            .collect()
+    }
+}
+
+fn kind_for(placeholder: &str) -> ArgKind {
+    match placeholder.trim_matches(|c| matches!(c, '<' | '>' | '"')) {
+        "project" | "slug" => ArgKind::Project,
+        "tag" => ArgKind::Tag,
+        "date" | "due" => ArgKind::Date,
+        "task" | "id" | "handle" => ArgKind::Task,
+        "view" => ArgKind::View,
+        "status" => ArgKind::Status,
+        _ => ArgKind::Text,
+    }
+}
+
+fn parse_param(tok: &str) -> Param {
+    if let Some(inner) = tok.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        return Param {
+            optional: true,
+            ..parse_param(inner)
+        };
+    }
+    let positional = |kind| Param {
+        prefix: None,
+        kind,
+        optional: false,
+    };
+    if let Some(inner) = tok.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        return positional(ArgKind::Choice(
+            inner.split('|').map(str::to_string).collect(),
+        ));
+    }
+    if tok.starts_with('"') || tok.starts_with('<') {
+        return positional(kind_for(tok));
+    }
+    if let Some(rest) = tok.strip_prefix('+') {
+        return Param {
+            prefix: Some("+".into()),
+            kind: kind_for(rest),
+            optional: true,
+        };
+    }
+    match tok.find(':') {
+        Some(i) => Param {
+            prefix: Some(tok[..=i].to_string()),
+            kind: kind_for(&tok[i + 1..]),
+            optional: true,
+        },
+        None => positional(ArgKind::Choice(vec![tok.to_string()])),
+    }
+}
+
+/// Split a signature on spaces outside quotes and brackets
+fn split_signature(sig: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut depth = 0i32;
+    let mut in_quotes = false;
+    for c in sig.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '[' | '(' | '<' if !in_quotes => depth += 1,
+            ']' | ')' | '>' if !in_quotes => depth -= 1,
+            _ => {}
+        }
+        if c.is_whitespace() && depth <= 0 && !in_quotes {
+            if !cur.is_empty() {
+                out.push(std::mem::take(&mut cur));
+            }
+        } else {
+            cur.push(c);
+        }
+    }
+    if !cur.is_empty() {
+        out.push(cur);
+    }
+    out
+}
+
+/// Commands understood by `parse_ex`
+pub fn builtin_commands() -> Vec<CommandDef> {
+    vec![
+        CommandDef::new(r#":new "title" project:<project> +tag due:<date> parent:<task>"#, "create a task"),
+        CommandDef::new(":status [<task>] <status>", "set the status of a task (default: selected); next/prev step through the workflow"),
+        CommandDef::new(":dep (add|rm) <task>", "the selected task waits for <task> (rm: no longer)"),
+        CommandDef::new(":move [<task>] project:<project>", "move a task to another project (default: selected)"),
+        CommandDef::new(":priority [<task>] (none|low|med|high)", "set the priority of a task (default: selected)"),
+        CommandDef::new(":snooze [<task>] <date>", "hide a task until a date: 1d, mon, next week (none: show it again)"),
+        CommandDef::new(":estimate [<task>] <duration>", "planned effort: 45m, 1h30m, 1.5h (none clears)"),
+        CommandDef::new(":promote [<task>]", "turn a note checkbox into a task file (the line gets its id)"),
+        CommandDef::new(r#":check.add "text""#, "add a checklist item to the selected task's body"),
+        CommandDef::new(":timer (start|stop)", "track time on the selected task (one timer runs at a time)"),
+        CommandDef::new(":open project:<project>", "show one project (empty: all)"),
+        CommandDef::new(
+            ":sort [(updated|created|due|priority|status|title|project)]",
+            "sort by a key (again: reverse it); +key adds a key, due,!p sets the order, empty resets",
+        ),
+        CommandDef::new(":group [(none|project|status|due|tag)]", "group the list (empty: back to the view's)"),
+        CommandDef::new(":view [<view>]", "show a named view (empty: all tasks)"),
+        CommandDef::new(":view.save <name>", "save the current view and filter as a named view"),
+        CommandDef::new(":view.delete <view>", "delete a saved view"),
+        CommandDef::new(r#":project.new "title" +tag"#, "create a project"),
+        CommandDef::new(":config.reload", "re-read config.lua and plugins"),
+    ]
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ExCommand {
@@ -11,23 +172,49 @@ pub enum ExCommand {
         project: Option<String>,
         tags: Vec<String>,
         due: Option<String>,
+        parent: Option<String>,
     },
-    /// :status <id?> (todo|doing|done|next|prev)
+    /// :status <id?> (<status>|next|prev)
     /// id optional → UI may apply to selected task
     Status { id: Option<String>, set: StatusSet },
+    /// :dep add|rm <handle> (for the selected task)
+    Dep { add: bool, on: String },
+    /// :move <id?> project:<slug>
+    Move { id: Option<String>, project: String },
+    /// :priority <id?> (none|low|med|high)
+    Priority { id: Option<String>, value: String },
+    /// :estimate <id?> <duration|none>
+    Estimate { id: Option<String>, value: String },
+    /// :snooze <id?> <when|none>
+    Snooze { id: Option<String>, when: String },
+    /// :promote <id?> (a note checkbox)
+    Promote { id: Option<String> },
+    /// :check.add "text" (for the selected task)
+    CheckAdd { text: String },
+    /// :timer start|stop (for the selected task)
+    Timer { start: bool },
     /// :open project:<slug>
     OpenProject { key: String },
     /// :project.new "Title" +tag
     ProjectNew { title: String, tags: Vec<String> },
+    /// :sort <key|+key|keys?>
+    Sort { arg: Option<String> },
+    /// :group <by?>
+    Group { by: Option<String> },
+    /// :view <name?>
+    OpenView { name: Option<String> },
+    /// :view.save <name>
+    SaveView { name: String },
+    /// :view.delete <name>
+    DeleteView { name: String },
     /// :config.reload
     ConfigReload,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum StatusSet {
-    Todo,
-    Doing,
-    Done,
+    /// a workflow status, checked when it runs
+    Named(String),
     Next,
     Prev,
 }
@@ -36,38 +223,46 @@ impl FromStr for StatusSet {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self> {
         Ok(match s {
-            "todo" => StatusSet::Todo,
-            "doing" | "in-progress" | "in_progress" => StatusSet::Doing,
-            "done" => StatusSet::Done,
             "next" => StatusSet::Next,
             "prev" => StatusSet::Prev,
-            _ => bail!("unknown status '{s}'"),
+            "" => bail!("missing status"),
+            _ => StatusSet::Named(s.to_string()),
         })
     }
 }
 
 /// Very small tokenizer that respects double quotes for a single field (title).
-fn tokenize(input: &str) -> Vec<String> {
+/// Also splits plugin command arguments.
+pub fn tokenize(input: &str) -> Vec<String> {
     let mut out = Vec::new();
     let mut cur = String::new();
     let mut in_quotes = false;
     for c in input.chars() {
         match c {
-            '"' => { in_quotes = !in_quotes; }
+            '"' => {
+                in_quotes = !in_quotes;
+            }
             ' ' if !in_quotes => {
-                if !cur.is_empty() { out.push(cur.clone()); cur.clear(); }
+                if !cur.is_empty() {
+                    out.push(cur.clone());
+                    cur.clear();
+                }
             }
             _ => cur.push(c),
         }
     }
-    if !cur.is_empty() { out.push(cur); }
+    if !cur.is_empty() {
+        out.push(cur);
+    }
     out
 }
 
 /// Parse ex-line (string without the leading colon)
 pub fn parse_ex(line: &str) -> Result<ExCommand> {
     let line = line.trim();
-    if line.is_empty() { bail!("empty command"); }
+    if line.is_empty() {
+        bail!("empty command");
+    }
 
     // config.reload special-case
     if line == "config.reload" {
@@ -84,27 +279,38 @@ pub fn parse_ex(line: &str) -> Result<ExCommand> {
             let mut project = None;
             let mut tags = Vec::new();
             let mut due = None;
+            let mut parent = None;
 
-            // first non-flag token that contains spaces must be quoted → already intact from tokenizer
-            if !toks.is_empty() && !toks[0].starts_with("project:") && !toks[0].starts_with('+') && !toks[0].starts_with("due:") {
-                title = toks.remove(0);
-            }
-
+            // a quoted title is one token; unquoted words are joined
             for t in toks {
                 if let Some(rest) = t.strip_prefix("project:") {
                     project = Some(rest.to_string());
                 } else if let Some(rest) = t.strip_prefix("due:") {
                     due = Some(rest.to_string());
+                } else if let Some(rest) = t.strip_prefix("parent:") {
+                    parent = Some(rest.to_string());
                 } else if let Some(rest) = t.strip_prefix('+') {
-                    if !rest.is_empty() { tags.push(rest.to_string()); }
+                    if !rest.is_empty() {
+                        tags.push(rest.to_string());
+                    }
                 } else if title.is_empty() {
                     title = t;
+                } else {
+                    title = format!("{title} {t}");
                 }
             }
 
-            if title.is_empty() { bail!(":new requires a title (quoted if it has spaces)"); }
+            if title.is_empty() {
+                bail!(":new requires a title");
+            }
 
-            Ok(ExCommand::New { title, project, tags, due })
+            Ok(ExCommand::New {
+                title,
+                project,
+                tags,
+                due,
+                parent,
+            })
         }
 
         "status" => {
@@ -116,12 +322,86 @@ pub fn parse_ex(line: &str) -> Result<ExCommand> {
             } else if toks.len() >= 2 {
                 (Some(toks[0].clone()), toks[1].as_str())
             } else {
-                bail!("usage: :status [<id>] (todo|doing|done|next|prev)")
+                bail!("usage: :status [<id>] (<status>|next|prev)")
             };
             let set = StatusSet::from_str(set_str)?;
             Ok(ExCommand::Status { id: id_opt, set })
         }
 
+        "dep" => match toks.as_slice() {
+            [op, on] if op == "add" || op == "rm" => Ok(ExCommand::Dep {
+                add: op == "add",
+                on: on.clone(),
+            }),
+            _ => bail!("usage: :dep (add|rm) <task>"),
+        },
+
+        "move" => {
+            let mut id = None;
+            let mut project = None;
+            for t in toks {
+                match t.strip_prefix("project:") {
+                    Some(rest) => project = Some(rest.to_string()),
+                    None => id = Some(t),
+                }
+            }
+            let project = project
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("usage: :move [<id>] project:<slug>"))?;
+            Ok(ExCommand::Move { id, project })
+        }
+
+        "priority" => {
+            let (id, value) = match toks.as_slice() {
+                [v] => (None, v.clone()),
+                [id, v, ..] => (Some(id.clone()), v.clone()),
+                [] => bail!("usage: :priority [<id>] (none|low|med|high)"),
+            };
+            Ok(ExCommand::Priority { id, value })
+        }
+
+        "estimate" => {
+            let (id, value) = match toks.as_slice() {
+                [v] => (None, v.clone()),
+                [id, v, ..] => (Some(id.clone()), v.clone()),
+                [] => bail!("usage: :estimate [<id>] <duration> (45m, 1h30m, none)"),
+            };
+            Ok(ExCommand::Estimate { id, value })
+        }
+
+        "snooze" => {
+            // the date may be two words (`next week`); a leading token that isn't part of
+            // one is the task
+            let all = toks.join(" ");
+            let is_date = |s: &str| s == "none" || date::parse_relative(s, date::today()).is_some();
+            match toks.as_slice() {
+                [] => bail!("usage: :snooze [<id>] <when> (1d, mon, next week, 2025-09-01, none)"),
+                [id, rest @ ..] if !rest.is_empty() && !is_date(&all) => Ok(ExCommand::Snooze {
+                    id: Some(id.clone()),
+                    when: rest.join(" "),
+                }),
+                _ => Ok(ExCommand::Snooze {
+                    id: None,
+                    when: all,
+                }),
+            }
+        }
+
+        "promote" => Ok(ExCommand::Promote {
+            id: toks.first().cloned(),
+        }),
+
+        "check.add" => match toks.join(" ") {
+            text if text.trim().is_empty() => bail!(r#"usage: :check.add "text""#),
+            text => Ok(ExCommand::CheckAdd { text }),
+        },
+
+        "timer" => match toks.first().map(String::as_str) {
+            Some("start") => Ok(ExCommand::Timer { start: true }),
+            Some("stop") => Ok(ExCommand::Timer { start: false }),
+            _ => bail!("usage: :timer (start|stop)"),
+        },
+
         "open" => {
             // :open project:<slug>
             let mut key = None;
@@ -130,7 +410,32 @@ pub fn parse_ex(line: &str) -> Result<ExCommand> {
                     key = Some(rest.to_string());
                 }
             }
-            Ok(ExCommand::OpenProject { key: key.unwrap_or_default() })
+            Ok(ExCommand::OpenProject {
+                key: key.unwrap_or_default(),
+            })
+        }
+
+        "sort" => Ok(ExCommand::Sort {
+            arg: toks.into_iter().next(),
+        }),
+
+        "group" => Ok(ExCommand::Group {
+            by: toks.into_iter().next(),
+        }),
+
+        "view" => Ok(ExCommand::OpenView {
+            name: toks.into_iter().next(),
+        }),
+
+        "view.save" | "view.delete" => {
+            let Some(name) = toks.into_iter().next() else {
+                bail!("usage: :{cmd} <name>")
+            };
+            Ok(if cmd == "view.save" {
+                ExCommand::SaveView { name }
+            } else {
+                ExCommand::DeleteView { name }
+            })
         }
 
         "project.new" => {
@@ -143,12 +448,16 @@ pub fn parse_ex(line: &str) -> Result<ExCommand> {
             }
             for t in toks {
                 if let Some(rest) = t.strip_prefix('+') {
-                    if !rest.is_empty() { tags.push(rest.to_string()); }
+                    if !rest.is_empty() {
+                        tags.push(rest.to_string());
+                    }
                 } else if title.is_empty() {
                     title = t;
                 }
             }
-            if title.is_empty() { bail!(":project.new requires a title"); }
+            if title.is_empty() {
+                bail!(":project.new requires a title");
+            }
             Ok(ExCommand::ProjectNew { title, tags })
         }
 
@@ -156,3 +465,155 @@ pub fn parse_ex(line: &str) -> Result<ExCommand> {
     }
 }
 
+/// What a frontend does after `run_ex`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExEffect {
+    /// show this message
+    Done(String),
+    /// switch the project view (`None`: all projects)
+    OpenProject(Option<String>),
+    /// a project was created; show it
+    ProjectCreated(String),
+    /// sort the list by this argument (`apply_sort_arg`); `None` goes back to the view's order
+    Sort(Option<String>),
+    /// group the list; `None` goes back to the view's grouping
+    Group(Option<GroupBy>),
+    /// switch to a named view (`None`: all tasks)
+    OpenView(Option<String>),
+    /// save what is shown now under this name (`View::save_current`)
+    SaveView(String),
+    /// the saved views changed; reload them and show this message
+    ViewsChanged(String),
+    /// done, but show this message as a warning
+    Warning(String),
+    ReloadConfig,
+}
+
+/// Run a parsed ex command against the vault; `selected` is the task a bare `:status` applies to
+pub fn run_ex(vault: &Vault, cmd: ExCommand, selected: Option<&str>) -> Result<ExEffect> {
+    Ok(match cmd {
+        ExCommand::ConfigReload => ExEffect::ReloadConfig,
+        ExCommand::New {
+            title,
+            project,
+            tags,
+            due,
+            parent,
+        } => {
+            let proj = project.unwrap_or_else(|| vault.cfg.default_project.clone());
+            let id = vault.create_task(TaskNew {
+                title,
+                project: proj.clone(),
+                due,
+                tags,
+                parent,
+            })?;
+            ExEffect::Done(format!("created task {id} in project {proj}"))
+        }
+        ExCommand::Dep { add, on } => {
+            let Some(id) = selected else {
+                bail!("no task selected")
+            };
+            if add {
+                let (_, on) = vault.add_dependency(id, &on)?;
+                ExEffect::Done(format!("now waits for {}", &on[..8.min(on.len())]))
+            } else {
+                vault.remove_dependency(id, &on)?;
+                ExEffect::Done(format!("no longer waits for {on}"))
+            }
+        }
+        ExCommand::Move { id, project } => {
+            let Some(id) = id.or_else(|| selected.map(str::to_string)) else {
+                bail!("no task selected")
+            };
+            vault.set_project(&id, &project)?;
+            ExEffect::Done(format!("moved to project {project}"))
+        }
+        ExCommand::Priority { id, value } => {
+            let Some(id) = id.or_else(|| selected.map(str::to_string)) else {
+                bail!("no task selected")
+            };
+            vault.set_priority(&id, &value)?;
+            ExEffect::Done(format!("priority set: {value}"))
+        }
+        ExCommand::Estimate { id, value } => {
+            let Some(id) = id.or_else(|| selected.map(str::to_string)) else {
+                bail!("no task selected")
+            };
+            match vault.set_estimate(&id, &value)? {
+                Some(e) => ExEffect::Done(format!("estimate set: {e}")),
+                None => ExEffect::Done("estimate cleared".into()),
+            }
+        }
+        ExCommand::Snooze { id, when } => {
+            let Some(id) = id.or_else(|| selected.map(str::to_string)) else {
+                bail!("no task selected")
+            };
+            match vault.snooze(&id, &when)? {
+                Some(d) => ExEffect::Done(format!("snoozed until {}", vault.cfg.display_date(&d))),
+                None => ExEffect::Done("no longer deferred".into()),
+            }
+        }
+        ExCommand::Promote { id } => {
+            let Some(id) = id.or_else(|| selected.map(str::to_string)) else {
+                bail!("no task selected")
+            };
+            let new_id = vault.promote_note_task(&vault.resolve_task(&id)?)?;
+            ExEffect::Done(format!("promoted to task {}", &new_id[..8]))
+        }
+        ExCommand::CheckAdd { text } => {
+            let Some(id) = selected else {
+                bail!("no task selected")
+            };
+            vault.add_check(id, &text)?;
+            ExEffect::Done(format!("added to the checklist: {}", text.trim()))
+        }
+        ExCommand::Timer { start } => {
+            let change = match (start, selected) {
+                (true, Some(id)) => vault.start_timer(id)?,
+                (true, None) => bail!("no task selected"),
+                (false, _) => vault.stop_timer(None)?,
+            };
+            match change.message() {
+                (true, msg) => ExEffect::Warning(msg),
+                (false, msg) => ExEffect::Done(msg),
+            }
+        }
+        ExCommand::Status { id, set } => {
+            let Some(id) = id.or_else(|| selected.map(str::to_string)) else {
+                bail!("no task selected")
+            };
+            let change = match set {
+                StatusSet::Named(s) => vault.set_status(&id, &s)?,
+                StatusSet::Next => vault.cycle_status(&id, 1)?,
+                StatusSet::Prev => vault.cycle_status(&id, -1)?,
+            };
+            match change.message() {
+                (true, msg) => ExEffect::Warning(msg),
+                (false, msg) => ExEffect::Done(msg),
+            }
+        }
+        ExCommand::Sort { arg } => {
+            if let Some(a) = &arg {
+                apply_sort_arg(&[], a)?;
+            }
+            ExEffect::Sort(arg)
+        }
+        ExCommand::Group { by } => ExEffect::Group(by.as_deref().map(GroupBy::parse).transpose()?),
+        ExCommand::OpenView { name } => {
+            if let Some(n) = &name {
+                vault.view(n)?;
+            }
+            ExEffect::OpenView(name)
+        }
+        ExCommand::SaveView { name } => ExEffect::SaveView(name),
+        ExCommand::DeleteView { name } => {
+            vault.delete_view(&name)?;
+            ExEffect::ViewsChanged(format!("deleted view {name}"))
+        }
+        ExCommand::OpenProject { key } => ExEffect::OpenProject((!key.is_empty()).then_some(key)),
+        ExCommand::ProjectNew { title, tags } => {
+            ExEffect::ProjectCreated(vault.create_project(ProjectNew { title, tags })?)
+        }
+    })
+}
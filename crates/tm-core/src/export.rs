@@ -0,0 +1,504 @@
+//! `tm export` / `tm import`: the vault as one versioned JSON document. Every task and
+//! project is its frontmatter (unknown keys included, relationships as ids: `parent`,
+//! `depends_on`) plus its Markdown body:
+//!
+//! ```json
+//! { "schema": "tm.vault", "version": 1, "exported": "2025-09-01T10:00:00Z",
+//!   "projects": [{ "key": "home", "title": "Home", ..., "body": "" }],
+//!   "tasks": [{ "id": "01J8...", "title": "Pay rent", "project": "home", ..., "body": "notes\n" }] }
+//! ```
+//!
+//! Import upserts by ULID (projects by key): new ones are written where tm would have
+//! created them (`tasks/YYYY/MM/` from `created`), identical ones are left alone, and
+//! differing ones follow the conflict strategy.
+
+use std::{collections::HashMap, fs};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use ulid::Ulid;
+
+use crate::{
+    date, extract_frontmatter_and_body, find_task_file_by_id, list_project_files,
+    project_file_path, task_file_path, track, Category, Config, Frontmatter, ProjectFrontmatter,
+    Query, Vault,
+};
+
+pub const SCHEMA: &str = "tm.vault";
+/// Bumped when a change would make older tm misread an export
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VaultExport {
+    pub schema: String,
+    pub version: u32,
+    pub exported: String,
+    #[serde(default)]
+    pub projects: Vec<ProjectRecord>,
+    #[serde(default)]
+    pub tasks: Vec<TaskRecord>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TaskRecord {
+    #[serde(flatten)]
+    pub frontmatter: Frontmatter,
+    #[serde(default)]
+    pub body: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProjectRecord {
+    #[serde(flatten)]
+    pub frontmatter: ProjectFrontmatter,
+    #[serde(default)]
+    pub body: String,
+}
+
+impl VaultExport {
+    /// Pretty-printed, one trailing newline
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)? + "\n")
+    }
+
+    pub fn from_json(s: &str) -> Result<Self> {
+        let v: serde_json::Value = serde_json::from_str(s).context("not JSON")?;
+        match (
+            v.get("schema").and_then(|s| s.as_str()),
+            v.get("version").and_then(|v| v.as_u64()),
+        ) {
+            (Some(SCHEMA), Some(n)) if n <= VERSION as u64 => {}
+            (Some(SCHEMA), Some(n)) => {
+                bail!("export version {n} is newer than this tm reads ({VERSION})")
+            }
+            _ => bail!("not a tm export (expected \"schema\": \"{SCHEMA}\" and a version)"),
+        }
+        Ok(serde_json::from_value(v)?)
+    }
+}
+
+/// What to do with a record whose id exists with different contents
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    /// keep the vault's version
+    Skip,
+    /// take the imported one
+    Overwrite,
+    /// whichever has the later `updated`
+    Newest,
+}
+
+impl Conflict {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "newest" | "newest-wins" => Ok(Self::Newest),
+            // the same, named for two-way syncs
+            "local-wins" => Ok(Self::Skip),
+            "remote-wins" => Ok(Self::Overwrite),
+            _ => bail!("unknown conflict strategy '{s}' (use: skip|overwrite|newest-wins, or local-wins|remote-wins)"),
+        }
+    }
+}
+
+/// What an import did (or would do, on a dry run): ids / keys by outcome
+#[derive(Clone, Debug, Default)]
+pub struct ImportReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub skipped: Vec<String>,
+    /// records that can't be imported, with the reason
+    pub errors: Vec<String>,
+}
+
+impl ImportReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} created, {} updated, {} unchanged, {} skipped, {} errors",
+            self.created.len(),
+            self.updated.len(),
+            self.unchanged.len(),
+            self.skipped.len(),
+            self.errors.len()
+        )
+    }
+}
+
+/// Whether `incoming` wins a conflict with `current` (by `updated` timestamps)
+pub(crate) fn incoming_wins(
+    strategy: Conflict,
+    current: Option<&str>,
+    incoming: Option<&str>,
+) -> bool {
+    let ts = |s: Option<&str>| s.and_then(|s| OffsetDateTime::parse(s, &Rfc3339).ok());
+    match strategy {
+        Conflict::Skip => false,
+        Conflict::Overwrite => true,
+        Conflict::Newest => match (ts(current), ts(incoming)) {
+            (Some(c), Some(i)) => i > c,
+            (None, Some(_)) => true,
+            _ => false,
+        },
+    }
+}
+
+/// Task records on their own, ready for `import_json`
+pub(crate) fn as_export(tasks: Vec<TaskRecord>) -> VaultExport {
+    VaultExport {
+        schema: SCHEMA.into(),
+        version: VERSION,
+        exported: track::now_rfc3339(),
+        projects: vec![],
+        tasks,
+    }
+}
+
+/// A task that doesn't exist yet (a new line, heading or remote item): the first open
+/// status in the default project, created now
+pub(crate) fn new_task(id: String, cfg: &Config) -> Result<Frontmatter> {
+    let now = track::now_rfc3339();
+    Ok(Frontmatter {
+        id,
+        key: String::new(),
+        title: String::new(),
+        status: cfg.workflow.first(Category::Open)?.to_string(),
+        project: cfg.default_project.clone(),
+        tags: vec![],
+        priority: "none".into(),
+        due: None,
+        start: None,
+        created: Some(now.clone()),
+        updated: Some(now),
+        done_at: None,
+        parent: None,
+        depends_on: vec![],
+        estimate: None,
+        time: vec![],
+        extra: Default::default(),
+    })
+}
+
+impl Vault {
+    /// Tasks matching `query` (all for an empty one) and the projects: all of them, or
+    /// those the exported tasks belong to when filtered
+    pub fn export_json(&self, query: &Query) -> Result<VaultExport> {
+        let today = date::today();
+        let mut tasks = Vec::new();
+        // note checkboxes live in their notes, not the vault
+        for t in self
+            .list_tasks(None)?
+            .into_iter()
+            .filter(|t| t.note.is_none())
+        {
+            if !query.is_empty() && !query.matches(&t, today) {
+                continue;
+            }
+            let path = self.task_path(&t.id)?;
+            let (frontmatter, body) = extract_frontmatter_and_body(&fs::read_to_string(&path)?)?;
+            tasks.push(TaskRecord { frontmatter, body });
+        }
+        tasks.sort_by(|a, b| a.frontmatter.id.cmp(&b.frontmatter.id));
+        let mut projects = Vec::new();
+        for path in list_project_files(&self.cfg.vault_path) {
+            let (frontmatter, body) = read_project(&fs::read_to_string(&path)?)?;
+            if query.is_empty()
+                || tasks
+                    .iter()
+                    .any(|t| t.frontmatter.project == frontmatter.key)
+            {
+                projects.push(ProjectRecord { frontmatter, body });
+            }
+        }
+        projects.sort_by(|a, b| a.frontmatter.key.cmp(&b.frontmatter.key));
+        Ok(VaultExport {
+            schema: SCHEMA.into(),
+            version: VERSION,
+            exported: crate::track::now_rfc3339(),
+            projects,
+            tasks,
+        })
+    }
+
+    /// Every task record, by id
+    pub(crate) fn records_by_id(&self) -> Result<HashMap<String, TaskRecord>> {
+        let data = self.export_json(&Query::default())?;
+        Ok(data
+            .tasks
+            .into_iter()
+            .map(|t| (t.frontmatter.id.clone(), t))
+            .collect())
+    }
+
+    /// Upsert everything in `data`; with `dry_run` only report what would change
+    pub fn import_json(
+        &self,
+        data: &VaultExport,
+        strategy: Conflict,
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        let mut report = ImportReport::default();
+        if !dry_run {
+            self.init_dirs()?;
+        }
+        for p in &data.projects {
+            let key = &p.frontmatter.key;
+            if key.is_empty() || *key != slug::slugify(key) {
+                report
+                    .errors
+                    .push(format!("project '{key}': key must be a slug"));
+                continue;
+            }
+            let path = project_file_path(&self.cfg.vault_path, key);
+            let current = match fs::read_to_string(&path) {
+                Ok(s) => Some(read_project(&s)?),
+                Err(_) => None,
+            };
+            let outcome = match &current {
+                None => &mut report.created,
+                Some((fm, body)) if *fm == p.frontmatter && *body == p.body => {
+                    report.unchanged.push(key.clone());
+                    continue;
+                }
+                Some((fm, _))
+                    if !incoming_wins(
+                        strategy,
+                        fm.updated.as_deref(),
+                        p.frontmatter.updated.as_deref(),
+                    ) =>
+                {
+                    report.skipped.push(key.clone());
+                    continue;
+                }
+                Some(_) => &mut report.updated,
+            };
+            outcome.push(key.clone());
+            if !dry_run {
+                let yml = serde_yaml::to_string(&p.frontmatter)?;
+                fs::write(&path, format!("---\n{yml}---\n{}", p.body))?;
+            }
+        }
+        for t in &data.tasks {
+            let fm = &t.frontmatter;
+            if Ulid::from_string(&fm.id).is_err() {
+                report
+                    .errors
+                    .push(format!("task '{}': id '{}' is not a ULID", fm.title, fm.id));
+                continue;
+            }
+            // the key names the task's file
+            if !fm.key.is_empty() && fm.key != slug::slugify(&fm.key) {
+                report.errors.push(format!(
+                    "task '{}': key '{}' must be a slug",
+                    fm.title, fm.key
+                ));
+                continue;
+            }
+            let existing = find_task_file_by_id(&self.cfg.vault_path, &fm.id);
+            let current = match &existing {
+                Some(path) => Some(extract_frontmatter_and_body(&fs::read_to_string(path)?)?),
+                None => None,
+            };
+            let outcome = match &current {
+                None => &mut report.created,
+                Some((cur, body)) if cur == fm && *body == t.body => {
+                    report.unchanged.push(fm.id.clone());
+                    continue;
+                }
+                Some((cur, _))
+                    if !incoming_wins(strategy, cur.updated.as_deref(), fm.updated.as_deref()) =>
+                {
+                    report.skipped.push(fm.id.clone());
+                    continue;
+                }
+                Some(_) => &mut report.updated,
+            };
+            outcome.push(fm.id.clone());
+            if !dry_run {
+                // laid out by creation date and slug, like tasks made here
+                let created = fm
+                    .created
+                    .as_deref()
+                    .and_then(date::parse_date)
+                    .unwrap_or_else(date::today);
+                let slug = if fm.key.is_empty() {
+                    slug::slugify(&fm.title)
+                } else {
+                    fm.key.clone()
+                };
+                let path = task_file_path(&self.cfg.vault_path, created, &slug, &fm.id);
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::write(
+                    &path,
+                    format!("---\n{}---\n{}", serde_yaml::to_string(fm)?, t.body),
+                )?;
+                if let Some(old) = existing.filter(|old| *old != path) {
+                    fs::remove_file(old)?;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Merge `patch` (task fields as in an export, `body` included) over the task with its
+    /// `id`, or over a new task, and write it if that changed anything; returns the task.
+    /// `null` clears a field, and `category` (`open`/`active`/`closed`) moves the task to
+    /// the first status of that category unless it is in one already.
+    pub fn upsert_task(
+        &self,
+        patch: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<TaskRecord> {
+        let id = patch.get("id").and_then(|v| v.as_str()).map(str::to_string);
+        let current = match id
+            .as_deref()
+            .and_then(|id| find_task_file_by_id(&self.cfg.vault_path, id))
+        {
+            Some(path) => {
+                let (frontmatter, body) = extract_frontmatter_and_body(&fs::read_to_string(path)?)?;
+                Some(TaskRecord { frontmatter, body })
+            }
+            None => None,
+        };
+        let base = match &current {
+            Some(r) => r.clone(),
+            None => {
+                let id = id.unwrap_or_else(|| Ulid::new().to_string());
+                TaskRecord {
+                    frontmatter: new_task(id, &self.cfg)?,
+                    body: String::new(),
+                }
+            }
+        };
+        let mut merged = serde_json::to_value(&base)?;
+        let fields = merged.as_object_mut().expect("a record is an object");
+        fields.extend(
+            patch
+                .iter()
+                .filter(|(k, _)| *k != "category")
+                .map(|(k, v)| (k.clone(), v.clone())),
+        );
+        let mut record: TaskRecord =
+            serde_json::from_value(merged).context("invalid task fields")?;
+        let fm = &mut record.frontmatter;
+        fm.extra.retain(|_, v| !v.is_null());
+        if fm.title.trim().is_empty() {
+            bail!("a task needs a title");
+        }
+        if fm.key.is_empty() {
+            fm.key = slug::slugify(&fm.title);
+        }
+        if fm.key != slug::slugify(&fm.key) {
+            bail!("task '{}': key '{}' must be a slug", fm.title, fm.key);
+        }
+        let wf = &self.cfg.workflow;
+        if let Some(c) = patch.get("category").and_then(|v| v.as_str()) {
+            let c: Category = serde_json::from_value(c.into())
+                .with_context(|| format!("unknown category '{c}' (use: open|active|closed)"))?;
+            if wf.category(&fm.status) != Some(c) {
+                fm.status = wf.first(c)?.to_string();
+            }
+        }
+        let was_closed = current
+            .as_ref()
+            .is_some_and(|r| wf.category(&r.frontmatter.status) == Some(Category::Closed));
+        match wf.category(&fm.status) {
+            Some(Category::Closed) if !was_closed => {
+                track::stop(&mut fm.time);
+                fm.done_at.get_or_insert_with(track::now_rfc3339);
+            }
+            Some(Category::Closed) => {}
+            _ => fm.done_at = None,
+        }
+        if current.as_ref() == Some(&record) {
+            return Ok(record);
+        }
+        if !patch.contains_key("updated") {
+            record.frontmatter.updated = Some(track::now_rfc3339());
+        }
+        let report =
+            self.import_json(&as_export(vec![record.clone()]), Conflict::Overwrite, false)?;
+        if let Some(e) = report.errors.first() {
+            bail!("{e}");
+        }
+        Ok(record)
+    }
+
+    /// Create project `key` titled `title` unless it exists
+    pub fn ensure_project(&self, key: &str, title: &str) -> Result<()> {
+        if key.is_empty() || key != slug::slugify(key) {
+            bail!("project '{key}': key must be a slug");
+        }
+        let path = project_file_path(&self.cfg.vault_path, key);
+        if path.exists() {
+            return Ok(());
+        }
+        let now = track::now_rfc3339();
+        let fm = ProjectFrontmatter {
+            key: key.to_string(),
+            title: title.to_string(),
+            status: "active".into(),
+            tags: vec![],
+            created: Some(now.clone()),
+            updated: Some(now),
+            description: None,
+            extra: Default::default(),
+        };
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, format!("---\n{}---\n", serde_yaml::to_string(&fm)?))?;
+        Ok(())
+    }
+}
+
+/// A project file's frontmatter and body
+fn read_project(s: &str) -> Result<(ProjectFrontmatter, String)> {
+    let (yml, body) = s
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---"))
+        .context("no project frontmatter")?;
+    let body = body.strip_prefix('\n').unwrap_or(body);
+    Ok((serde_yaml::from_str(yml)?, body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{task, vault};
+
+    #[test]
+    fn task_keys_that_are_not_slugs_are_rejected() {
+        let (dir, vault) = vault();
+        task(&vault, "first");
+        let mut data = vault.export_json(&Query::default()).unwrap();
+        let id = Ulid::new().to_string();
+        let fm = &mut data.tasks[0].frontmatter;
+        fm.id = id.clone();
+        fm.key = "x/../../../escaped".into();
+
+        let report = vault
+            .import_json(&data, Conflict::Overwrite, false)
+            .unwrap();
+        assert!(report.created.is_empty());
+        assert!(
+            report.errors[0].contains("must be a slug"),
+            "{:?}",
+            report.errors
+        );
+        assert!(!dir.path().join(format!("escaped--{id}.md")).exists());
+        assert_eq!(vault.list_tasks(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn upsert_fills_in_a_slug_key_from_the_title() {
+        let (_dir, vault) = vault();
+        let patch = serde_json::json!({ "title": "Write the Report" });
+        let record = vault.upsert_task(patch.as_object().unwrap()).unwrap();
+        assert_eq!(record.frontmatter.key, "write-the-report");
+
+        let patch = serde_json::json!({ "title": "second", "key": "../escaped" });
+        let err = vault.upsert_task(patch.as_object().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("must be a slug"));
+        assert_eq!(vault.list_tasks(None).unwrap().len(), 1);
+    }
+}
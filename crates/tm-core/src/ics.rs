@@ -0,0 +1,441 @@
+//! iCalendar (RFC 5545): `tm export --to ics` writes a VTODO per task, plus an all-day
+//! VEVENT per due date with `--events`, and `tm feed` keeps a configured file up to date
+//! for calendar apps to subscribe to:
+//!
+//! ```lua
+//! ics = { path = '~/Calendars/tm.ics', events = true, query = 'is:open' },
+//! ```
+//!
+//! The UID is the ULID, DTSTAMP the task's `updated` (so an unchanged vault renders the
+//! same bytes and the feed is only rewritten when something changed), priorities are
+//! 1/5/9, tags are CATEGORIES, and a `recur:` key (kept from Taskwarrior imports) becomes
+//! an RRULE.
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime,
+    UtcOffset,
+};
+
+use crate::{date, export::TaskRecord, Category, Query, Vault, Workflow};
+
+/// How often `tm feed --watch` checks whether the feed needs rewriting
+pub const FEED_INTERVAL: Duration = Duration::from_secs(2);
+
+/// `ics = { path, events, query }`: the file `tm feed` keeps up to date
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IcsFeed {
+    pub path: PathBuf,
+    /// an all-day VEVENT for each due date, next to the VTODOs
+    pub events: bool,
+    /// only tasks matching this query (empty: all)
+    pub query: String,
+}
+
+/// Escape a TEXT value
+fn text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// `NAME:value` folded at 75 octets, CRLF-terminated
+fn line(out: &mut String, name: &str, value: &str) {
+    let full = format!("{name}:{value}");
+    let mut width = 0;
+    for c in full.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// The UID tm gives a task
+pub(crate) fn uid(id: &str) -> String {
+    format!("{id}@tm")
+}
+
+fn ics_date(d: Date) -> String {
+    d.format(format_description!("[year][month][day]")).unwrap()
+}
+
+/// An RFC 3339 timestamp as UTC `20261018T184437Z`
+fn ics_time(s: &str) -> Option<String> {
+    let t = OffsetDateTime::parse(s, &Rfc3339)
+        .ok()?
+        .to_offset(UtcOffset::UTC);
+    Some(
+        t.format(format_description!(
+            "[year][month][day]T[hour][minute][second]Z"
+        ))
+        .unwrap(),
+    )
+}
+
+/// Taskwarrior-style `recur` (`weekly`, `3d`, `2w`, `monthly`, `weekdays`, …) as an RRULE
+fn rrule(recur: &str) -> Option<String> {
+    let r = recur.trim().to_lowercase();
+    let named = match r.as_str() {
+        "daily" | "day" => Some("FREQ=DAILY"),
+        "weekdays" => Some("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR"),
+        "weekly" | "week" => Some("FREQ=WEEKLY"),
+        "biweekly" | "fortnight" => Some("FREQ=WEEKLY;INTERVAL=2"),
+        "monthly" | "month" => Some("FREQ=MONTHLY"),
+        "bimonthly" => Some("FREQ=MONTHLY;INTERVAL=2"),
+        "quarterly" => Some("FREQ=MONTHLY;INTERVAL=3"),
+        "semiannual" => Some("FREQ=MONTHLY;INTERVAL=6"),
+        "annual" | "yearly" | "year" => Some("FREQ=YEARLY"),
+        "biannual" | "biyearly" => Some("FREQ=YEARLY;INTERVAL=2"),
+        _ => None,
+    };
+    if let Some(rule) = named {
+        return Some(rule.to_string());
+    }
+    let split = r.find(|c: char| !c.is_ascii_digit())?;
+    let n: u32 = r[..split].parse().ok().filter(|n| *n > 0)?;
+    let (freq, n) = match &r[split..] {
+        "d" | "day" | "days" => ("DAILY", n),
+        "w" | "wk" | "wks" | "week" | "weeks" => ("WEEKLY", n),
+        "mo" | "mos" | "month" | "months" => ("MONTHLY", n),
+        "q" | "qtr" | "qtrs" | "quarter" | "quarters" => ("MONTHLY", n.checked_mul(3)?),
+        "y" | "yr" | "yrs" | "year" | "years" => ("YEARLY", n),
+        _ => return None,
+    };
+    Some(if n == 1 {
+        format!("FREQ={freq}")
+    } else {
+        format!("FREQ={freq};INTERVAL={n}")
+    })
+}
+
+/// Undo `text`
+pub(crate) fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+/// A content line: `DUE;VALUE=DATE:20261020` is name `DUE`, params `[(VALUE, DATE)]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Prop {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+/// The properties of each `kind` component (`VTODO`) in a calendar, lines unfolded;
+/// nested components (alarms) are skipped
+pub(crate) fn components(text: &str, kind: &str) -> Vec<Vec<Prop>> {
+    let mut lines: Vec<String> = vec![];
+    for l in text.lines() {
+        let l = l.trim_end_matches('\r');
+        match (l.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(l.to_string()),
+        }
+    }
+    let mut out = vec![];
+    let mut cur: Option<Vec<Prop>> = None;
+    let mut depth = 0;
+    for l in &lines {
+        // the value starts at the first colon outside a quoted parameter
+        let mut quoted = false;
+        let Some(colon) = l.char_indices().find(|&(_, c)| {
+            quoted ^= c == '"';
+            c == ':' && !quoted
+        }) else {
+            continue;
+        };
+        let (head, value) = (&l[..colon.0], &l[colon.0 + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next().unwrap_or_default().to_uppercase();
+        match (name.as_str(), value) {
+            ("BEGIN", v) if v.eq_ignore_ascii_case(kind) && cur.is_none() => cur = Some(vec![]),
+            ("BEGIN", _) if cur.is_some() => depth += 1,
+            ("END", _) if depth > 0 => depth -= 1,
+            ("END", v) if v.eq_ignore_ascii_case(kind) => out.extend(cur.take()),
+            _ if depth == 0 => {
+                if let Some(props) = cur.as_mut() {
+                    let params = parts
+                        .filter_map(|p| p.split_once('='))
+                        .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+                        .collect();
+                    props.push(Prop {
+                        name,
+                        params,
+                        value: value.to_string(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// `DUE:20261020`, `DUE;VALUE=DATE:20261020` or `DUE:20261020T100000Z` as a date
+pub(crate) fn parse_ics_date(s: &str) -> Option<Date> {
+    Date::parse(s.get(..8)?, format_description!("[year][month][day]")).ok()
+}
+
+/// A UTC `20261018T184437Z` as RFC 3339
+pub(crate) fn parse_ics_time(s: &str) -> Option<String> {
+    let t = time::PrimitiveDateTime::parse(
+        s.trim_end_matches('Z'),
+        format_description!("[year][month][day]T[hour][minute][second]"),
+    )
+    .ok()?;
+    t.assume_utc().format(&Rfc3339).ok()
+}
+
+/// The VTODO STATUS for a workflow status
+pub(crate) fn vtodo_status(status: &str, wf: &Workflow) -> &'static str {
+    match wf.category(status) {
+        Some(Category::Closed) if wf.is_cancelled(status) => "CANCELLED",
+        Some(Category::Closed) => "COMPLETED",
+        Some(Category::Active) => "IN-PROCESS",
+        _ => "NEEDS-ACTION",
+    }
+}
+
+fn header(out: &mut String) {
+    line(out, "BEGIN", "VCALENDAR");
+    line(out, "VERSION", "2.0");
+    line(
+        out,
+        "PRODID",
+        &format!("-//tm//tm {}//EN", env!("CARGO_PKG_VERSION")),
+    );
+    line(out, "CALSCALE", "GREGORIAN");
+}
+
+/// One task as a calendar object (the body of a CalDAV PUT)
+pub(crate) fn todo_object(t: &TaskRecord, uid: &str, wf: &Workflow) -> String {
+    let mut out = String::new();
+    header(&mut out);
+    component(&mut out, t, uid, wf, false);
+    line(&mut out, "END", "VCALENDAR");
+    out
+}
+
+/// The VTODO (and with `events` the VEVENT) of one task
+fn component(out: &mut String, t: &TaskRecord, uid: &str, wf: &Workflow, events: bool) {
+    let fm = &t.frontmatter;
+    let day = |s: &Option<String>| s.as_deref().and_then(date::parse_date);
+    let stamp = fm
+        .updated
+        .as_deref()
+        .or(fm.created.as_deref())
+        .and_then(ics_time)
+        .unwrap_or_else(|| "19700101T000000Z".into());
+    let recur = match fm.extra.get("recur") {
+        Some(serde_yaml::Value::String(r)) => rrule(r),
+        _ => None,
+    };
+    // an RRULE needs a DTSTART; the due date stands in when there is no start. DUE may not
+    // come before DTSTART, so a start after the due date is left out
+    let start = day(&fm.start)
+        .filter(|s| day(&fm.due).is_none_or(|d| *s <= d))
+        .or_else(|| recur.as_ref().and(day(&fm.due)));
+
+    line(out, "BEGIN", "VTODO");
+    line(out, "UID", uid);
+    line(out, "DTSTAMP", &stamp);
+    if let Some(c) = fm.created.as_deref().and_then(ics_time) {
+        line(out, "CREATED", &c);
+    }
+    line(out, "LAST-MODIFIED", &stamp);
+    line(out, "SUMMARY", &text(&fm.title));
+    let body = t.body.trim_matches('\n');
+    if !body.is_empty() {
+        line(out, "DESCRIPTION", &text(body));
+    }
+    if let Some(d) = start {
+        line(out, "DTSTART;VALUE=DATE", &ics_date(d));
+    }
+    if let Some(d) = day(&fm.due) {
+        line(out, "DUE;VALUE=DATE", &ics_date(d));
+    }
+    let status = vtodo_status(&fm.status, wf);
+    line(out, "STATUS", status);
+    if status == "COMPLETED" {
+        if let Some(c) = fm.done_at.as_deref().and_then(ics_time) {
+            line(out, "COMPLETED", &c);
+        }
+    }
+    let priority = match fm.priority.as_str() {
+        "high" => Some("1"),
+        "med" => Some("5"),
+        "low" => Some("9"),
+        _ => None,
+    };
+    if let Some(p) = priority {
+        line(out, "PRIORITY", p);
+    }
+    if !fm.tags.is_empty() {
+        line(
+            out,
+            "CATEGORIES",
+            &fm.tags
+                .iter()
+                .map(|t| text(t))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    }
+    if let Some(p) = &fm.parent {
+        line(out, "RELATED-TO", &format!("{p}@tm"));
+    }
+    for d in &fm.depends_on {
+        line(out, "RELATED-TO;RELTYPE=DEPENDS-ON", &format!("{d}@tm"));
+    }
+    if let Some(r) = recur.filter(|_| start.is_some()) {
+        line(out, "RRULE", &r);
+    }
+    line(out, "END", "VTODO");
+
+    if let Some(due) = day(&fm.due).filter(|_| events) {
+        line(out, "BEGIN", "VEVENT");
+        line(out, "UID", &format!("{}-due@tm", fm.id));
+        line(out, "DTSTAMP", &stamp);
+        line(out, "SUMMARY", &text(&fm.title));
+        line(out, "DTSTART;VALUE=DATE", &ics_date(due));
+        line(
+            out,
+            "DTEND;VALUE=DATE",
+            &ics_date(due.next_day().unwrap_or(due)),
+        );
+        line(out, "TRANSP", "TRANSPARENT");
+        line(out, "RELATED-TO", &format!("{}@tm", fm.id));
+        line(out, "END", "VEVENT");
+    }
+}
+
+impl Vault {
+    /// Matching tasks (all for an empty query) as an iCalendar file
+    pub fn export_ics(&self, query: &Query, events: bool) -> Result<String> {
+        let data = self.export_json(query)?;
+        let mut out = String::new();
+        header(&mut out);
+        line(&mut out, "X-WR-CALNAME", "tm");
+        for t in &data.tasks {
+            component(
+                &mut out,
+                t,
+                &uid(&t.frontmatter.id),
+                &self.cfg.workflow,
+                events,
+            );
+        }
+        line(&mut out, "END", "VCALENDAR");
+        Ok(out)
+    }
+
+    /// Rewrite the `ics` feed if its contents changed; returns whether it was written
+    pub fn write_ics_feed(&self, feed: &IcsFeed) -> Result<bool> {
+        let query = Query::parse(&feed.query).context("ics.query")?;
+        let text = self.export_ics(&query, feed.events)?;
+        if fs::read_to_string(&feed.path).is_ok_and(|old| old == text) {
+            return Ok(false);
+        }
+        if let Some(dir) = feed.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        // calendar apps may read it at any moment: never leave half a file
+        let tmp = feed.path.with_extension("ics.tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &feed.path).with_context(|| format!("writing {}", feed.path.display()))?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{task, vault};
+
+    #[test]
+    fn recurrences_become_rrules() {
+        assert_eq!(rrule("weekly").as_deref(), Some("FREQ=WEEKLY"));
+        assert_eq!(rrule("3d").as_deref(), Some("FREQ=DAILY;INTERVAL=3"));
+        assert_eq!(rrule("1q").as_deref(), Some("FREQ=MONTHLY;INTERVAL=3"));
+        assert_eq!(
+            rrule("1431655765q").as_deref(),
+            Some("FREQ=MONTHLY;INTERVAL=4294967295")
+        );
+        assert_eq!(rrule("1431655766q"), None);
+        assert_eq!(rrule("0d"), None);
+        assert_eq!(rrule("sometimes"), None);
+    }
+
+    #[test]
+    fn text_escapes_and_folds_round_trip() {
+        let s = "Milk, eggs; and \\ more\nsecond line";
+        assert_eq!(unescape(&text(s)), s);
+        let mut out = String::new();
+        let long = "é".repeat(60);
+        line(&mut out, "SUMMARY", &long);
+        assert!(out.split("\r\n").all(|l| l.len() <= 75));
+        let props = components(&format!("BEGIN:VTODO\r\n{out}END:VTODO\r\n"), "VTODO");
+        assert_eq!(props[0][0].value, long);
+    }
+
+    #[test]
+    fn an_export_parses_back() {
+        let (_dir, vault) = vault();
+        let id = task(&vault, "Pay rent, on time");
+        vault.set_due(&id, "2026-10-20").unwrap();
+        vault.set_priority(&id, "high").unwrap();
+        let cancelled = task(&vault, "Call the bank");
+        vault.set_status(&cancelled, "cancelled").unwrap();
+
+        let text = vault.export_ics(&Query::default(), true).unwrap();
+        let todos = components(&text, "VTODO");
+        assert_eq!(todos.len(), 2);
+        let get = |props: &[Prop], name: &str| {
+            props
+                .iter()
+                .find(|p| p.name == name)
+                .map(|p| unescape(&p.value))
+        };
+        let todo = todos
+            .iter()
+            .find(|p| get(p, "UID") == Some(uid(&id)))
+            .unwrap();
+        assert_eq!(get(todo, "SUMMARY").as_deref(), Some("Pay rent, on time"));
+        assert_eq!(
+            get(todo, "DUE").as_deref().and_then(parse_ics_date),
+            date::parse_date("2026-10-20")
+        );
+        assert_eq!(get(todo, "PRIORITY").as_deref(), Some("1"));
+        let other = todos
+            .iter()
+            .find(|p| get(p, "UID") == Some(uid(&cancelled)))
+            .unwrap();
+        assert_eq!(get(other, "STATUS").as_deref(), Some("CANCELLED"));
+        assert_eq!(components(&text, "VEVENT").len(), 1);
+        // same vault, same bytes
+        assert_eq!(vault.export_ics(&Query::default(), true).unwrap(), text);
+    }
+}
@@ -0,0 +1,403 @@
+//! Single-line text input driven by keymap actions (ex bar, filter, edit panels, pickers)
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::Action;
+
+/// Text plus a cursor (byte offset, always on a char boundary)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineEdit {
+    text: String,
+    cursor: usize,
+}
+
+impl LineEdit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Cursor position in chars, for rendering
+    pub fn cursor_chars(&self) -> usize {
+        self.text[..self.cursor].chars().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Replace the text, cursor at the end
+    pub fn set(&mut self, s: impl Into<String>) {
+        self.text = s.into();
+        self.cursor = self.text.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    /// Replace `start..end` (byte offsets) with `s`, cursor after the inserted text
+    pub fn replace_range(&mut self, start: usize, end: usize, s: &str) {
+        let end = end.min(self.text.len());
+        let start = start.min(end);
+        self.text.replace_range(start..end, s);
+        self.cursor = start + s.len();
+    }
+
+    /// Apply an editing action; false if `action` is not an editing action
+    pub fn apply(&mut self, action: Action) -> bool {
+        match action {
+            Action::CursorLeft => self.cursor = self.prev_boundary(self.cursor),
+            Action::CursorRight => self.cursor = self.next_boundary(self.cursor),
+            Action::CursorWordLeft => self.cursor = self.word_start(),
+            Action::CursorWordRight => self.cursor = self.word_end(),
+            Action::CursorStart => self.cursor = 0,
+            Action::CursorEnd => self.cursor = self.text.len(),
+            Action::DeleteBack => {
+                let start = self.prev_boundary(self.cursor);
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            Action::DeleteForward => {
+                let end = self.next_boundary(self.cursor);
+                self.text.replace_range(self.cursor..end, "");
+            }
+            Action::DeleteWordBack => {
+                let start = self.word_start();
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            Action::DeleteToStart => {
+                self.text.replace_range(..self.cursor, "");
+                self.cursor = 0;
+            }
+            Action::DeleteToEnd => self.text.truncate(self.cursor),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prev_boundary(&self, i: usize) -> usize {
+        self.text[..i]
+            .char_indices()
+            .next_back()
+            .map(|(j, _)| j)
+            .unwrap_or(0)
+    }
+
+    fn next_boundary(&self, i: usize) -> usize {
+        self.text[i..]
+            .chars()
+            .next()
+            .map(|c| i + c.len_utf8())
+            .unwrap_or(i)
+    }
+
+    /// Start of the word before the cursor (skipping whitespace first), like readline
+    fn word_start(&self) -> usize {
+        let before = &self.text[..self.cursor];
+        let trimmed = before.trim_end();
+        trimmed
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map(|(j, c)| j + c.len_utf8())
+            .unwrap_or(0)
+    }
+
+    fn word_end(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let skip = after.len() - after.trim_start().len();
+        let rest = &after[skip..];
+        let word = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.cursor + skip + word
+    }
+}
+
+/// Previously submitted lines, browsed with history_prev / history_next. Browsing only
+/// visits entries starting with what was typed before the first step (prefix search);
+/// the typed line comes back past the newest match. With a file (`.tm/history`) every
+/// submitted line is appended to it.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    pos: Option<usize>,
+    draft: String,
+    file: Option<PathBuf>,
+}
+
+/// Entries kept in memory and on disk
+const MAX_HISTORY: usize = 1000;
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// History backed by a file (one line per entry; missing file = empty)
+    pub fn load(path: &Path) -> Self {
+        let mut entries: Vec<String> = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(str::to_string)
+            .collect();
+        let extra = entries.len().saturating_sub(MAX_HISTORY);
+        entries.drain(..extra);
+        Self {
+            entries,
+            file: Some(path.to_path_buf()),
+            ..Self::default()
+        }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Remember a submitted line (skips blanks and immediate repeats)
+    pub fn push(&mut self, line: &str) -> Result<()> {
+        self.reset();
+        let line = line.trim();
+        if line.is_empty() || self.entries.last().map(|l| l == line).unwrap_or(false) {
+            return Ok(());
+        }
+        self.entries.push(line.to_string());
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        if self.entries.len() > MAX_HISTORY {
+            // trim the file along with memory
+            self.entries.remove(0);
+            fs::write(path, self.entries.join("\n") + "\n")
+        } else {
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut f| writeln!(f, "{line}"))
+        }
+        .with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Step back to the previous entry starting with the typed prefix; `current` is the
+    /// line as typed so far
+    pub fn older(&mut self, current: &str) -> Option<&str> {
+        let from = match self.pos {
+            None => {
+                self.draft = current.to_string();
+                self.entries.len()
+            }
+            Some(p) => p,
+        };
+        let shown = self.pos.map(|p| self.entries[p].clone());
+        let found = (0..from).rev().find(|&i| {
+            let e = &self.entries[i];
+            e.starts_with(&self.draft) && Some(e) != shown.as_ref() && e != current
+        })?;
+        self.pos = Some(found);
+        Some(&self.entries[found])
+    }
+
+    /// Step forward; past the newest match the typed line comes back
+    pub fn newer(&mut self) -> Option<&str> {
+        let p = self.pos?;
+        let shown = self.entries[p].clone();
+        let found = (p + 1..self.entries.len())
+            .find(|&i| self.entries[i].starts_with(&self.draft) && self.entries[i] != shown);
+        match found {
+            Some(i) => {
+                self.pos = Some(i);
+                Some(&self.entries[i])
+            }
+            None => {
+                self.pos = None;
+                Some(&self.draft)
+            }
+        }
+    }
+
+    /// Forget the browse position (e.g. when the input closes)
+    pub fn reset(&mut self) {
+        self.pos = None;
+        self.draft.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::{default_keymap, parse_keys, Lookup, Mode};
+
+    fn edit(text: &str, cursor: usize) -> LineEdit {
+        let mut e = LineEdit::new();
+        e.set(text);
+        e.cursor = cursor;
+        e
+    }
+
+    #[test]
+    fn editing_actions_move_and_delete_by_char_and_word() {
+        let mut e = edit("due tomorrow 9am", 12);
+        assert!(e.apply(Action::CursorWordLeft));
+        assert_eq!(e.cursor(), 4);
+        e.apply(Action::CursorWordRight);
+        assert_eq!(e.cursor(), 12);
+        e.apply(Action::DeleteWordBack);
+        assert_eq!((e.text(), e.cursor()), ("due  9am", 4));
+        e.apply(Action::DeleteForward);
+        e.apply(Action::DeleteBack);
+        assert_eq!((e.text(), e.cursor()), ("due9am", 3));
+        e.apply(Action::DeleteToEnd);
+        assert_eq!(e.text(), "due");
+        e.apply(Action::CursorLeft);
+        e.apply(Action::DeleteToStart);
+        assert_eq!((e.text(), e.cursor()), ("e", 0));
+        e.apply(Action::CursorEnd);
+        e.insert_str("nd");
+        e.apply(Action::CursorStart);
+        e.insert('s');
+        assert_eq!((e.text(), e.cursor()), ("send", 1));
+
+        // only editing actions touch the line
+        assert!(!e.apply(Action::Submit));
+        assert!(!e.apply(Action::MoveDown));
+        assert_eq!(e.text(), "send");
+    }
+
+    #[test]
+    fn the_cursor_stays_on_char_boundaries() {
+        let mut e = edit("café ☕", 0);
+        e.apply(Action::CursorEnd);
+        e.apply(Action::CursorLeft);
+        assert_eq!(e.cursor_chars(), 5);
+        e.apply(Action::CursorLeft);
+        e.apply(Action::DeleteBack);
+        assert_eq!((e.text(), e.cursor_chars()), ("caf ☕", 3));
+        e.apply(Action::CursorRight);
+        e.apply(Action::DeleteForward);
+        assert_eq!(e.text(), "caf ");
+        e.apply(Action::CursorStart);
+        e.apply(Action::CursorLeft);
+        e.apply(Action::DeleteBack);
+        assert_eq!((e.text(), e.cursor()), ("caf ", 0));
+    }
+
+    #[test]
+    fn keys_map_to_input_actions_by_mode() {
+        let km = default_keymap();
+        let action = |mode, seq: &str| match km.lookup(mode, &parse_keys(seq, &[])) {
+            Lookup::Exact(a) | Lookup::Ambiguous(a) => Some(a),
+            _ => None,
+        };
+        assert_eq!(action(Mode::Normal, "Up"), Some(Action::MoveUp));
+        assert_eq!(action(Mode::Command, "Up"), Some(Action::HistoryPrev));
+        assert_eq!(action(Mode::Search, "Ctrl-n"), Some(Action::HistoryNext));
+        assert_eq!(action(Mode::Picker, "Up"), Some(Action::MoveUp));
+        assert_eq!(action(Mode::Insert, "Up"), None);
+        assert_eq!(action(Mode::Command, "Tab"), Some(Action::Complete));
+        assert_eq!(action(Mode::Search, "Tab"), None);
+        // the same editing keys in every text mode; normal mode keeps its own meaning
+        for mode in [Mode::Insert, Mode::Command, Mode::Search, Mode::Picker] {
+            assert_eq!(
+                action(mode, "Ctrl-w"),
+                Some(Action::DeleteWordBack),
+                "{mode}"
+            );
+            assert_eq!(action(mode, "Esc"), Some(Action::Cancel), "{mode}");
+            assert_eq!(action(mode, "j"), None, "{mode}");
+        }
+        assert_eq!(action(Mode::Normal, "Ctrl-u"), Some(Action::HalfPageUp));
+        assert_eq!(action(Mode::Insert, "Ctrl-u"), Some(Action::DeleteToStart));
+    }
+
+    #[test]
+    fn history_browses_back_and_restores_the_draft() {
+        let mut h = History::new();
+        for line in ["due 1", "  ", "due 1", "tag +x"] {
+            h.push(line).unwrap();
+        }
+        assert_eq!(h.entries(), ["due 1", "tag +x"]);
+        assert_eq!(h.newer(), None);
+        assert_eq!(h.older(""), Some("tag +x"));
+        assert_eq!(h.older("tag +x"), Some("due 1"));
+        assert_eq!(h.older("due 1"), None);
+        assert_eq!(h.newer(), Some("tag +x"));
+        assert_eq!(h.newer(), Some(""));
+        assert_eq!(h.newer(), None);
+        assert_eq!(History::new().older("x"), None);
+    }
+
+    #[test]
+    fn history_steps_only_through_entries_with_the_typed_prefix() {
+        let mut h = History::new();
+        for line in ["due 1", "tag +x", "due 2", "due 1"] {
+            h.push(line).unwrap();
+        }
+        assert_eq!(h.older("du"), Some("due 1"));
+        // the line on screen is not offered again right away
+        assert_eq!(h.older("due 1"), Some("due 2"));
+        assert_eq!(h.older("due 2"), Some("due 1"));
+        assert_eq!(h.older("due 1"), None);
+        assert_eq!(h.newer(), Some("due 2"));
+        assert_eq!(h.newer(), Some("due 1"));
+        assert_eq!(h.newer(), Some("du"));
+        h.reset();
+        assert_eq!(h.older("tag"), Some("tag +x"));
+    }
+
+    #[test]
+    fn history_is_appended_to_its_file_and_capped_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".tm").join("history");
+        let mut h = History::load(&path);
+        assert!(h.entries().is_empty());
+        h.push("new \"a\"").unwrap();
+        h.push("open project:work").unwrap();
+        h.push("open project:work").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "new \"a\"\nopen project:work\n"
+        );
+        assert_eq!(History::load(&path).entries(), h.entries());
+
+        let lines: Vec<String> = (0..MAX_HISTORY + 5).map(|i| format!("cmd {i}")).collect();
+        fs::write(&path, lines.join("\n")).unwrap();
+        let mut h = History::load(&path);
+        assert_eq!(h.entries().len(), MAX_HISTORY);
+        assert_eq!(h.entries()[0], "cmd 5");
+        // the next push trims the file along with memory
+        h.push("last").unwrap();
+        let kept = fs::read_to_string(&path).unwrap();
+        assert_eq!(kept.lines().count(), MAX_HISTORY);
+        assert_eq!(kept.lines().next(), Some("cmd 6"));
+        assert_eq!(kept.lines().last(), Some("last"));
+    }
+}
@@ -0,0 +1,936 @@
+//! GitHub / GitLab issues: `tm sync issues` keeps a project in step with a repository's issues.
+//!
+//! ```lua
+//! issues = {
+//!   assignee = '@me',            -- import issues assigned to this user ('@me': the token's owner)
+//!   labels = { 'tm' },           -- ...or carrying one of these labels
+//!   repos = {
+//!     { repo = 'acme/api', project = 'api' },    -- token from $TM_GITHUB_TOKEN
+//!     { forge = 'gitlab', repo = 'group/web', project = 'web', url = 'https://gitlab.example.com', labels = { 'frontend' } },
+//!   },
+//! },
+//! ```
+//!
+//! Open issues that match become tasks with the issue (`github: acme/api#12`) and its page
+//! (`source:`) in their frontmatter. From then on the title, labels (as tags, next to any
+//! added here), GitLab's due date and the open/closed state follow the issue; closing or
+//! reopening the task closes or reopens the issue (a cancelled task as "not planned").
+//! `.tm/issues.json` keeps what was agreed per task, `.tm/issues-repos.json` when each
+//! repository was last read, so a sync only fetches issues updated since; both are saved
+//! after each repository is read and each issue is closed or reopened. A task deleted
+//! here stays deleted; its issue is left alone.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+use ulid::Ulid;
+
+use crate::{
+    date,
+    export::{self, Conflict, TaskRecord},
+    sync::SyncReport,
+    track, Category, Frontmatter, Vault, Workflow,
+};
+
+/// The default variables holding the API tokens
+pub const ENV_GITHUB_TOKEN: &str = "TM_GITHUB_TOKEN";
+pub const ENV_GITLAB_TOKEN: &str = "TM_GITLAB_TOKEN";
+
+/// The frontmatter key with the issue's web page
+pub const SOURCE_KEY: &str = "source";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    #[default]
+    Github,
+    Gitlab,
+}
+
+impl Forge {
+    /// Also the frontmatter key holding the issue reference
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Forge::Github => "github",
+            Forge::Gitlab => "gitlab",
+        }
+    }
+}
+
+/// `issues = { assignee, labels, repos }`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Issues {
+    /// import issues assigned to this user (default `@me`, the token's owner; `''` for none)
+    pub assignee: Option<String>,
+    /// ...or labelled with any of these
+    pub labels: Vec<String>,
+    pub repos: Vec<IssueRepo>,
+}
+
+/// One repository synced into a project; `assignee` and `labels` override the shared ones
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IssueRepo {
+    pub forge: Forge,
+    /// `owner/name` (GitLab: the full `group/subgroup/name` path)
+    pub repo: String,
+    pub project: String,
+    /// the API (default `https://api.github.com`) or the GitLab instance (`https://gitlab.com`)
+    pub url: String,
+    /// the environment variable holding the token (default `TM_GITHUB_TOKEN` / `TM_GITLAB_TOKEN`)
+    pub token_env: String,
+    pub assignee: Option<String>,
+    pub labels: Option<Vec<String>>,
+}
+
+impl IssueRepo {
+    /// `acme/api#12`, the value of the `github:` / `gitlab:` key
+    fn reference(&self, number: u64) -> String {
+        format!("{}#{number}", self.repo)
+    }
+
+    fn api(&self) -> String {
+        match (self.forge, self.url.trim_end_matches('/')) {
+            (Forge::Github, "") => "https://api.github.com".into(),
+            (Forge::Github, url) => url.into(),
+            (Forge::Gitlab, "") => "https://gitlab.com/api/v4".into(),
+            (Forge::Gitlab, url) => format!("{url}/api/v4"),
+        }
+    }
+
+    fn issues_url(&self) -> String {
+        match self.forge {
+            Forge::Github => format!("{}/repos/{}/issues", self.api(), self.repo),
+            Forge::Gitlab => format!(
+                "{}/projects/{}/issues",
+                self.api(),
+                self.repo.replace('/', "%2F")
+            ),
+        }
+    }
+}
+
+impl Issues {
+    /// A repository's filter: the assignee (if any) and the labels
+    fn filter<'a>(&'a self, repo: &'a IssueRepo) -> (Option<&'a str>, &'a [String]) {
+        let assignee = repo
+            .assignee
+            .as_deref()
+            .or(self.assignee.as_deref())
+            .unwrap_or("@me");
+        (
+            Some(assignee).filter(|a| !a.is_empty()),
+            repo.labels.as_deref().unwrap_or(&self.labels),
+        )
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        let mut seen = BTreeSet::new();
+        for r in &self.repos {
+            if r.repo.split('/').filter(|s| !s.is_empty()).count() < 2 {
+                bail!("issues: repo '{}' must be `owner/name`", r.repo);
+            }
+            if r.project.is_empty() || r.project != slug::slugify(&r.project) {
+                bail!("issues: {}: `project` must be a project key", r.repo);
+            }
+            if !seen.insert((r.forge, &r.repo)) {
+                bail!("issues: {} {} is listed twice", r.forge.as_str(), r.repo);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What `.tm/issues.json` keeps per task id
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Synced {
+    forge: Forge,
+    /// `acme/api#12`
+    issue: String,
+    /// the issue's `updated_at` as last read
+    remote: String,
+    /// the state both sides agreed on
+    closed: bool,
+    /// the tags that came from labels
+    labels: Vec<String>,
+}
+
+/// An issue in the shape both forges share
+struct Issue {
+    number: u64,
+    title: String,
+    body: String,
+    closed: bool,
+    /// GitHub's `state_reason: not_planned`
+    not_planned: bool,
+    labels: Vec<String>,
+    assignees: Vec<String>,
+    url: String,
+    updated: String,
+    closed_at: Option<String>,
+    due: Option<String>,
+}
+
+impl Issue {
+    fn from_json(forge: Forge, v: &Json) -> Option<Self> {
+        let s = |k: &str| v[k].as_str().map(str::to_string);
+        let names = |k: &str, field: &str| -> Vec<String> {
+            v[k].as_array()
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|x| {
+                            x.as_str().or_else(|| x[field].as_str()).map(str::to_string)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let (number, body, url, user) = match forge {
+            Forge::Github => (v["number"].as_u64()?, s("body"), s("html_url"), "login"),
+            Forge::Gitlab => (
+                v["iid"].as_u64()?,
+                s("description"),
+                s("web_url"),
+                "username",
+            ),
+        };
+        Some(Self {
+            number,
+            title: s("title")?,
+            body: body.unwrap_or_default(),
+            closed: v["state"].as_str() == Some("closed"),
+            not_planned: v["state_reason"].as_str() == Some("not_planned"),
+            labels: names("labels", "name"),
+            assignees: names("assignees", user),
+            url: url.unwrap_or_default(),
+            updated: s("updated_at").unwrap_or_default(),
+            closed_at: s("closed_at"),
+            due: s("due_date"),
+        })
+    }
+
+    fn matches(&self, assignee: Option<&str>, labels: &[String]) -> bool {
+        assignee.is_some_and(|a| self.assignees.iter().any(|x| x.eq_ignore_ascii_case(a)))
+            || self
+                .labels
+                .iter()
+                .any(|l| labels.iter().any(|w| w.eq_ignore_ascii_case(l)))
+    }
+}
+
+/// A label as a tag: `good first issue` → `good-first-issue`
+fn label_tag(label: &str) -> String {
+    slug::slugify(label)
+}
+
+/// A forge's REST API, with the token it needs
+struct Client {
+    agent: ureq::Agent,
+    forge: Forge,
+    token: String,
+}
+
+fn http_error(method: &str, url: &str, e: ureq::Error) -> anyhow::Error {
+    match e {
+        ureq::Error::Status(code, resp) => {
+            let text: String = resp
+                .into_string()
+                .unwrap_or_default()
+                .chars()
+                .take(200)
+                .collect();
+            anyhow!("issues: {method} {url}: {code} {}", text.trim())
+        }
+        e => anyhow!("issues: {method} {url}: {e}"),
+    }
+}
+
+/// The `rel="next"` URL of a `Link` header
+fn next_link(link: &str) -> Option<String> {
+    link.split(',')
+        .find(|l| l.contains("rel=\"next\""))
+        .and_then(|l| Some(l[l.find('<')? + 1..l.find('>')?].to_string()))
+}
+
+impl Client {
+    fn new(repo: &IssueRepo) -> Result<Self> {
+        let var = match (repo.token_env.as_str(), repo.forge) {
+            ("", Forge::Github) => ENV_GITHUB_TOKEN,
+            ("", Forge::Gitlab) => ENV_GITLAB_TOKEN,
+            (var, _) => var,
+        };
+        let token = env::var(var)
+            .ok()
+            .filter(|t| !t.is_empty())
+            .with_context(|| format!("issues: set ${var} to a {} token", repo.forge.as_str()))?;
+        Ok(Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+            forge: repo.forge,
+            token,
+        })
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let req = self
+            .agent
+            .request(method, url)
+            .set("User-Agent", concat!("tm/", env!("CARGO_PKG_VERSION")));
+        match self.forge {
+            Forge::Github => req
+                .set("Accept", "application/vnd.github+json")
+                .set("X-GitHub-Api-Version", "2022-11-28")
+                .set("Authorization", &format!("Bearer {}", self.token)),
+            Forge::Gitlab => req.set("PRIVATE-TOKEN", &self.token),
+        }
+    }
+
+    fn get(&self, url: &str) -> Result<(Json, Option<String>)> {
+        let resp = self
+            .request("GET", url)
+            .call()
+            .map_err(|e| http_error("GET", url, e))?;
+        let next = resp.header("Link").and_then(next_link);
+        let json = serde_json::from_str(&resp.into_string()?)
+            .with_context(|| format!("issues: GET {url}: the response is not JSON"))?;
+        Ok((json, next))
+    }
+
+    /// The login the token belongs to
+    fn me(&self, api: &str) -> Result<String> {
+        let (user, _) = self.get(&format!("{api}/user"))?;
+        let field = if self.forge == Forge::Github {
+            "login"
+        } else {
+            "username"
+        };
+        user[field]
+            .as_str()
+            .map(str::to_string)
+            .with_context(|| format!("issues: {api}/user has no {field}"))
+    }
+
+    /// Every page of a repository's issues for `query` (pull requests left out)
+    fn issues(&self, repo: &IssueRepo, query: &[(&str, String)]) -> Result<Vec<Issue>> {
+        let query: String = query
+            .iter()
+            .map(|(k, v)| format!("&{k}={}", urlencode(v)))
+            .collect();
+        let mut url = Some(format!("{}?per_page=100{query}", repo.issues_url()));
+        let mut out = vec![];
+        while let Some(u) = url {
+            let (page, next) = self.get(&u)?;
+            let items = page
+                .as_array()
+                .with_context(|| format!("issues: GET {u}: expected a list of issues"))?;
+            out.extend(
+                items
+                    .iter()
+                    .filter(|i| i.get("pull_request").is_none())
+                    .filter_map(|i| Issue::from_json(self.forge, i)),
+            );
+            url = next;
+        }
+        Ok(out)
+    }
+
+    /// Close (`not_planned` on GitHub for a cancelled task) or reopen an issue
+    fn set_state(
+        &self,
+        repo: &IssueRepo,
+        number: u64,
+        closed: bool,
+        not_planned: bool,
+    ) -> Result<()> {
+        let url = format!("{}/{number}", repo.issues_url());
+        let (method, body) = match (self.forge, closed) {
+            (Forge::Github, true) => (
+                "PATCH",
+                json!({ "state": "closed", "state_reason": if not_planned { "not_planned" } else { "completed" } }),
+            ),
+            (Forge::Github, false) => ("PATCH", json!({ "state": "open" })),
+            (Forge::Gitlab, closed) => (
+                "PUT",
+                json!({ "state_event": if closed { "close" } else { "reopen" } }),
+            ),
+        };
+        self.request(method, &url)
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string())
+            .map_err(|e| http_error(method, &url, e))?;
+        Ok(())
+    }
+}
+
+fn urlencode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Move `fm` to the issue's state, if it isn't in it already
+fn set_closed(fm: &mut Frontmatter, issue: &Issue, wf: &Workflow) -> Result<()> {
+    let closed = wf.category(&fm.status) == Some(Category::Closed);
+    if issue.closed && !closed {
+        fm.status = if issue.not_planned {
+            wf.cancel_status()?
+        } else {
+            wf.first(Category::Closed)?
+        }
+        .to_string();
+        track::stop(&mut fm.time);
+        fm.done_at = issue
+            .closed_at
+            .clone()
+            .or_else(|| Some(track::now_rfc3339()));
+    } else if !issue.closed && closed {
+        fm.status = wf.first(Category::Open)?.to_string();
+        fm.done_at = None;
+    }
+    Ok(())
+}
+
+/// Put what the issue says into `fm`: title, labels (replacing those from `old_labels`), due date
+fn apply(issue: &Issue, fm: &mut Frontmatter, old_labels: &[String]) -> Vec<String> {
+    fm.title = issue.title.clone();
+    if fm.key.is_empty() {
+        fm.key = slug::slugify(&fm.title);
+    }
+    let labels: Vec<String> = issue
+        .labels
+        .iter()
+        .map(|l| label_tag(l))
+        .filter(|t| !t.is_empty())
+        .collect();
+    let mut tags: Vec<String> = fm
+        .tags
+        .iter()
+        .filter(|t| !old_labels.contains(t) || labels.contains(t))
+        .cloned()
+        .collect();
+    tags.extend(labels.iter().filter(|l| !fm.tags.contains(l)).cloned());
+    fm.tags = tags;
+    if let Some(due) = &issue.due {
+        fm.due = date::same_day(&fm.due, date::parse_date(due));
+    }
+    labels
+}
+
+impl Vault {
+    /// Import and update the issues of the configured repositories, then close or reopen
+    /// the issues of tasks closed or reopened here. `full` reads every open issue again
+    /// instead of those updated since the last sync.
+    pub fn sync_issues(&self, full: bool) -> Result<SyncReport> {
+        let cfg = self.cfg.issues.as_ref().filter(|c| !c.repos.is_empty());
+        let cfg = cfg.context("issues: not configured (add `issues = { repos = { { repo, project } } }` to config.lua)")?;
+        let wf = &self.cfg.workflow;
+        let mut state: BTreeMap<String, Synced> = self.load_sync_state("issues")?;
+        let mut since: BTreeMap<String, String> = self.load_sync_state("issues-repos")?;
+        let mut local = self.records_by_id()?;
+        let mut report = SyncReport::default();
+        let mut clients = vec![];
+
+        for repo in &cfg.repos {
+            let client = Client::new(repo)?;
+            let (assignee, labels) = cfg.filter(repo);
+            let assignee = match assignee {
+                Some("@me") => Some(client.me(&repo.api())?),
+                a => a.map(str::to_string),
+            };
+            let repo_key = format!("{}:{}", repo.forge.as_str(), repo.repo);
+            let last = since.get(&repo_key).filter(|_| !full);
+            // after the first sync, everything updated since: known issues change however
+            // they stopped matching, new ones are filtered here
+            let issues = match last {
+                Some(t) => {
+                    let key = if repo.forge == Forge::Github {
+                        "since"
+                    } else {
+                        "updated_after"
+                    };
+                    client.issues(repo, &[("state", "all".into()), (key, t.clone())])?
+                }
+                None => {
+                    let (open, by_assignee) = match repo.forge {
+                        Forge::Github => ("open", "assignee"),
+                        Forge::Gitlab => ("opened", "assignee_username"),
+                    };
+                    let filters = assignee
+                        .iter()
+                        .map(|a| (by_assignee, a.clone()))
+                        .chain(labels.iter().map(|l| ("labels", l.clone())));
+                    let mut by_number = BTreeMap::new();
+                    for filter in filters {
+                        for i in client.issues(repo, &[("state", open.into()), filter])? {
+                            by_number.insert(i.number, i);
+                        }
+                    }
+                    by_number.into_values().collect()
+                }
+            };
+
+            let key = repo.forge.as_str();
+            let mut pulled: Vec<TaskRecord> = vec![];
+            let known: HashMap<String, String> = local
+                .values()
+                .filter_map(|r| {
+                    r.frontmatter
+                        .extra
+                        .get(key)
+                        .and_then(|v| v.as_str())
+                        .map(|i| (i.to_string(), r.frontmatter.id.clone()))
+                })
+                .chain(
+                    state
+                        .iter()
+                        .filter(|(_, s)| s.forge == repo.forge)
+                        .map(|(id, s)| (s.issue.clone(), id.clone())),
+                )
+                .collect();
+            for issue in &issues {
+                let reference = repo.reference(issue.number);
+                let id = known.get(&reference).cloned();
+                let last = id.as_ref().and_then(|id| state.get(id));
+                if last.is_some_and(|s| s.remote == issue.updated) {
+                    continue;
+                }
+                let (mut fm, body) = match id.as_ref().map(|id| local.get(id)) {
+                    Some(Some(r)) => (r.frontmatter.clone(), r.body.clone()),
+                    // deleted here
+                    Some(None) if last.is_some() => continue,
+                    _ if issue.closed || !issue.matches(assignee.as_deref(), labels) => continue,
+                    _ => {
+                        self.ensure_project(&repo.project, &repo.repo)?;
+                        let text = issue.body.trim().replace("\r\n", "\n");
+                        let body = if text.is_empty() {
+                            String::new()
+                        } else {
+                            format!("\n{text}\n")
+                        };
+                        (
+                            export::new_task(
+                                id.clone().unwrap_or_else(|| Ulid::new().to_string()),
+                                &self.cfg,
+                            )?,
+                            body,
+                        )
+                    }
+                };
+                let before = (fm.clone(), body.clone());
+                let tags = apply(issue, &mut fm, last.map_or(&[][..], |s| &s.labels));
+                if last.is_none_or(|s| s.closed != issue.closed) {
+                    set_closed(&mut fm, issue, wf)?;
+                }
+                if last.is_none() && !local.contains_key(&fm.id) {
+                    fm.project = repo.project.clone();
+                }
+                fm.extra.insert(key.into(), reference.clone().into());
+                fm.extra.insert(SOURCE_KEY.into(), issue.url.clone().into());
+                if (&fm, &body) != (&before.0, &before.1) {
+                    fm.updated = Some(track::now_rfc3339());
+                    report.pulled.push(fm.title.clone());
+                    let record = TaskRecord {
+                        frontmatter: fm.clone(),
+                        body,
+                    };
+                    local.insert(fm.id.clone(), record.clone());
+                    pulled.push(record);
+                }
+                state.insert(
+                    fm.id.clone(),
+                    Synced {
+                        forge: repo.forge,
+                        issue: reference,
+                        remote: issue.updated.clone(),
+                        closed: issue.closed,
+                        labels: tags,
+                    },
+                );
+            }
+            // a full read only sees open issues, so it doesn't move a mark that already exists
+            if !(full && since.contains_key(&repo_key)) {
+                if let Some(newest) = issues.iter().map(|i| &i.updated).max() {
+                    since.insert(repo_key, newest.clone());
+                }
+            }
+            if !pulled.is_empty() {
+                self.import_json(&export::as_export(pulled), Conflict::Overwrite, false)?;
+            }
+            self.save_sync_state("issues", &state)?;
+            self.save_sync_state("issues-repos", &since)?;
+            clients.push((repo, client));
+        }
+
+        let changed: Vec<(String, bool)> = state
+            .iter()
+            .filter_map(|(id, s)| {
+                let closed =
+                    wf.category(&local.get(id)?.frontmatter.status) == Some(Category::Closed);
+                (closed != s.closed).then(|| (id.clone(), closed))
+            })
+            .collect();
+        for (id, closed) in changed {
+            let (r, s) = (&local[&id], &state[&id]);
+            let (path, number) = s
+                .issue
+                .rsplit_once('#')
+                .with_context(|| format!("issues: malformed reference '{}'", s.issue))?;
+            // a repository no longer configured is left alone
+            let Some((repo, client)) = clients
+                .iter()
+                .find(|(repo, _)| repo.forge == s.forge && repo.repo == path)
+            else {
+                continue;
+            };
+            let number = number
+                .parse()
+                .with_context(|| format!("issues: malformed reference '{}'", s.issue))?;
+            client.set_state(repo, number, closed, wf.is_cancelled(&r.frontmatter.status))?;
+            report.pushed.push(r.frontmatter.title.clone());
+            if let Some(s) = state.get_mut(&id) {
+                s.closed = closed;
+            }
+            self.save_sync_state("issues", &state)?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::tests::{FakeServer, Hit, Reply};
+
+    const TOKEN_ENV: &str = "TM_TEST_FORGE_TOKEN";
+
+    /// One repository's issues on a fake GitHub or GitLab: `GET /user`, the issue list
+    /// (whatever the query) and closing / reopening an issue, which bumps its `updated_at`.
+    /// Requests without the token get a 401; changes to issue `fail` get a 500.
+    struct Forge {
+        server: FakeServer,
+        issues: Arc<Mutex<Vec<Json>>>,
+        /// `METHOD path body` of each change
+        changes: Arc<Mutex<Vec<String>>>,
+        fail: Arc<Mutex<Option<u64>>>,
+    }
+
+    impl Forge {
+        fn start(forge: super::Forge, issues: Vec<Json>) -> Self {
+            env::set_var(TOKEN_ENV, "tok");
+            let issues = Arc::new(Mutex::new(issues));
+            let changes: Arc<Mutex<Vec<String>>> = Arc::default();
+            let fail: Arc<Mutex<Option<u64>>> = Arc::default();
+            let (list, log, failing) = (issues.clone(), changes.clone(), fail.clone());
+            let mut clock = 0;
+            let server = FakeServer::start(move |hit: &Hit| -> Reply {
+                let authorized = match forge {
+                    super::Forge::Github => hit.header("Authorization") == Some("Bearer tok"),
+                    super::Forge::Gitlab => hit.header("PRIVATE-TOKEN") == Some("tok"),
+                };
+                if !authorized {
+                    return (401, vec![], "{}".into());
+                }
+                let path = hit.url.split('?').next().unwrap();
+                let (number_key, user_key) = match forge {
+                    super::Forge::Github => ("number", "login"),
+                    super::Forge::Gitlab => ("iid", "username"),
+                };
+                if path.ends_with("/user") {
+                    return (200, vec![], json!({ user_key: "me" }).to_string());
+                }
+                let mut list = list.lock().unwrap();
+                if path.ends_with("/issues") {
+                    return (200, vec![], Json::Array(list.clone()).to_string());
+                }
+                let number: u64 = path.rsplit('/').next().unwrap().parse().unwrap();
+                if *failing.lock().unwrap() == Some(number) {
+                    return (500, vec![], String::new());
+                }
+                log.lock()
+                    .unwrap()
+                    .push(format!("{} {path} {}", hit.method, hit.body));
+                let change: Json = serde_json::from_str(&hit.body).unwrap();
+                let issue = list.iter_mut().find(|i| i[number_key] == number).unwrap();
+                match change["state_event"].as_str() {
+                    Some(event) => {
+                        issue["state"] = (if event == "close" { "closed" } else { "opened" }).into()
+                    }
+                    None => {
+                        issue["state"] = change["state"].clone();
+                        issue["state_reason"] = change["state_reason"].clone();
+                    }
+                }
+                clock += 1;
+                issue["updated_at"] = format!("2030-02-01T00:00:{clock:02}Z").into();
+                (200, vec![], issue.to_string())
+            });
+            Self {
+                server,
+                issues,
+                changes,
+                fail,
+            }
+        }
+
+        /// Change an issue as if on the forge's side
+        fn edit(&self, number: u64, updated_at: &str, f: impl FnOnce(&mut Json)) {
+            let mut issues = self.issues.lock().unwrap();
+            let issue = issues
+                .iter_mut()
+                .find(|i| i["number"] == number || i["iid"] == number)
+                .unwrap();
+            f(issue);
+            issue["updated_at"] = updated_at.into();
+        }
+
+        fn changes(&self) -> Vec<String> {
+            self.changes.lock().unwrap().clone()
+        }
+    }
+
+    fn github_issue(number: u64, title: &str, assignee: &str) -> Json {
+        json!({
+            "number": number, "title": title, "body": "Steps\r\nto reproduce", "state": "open",
+            "labels": [{ "name": "good first issue" }], "assignees": [{ "login": assignee }],
+            "html_url": format!("https://github.com/acme/api/issues/{number}"), "updated_at": "2030-01-01T00:00:00Z",
+        })
+    }
+
+    fn repo(forge: super::Forge, repo: &str, project: &str, url: &str) -> IssueRepo {
+        IssueRepo {
+            forge,
+            repo: repo.into(),
+            project: project.into(),
+            url: url.into(),
+            token_env: TOKEN_ENV.into(),
+            ..IssueRepo::default()
+        }
+    }
+
+    fn configure(vault: &mut Vault, repos: Vec<IssueRepo>) {
+        vault.cfg.issues = Some(Issues {
+            repos,
+            ..Issues::default()
+        });
+    }
+
+    /// The task linked to `reference` (`acme/api#12`) on `forge`
+    fn linked(vault: &Vault, forge: super::Forge, reference: &str) -> TaskRecord {
+        let records = vault.records_by_id().unwrap();
+        records
+            .into_values()
+            .find(|r| {
+                r.frontmatter
+                    .extra
+                    .get(forge.as_str())
+                    .and_then(|v| v.as_str())
+                    == Some(reference)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn github_issues_become_tasks_and_their_state_follows_both_ways() {
+        let (_dir, mut vault) = crate::tests::vault();
+        let forge = Forge::start(
+            super::Forge::Github,
+            vec![
+                github_issue(12, "Fix login", "me"),
+                github_issue(13, "Not mine", "someone"),
+            ],
+        );
+        configure(
+            &mut vault,
+            vec![repo(
+                super::Forge::Github,
+                "acme/api",
+                "api",
+                &forge.server.url,
+            )],
+        );
+
+        assert_eq!(vault.sync_issues(false).unwrap().pulled, ["Fix login"]);
+        let r = linked(&vault, super::Forge::Github, "acme/api#12");
+        assert_eq!(
+            (r.frontmatter.project.as_str(), r.frontmatter.tags.clone()),
+            ("api", vec!["good-first-issue".to_string()])
+        );
+        assert_eq!(
+            r.frontmatter.extra[SOURCE_KEY].as_str(),
+            Some("https://github.com/acme/api/issues/12")
+        );
+        assert_eq!(r.body.trim(), "Steps\nto reproduce");
+        assert_eq!(vault.records_by_id().unwrap().len(), 1);
+
+        // cancelled here: closed as not planned
+        let wf = vault.cfg.workflow.clone();
+        vault
+            .set_status(&r.frontmatter.id, wf.cancel_status().unwrap())
+            .unwrap();
+        assert_eq!(vault.sync_issues(false).unwrap().pushed, ["Fix login"]);
+        assert_eq!(
+            forge.changes(),
+            [r#"PATCH /repos/acme/api/issues/12 {"state":"closed","state_reason":"not_planned"}"#]
+        );
+        assert!(vault.sync_issues(false).unwrap().is_empty());
+
+        let status = |vault: &Vault| {
+            linked(vault, super::Forge::Github, "acme/api#12")
+                .frontmatter
+                .status
+        };
+        forge.edit(12, "2030-03-01T00:00:00Z", |i| i["state"] = "open".into());
+        vault.sync_issues(false).unwrap();
+        assert_eq!(wf.category(&status(&vault)), Some(Category::Open));
+        forge.edit(12, "2030-03-02T00:00:00Z", |i| {
+            i["state"] = "closed".into();
+            i["state_reason"] = "completed".into();
+        });
+        vault.sync_issues(false).unwrap();
+        assert_eq!(status(&vault), wf.first(Category::Closed).unwrap());
+        assert_eq!(forge.changes().len(), 1);
+    }
+
+    #[test]
+    fn gitlab_issues_use_iids_due_dates_and_state_events() {
+        let (_dir, mut vault) = crate::tests::vault();
+        let issue = json!({
+            "iid": 3, "title": "Dark mode", "description": "", "state": "opened", "labels": ["frontend"],
+            "assignees": [], "web_url": "https://gitlab.example.com/group/web/-/issues/3",
+            "updated_at": "2030-01-01T00:00:00Z", "due_date": "2030-01-15",
+        });
+        let forge = Forge::start(super::Forge::Gitlab, vec![issue]);
+        let web = IssueRepo {
+            assignee: Some(String::new()),
+            labels: Some(vec!["Frontend".into()]),
+            ..repo(super::Forge::Gitlab, "group/web", "web", &forge.server.url)
+        };
+        configure(&mut vault, vec![web]);
+
+        assert_eq!(vault.sync_issues(false).unwrap().pulled, ["Dark mode"]);
+        let r = linked(&vault, super::Forge::Gitlab, "group/web#3");
+        assert_eq!(
+            (r.frontmatter.due.as_deref(), r.frontmatter.tags.clone()),
+            (Some("2030-01-15"), vec!["frontend".to_string()])
+        );
+
+        let wf = vault.cfg.workflow.clone();
+        vault
+            .set_status(&r.frontmatter.id, wf.first(Category::Closed).unwrap())
+            .unwrap();
+        vault.sync_issues(false).unwrap();
+        vault
+            .set_status(&r.frontmatter.id, wf.first(Category::Open).unwrap())
+            .unwrap();
+        vault.sync_issues(false).unwrap();
+        assert_eq!(
+            forge.changes(),
+            [
+                r#"PUT /api/v4/projects/group%2Fweb/issues/3 {"state_event":"close"}"#,
+                r#"PUT /api/v4/projects/group%2Fweb/issues/3 {"state_event":"reopen"}"#
+            ]
+        );
+    }
+
+    #[test]
+    fn issues_closed_before_a_failure_are_not_closed_again() {
+        let (_dir, mut vault) = crate::tests::vault();
+        let forge = Forge::start(
+            super::Forge::Github,
+            vec![github_issue(12, "One", "me"), github_issue(13, "Two", "me")],
+        );
+        configure(
+            &mut vault,
+            vec![repo(
+                super::Forge::Github,
+                "acme/api",
+                "api",
+                &forge.server.url,
+            )],
+        );
+        vault.sync_issues(false).unwrap();
+
+        let done = vault
+            .cfg
+            .workflow
+            .first(Category::Closed)
+            .unwrap()
+            .to_string();
+        for reference in ["acme/api#12", "acme/api#13"] {
+            vault
+                .set_status(
+                    &linked(&vault, super::Forge::Github, reference)
+                        .frontmatter
+                        .id,
+                    &done,
+                )
+                .unwrap();
+        }
+        *forge.fail.lock().unwrap() = Some(13);
+        assert!(vault.sync_issues(false).is_err());
+        *forge.fail.lock().unwrap() = None;
+        vault.sync_issues(false).unwrap();
+
+        let closes = |n: u64| {
+            forge
+                .changes()
+                .iter()
+                .filter(|c| c.starts_with(&format!("PATCH /repos/acme/api/issues/{n} ")))
+                .count()
+        };
+        assert_eq!((closes(12), closes(13)), (1, 1));
+    }
+
+    #[test]
+    fn a_failing_repository_keeps_what_the_earlier_ones_pulled() {
+        let (_dir, mut vault) = crate::tests::vault();
+        let forge = Forge::start(
+            super::Forge::Github,
+            vec![github_issue(12, "Fix login", "me")],
+        );
+        let broken = IssueRepo {
+            token_env: "TM_TEST_UNSET_TOKEN".into(),
+            ..repo(
+                super::Forge::Github,
+                "acme/other",
+                "other",
+                &forge.server.url,
+            )
+        };
+        configure(
+            &mut vault,
+            vec![
+                repo(super::Forge::Github, "acme/api", "api", &forge.server.url),
+                broken,
+            ],
+        );
+
+        assert!(vault.sync_issues(false).is_err());
+        assert_eq!(
+            linked(&vault, super::Forge::Github, "acme/api#12")
+                .frontmatter
+                .title,
+            "Fix login"
+        );
+        let state: BTreeMap<String, Synced> = vault.load_sync_state("issues").unwrap();
+        assert_eq!(
+            state.values().map(|s| s.issue.as_str()).collect::<Vec<_>>(),
+            ["acme/api#12"]
+        );
+    }
+}
@@ -1,159 +1,476 @@
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{collections::HashMap, fmt, time::Duration};
 
-use anyhow::{anyhow, Context, Result};
-use directories::ProjectDirs;
-use mlua::{Lua, Table, Value};
+use crate::{Action, Config};
 
-use crate::Action;
+/// Which part of the UI has the keyboard; each mode has its own bindings (`keymaps.<mode>`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Mode {
+    /// the task list
+    #[default]
+    Normal,
+    /// single-line edit panels (due, title, tags, new project)
+    Insert,
+    /// the `:` ex bar
+    Command,
+    /// the `/` filter
+    Search,
+    /// pick-one lists (project picker)
+    Picker,
+}
 
-/// Cross-frontend keymap: normalized tokens like "j", "k", "Ctrl-d", "G", "/", "1"
-#[derive(Clone, Debug, Default)]
+impl Mode {
+    pub const ALL: [Mode; 5] = [
+        Mode::Normal,
+        Mode::Insert,
+        Mode::Command,
+        Mode::Search,
+        Mode::Picker,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Insert => "insert",
+            Mode::Command => "command",
+            Mode::Search => "search",
+            Mode::Picker => "picker",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|m| m.as_str() == s)
+    }
+
+    /// Modes with a text input: unbound printable keys are typed into it
+    pub fn is_text(self) -> bool {
+        self != Mode::Normal
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Cross-frontend keymap: one trie per mode over normalized key tokens like "j", "Ctrl-d",
+/// "G", "Space". Bindings are written as sequences: `gg`, `zt`, `<leader>p`, `<C-d>`, or
+/// space-separated tokens (`Ctrl-w j`).
+#[derive(Clone, Debug)]
 pub struct Keymap {
-    pub normal: HashMap<String, Action>,
+    modes: HashMap<Mode, KeyTrie>,
+    /// how long an ambiguous prefix (`g` vs `gg`, `1` vs `13j`) waits for the next key
+    pub timeout: Duration,
+    leader: Vec<String>,
 }
 
+/// One node per key token; `action` is set where a complete binding ends
+#[derive(Clone, Debug, Default)]
+pub struct KeyTrie {
+    action: Option<Action>,
+    children: HashMap<String, KeyTrie>,
+}
+
+/// Result of looking up a (partial) key sequence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lookup {
+    /// no binding starts with these keys
+    None,
+    /// longer bindings start with these keys
+    Prefix,
+    /// a complete binding with nothing longer behind it
+    Exact(Action),
+    /// a complete binding that is also the prefix of a longer one (resolved on timeout)
+    Ambiguous(Action),
+}
+
+impl KeyTrie {
+    pub fn insert(&mut self, keys: &[String], action: Option<Action>) {
+        let mut node = self;
+        for k in keys {
+            node = node.children.entry(k.clone()).or_default();
+        }
+        node.action = action;
+    }
+
+    pub fn lookup(&self, keys: &[String]) -> Lookup {
+        let mut node = self;
+        for k in keys {
+            match node.children.get(k) {
+                Some(n) => node = n,
+                None => return Lookup::None,
+            }
+        }
+        let has_children = node.children.values().any(KeyTrie::has_binding);
+        match (node.action, has_children) {
+            (Some(a), false) => Lookup::Exact(a),
+            (Some(a), true) => Lookup::Ambiguous(a),
+            (None, true) => Lookup::Prefix,
+            (None, false) => Lookup::None,
+        }
+    }
+
+    fn has_binding(&self) -> bool {
+        self.action.is_some() || self.children.values().any(KeyTrie::has_binding)
+    }
+}
+
+pub const DEFAULT_LEADER: &str = "\\";
+pub const DEFAULT_TIMEOUT_MS: u64 = 1000;
+
 impl Keymap {
-    pub fn lookup(&self, token: &str) -> Option<Action> {
-        self.normal.get(token).copied()
+    fn empty(leader: &str, timeout: Duration) -> Self {
+        Self {
+            modes: HashMap::new(),
+            timeout,
+            leader: parse_keys(leader, &[]),
+        }
+    }
+
+    /// Defaults overridden by `keymaps.<mode>` from the config (unknown modes and action
+    /// names are rejected by `Config::validate`). `"nop"` removes a default binding.
+    pub fn from_config(cfg: &Config) -> Self {
+        let mut km = defaults_with(&cfg.leader, Duration::from_millis(cfg.key_timeout_ms));
+        for (mode, tbl) in &cfg.keymaps {
+            let Some(mode) = Mode::parse(mode) else {
+                continue;
+            };
+            for (seq, name) in tbl {
+                if name == "nop" {
+                    km.unbind(mode, seq);
+                } else if let Some(act) = parse_action_name(name) {
+                    km.bind(mode, seq, act);
+                }
+            }
+        }
+        km
+    }
+
+    /// Bind a sequence such as `gg` or `<leader>p`
+    pub fn bind(&mut self, mode: Mode, seq: &str, action: Action) {
+        let keys = parse_keys(seq, &self.leader);
+        if !keys.is_empty() {
+            self.modes
+                .entry(mode)
+                .or_default()
+                .insert(&keys, Some(action));
+        }
+    }
+
+    pub fn unbind(&mut self, mode: Mode, seq: &str) {
+        let keys = parse_keys(seq, &self.leader);
+        self.modes.entry(mode).or_default().insert(&keys, None);
+    }
+
+    pub fn lookup(&self, mode: Mode, keys: &[String]) -> Lookup {
+        self.modes
+            .get(&mode)
+            .map(|t| t.lookup(keys))
+            .unwrap_or(Lookup::None)
     }
 }
 
-/// Built-in defaults (what we hardcoded previously)
+/// Built-in defaults
 pub fn default_keymap() -> Keymap {
-    use Action::*;
-    let mut m = HashMap::new();
-
-    // navigation
-    m.insert("j".into(), MoveDown);
-    m.insert("Down".into(), MoveDown);
-    m.insert("k".into(), MoveUp);
-    m.insert("Up".into(), MoveUp);
-    m.insert("Ctrl-d".into(), HalfPageDown);
-    m.insert("Ctrl-u".into(), HalfPageUp);
-    m.insert("G".into(), GoBottom);
-    m.insert("/".into(), FocusFilter);
-    m.insert("q".into(), Quit);
-
-    // status
-    m.insert("x".into(), StatusNext);
-    m.insert("X".into(), StatusPrev);
-    m.insert("1".into(), SetTodo);
-    m.insert("2".into(), SetDoing);
-    m.insert("3".into(), SetDone);
-
-    Keymap { normal: m }
+    defaults_with(DEFAULT_LEADER, Duration::from_millis(DEFAULT_TIMEOUT_MS))
 }
 
-/// XDG: ~/.config/tm/config.lua  (also accept ~/.config/tm/config as a plain file)
-pub fn default_config_path() -> PathBuf {
-    let proj = ProjectDirs::from("dev", "example", "tm").expect("project dirs");
-    let base = proj.config_dir().to_path_buf(); // ~/.config/tm
-    let lua = base.join("config.lua");
-    let plain = base.join("config");
-    if lua.exists() {
-        lua
-    } else if plain.exists() {
-        plain
-    } else {
-        lua
-    }
-}
+fn defaults_with(leader: &str, timeout: Duration) -> Keymap {
+    use Action::*;
+    let mut km = Keymap::empty(leader, timeout);
+    let normal: &[(&str, Action)] = &[
+        // navigation
+        ("j", MoveDown),
+        ("Down", MoveDown),
+        ("k", MoveUp),
+        ("Up", MoveUp),
+        ("Ctrl-d", HalfPageDown),
+        ("Ctrl-u", HalfPageUp),
+        ("gg", GoTop),
+        ("G", GoBottom),
+        ("zt", ScrollTop),
+        ("zz", ScrollCenter),
+        ("zb", ScrollBottom),
+        ("/", FocusFilter),
+        (":", EnterCommand),
+        ("q", Quit),
+        // status
+        ("x", StatusNext),
+        ("X", SetCancelled),
+        ("1", SetTodo),
+        ("2", SetDoing),
+        ("3", SetDone),
+        // projects
+        ("O", PickProject),
+        ("]", NextProject),
+        ("[", PrevProject),
+        ("P", NewProject),
+        // views
+        ("gt", NextView),
+        ("gT", PrevView),
+        ("o", SortNext),
+        ("~", SortReverse),
+        ("za", ToggleFold),
+        ("zR", OpenFolds),
+        ("zM", CloseFolds),
+        // edit panels
+        ("D", EditDue),
+        ("R", EditTitle),
+        ("T", EditTags),
+        ("s", Snooze),
+        ("E", OpenInEditor),
+        ("t", ToggleTimer),
+        // checklist
+        ("Tab", FocusDetail),
+        ("<leader>p", OpenPalette),
+        ("<leader>a", QuickActions),
+    ];
+    // line editing, shared by every mode with a text input
+    let editing: &[(&str, Action)] = &[
+        ("Left", CursorLeft),
+        ("Ctrl-b", CursorLeft),
+        ("Right", CursorRight),
+        ("Ctrl-f", CursorRight),
+        ("Alt-b", CursorWordLeft),
+        ("Alt-f", CursorWordRight),
+        ("Home", CursorStart),
+        ("Ctrl-a", CursorStart),
+        ("End", CursorEnd),
+        ("Ctrl-e", CursorEnd),
+        ("Backspace", DeleteBack),
+        ("Ctrl-h", DeleteBack),
+        ("Delete", DeleteForward),
+        ("Ctrl-w", DeleteWordBack),
+        ("Ctrl-u", DeleteToStart),
+        ("Ctrl-k", DeleteToEnd),
+        ("Enter", Submit),
+        ("Esc", Cancel),
+    ];
+    let history: &[(&str, Action)] = &[
+        ("Up", HistoryPrev),
+        ("Ctrl-p", HistoryPrev),
+        ("Down", HistoryNext),
+        ("Ctrl-n", HistoryNext),
+    ];
+    let picker: &[(&str, Action)] = &[
+        ("Up", MoveUp),
+        ("Ctrl-p", MoveUp),
+        ("BackTab", MoveUp),
+        ("Down", MoveDown),
+        ("Ctrl-n", MoveDown),
+        ("Tab", MoveDown),
+    ];
 
-/// Try to load user keymap; fallback to defaults on any error
-pub fn load_keymap_from_user() -> Keymap {
-    let path = default_config_path();
-    if !path.exists() {
-        return default_keymap();
-    }
-    match load_keymap_from_file(&path) {
-        Ok(km) => km,
-        Err(e) => {
-            eprintln!("[tm] failed to load keymap from {:?}: {e}", path);
-            default_keymap()
+    type Bindings<'a> = &'a [(&'a str, Action)];
+    let tables: &[(Mode, &[Bindings])] = &[
+        (Mode::Normal, &[normal]),
+        (Mode::Insert, &[editing]),
+        (Mode::Command, &[editing, history, &[("Tab", Complete)]]),
+        (Mode::Search, &[editing, history]),
+        (Mode::Picker, &[editing, picker]),
+    ];
+    for (mode, parts) in tables {
+        for (seq, act) in parts.iter().flat_map(|p| p.iter()) {
+            km.bind(*mode, seq, *act);
         }
     }
+    // with `leader = ' '` Space belongs to the leader; bind toggle_check elsewhere then
+    if km.leader != ["Space"] {
+        km.bind(Mode::Normal, "Space", ToggleCheck);
+    }
+    km
 }
 
-fn load_keymap_from_file(path: &PathBuf) -> Result<Keymap> {
-    let lua_src = fs::read_to_string(path).with_context(|| format!("reading {:?}", path))?;
+/// Named keys that are a single token even though they are several characters long
+const NAMED_KEYS: &[&str] = &[
+    "Up",
+    "Down",
+    "Left",
+    "Right",
+    "Home",
+    "End",
+    "PageUp",
+    "PageDown",
+    "Esc",
+    "Enter",
+    "Tab",
+    "BackTab",
+    "Backspace",
+    "Delete",
+    "Insert",
+    "Space",
+];
 
-    // IMPORTANT: never bubble mlua::Error with `?` directly; map to string.
-    let lua = Lua::new();
-    let cfg_val = lua
-        .load(&lua_src)
-        .eval::<Value>()
-        .map_err(|e| anyhow!(e.to_string()))?;
+fn is_named(tok: &str) -> bool {
+    NAMED_KEYS.contains(&tok)
+        || tok
+            .strip_prefix("Ctrl-")
+            .map(|r| !r.is_empty())
+            .unwrap_or(false)
+        || tok
+            .strip_prefix("Alt-")
+            .map(|r| !r.is_empty())
+            .unwrap_or(false)
+        || (tok.len() > 1 && tok.starts_with('F') && tok[1..].parse::<u8>().is_ok())
+}
 
-    let cfg_tbl: Table = match cfg_val {
-        Value::Table(t) => t,
-        _ => return Ok(default_keymap()),
+/// `<C-d>` / `<leader>` / `<Space>` → canonical token(s)
+fn angle_token(inner: &str, leader: &[String]) -> Vec<String> {
+    let lower = inner.to_ascii_lowercase();
+    if lower == "leader" {
+        return leader.to_vec();
+    }
+    if let Some(k) = lower.strip_prefix("c-") {
+        return vec![format!("Ctrl-{k}")];
+    }
+    if let Some(k) = lower.strip_prefix("a-").or(lower.strip_prefix("m-")) {
+        return vec![format!("Alt-{k}")];
+    }
+    let named = match lower.as_str() {
+        "cr" | "enter" | "return" => "Enter",
+        "esc" => "Esc",
+        "tab" => "Tab",
+        "s-tab" => "BackTab",
+        "bs" | "backspace" => "Backspace",
+        "del" | "delete" => "Delete",
+        "space" => "Space",
+        "lt" => "<",
+        "up" => "Up",
+        "down" => "Down",
+        "left" => "Left",
+        "right" => "Right",
+        "home" => "Home",
+        "end" => "End",
+        "pageup" => "PageUp",
+        "pagedown" => "PageDown",
+        _ => inner,
     };
+    vec![named.to_string()]
+}
 
-    let mut km = default_keymap(); // start with defaults, allow overrides
-
-    // cfg.keymaps.normal = { ["j"] = "move_down", ... }
-    if let Ok(keymaps_val) = cfg_tbl.get::<Value>("keymaps") {
-        if let Value::Table(keymaps_tbl) = keymaps_val {
-            if let Ok(normal_val) = keymaps_tbl.get::<Value>("normal") {
-                if let Value::Table(normal_tbl) = normal_val {
-                    for pair in normal_tbl.pairs::<Value, Value>() {
-                        // Map mlua::Error to anyhow via to_string()
-                        let (k, v) = pair.map_err(|e| anyhow!(e.to_string()))?;
-
-                        // token (key)
-                        let token = match k {
-                            Value::String(s) => s
-                                .to_str()
-                                .map_err(|e| anyhow!(e.to_string()))?
-                                .to_string(),
-                            Value::Integer(n) => n.to_string(),
-                            Value::Number(n) => n.to_string(),
-                            _ => continue,
-                        };
-
-                        // action string
-                        let action_name = match v {
-                            Value::String(s) => s
-                                .to_str()
-                                .map_err(|e| anyhow!(e.to_string()))?
-                                .to_string(),
-                            _ => continue,
-                        };
-
-                        if let Some(act) = parse_action_name(&action_name) {
-                            km.normal.insert(token, act);
-                        }
-                    }
+/// Split a binding into key tokens: `gg` → [g, g], `<leader>p` → [\, p],
+/// `Ctrl-d` → [Ctrl-d], `Ctrl-w j` → [Ctrl-w, j]
+pub fn parse_keys(seq: &str, leader: &[String]) -> Vec<String> {
+    if seq.contains(' ') && seq.trim() != "" {
+        return seq
+            .split_whitespace()
+            .flat_map(|p| parse_keys(p, leader))
+            .collect();
+    }
+    if seq == " " {
+        return vec!["Space".into()];
+    }
+    if is_named(seq) {
+        return vec![seq.to_string()];
+    }
+    let mut out = Vec::new();
+    let mut rest = seq;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                if end > 1 {
+                    out.extend(angle_token(&rest[1..end], leader));
+                    rest = &rest[end + 1..];
+                    continue;
                 }
             }
         }
+        out.push(c.to_string());
+        rest = &rest[c.len_utf8()..];
     }
+    out
+}
 
-    Ok(km)
+/// Map action names from config strings to Action enum
+pub(crate) fn parse_action_name(s: &str) -> Option<Action> {
+    Action::from_name(s)
 }
 
-/// Map action names from Lua strings to Action enum
-fn parse_action_name(s: &str) -> Option<Action> {
-    use Action::*;
-    match s {
-        // nav
-        "move_down" => Some(MoveDown),
-        "move_up" => Some(MoveUp),
-        "half_page_down" => Some(HalfPageDown),
-        "half_page_up" => Some(HalfPageUp),
-        "go_top" => Some(GoTop),
-        "go_bottom" => Some(GoBottom),
-        "focus_filter" => Some(FocusFilter),
-        "quit" => Some(Quit),
-
-        // status ops
-        "status_next" => Some(StatusNext),
-        "status_prev" => Some(StatusPrev),
-        "set_todo" => Some(SetTodo),
-        "set_doing" => Some(SetDoing),
-        "set_done" => Some(SetDone),
-
-        _ => None,
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn keys(seq: &str) -> Vec<String> {
+        parse_keys(seq, &["\\".to_string()])
     }
-}
 
+    #[test]
+    fn bindings_split_into_key_tokens() {
+        assert_eq!(keys("gg"), ["g", "g"]);
+        assert_eq!(keys("<leader>p"), ["\\", "p"]);
+        assert_eq!(keys("Ctrl-d"), ["Ctrl-d"]);
+        assert_eq!(keys("<C-d>"), ["Ctrl-d"]);
+        assert_eq!(keys("Ctrl-w j"), ["Ctrl-w", "j"]);
+        assert_eq!(keys("<Space>x"), ["Space", "x"]);
+        assert_eq!(keys(" "), ["Space"]);
+        assert_eq!(keys("<lt>"), ["<"]);
+        assert_eq!(keys("<"), ["<"]);
+        assert_eq!(parse_keys("<Space>", &[]), ["Space"]);
+    }
+
+    #[test]
+    fn lookup_tells_prefixes_from_complete_bindings() {
+        let mut km = default_keymap();
+        km.bind(Mode::Normal, "g", Action::GoBottom);
+        assert_eq!(
+            km.lookup(Mode::Normal, &keys("j")),
+            Lookup::Exact(Action::MoveDown)
+        );
+        assert_eq!(km.lookup(Mode::Normal, &keys("z")), Lookup::Prefix);
+        assert_eq!(
+            km.lookup(Mode::Normal, &keys("zt")),
+            Lookup::Exact(Action::ScrollTop)
+        );
+        assert_eq!(
+            km.lookup(Mode::Normal, &keys("g")),
+            Lookup::Ambiguous(Action::GoBottom)
+        );
+        assert_eq!(
+            km.lookup(Mode::Normal, &keys("gg")),
+            Lookup::Exact(Action::GoTop)
+        );
+        assert_eq!(km.lookup(Mode::Normal, &keys("gx")), Lookup::None);
+        assert_eq!(km.lookup(Mode::Normal, &keys("J")), Lookup::None);
+
+        // once `gg`, `gt` and `gT` are gone, `g` no longer waits for a second key
+        for seq in ["gg", "gt", "gT"] {
+            km.unbind(Mode::Normal, seq);
+        }
+        assert_eq!(
+            km.lookup(Mode::Normal, &keys("g")),
+            Lookup::Exact(Action::GoBottom)
+        );
+    }
+
+    #[test]
+    fn config_sets_the_leader_and_overrides_defaults() {
+        let normal = BTreeMap::from([
+            ("<leader>g".to_string(), "go_top".to_string()),
+            ("x".to_string(), "nop".to_string()),
+        ]);
+        let cfg = Config {
+            leader: "<Space>".into(),
+            keymaps: BTreeMap::from([("normal".to_string(), normal)]),
+            ..Config::defaults()
+        };
+        let km = Keymap::from_config(&cfg);
+        let lookup = |seq: &[&str]| {
+            km.lookup(
+                Mode::Normal,
+                &seq.iter().map(|k| k.to_string()).collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(lookup(&["Space", "g"]), Lookup::Exact(Action::GoTop));
+        assert_eq!(lookup(&["\\", "g"]), Lookup::None);
+        assert_eq!(lookup(&["x"]), Lookup::None);
+        assert_eq!(lookup(&["X"]), Lookup::Exact(Action::SetCancelled));
+        // a Space leader takes the key from toggle_check instead of making it wait
+        assert_eq!(lookup(&["Space"]), Lookup::Prefix);
+        assert_eq!(
+            default_keymap().lookup(Mode::Normal, &keys("Space")),
+            Lookup::Exact(Action::ToggleCheck)
+        );
+    }
+}
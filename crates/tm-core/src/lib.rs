@@ -1,11 +1,11 @@
 //! Core domain + storage + index (skeleton)
 
 use anyhow::{Context, Result};
-use directories::ProjectDirs;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs,
+    collections::BTreeMap,
+    fmt, fs,
     io::Write,
     path::{Path, PathBuf},
 };
@@ -19,33 +19,82 @@ pub use actions::Action;
 
 // Keymap Configs
 pub mod keymap;
-pub use keymap::{Keymap, default_keymap, load_keymap_from_user};
+pub use keymap::{default_keymap, Keymap, Mode};
+pub mod dispatch;
+pub use dispatch::{motion_target, Dispatch, KeyDispatcher, KeyOutcome};
+pub mod input;
+pub use input::{History, LineEdit};
 
 pub mod ex;
-pub use ex::{parse_ex, ExCommand, StatusSet};
+pub use ex::{
+    builtin_commands, parse_ex, run_ex, ArgKind, CommandDef, ExCommand, ExEffect, StatusSet,
+};
+pub mod complete;
+pub use complete::{complete, signature_hint, Completion, CompletionData};
+pub mod quick;
+pub use quick::{QuickAction, QuickActions, QuickContext, QuickRun, When};
+pub mod palette;
+pub use palette::{
+    fuzzy_match, preview, Palette, PaletteItem, PaletteMatch, PaletteSource, PaletteTarget,
+};
 
+// Plugin declarations (`use({...})` in config.lua)
+pub mod plugin;
+pub use plugin::PluginSpec;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Config {
-    pub vault_path: PathBuf,
-}
+// User configuration (config.lua / toml / yaml + env)
+pub mod config;
+pub use config::{default_config_path, Config, Layout, Origin, PanePosition};
 
-impl Config {
-    pub fn load_default() -> Result<Self> {
-        let proj = ProjectDirs::from("dev", "example", "tm").expect("project dirs");
+// Hot reload
+pub mod watch;
+pub use watch::FileWatcher;
 
-        let default_vault = directories::UserDirs::new()
-            .map(|u| u.home_dir().to_path_buf().join("TasksVault"))
-            .unwrap_or_else(|| PathBuf::from("TasksVault"));
+pub mod date;
 
-        let cfg_dir = proj.config_dir().to_path_buf();
-        fs::create_dir_all(&cfg_dir).ok();
+pub mod sort;
+pub use sort::{
+    apply_sort_arg, cycle_sort, format_sort, parse_sort, toggle_sort, SortKey, SortSpec,
+};
 
-        Ok(Self {
-            vault_path: default_vault,
-        })
-    }
-}
+// Queries and named views
+pub mod query;
+pub use query::Query;
+pub mod view;
+pub use view::{
+    fit, format_row, group_tasks, Column, ColumnSpec, Group, GroupBy, ListLayout, View, ViewSpec,
+    BLOCKED_MARKER, DEFAULT_COLUMNS,
+};
+
+// Themes
+pub mod theme;
+pub use theme::{Rgb, Role, Style, Theme, ThemeSpec};
+
+// Workflow statuses and dependencies
+pub mod deps;
+pub mod export;
+pub use export::{Conflict, ImportReport, VaultExport};
+pub mod taskwarrior;
+pub use taskwarrior::MappingReport;
+pub mod todotxt;
+pub use todotxt::{MirrorReport, MirrorTrigger};
+pub mod ics;
+pub mod org;
+pub use ics::IcsFeed;
+pub mod caldav;
+pub use caldav::CalDav;
+pub mod issues;
+pub use issues::Issues;
+pub mod sync;
+pub use sync::SyncReport;
+pub mod notes;
+pub use notes::{NoteRef, Notes};
+pub mod checklist;
+pub use checklist::CheckItem;
+pub mod track;
+pub use track::{format_duration, parse_duration, Interval, ReportBy, ReportRow};
+pub mod workflow;
+pub use workflow::{unknown_statuses, Category, Workflow};
 
 #[derive(Clone, Debug)]
 pub struct Vault {
@@ -71,6 +120,15 @@ fn list_project_files(base: &Path) -> Vec<PathBuf> {
         .collect()
 }
 
+/// `tasks/YYYY/MM/YYYY-MM-DD--slug--ID.md` for a task created on `date`
+fn task_file_path(base: &Path, date: time::Date, slug: &str, id: &str) -> PathBuf {
+    let (y, m) = (date.year(), u8::from(date.month()));
+    base.join("tasks")
+        .join(format!("{y:04}"))
+        .join(format!("{m:02}"))
+        .join(format!("{y:04}-{m:02}-{:02}--{slug}--{id}.md", date.day()))
+}
+
 // locate a task file by frontmatter.id
 fn find_task_file_by_id(base: &Path, id: &str) -> Option<PathBuf> {
     let tasks_dir = base.join("tasks");
@@ -94,11 +152,36 @@ fn find_task_file_by_id(base: &Path, id: &str) -> Option<PathBuf> {
 
 /* ---------- Vault impl ---------- */
 
+/// The id `handle` names among `ids`: the id itself, or a unique prefix of 4+ characters
+fn match_handle<'a>(handle: &str, ids: impl Iterator<Item = &'a str>) -> Result<String> {
+    let h = handle.trim().to_uppercase();
+    if h.len() < 4 {
+        anyhow::bail!("task handle '{handle}' is too short (4+ characters of the id)");
+    }
+    let hits: Vec<&str> = ids.filter(|id| id.starts_with(&h)).collect();
+    match hits.as_slice() {
+        [id] => Ok(id.to_string()),
+        [] => anyhow::bail!("no task matches '{handle}'"),
+        _ => match hits.iter().find(|id| **id == h) {
+            Some(id) => Ok(id.to_string()),
+            None => anyhow::bail!(
+                "'{handle}' matches {} tasks; type more of the id",
+                hits.len()
+            ),
+        },
+    }
+}
+
 impl Vault {
     pub fn new(cfg: Config) -> Result<Self> {
         Ok(Self { cfg })
     }
 
+    /// Per-vault state that is not task data (`.tm/`: ex history, ...)
+    pub fn state_dir(&self) -> PathBuf {
+        self.cfg.vault_path.join(".tm")
+    }
+
     pub fn init_dirs(&self) -> Result<()> {
         let base = &self.cfg.vault_path;
         fs::create_dir_all(base.join("projects"))?;
@@ -120,6 +203,7 @@ impl Vault {
             created: Some(now.format(&Rfc3339).unwrap()),
             updated: Some(now.format(&Rfc3339).unwrap()),
             description: None,
+            extra: BTreeMap::new(),
         };
         let md = format!("---\n{}---\n", serde_yaml::to_string(&fm)?);
         let path = project_file_path(&self.cfg.vault_path, &key);
@@ -158,52 +242,109 @@ impl Vault {
         }
         for entry in WalkDir::new(tasks_dir).into_iter().filter_map(|e| e.ok()) {
             if entry.path().extension().and_then(|s| s.to_str()) == Some("md") {
-                if let Ok(t) = Task::from_md_file(entry.path()) {
+                if let Ok(mut t) = Task::from_md_file(entry.path()) {
+                    t.category = self.cfg.workflow.category(&t.status);
                     out.push(t);
                 }
             }
         }
-        // naive sort by updated desc
-        out.sort_by(|a, b| b.updated.cmp(&a.updated));
+        if !self.cfg.notes.folders.is_empty() {
+            let ids: Vec<String> = out.iter().map(|t| t.id.clone()).collect();
+            out.extend(self.note_tasks(&ids)?);
+        }
+        deps::mark_blocked(&mut out);
+        let order = parse_sort(&self.cfg.sort)?;
+        sort::sort_tasks(&mut out, &order);
+        Ok(out)
+    }
+
+    /// A task id from a handle: the full ULID or a unique prefix of it (the list shows 8 chars)
+    pub fn resolve_task(&self, handle: &str) -> Result<String> {
+        let tasks = self.list_tasks(None)?;
+        match_handle(handle, tasks.iter().map(|t| t.id.as_str()))
+    }
+
+    /// Read a task's frontmatter, change it, and write it back with a new `updated`
+    fn edit_task<T>(&self, id: &str, f: impl FnOnce(&mut Frontmatter) -> Result<T>) -> Result<T> {
+        if notes::is_note_id(id) {
+            anyhow::bail!("that task is a checkbox in a note: only its status can change here (`:promote` it to a task file for more)");
+        }
+        let path = find_task_file_by_id(&self.cfg.vault_path, id)
+            .with_context(|| format!("task {id} not found"))?;
+        let content = fs::read_to_string(&path)?;
+        let (mut fm, body) =
+            extract_frontmatter_and_body(&content).context("invalid frontmatter")?;
+        let out = f(&mut fm)?;
+        fm.updated = Some(OffsetDateTime::now_utc().format(&Rfc3339).unwrap());
+        fs::write(
+            &path,
+            format!("---\n{}---\n{}", serde_yaml::to_string(&fm)?, body),
+        )?;
         Ok(out)
     }
 
+    /// `id` waits for `on` (both handles); refuses self-references and cycles
+    pub fn add_dependency(&self, id: &str, on: &str) -> Result<(String, String)> {
+        let (id, on) = (self.resolve_task(id)?, self.resolve_task(on)?);
+        let tasks = self.list_tasks(None)?;
+        if let Some(cycle) = deps::cycle_path(&tasks, &id, &on) {
+            let short: Vec<String> = cycle.iter().map(|i| i.chars().take(8).collect()).collect();
+            anyhow::bail!("that would make a cycle: {}", short.join(" → "));
+        }
+        self.edit_task(&id, |fm| {
+            if !fm.depends_on.contains(&on) {
+                fm.depends_on.push(on.clone());
+            }
+            Ok(())
+        })?;
+        Ok((id, on))
+    }
+
+    pub fn remove_dependency(&self, id: &str, on: &str) -> Result<(String, String)> {
+        let id = self.resolve_task(id)?;
+        self.edit_task(&id, |fm| {
+            // a dangling id can't be resolved; match it by prefix in the list itself
+            let dep = match_handle(on, fm.depends_on.iter().map(String::as_str))
+                .map_err(|e| anyhow::anyhow!("{}: {e}", fm.title))?;
+            fm.depends_on.retain(|d| *d != dep);
+            Ok(())
+        })?;
+        Ok((id, on.to_string()))
+    }
+
+    /// Markdown body of a task (below the frontmatter)
+    pub fn task_body(&self, id: &str) -> Result<String> {
+        let path = find_task_file_by_id(&self.cfg.vault_path, id).context("task not found")?;
+        let (_, body) = extract_frontmatter_and_body(&fs::read_to_string(&path)?)?;
+        Ok(body)
+    }
+
     pub fn create_task(&self, t: TaskNew) -> Result<String> {
         self.init_dirs().ok();
         let id = Ulid::new().to_string();
         let now = OffsetDateTime::now_utc();
         let slug = slug::slugify(&t.title);
-        let date = now.date();
-        let y = date.year();
-        let m = u8::from(date.month());
-        let file = self
-            .cfg
-            .vault_path
-            .join("tasks")
-            .join(format!("{y:04}"))
-            .join(format!("{m:02}"))
-            .join(format!(
-                "{:04}-{:02}-{:02}--{}--{}.md",
-                y,
-                m,
-                date.day(),
-                slug,
-                id
-            ));
+        let file = task_file_path(&self.cfg.vault_path, now.date(), &slug, &id);
         fs::create_dir_all(file.parent().unwrap())?;
 
         let frontmatter = Frontmatter {
             id: id.clone(),
             key: slug,
             title: t.title,
-            status: "todo".into(),
+            status: self.cfg.workflow.first(Category::Open)?.to_string(),
             project: t.project,
             tags: t.tags,
             priority: "none".into(),
             due: t.due,
+            start: None,
             created: Some(now.format(&Rfc3339).unwrap()),
             updated: Some(now.format(&Rfc3339).unwrap()),
-            parent: None,
+            done_at: None,
+            parent: t.parent,
+            depends_on: vec![],
+            estimate: None,
+            time: vec![],
+            extra: BTreeMap::new(),
         };
         let md = frontmatter.to_markdown("---\n")?;
         let mut f = fs::File::create(&file)?;
@@ -211,46 +352,224 @@ impl Vault {
         Ok(id)
     }
 
-    pub fn set_status(&self, id: &str, status: Status) -> Result<()> {
+    /// Set a workflow status (`in-progress` works for the first active one), if the current
+    /// status may move there. Starting a task that still waits for others is allowed, with a
+    /// warning.
+    pub fn set_status(&self, id: &str, status: &str) -> Result<StatusChange> {
+        let wf = &self.cfg.workflow;
+        let status = wf.resolve(status)?;
+        if notes::is_note_id(id) {
+            self.set_note_status(id, &status)?;
+            return Ok(StatusChange {
+                status,
+                warning: None,
+            });
+        }
+        let (depends_on, note) = self.edit_task(id, |fm| {
+            wf.check_transition(&fm.status, &status)?;
+            let was_closed = wf.category(&fm.status) == Some(Category::Closed);
+            fm.status = status.clone();
+            if wf.category(&status) == Some(Category::Closed) {
+                track::stop(&mut fm.time);
+                if !was_closed || fm.done_at.is_none() {
+                    fm.done_at = Some(track::now_rfc3339());
+                }
+            } else {
+                fm.done_at = None;
+            }
+            let note = fm
+                .extra
+                .get(notes::NOTE_KEY)
+                .and_then(|n| n.as_str())
+                .map(str::to_string);
+            Ok((fm.depends_on.clone(), note))
+        })?;
+        let mut warning = None;
+        if let Some(note) = note {
+            if let Err(e) = self.sync_promoted(id, &note, &status) {
+                warning = Some(format!("note not updated: {e:#}"));
+            }
+        }
+        if wf.category(&status) == Some(Category::Active) && !depends_on.is_empty() {
+            let tasks = self.list_tasks(None)?;
+            let open: Vec<String> = tasks
+                .iter()
+                .filter(|t| depends_on.contains(&t.id) && !t.is_closed())
+                .map(|t| t.title.clone())
+                .collect();
+            if !open.is_empty() {
+                warning = Some(format!("still blocked by: {}", open.join(", ")));
+            }
+        }
+        Ok(StatusChange { status, warning })
+    }
+
+    /// The task whose timer runs, if any
+    pub fn running_timer(&self) -> Result<Option<Task>> {
+        Ok(self.list_tasks(None)?.into_iter().find(Task::timer_running))
+    }
+
+    /// Start tracking `id`, stopping any other timer first; an open task moves to the
+    /// first active status
+    pub fn start_timer(&self, id: &str) -> Result<TimerChange> {
+        let tasks = self.list_tasks(None)?;
+        let Some(task) = tasks.iter().find(|t| t.id == id) else {
+            anyhow::bail!("task {id} not found")
+        };
+        if task.is_closed() {
+            anyhow::bail!("{} is {}; reopen it to track time", task.title, task.status);
+        }
+        let mut change = TimerChange::default();
+        if let Some(t) = tasks.iter().find(|t| t.timer_running()) {
+            if t.id == id {
+                anyhow::bail!("the timer already runs for {}", t.title);
+            }
+            change.stopped = self.stop_timer(Some(&t.id))?.stopped;
+        }
+        self.edit_task(id, |fm| {
+            fm.time.push(Interval::now());
+            Ok(())
+        })?;
+        if task.category == Some(Category::Open) {
+            change.status = Some(self.set_status_category(id, Category::Active)?);
+        }
+        change.started = Some(task.title.clone());
+        Ok(change)
+    }
+
+    /// Stop the running timer (of `id`, or whichever runs)
+    pub fn stop_timer(&self, id: Option<&str>) -> Result<TimerChange> {
+        let id = match id {
+            Some(id) => id.to_string(),
+            None => match self.running_timer()? {
+                Some(t) => t.id,
+                None => anyhow::bail!("no timer is running"),
+            },
+        };
+        let stopped = self.edit_task(&id, |fm| match track::stop(&mut fm.time) {
+            Some(secs) => Ok((fm.title.clone(), secs)),
+            None => anyhow::bail!("no timer runs for {}", fm.title),
+        })?;
+        Ok(TimerChange {
+            stopped: Some(stopped),
+            ..Default::default()
+        })
+    }
+
+    /// Start the timer of `id`, or stop it if it runs (`t`)
+    pub fn toggle_timer(&self, id: &str) -> Result<TimerChange> {
+        match self.running_timer()? {
+            Some(t) if t.id == id => self.stop_timer(Some(id)),
+            _ => self.start_timer(id),
+        }
+    }
+
+    /// Defer a task until `when` (`1d`, `mon`, `next week`, a date); `none` clears it
+    pub fn snooze(&self, id: &str, when: &str) -> Result<Option<String>> {
+        let start = match when.trim() {
+            "" | "none" => None,
+            w => {
+                let today = date::today();
+                let d = date::parse_relative(w, today)
+                    .ok_or_else(|| anyhow::anyhow!("can't read date '{w}'"))?;
+                Some(date::format_date("YYYY-MM-DD", d))
+            }
+        };
+        self.edit_task(id, |fm| {
+            fm.start = start.clone();
+            Ok(())
+        })?;
+        Ok(start)
+    }
+
+    /// `2h30m`; empty or `none` clears it
+    pub fn set_estimate(&self, id: &str, estimate: &str) -> Result<Option<String>> {
+        let value = match estimate.trim() {
+            "" | "none" => None,
+            e => Some(format_duration(parse_duration(e)?)),
+        };
+        self.edit_task(id, |fm| {
+            fm.estimate = value.clone();
+            Ok(())
+        })?;
+        Ok(value)
+    }
+
+    /// The first status of a category (`1`, `2`, `3`)
+    pub fn set_status_category(&self, id: &str, category: Category) -> Result<StatusChange> {
+        let status = self.cfg.workflow.first(category)?.to_string();
+        self.set_status(id, &status)
+    }
+
+    /// Step through the workflow order; an unknown current status is an error, not `todo`
+    pub fn cycle_status(&self, id: &str, direction: i8) -> Result<StatusChange> {
+        if notes::is_note_id(id) {
+            let next = self
+                .cfg
+                .workflow
+                .step(&self.note_task(id)?.0.status, direction)?;
+            return self.set_status(id, &next);
+        }
         let path = find_task_file_by_id(&self.cfg.vault_path, id)
             .with_context(|| format!("task {id} not found"))?;
         let content = fs::read_to_string(&path)?;
-        let (mut fm, body) =
-            extract_frontmatter_and_body(&content).with_context(|| "invalid frontmatter")?;
-        fm.status = status.as_str().to_string();
+        let (fm, _) = extract_frontmatter_and_body(&content)?;
+        let next = self.cfg.workflow.step(&fm.status, direction)?;
+        self.set_status(id, &next)
+    }
+
+    pub fn set_due(&self, id: &str, due: &str) -> Result<()> {
+        let path = find_task_file_by_id(&self.cfg.vault_path, id)
+            .with_context(|| format!("task {id} not found"))?;
+        let content = fs::read_to_string(&path)?;
+        let (mut fm, body) = extract_frontmatter_and_body(&content)?;
+        fm.due = Some(due.to_string());
         fm.updated = Some(OffsetDateTime::now_utc().format(&Rfc3339).unwrap());
         let new = format!("---\n{}---\n{}", serde_yaml::to_string(&fm)?, body);
         fs::write(&path, new)?;
         Ok(())
     }
 
-    pub fn cycle_status(&self, id: &str, direction: i8) -> Result<Status> {
+    pub fn set_project(&self, id: &str, project: &str) -> Result<()> {
         let path = find_task_file_by_id(&self.cfg.vault_path, id)
             .with_context(|| format!("task {id} not found"))?;
         let content = fs::read_to_string(&path)?;
-        let (fm, _) = extract_frontmatter_and_body(&content)?;
-        let cur = Status::from_str(&fm.status);
-        let next = if direction >= 0 {
-            cur.next()
-        } else {
-            cur.prev()
-        };
-        self.set_status(id, next.clone())?;
-        Ok(next)
+        let (mut fm, body) = extract_frontmatter_and_body(&content)?;
+        fm.project = project.to_string();
+        fm.updated = Some(OffsetDateTime::now_utc().format(&Rfc3339).unwrap());
+        let new = format!("---\n{}---\n{}", serde_yaml::to_string(&fm)?, body);
+        fs::write(&path, new)?;
+        Ok(())
     }
 
-    pub fn set_due(&self, id: &str, due: &str) -> Result<()> {
+    /// `none`, `low`, `med` or `high`
+    pub fn set_priority(&self, id: &str, priority: &str) -> Result<()> {
+        if !PRIORITIES.contains(&priority) {
+            anyhow::bail!(
+                "unknown priority '{priority}' (use: {})",
+                PRIORITIES.join("|")
+            );
+        }
         let path = find_task_file_by_id(&self.cfg.vault_path, id)
             .with_context(|| format!("task {id} not found"))?;
         let content = fs::read_to_string(&path)?;
         let (mut fm, body) = extract_frontmatter_and_body(&content)?;
-        fm.due = Some(due.to_string());
+        fm.priority = priority.to_string();
         fm.updated = Some(OffsetDateTime::now_utc().format(&Rfc3339).unwrap());
         let new = format!("---\n{}---\n{}", serde_yaml::to_string(&fm)?, body);
         fs::write(&path, new)?;
         Ok(())
     }
 
+    /// The task's Markdown file, or a note task's note (for `$EDITOR`)
+    pub fn task_path(&self, id: &str) -> Result<PathBuf> {
+        if notes::is_note_id(id) {
+            return Ok(self.note_task(id)?.0.note.context("not a note task")?.path);
+        }
+        find_task_file_by_id(&self.cfg.vault_path, id)
+            .with_context(|| format!("task {id} not found"))
+    }
+
     pub fn set_tags_csv(&self, id: &str, csv: &str) -> Result<()> {
         let path = find_task_file_by_id(&self.cfg.vault_path, id)
             .with_context(|| format!("task {id} not found"))?;
@@ -286,12 +605,18 @@ impl Vault {
         fs::write(&path, updated)?;
 
         // rename file to: YYYY-MM-DD--slug--ID.md
-        if let (Some(parent), Some(stem)) = (path.parent(), path.file_stem().and_then(|s| s.to_str()))
+        if let (Some(parent), Some(stem)) =
+            (path.parent(), path.file_stem().and_then(|s| s.to_str()))
         {
             let parts: Vec<&str> = stem.split("--").collect();
             if parts.len() >= 3 {
                 let date_part = parts[0];
-                let new_name = format!("{date}--{slug}--{id}.md", date = date_part, slug = new_slug, id = id);
+                let new_name = format!(
+                    "{date}--{slug}--{id}.md",
+                    date = date_part,
+                    slug = new_slug,
+                    id = id
+                );
                 let new_path = parent.join(new_name);
                 if new_path != path {
                     let _ = fs::rename(&path, &new_path);
@@ -304,15 +629,20 @@ impl Vault {
 
 /* ---------- Task types ---------- */
 
+/// Priority values, lowest first
+pub const PRIORITIES: &[&str] = &["none", "low", "med", "high"];
+
 #[derive(Clone, Debug)]
 pub struct TaskNew {
     pub title: String,
     pub project: String,
     pub due: Option<String>,
     pub tags: Vec<String>,
+    /// id of the parent task (subtasks)
+    pub parent: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Frontmatter {
     pub id: String,
     pub key: String,
@@ -322,9 +652,27 @@ pub struct Frontmatter {
     pub tags: Vec<String>,
     pub priority: String,
     pub due: Option<String>,
+    /// hidden from the default lists before this date (deferred / scheduled)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
     pub created: Option<String>,
     pub updated: Option<String>,
+    /// when the task was closed; cleared when it reopens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done_at: Option<String>,
     pub parent: Option<String>,
+    /// ids of tasks that must be closed before this one can start
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// planned effort, e.g. `2h30m`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<String>,
+    /// tracked intervals; the one without `end` is running
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time: Vec<Interval>,
+    /// keys tm doesn't know, kept as they are
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
 impl Frontmatter {
@@ -334,13 +682,34 @@ impl Frontmatter {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Task {
     pub id: String,
     pub title: String,
     pub status: String,
     pub project: String,
+    pub tags: Vec<String>,
+    pub priority: String,
+    pub due: Option<String>,
+    pub start: Option<String>,
+    pub created: String,
     pub updated: String,
+    pub done_at: Option<String>,
+    /// the status's category in the workflow; None if the workflow doesn't know it
+    pub category: Option<Category>,
+    /// ids of the tasks this one waits for
+    pub depends_on: Vec<String>,
+    /// waits for a task that isn't closed (set by `list_tasks`)
+    pub blocked: bool,
+    /// planned effort in seconds
+    pub estimate: Option<i64>,
+    /// tracked seconds, up to now while the timer runs
+    pub actual: i64,
+    pub time: Vec<Interval>,
+    /// a checkbox in a note rather than a task file
+    pub note: Option<NoteRef>,
+    /// `- [ ]` items in the body
+    pub checklist: Vec<CheckItem>,
 }
 
 impl Task {
@@ -354,9 +723,64 @@ impl Task {
             title: fm.title,
             status: fm.status,
             project: fm.project,
+            tags: fm.tags,
+            priority: fm.priority,
+            due: fm.due,
+            start: fm.start,
+            created: fm.created.unwrap_or_default(),
             updated: fm.updated.unwrap_or_default(),
+            done_at: fm.done_at,
+            category: None,
+            depends_on: fm.depends_on,
+            blocked: false,
+            estimate: fm.estimate.as_deref().and_then(|e| parse_duration(e).ok()),
+            actual: track::tracked(&fm.time),
+            time: fm.time,
+            note: None,
+            checklist: checklist::parse(
+                s[caps.get(0).unwrap().end()..]
+                    .strip_prefix('\n')
+                    .unwrap_or_default(),
+            ),
         })
     }
+
+    /// Open with a start date after `today`
+    pub fn is_deferred(&self, today: time::Date) -> bool {
+        !self.is_closed()
+            && self
+                .start
+                .as_deref()
+                .and_then(date::parse_date)
+                .is_some_and(|s| s > today)
+    }
+
+    pub fn timer_running(&self) -> bool {
+        self.time.iter().any(Interval::is_running)
+    }
+
+    /// Done, cancelled or another closed status
+    pub fn is_closed(&self) -> bool {
+        self.category == Some(Category::Closed)
+    }
+}
+
+impl Task {
+    /// The `/` filter: case-insensitive substring of status, title, project and `+tags`
+    pub fn matches_text(&self, needle: &str) -> bool {
+        if needle.is_empty() {
+            return true;
+        }
+        let tags: Vec<String> = self.tags.iter().map(|t| format!("+{t}")).collect();
+        let hay = format!(
+            "[{}] {} {} {}",
+            self.status,
+            self.title,
+            self.project,
+            tags.join(" ")
+        );
+        hay.to_lowercase().contains(&needle.to_lowercase())
+    }
 }
 
 fn extract_frontmatter(s: &str) -> Result<Frontmatter> {
@@ -370,47 +794,66 @@ fn extract_frontmatter_and_body(s: &str) -> Result<(Frontmatter, String)> {
     let re = Regex::new(r"(?s)^---\n(.*?)\n---\n?(.*)$")?;
     let caps = re.captures(s).context("no frontmatter")?;
     let fm: Frontmatter = serde_yaml::from_str(&caps[1])?;
-    let body = caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default();
+    let body = caps
+        .get(2)
+        .map(|m| m.as_str().to_string())
+        .unwrap_or_default();
     Ok((fm, body))
 }
 
-/* ---------- Status ---------- */
-
+/// What `set_status` did
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub enum Status {
-    Todo,
-    Doing,
-    Done,
+pub struct StatusChange {
+    pub status: String,
+    /// e.g. the task was started while it still waits for others
+    pub warning: Option<String>,
 }
 
-impl Status {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            Status::Todo => "todo",
-            Status::Doing => "doing",
-            Status::Done => "done",
+impl StatusChange {
+    /// `(is_warning, "status -> doing ...")` for the result line
+    pub fn message(&self) -> (bool, String) {
+        match &self.warning {
+            Some(w) => (true, format!("status -> {} (warning: {w})", self.status)),
+            None => (false, format!("status -> {}", self.status)),
         }
     }
-    pub fn from_str(s: &str) -> Self {
-        match s {
-            "doing" | "in-progress" | "in_progress" => Status::Doing,
-            "done" => Status::Done,
-            _ => Status::Todo,
-        }
-    }
-    pub fn next(&self) -> Self {
-        match self {
-            Status::Todo => Status::Doing,
-            Status::Doing => Status::Done,
-            Status::Done => Status::Todo,
-        }
+}
+
+impl fmt::Display for StatusChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.status)
     }
-    pub fn prev(&self) -> Self {
-        match self {
-            Status::Todo => Status::Done,
-            Status::Doing => Status::Todo,
-            Status::Done => Status::Doing,
+}
+
+/// What a timer command did
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimerChange {
+    /// title of the task now tracked
+    pub started: Option<String>,
+    /// title and length of the interval just closed
+    pub stopped: Option<(String, i64)>,
+    /// the task moved to an active status
+    pub status: Option<StatusChange>,
+}
+
+impl TimerChange {
+    /// `(is_warning, "timer: started ... (stopped ... after 25m)")` for the result line
+    pub fn message(&self) -> (bool, String) {
+        let stopped = self
+            .stopped
+            .as_ref()
+            .map(|(t, s)| format!("stopped {t} after {}", format_duration(*s)));
+        let mut msg = match (&self.started, stopped) {
+            (Some(t), Some(s)) => format!("timer: started {t} ({s})"),
+            (Some(t), None) => format!("timer: started {t}"),
+            (None, Some(s)) => format!("timer: {s}"),
+            (None, None) => "timer: nothing to do".to_string(),
+        };
+        let warning = self.status.as_ref().and_then(|c| c.warning.clone());
+        if let Some(w) = &warning {
+            msg.push_str(&format!(" (warning: {w})"));
         }
+        (warning.is_some(), msg)
     }
 }
 
@@ -422,7 +865,7 @@ pub struct ProjectNew {
     pub tags: Vec<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ProjectFrontmatter {
     pub key: String, // slug
     pub title: String,
@@ -431,6 +874,9 @@ pub struct ProjectFrontmatter {
     pub created: Option<String>,
     pub updated: Option<String>,
     pub description: Option<String>, // optional markdown body below fm
+    /// keys tm doesn't know, kept as they are
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
 #[derive(Clone, Debug)]
@@ -458,3 +904,173 @@ impl Project {
     }
 }
 
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// An empty vault in a temporary directory (removed when the guard drops)
+    pub(crate) fn vault() -> (tempfile::TempDir, Vault) {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = Config {
+            vault_path: dir.path().to_path_buf(),
+            config_path: dir.path().join("config.lua"),
+            ..Config::defaults()
+        };
+        let vault = Vault::new(cfg).unwrap();
+        vault.init_dirs().unwrap();
+        (dir, vault)
+    }
+
+    pub(crate) fn task(vault: &Vault, title: &str) -> String {
+        let t = TaskNew {
+            title: title.into(),
+            project: "inbox".into(),
+            due: None,
+            tags: vec![],
+            parent: None,
+        };
+        vault.create_task(t).unwrap()
+    }
+
+    /// One request the fake server received
+    #[derive(Clone, Debug)]
+    pub(crate) struct Hit {
+        pub method: String,
+        /// path and query
+        pub url: String,
+        pub headers: Vec<(String, String)>,
+        pub body: String,
+    }
+
+    impl Hit {
+        pub(crate) fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// What the fake server answers: status, headers, body
+    pub(crate) type Reply = (u16, Vec<(&'static str, String)>, String);
+
+    /// A local HTTP server standing in for a remote service; stops when dropped
+    pub(crate) struct FakeServer {
+        pub url: String,
+        server: std::sync::Arc<tiny_http::Server>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl FakeServer {
+        pub(crate) fn start(mut handle: impl FnMut(&Hit) -> Reply + Send + 'static) -> Self {
+            let server = std::sync::Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+            let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+            let incoming = server.clone();
+            let thread = std::thread::spawn(move || {
+                for mut req in incoming.incoming_requests() {
+                    let mut body = String::new();
+                    req.as_reader().read_to_string(&mut body).unwrap();
+                    let hit = Hit {
+                        method: req.method().to_string(),
+                        url: req.url().to_string(),
+                        headers: req
+                            .headers()
+                            .iter()
+                            .map(|h| (h.field.to_string(), h.value.to_string()))
+                            .collect(),
+                        body,
+                    };
+                    let (status, headers, body) = handle(&hit);
+                    let mut resp = tiny_http::Response::from_string(body).with_status_code(status);
+                    for (k, v) in headers {
+                        resp.add_header(tiny_http::Header::from_bytes(k, v).unwrap());
+                    }
+                    let _ = req.respond(resp);
+                }
+            });
+            Self {
+                url,
+                server,
+                thread: Some(thread),
+            }
+        }
+    }
+
+    impl Drop for FakeServer {
+        fn drop(&mut self) {
+            self.server.unblock();
+            if let Some(t) = self.thread.take() {
+                let _ = t.join();
+            }
+        }
+    }
+
+    #[test]
+    fn handles_need_four_characters_and_must_be_unique() {
+        let ids = ["01ABCDEF", "01ABCXYZ", "01QQQQQQ"];
+        assert!(match_handle("01", ids.into_iter()).is_err());
+        assert!(match_handle("01ab", ids.into_iter())
+            .unwrap_err()
+            .to_string()
+            .contains("matches 2"));
+        assert_eq!(match_handle("01abcd", ids.into_iter()).unwrap(), "01ABCDEF");
+        assert_eq!(
+            match_handle("01QQQQQQ", ids.into_iter()).unwrap(),
+            "01QQQQQQ"
+        );
+        assert!(match_handle("02AB", ids.into_iter()).is_err());
+    }
+
+    #[test]
+    fn removing_a_dependency_takes_only_the_one_named() {
+        let (_dir, vault) = vault();
+        let a = task(&vault, "a");
+        let (b, c) = (task(&vault, "b"), task(&vault, "c"));
+        vault.add_dependency(&a, &b).unwrap();
+        vault.add_dependency(&a, &c).unwrap();
+        let deps = |v: &Vault| {
+            v.list_tasks(None)
+                .unwrap()
+                .into_iter()
+                .find(|t| t.id == a)
+                .unwrap()
+                .depends_on
+        };
+
+        assert!(vault.remove_dependency(&a, "0").is_err());
+        assert!(vault.remove_dependency(&a, &b[..2]).is_err());
+        assert_eq!(deps(&vault).len(), 2);
+        vault.remove_dependency(&a, &b).unwrap();
+        assert_eq!(deps(&vault), vec![c]);
+    }
+
+    #[test]
+    fn snooze_takes_relative_dates_and_rejects_the_rest() {
+        let (_dir, vault) = vault();
+        let id = task(&vault, "later");
+        let start = |v: &Vault| {
+            v.list_tasks(None)
+                .unwrap()
+                .into_iter()
+                .find(|t| t.id == id)
+                .unwrap()
+                .start
+        };
+
+        let in_a_week = date::format_date("YYYY-MM-DD", date::today() + time::Duration::weeks(1));
+        assert_eq!(
+            vault.snooze(&id, "1w").unwrap().as_deref(),
+            Some(in_a_week.as_str())
+        );
+        assert_eq!(start(&vault).as_deref(), Some(in_a_week.as_str()));
+
+        for bad in ["+é", "-ü", "é", "+200000000000000d", "99999999999999w"] {
+            let err = vault.snooze(&id, bad).unwrap_err();
+            assert!(err.to_string().contains("can't read date"), "{bad}: {err}");
+        }
+        assert_eq!(start(&vault).as_deref(), Some(in_a_week.as_str()));
+
+        assert_eq!(vault.snooze(&id, "none").unwrap(), None);
+        assert_eq!(start(&vault), None);
+    }
+}
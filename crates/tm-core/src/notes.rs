@@ -0,0 +1,496 @@
+//! Obsidian-Tasks-style checkboxes in ordinary notes (`notes = { folders = { '~/Notes' } }`):
+//!
+//! ```markdown
+//! - [ ] Call the bank 📅 2025-09-02 ⏫ #finance
+//! - [/] Draft the talk [start:: 2025-09-01] [priority:: high]
+//! - [x] Renew passport ✅ 2025-08-30
+//! ```
+//!
+//! Each one is listed as a task linked in place: `[ ]` open, `[/]` active, `[x]` closed and
+//! `[-]` cancelled; setting the status rewrites just that line (adding or removing the ✅ / ❌
+//! date). The project is the note's name, `#tags` are tags, and the emoji (📅 due, 🛫 / ⏳
+//! start, ➕ created, ✅ done, 🔺⏫🔼🔽⏬ priority) or dataview (`[due:: …]`) fields fill
+//! in the rest. Promoting one makes it a task file and marks the line `🆔 <ULID>`; the
+//! task's status is then mirrored onto the line.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use walkdir::WalkDir;
+
+use crate::{date, Category, Task, TaskNew, Vault, Workflow};
+
+/// Prefix of the ids given to note checkboxes
+pub const ID_PREFIX: &str = "NOTE";
+
+/// The frontmatter key a promoted task keeps its note in
+pub const NOTE_KEY: &str = "note";
+
+/// `notes = { folders = { '~/Notes' } }`: where to look for checkboxes (recursively)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Notes {
+    pub folders: Vec<PathBuf>,
+}
+
+/// Where a note task lives
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NoteRef {
+    pub path: PathBuf,
+    /// 0-based
+    pub line: usize,
+}
+
+/// One checkbox line, parsed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Checkbox {
+    /// the character between the brackets
+    pub mark: char,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub priority: Option<&'static str>,
+    pub due: Option<String>,
+    pub start: Option<String>,
+    pub scheduled: Option<String>,
+    pub created: Option<String>,
+    pub done: Option<String>,
+    /// `🆔` / `[id:: …]`
+    pub id: Option<String>,
+}
+
+/// A regex compiled on first use: every line of every note goes through them on each listing
+macro_rules! regex {
+    ($re:literal) => {{
+        static RE: OnceLock<Regex> = OnceLock::new();
+        RE.get_or_init(|| Regex::new($re).unwrap())
+    }};
+}
+
+pub(crate) fn checkbox_re() -> &'static Regex {
+    regex!(r"^(\s*(?:[-*+]|\d+[.)])\s+\[)(.)(\]\s+)(.*)$")
+}
+
+impl Checkbox {
+    pub fn parse(line: &str) -> Option<Self> {
+        let caps = checkbox_re().captures(line)?;
+        let mut text = caps[4].to_string();
+        let mut cb = Checkbox {
+            mark: caps[2].chars().next()?,
+            ..Default::default()
+        };
+        let dates = regex!(r"(📅|📆|🗓|⏳|⌛|🛫|➕|✅|❌)\u{FE0F}?\s*(\d{4}-\d{2}-\d{2})");
+        for c in dates.captures_iter(&text.clone()) {
+            let d = Some(c[2].to_string());
+            match &c[1] {
+                "📅" | "📆" | "🗓" => cb.due = d,
+                "⏳" | "⌛" => cb.scheduled = d,
+                "🛫" => cb.start = d,
+                "➕" => cb.created = d,
+                "✅" | "❌" => cb.done = d,
+                _ => {}
+            }
+        }
+        text = dates.replace_all(&text, "").into_owned();
+        let dataview = regex!(r"[\[(]([a-zA-Z]+)::\s*([^\])]*)[\])]");
+        for c in dataview.captures_iter(&text.clone()) {
+            let v = c[2].trim().to_string();
+            match &c[1] {
+                "due" => cb.due = Some(v),
+                "scheduled" => cb.scheduled = Some(v),
+                "start" => cb.start = Some(v),
+                "created" => cb.created = Some(v),
+                "completion" | "cancelled" => cb.done = Some(v),
+                "priority" => cb.priority = priority_word(&v),
+                "id" => cb.id = Some(v),
+                _ => {}
+            }
+        }
+        text = dataview.replace_all(&text, "").into_owned();
+        for (emoji, p) in [
+            ("🔺", "high"),
+            ("⏫", "high"),
+            ("🔼", "med"),
+            ("🔽", "low"),
+            ("⏬", "low"),
+        ] {
+            if text.contains(emoji) {
+                cb.priority = Some(p);
+                text = text.replace(emoji, "");
+            }
+        }
+        if let Some(c) = regex!(r"🆔\u{FE0F}?\s*([\w-]+)").captures(&text) {
+            cb.id = Some(c[1].to_string());
+        }
+        // recurrence, ids and dependencies: tm has no use for them in the title
+        let rest = regex!(
+            r"(🔁\u{FE0F}?\s*[\w ,]*\w|🆔\u{FE0F}?\s*[\w-]+|⛔\u{FE0F}?\s*[\w,-]+|🏁\u{FE0F}?\s*\w+)"
+        );
+        text = rest.replace_all(&text, "").into_owned();
+        let tags = regex!(r"(?:^|\s)#([\p{L}\p{N}_/-]+)");
+        cb.tags = tags
+            .captures_iter(&text)
+            .map(|c| c[1].to_string())
+            .collect();
+        text = tags.replace_all(&text, "").into_owned();
+        cb.title = text
+            .replace('\u{FE0F}', "")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        Some(cb)
+    }
+
+    /// The workflow status for the mark (unknown marks count as open)
+    pub fn status(&self, wf: &Workflow) -> Result<String> {
+        let first = |c| wf.first(c).map(str::to_string);
+        match self.mark {
+            'x' | 'X' => first(Category::Closed),
+            '/' => first(Category::Active),
+            '-' => wf.cancel_status().map(str::to_string),
+            _ => first(Category::Open),
+        }
+    }
+}
+
+fn priority_word(s: &str) -> Option<&'static str> {
+    match s.to_lowercase().as_str() {
+        "highest" | "high" => Some("high"),
+        "medium" | "med" => Some("med"),
+        "low" | "lowest" => Some("low"),
+        _ => None,
+    }
+}
+
+/// The mark for a status: `[ ]`, `[/]`, `[x]`, or `[-]` for the workflow's cancelled one
+fn mark_for(status: &str, wf: &Workflow) -> char {
+    match wf.category(status) {
+        Some(Category::Active) => '/',
+        Some(Category::Closed) if wf.is_cancelled(status) => '-',
+        Some(Category::Closed) => 'x',
+        _ => ' ',
+    }
+}
+
+/// `line` with a new mark, the ✅ / ❌ date added when it closes and dropped when it reopens
+fn set_mark(line: &str, mark: char) -> String {
+    let Some(caps) = checkbox_re().captures(line) else {
+        return line.to_string();
+    };
+    let stamps = regex!(r"\s*[✅❌]\u{FE0F}?\s*\d{4}-\d{2}-\d{2}");
+    let mut text = stamps.replace_all(&caps[4], "").into_owned();
+    match mark {
+        'x' => text.push_str(&format!(
+            " ✅ {}",
+            date::format_date("YYYY-MM-DD", date::today())
+        )),
+        '-' => text.push_str(&format!(
+            " ❌ {}",
+            date::format_date("YYYY-MM-DD", date::today())
+        )),
+        _ => {}
+    }
+    format!("{}{mark}{}{text}", &caps[1], &caps[3])
+}
+
+/// FNV-1a: stable across runs and Rust versions, unlike `DefaultHasher`
+fn fnv(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// `NOTE` + 12 hex digits of the note path and the checkbox's `🆔` or title (and how many
+/// identical ones came before it in the note)
+fn note_id(path: &Path, cb: &Checkbox, nth: usize) -> String {
+    let key = match &cb.id {
+        Some(id) => format!("{}\0id\0{id}", path.display()),
+        None => format!("{}\0{}\0{nth}", path.display(), cb.title),
+    };
+    format!("{ID_PREFIX}{:012X}", fnv(&key) & 0xFFFF_FFFF_FFFF)
+}
+
+fn rfc3339(t: SystemTime) -> String {
+    OffsetDateTime::from(t)
+        .replace_nanosecond(0)
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+        .format(&Rfc3339)
+        .unwrap()
+}
+
+/// The checkboxes of one note, as tasks; code blocks are skipped
+fn scan_note(path: &Path, wf: &Workflow) -> Result<Vec<(Task, Checkbox)>> {
+    let text = fs::read_to_string(path)?;
+    let modified = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(rfc3339)
+        .unwrap_or_default();
+    let project = slug::slugify(path.file_stem().and_then(|s| s.to_str()).unwrap_or("notes"));
+    let mut out: Vec<(Task, Checkbox)> = vec![];
+    let mut in_code = false;
+    for (line, s) in text.lines().enumerate() {
+        if s.trim_start().starts_with("```") || s.trim_start().starts_with("~~~") {
+            in_code = !in_code;
+        }
+        if in_code {
+            continue;
+        }
+        let Some(cb) = Checkbox::parse(s) else {
+            continue;
+        };
+        let nth = out
+            .iter()
+            .filter(|(t, c)| c.id.is_none() && t.title == cb.title)
+            .count();
+        let status = cb.status(wf)?;
+        let day = |d: &Option<String>| {
+            d.as_deref()
+                .filter(|d| date::parse_date(d).is_some())
+                .map(str::to_string)
+        };
+        let task = Task {
+            id: note_id(path, &cb, nth),
+            title: cb.title.clone(),
+            category: wf.category(&status),
+            status,
+            project: project.clone(),
+            tags: cb.tags.clone(),
+            priority: cb.priority.unwrap_or("none").into(),
+            due: day(&cb.due),
+            start: day(&cb.start).or_else(|| day(&cb.scheduled)),
+            created: day(&cb.created).unwrap_or_else(|| modified.clone()),
+            updated: modified.clone(),
+            done_at: day(&cb.done),
+            depends_on: vec![],
+            blocked: false,
+            estimate: None,
+            actual: 0,
+            time: vec![],
+            note: Some(NoteRef {
+                path: path.to_path_buf(),
+                line,
+            }),
+            checklist: vec![],
+        };
+        out.push((task, cb));
+    }
+    Ok(out)
+}
+
+fn note_files(folders: &[PathBuf]) -> impl Iterator<Item = PathBuf> + '_ {
+    folders.iter().flat_map(|dir| {
+        WalkDir::new(dir)
+            .into_iter()
+            // .obsidian, .trash, ...
+            .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("md"))
+            .map(|e| e.into_path())
+    })
+}
+
+/// Rewrite one line of a file, keeping the rest byte for byte
+pub(crate) fn rewrite_line(path: &Path, line: usize, f: impl FnOnce(&str) -> String) -> Result<()> {
+    let text = fs::read_to_string(path)?;
+    let mut lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let Some(l) = lines.get(line).copied() else {
+        bail!("{} has no line {}", path.display(), line + 1)
+    };
+    let body = l.trim_end_matches(['\r', '\n']);
+    let new = f(body) + &l[body.len()..];
+    lines[line] = &new;
+    fs::write(path, lines.concat())?;
+    Ok(())
+}
+
+impl Vault {
+    /// Checkboxes in the configured note folders, except promoted ones (their `🆔` names a
+    /// task file)
+    pub fn note_tasks(&self, task_ids: &[String]) -> Result<Vec<Task>> {
+        let mut out = vec![];
+        for path in note_files(&self.cfg.notes.folders) {
+            // an unreadable note shouldn't hide every task
+            let Ok(found) = scan_note(&path, &self.cfg.workflow) else {
+                continue;
+            };
+            for (t, cb) in found {
+                if !cb.id.as_ref().is_some_and(|id| task_ids.contains(id)) {
+                    out.push(t);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// The note checkbox with this id, freshly scanned (the line may have moved)
+    pub fn note_task(&self, id: &str) -> Result<(Task, Checkbox)> {
+        for path in note_files(&self.cfg.notes.folders) {
+            if let Some(hit) = scan_note(&path, &self.cfg.workflow)?
+                .into_iter()
+                .find(|(t, _)| t.id == id)
+            {
+                return Ok(hit);
+            }
+        }
+        bail!("note task {id} not found (edited or moved?)")
+    }
+
+    /// Set a note checkbox's status by rewriting its mark
+    pub(crate) fn set_note_status(&self, id: &str, status: &str) -> Result<()> {
+        let (task, _) = self.note_task(id)?;
+        let note = task.note.context("not a note task")?;
+        self.cfg.workflow.check_transition(&task.status, status)?;
+        let mark = mark_for(status, &self.cfg.workflow);
+        rewrite_line(&note.path, note.line, |l| set_mark(l, mark))
+    }
+
+    /// After a promoted task's status changed: mark its `🆔` line in the note to match
+    pub(crate) fn sync_promoted(&self, id: &str, note: &str, status: &str) -> Result<()> {
+        let path = PathBuf::from(note);
+        let text = fs::read_to_string(&path).with_context(|| format!("reading {note}"))?;
+        let Some(line) = text
+            .lines()
+            .position(|l| Checkbox::parse(l).is_some_and(|cb| cb.id.as_deref() == Some(id)))
+        else {
+            return Ok(());
+        };
+        let mark = mark_for(status, &self.cfg.workflow);
+        rewrite_line(&path, line, |l| {
+            if Checkbox::parse(l).is_some_and(|cb| cb.mark == mark) {
+                l.to_string()
+            } else {
+                set_mark(l, mark)
+            }
+        })
+    }
+
+    /// Make a task file from a note checkbox: same fields, `note:` pointing back, and the
+    /// line tagged `🆔 <ULID>` so it isn't listed twice. Returns the new id.
+    pub fn promote_note_task(&self, id: &str) -> Result<String> {
+        if !is_note_id(id) {
+            bail!("{id} is already a task file");
+        }
+        let (task, _) = self.note_task(id)?;
+        let note = task.note.clone().context("not a note task")?;
+        let new_id = self.create_task(TaskNew {
+            title: task.title.clone(),
+            project: task.project.clone(),
+            due: task.due.clone(),
+            tags: task.tags.clone(),
+            parent: None,
+        })?;
+        self.edit_task(&new_id, |fm| {
+            fm.status = task.status.clone();
+            fm.priority = task.priority.clone();
+            fm.start = task.start.clone();
+            fm.done_at = task.done_at.clone();
+            fm.extra
+                .insert(NOTE_KEY.into(), note.path.display().to_string().into());
+            Ok(())
+        })?;
+        let id_mark = regex!(r"\s*🆔\u{FE0F}?\s*[\w-]+");
+        rewrite_line(&note.path, note.line, |l| {
+            format!("{} 🆔 {new_id}", id_mark.replace_all(l, ""))
+        })?;
+        Ok(new_id)
+    }
+}
+
+/// Whether an id is a note checkbox's (`O` is not in the ULID alphabet, so no task file's is)
+pub fn is_note_id(id: &str) -> bool {
+    id.starts_with(ID_PREFIX)
+}
+
+/// For the CLI: `path:line` (1-based)
+pub fn location(note: &NoteRef) -> String {
+    format!("{}:{}", note.path.display(), note.line + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::vault;
+
+    #[test]
+    fn checkbox_fields_are_read() {
+        let cb = Checkbox::parse("- [/] Call the bank 📅 2025-09-02 ⏫ #finance [start:: 2025-09-01] 🔁 every week 🆔 abc1").unwrap();
+        assert_eq!(
+            (cb.mark, cb.title.as_str(), cb.priority),
+            ('/', "Call the bank", Some("high"))
+        );
+        assert_eq!(
+            (cb.due.as_deref(), cb.start.as_deref()),
+            (Some("2025-09-02"), Some("2025-09-01"))
+        );
+        assert_eq!(
+            (cb.tags.as_slice(), cb.id.as_deref()),
+            (&["finance".to_string()][..], Some("abc1"))
+        );
+        assert!(Checkbox::parse("- not a checkbox").is_none());
+        assert_eq!(
+            Checkbox::parse("1. [x] Done ✅ 2025-08-30")
+                .unwrap()
+                .done
+                .as_deref(),
+            Some("2025-08-30")
+        );
+    }
+
+    #[test]
+    fn marks_and_statuses_round_trip() {
+        let wf = Workflow::default();
+        for (mark, status) in [
+            (' ', "todo"),
+            ('/', "doing"),
+            ('x', "done"),
+            ('-', "cancelled"),
+        ] {
+            let cb = Checkbox {
+                mark,
+                ..Default::default()
+            };
+            assert_eq!(cb.status(&wf).unwrap(), status);
+            assert_eq!(mark_for(status, &wf), mark);
+        }
+        let wf = Workflow {
+            cancelled: Some("done".into()),
+            ..Workflow::default()
+        };
+        assert_eq!(mark_for("done", &wf), '-');
+        assert_eq!(mark_for("cancelled", &wf), 'x');
+    }
+
+    #[test]
+    fn setting_a_status_rewrites_only_its_line() {
+        let (dir, mut vault) = vault();
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(&notes).unwrap();
+        let path = notes.join("Home.md");
+        fs::write(&path, "# Home\n\n- [ ] Fix the tap #diy\n```\n- [ ] in code\n```\n- [x] Paint ✅ 2025-08-30\n").unwrap();
+        vault.cfg.notes.folders = vec![notes];
+
+        let tasks = vault.note_tasks(&[]).unwrap();
+        assert_eq!(
+            tasks
+                .iter()
+                .map(|t| (t.title.as_str(), t.status.as_str()))
+                .collect::<Vec<_>>(),
+            [("Fix the tap", "todo"), ("Paint", "done")]
+        );
+        assert_eq!(tasks[0].project, "home");
+
+        vault.set_note_status(&tasks[0].id, "cancelled").unwrap();
+        vault.set_note_status(&tasks[1].id, "todo").unwrap();
+        let today = date::format_date("YYYY-MM-DD", date::today());
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text, format!("# Home\n\n- [-] Fix the tap #diy ❌ {today}\n```\n- [ ] in code\n```\n- [ ] Paint\n"));
+        assert_eq!(vault.note_task(&tasks[0].id).unwrap().0.status, "cancelled");
+    }
+}
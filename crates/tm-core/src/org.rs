@@ -0,0 +1,576 @@
+//! Org-mode: `tm export --to org` writes one heading per project with its tasks below, and
+//! `tm import --from org` reads the same file back, merging edits made in Emacs by ULID.
+//!
+//! ```org
+//! #+TODO: TODO DOING | DONE CANCELLED
+//! * Work                                                   :office:
+//!   :PROPERTIES:
+//!   :PROJECT: work
+//!   :END:
+//! ** DOING [#A] Write report                               :writing:q4:
+//!    DEADLINE: <2026-10-20 Tue> SCHEDULED: <2026-10-14 Wed>
+//!    :PROPERTIES:
+//!    :ID: 01J8...
+//!    :Effort: 2:30
+//!    :END:
+//!    The body, indented under its heading.
+//! ```
+//!
+//! Statuses are upper-cased into keywords (`#+TODO` lists them, closed ones after `|`),
+//! `[#A]`/`[#B]`/`[#C]` are high/med/low, `DEADLINE` is `due`, `SCHEDULED` is `start` and
+//! `CLOSED` the day the task was closed. `:PARENT:` and `:DEPENDS_ON:` hold ids. Every
+//! heading below a project is a task; one without an `:ID:` is new unless its project
+//! already has a task by that title. Tasks missing from the file are left alone.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use regex::Regex;
+use time::Date;
+use ulid::Ulid;
+
+use crate::{
+    date,
+    export::{self, Conflict, ImportReport, ProjectRecord, TaskRecord},
+    parse_duration, track, Category, Config, Frontmatter, ProjectFrontmatter, Query, Vault,
+};
+
+/// The keyword for a status: `in review` → `IN_REVIEW`
+fn keyword(status: &str) -> String {
+    status
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Org tags are letters, digits and `_@#%`
+fn org_tag(tag: &str) -> String {
+    tag.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "_@#%".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// `<2026-10-20 Tue>` (`[…]` when inactive)
+fn timestamp(d: Date, active: bool) -> String {
+    let (open, close) = if active { ('<', '>') } else { ('[', ']') };
+    let day = d.weekday().to_string();
+    format!(
+        "{open}{} {}{close}",
+        date::format_date("YYYY-MM-DD", d),
+        &day[..3]
+    )
+}
+
+/// Org's `H:MM` effort
+fn effort(secs: i64) -> String {
+    format!("{}:{:02}", secs / 3600, secs % 3600 / 60)
+}
+
+fn parse_effort(s: &str) -> Option<i64> {
+    match s.trim().split_once(':') {
+        Some((h, m)) => Some(h.parse::<i64>().ok()? * 3600 + m.parse::<i64>().ok()? * 60),
+        None => parse_duration(s).ok(),
+    }
+}
+
+/// A heading with its planning line, properties and body (unindented)
+#[derive(Clone, Debug, Default)]
+struct Heading {
+    level: usize,
+    keyword: Option<String>,
+    priority: Option<char>,
+    title: String,
+    tags: Vec<String>,
+    deadline: Option<Date>,
+    scheduled: Option<Date>,
+    closed: Option<Date>,
+    props: BTreeMap<String, String>,
+    body: Vec<String>,
+}
+
+/// Headings of an org file; `keywords` are the ones a heading may start with
+fn parse(text: &str, keywords: &[String]) -> Vec<Heading> {
+    let heading = Regex::new(r"^(\*+)\s+(.*?)\s*$").unwrap();
+    let tags = Regex::new(r"\s+:([\w@#%:]+):$").unwrap();
+    let priority = Regex::new(r"^\[#([A-Z])\]\s*").unwrap();
+    let planning =
+        Regex::new(r"(DEADLINE|SCHEDULED|CLOSED):\s*[<\[](\d{4}-\d{2}-\d{2})[^>\]]*[>\]]").unwrap();
+    let property = Regex::new(r"^\s*:([^:\s]+):\s*(.*?)\s*$").unwrap();
+    let mut out: Vec<Heading> = vec![];
+    let mut in_drawer = false;
+    for line in text.lines() {
+        if let Some(caps) = heading.captures(line) {
+            let mut h = Heading {
+                level: caps[1].len(),
+                ..Default::default()
+            };
+            let mut rest = caps[2].to_string();
+            if let Some(t) = tags.captures(&rest) {
+                h.tags = t[1]
+                    .split(':')
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                rest.truncate(t.get(0).unwrap().start());
+            }
+            let (first, more) = rest.split_once(' ').unwrap_or((&rest, ""));
+            if keywords.iter().any(|k| k == first) {
+                h.keyword = Some(first.to_string());
+                rest = more.trim_start().to_string();
+            }
+            if let Some(p) = priority.captures(&rest) {
+                h.priority = p[1].chars().next();
+                rest = rest[p.get(0).unwrap().end()..].to_string();
+            }
+            h.title = rest.trim().to_string();
+            out.push(h);
+            in_drawer = false;
+            continue;
+        }
+        let Some(h) = out.last_mut() else { continue }; // `#+TODO:` and other preamble
+        let trimmed = line.trim();
+        let at_top = h.body.is_empty();
+        if in_drawer {
+            if trimmed == ":END:" {
+                in_drawer = false;
+            } else if let Some(caps) = property.captures(line) {
+                h.props.insert(caps[1].to_uppercase(), caps[2].to_string());
+            }
+        } else if at_top && trimmed == ":PROPERTIES:" {
+            in_drawer = true;
+        } else if at_top
+            && h.props.is_empty()
+            && ["DEADLINE:", "SCHEDULED:", "CLOSED:"]
+                .iter()
+                .any(|p| trimmed.starts_with(p))
+        {
+            for caps in planning.captures_iter(trimmed) {
+                let d = date::parse_date(&caps[2]);
+                match &caps[1] {
+                    "DEADLINE" => h.deadline = d,
+                    "SCHEDULED" => h.scheduled = d,
+                    _ => h.closed = d,
+                }
+            }
+        } else {
+            // the body is indented under the heading; take that back off
+            let indent = line.len() - line.trim_start_matches(' ').len();
+            h.body.push(line[indent.min(h.level + 1)..].to_string());
+        }
+    }
+    out
+}
+
+impl Heading {
+    fn prop(&self, key: &str) -> Option<&str> {
+        self.props
+            .get(&key.to_uppercase())
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    }
+
+    fn body_text(&self) -> String {
+        let lines = &self.body;
+        let start = lines
+            .iter()
+            .position(|l| !l.trim().is_empty())
+            .unwrap_or(lines.len());
+        let end = lines
+            .iter()
+            .rposition(|l| !l.trim().is_empty())
+            .map_or(start, |i| i + 1);
+        lines[start..end].join("\n")
+    }
+
+    /// Put what the heading says into `fm` / `body`; fields org doesn't carry stay as they are
+    fn apply(
+        &self,
+        fm: &mut Frontmatter,
+        body: &mut String,
+        project: &str,
+        cfg: &Config,
+    ) -> Result<()> {
+        let wf = &cfg.workflow;
+        fm.title = self.title.clone();
+        if fm.key.is_empty() {
+            fm.key = slug::slugify(&self.title);
+        }
+        fm.project = project.to_string();
+        let was_closed = wf.category(&fm.status) == Some(Category::Closed);
+        if let Some(kw) = &self.keyword {
+            if let Some(s) = wf.names().into_iter().find(|s| keyword(s) == *kw) {
+                fm.status = s;
+            }
+        }
+        fm.priority = match self.priority {
+            Some('A') => "high",
+            Some('B') => "med",
+            Some('C') => "low",
+            _ => "none",
+        }
+        .into();
+        // keep a tag's spelling when only org's tag rules changed it
+        let old = std::mem::take(&mut fm.tags);
+        fm.tags = self
+            .tags
+            .iter()
+            .map(|t| {
+                old.iter()
+                    .find(|o| org_tag(o) == *t)
+                    .cloned()
+                    .unwrap_or_else(|| t.clone())
+            })
+            .collect();
+        fm.due = date::same_day(&fm.due, self.deadline);
+        fm.start = date::same_day(&fm.start, self.scheduled);
+        fm.parent = self.prop("PARENT").map(str::to_string);
+        fm.depends_on = self
+            .prop("DEPENDS_ON")
+            .map(|d| d.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+        let secs = self.prop("EFFORT").and_then(parse_effort);
+        if secs != fm.estimate.as_deref().and_then(|e| parse_duration(e).ok()) {
+            fm.estimate = secs.map(track::format_duration);
+        }
+        let closed = wf.category(&fm.status) == Some(Category::Closed);
+        if closed && !was_closed {
+            track::stop(&mut fm.time);
+            fm.done_at = Some(match self.closed {
+                Some(d) if d != date::today() => date::local_midnight(d),
+                _ => track::now_rfc3339(),
+            });
+        } else if !closed {
+            fm.done_at = None;
+        }
+        let text = self.body_text();
+        if text != body.trim_matches('\n') {
+            *body = if text.is_empty() {
+                String::new()
+            } else {
+                format!("\n{text}\n")
+            };
+        }
+        Ok(())
+    }
+}
+
+impl Vault {
+    /// Matching tasks (all for an empty query) as an org file, one heading per project
+    pub fn export_org(&self, query: &Query) -> Result<String> {
+        let data = self.export_json(query)?;
+        let wf = &self.cfg.workflow;
+        let names = wf.names();
+        let (closed, open): (Vec<&String>, Vec<&String>) = names
+            .iter()
+            .partition(|s| wf.category(s) == Some(Category::Closed));
+        let kw = |v: Vec<&String>| {
+            v.into_iter()
+                .map(|s| keyword(s))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut out = format!("#+TODO: {} | {}\n", kw(open), kw(closed));
+
+        let mut by_project: BTreeMap<&str, Vec<&TaskRecord>> = BTreeMap::new();
+        for t in &data.tasks {
+            by_project
+                .entry(&t.frontmatter.project)
+                .or_default()
+                .push(t);
+        }
+        let titles: HashMap<&str, &ProjectRecord> = data
+            .projects
+            .iter()
+            .map(|p| (p.frontmatter.key.as_str(), p))
+            .collect();
+        for (key, tasks) in by_project {
+            let (title, tags) = match titles.get(key) {
+                Some(p) => (p.frontmatter.title.as_str(), p.frontmatter.tags.as_slice()),
+                None => (key, &[][..]),
+            };
+            out += &heading_line(1, None, None, title, tags);
+            out += &format!("  :PROPERTIES:\n  :PROJECT: {key}\n  :END:\n");
+            for t in tasks {
+                let fm = &t.frontmatter;
+                let pri = match fm.priority.as_str() {
+                    "high" => Some('A'),
+                    "med" => Some('B'),
+                    "low" => Some('C'),
+                    _ => None,
+                };
+                out += &heading_line(2, Some(&keyword(&fm.status)), pri, &fm.title, &fm.tags);
+                let day = |s: &Option<String>| s.as_deref().and_then(date::parse_date);
+                let mut planning = vec![];
+                if let Some(d) = day(&fm.done_at) {
+                    planning.push(format!("CLOSED: {}", timestamp(d, false)));
+                }
+                if let Some(d) = day(&fm.due) {
+                    planning.push(format!("DEADLINE: {}", timestamp(d, true)));
+                }
+                if let Some(d) = day(&fm.start) {
+                    planning.push(format!("SCHEDULED: {}", timestamp(d, true)));
+                }
+                if !planning.is_empty() {
+                    out += &format!("   {}\n", planning.join(" "));
+                }
+                out += &format!("   :PROPERTIES:\n   :ID: {}\n", fm.id);
+                if let Some(p) = &fm.parent {
+                    out += &format!("   :PARENT: {p}\n");
+                }
+                if !fm.depends_on.is_empty() {
+                    out += &format!("   :DEPENDS_ON: {}\n", fm.depends_on.join(" "));
+                }
+                if let Some(secs) = fm.estimate.as_deref().and_then(|e| parse_duration(e).ok()) {
+                    out += &format!("   :Effort: {}\n", effort(secs));
+                }
+                out += "   :END:\n";
+                for line in t.body.trim_matches('\n').lines() {
+                    out += &if line.is_empty() {
+                        "\n".to_string()
+                    } else {
+                        format!("   {line}\n")
+                    };
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Merge an org file into the vault: headings with a known `:ID:` update their task,
+    /// others are new tasks; unknown projects are created
+    pub fn import_org(
+        &self,
+        text: &str,
+        strategy: Conflict,
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        let existing = self.records_by_id()?;
+        // projects with a file or with tasks
+        let mut known: Vec<String> = self.list_projects()?.into_iter().map(|p| p.key).collect();
+        known.extend(existing.values().map(|r| r.frontmatter.project.clone()));
+        let keywords: Vec<String> = self
+            .cfg
+            .workflow
+            .names()
+            .iter()
+            .map(|s| keyword(s))
+            .collect();
+        let mut records = vec![];
+        let mut unchanged = vec![];
+        let mut projects: BTreeMap<String, ProjectRecord> = BTreeMap::new();
+        let mut project = self.cfg.default_project.clone();
+        for h in parse(text, &keywords) {
+            if h.level == 1 {
+                project = h
+                    .prop("PROJECT")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| slug::slugify(&h.title));
+                if !known.contains(&project) {
+                    let frontmatter = ProjectFrontmatter {
+                        key: project.clone(),
+                        title: h.title.clone(),
+                        status: "active".into(),
+                        tags: h.tags.clone(),
+                        created: None,
+                        updated: None,
+                        description: None,
+                        extra: BTreeMap::new(),
+                    };
+                    projects.insert(
+                        project.clone(),
+                        ProjectRecord {
+                            frontmatter,
+                            body: String::new(),
+                        },
+                    );
+                }
+                continue;
+            }
+            let known_id = h.prop("ID").filter(|id| Ulid::from_string(id).is_ok());
+            // a heading added in Emacs has no id yet; importing it again finds the task by title
+            let found = match known_id {
+                Some(id) => existing.get(id),
+                None => existing
+                    .values()
+                    .find(|r| r.frontmatter.project == project && r.frontmatter.title == h.title),
+            };
+            let (mut fm, mut body) = match found {
+                Some(r) => (r.frontmatter.clone(), r.body.clone()),
+                None => {
+                    let id = known_id
+                        .map(str::to_string)
+                        .unwrap_or_else(|| Ulid::new().to_string());
+                    (export::new_task(id, &self.cfg)?, String::new())
+                }
+            };
+            let before = (fm.clone(), body.clone());
+            h.apply(&mut fm, &mut body, &project, &self.cfg)?;
+            if (&fm, &body) != (&before.0, &before.1) || !existing.contains_key(&fm.id) {
+                fm.updated = Some(track::now_rfc3339());
+                records.push(TaskRecord {
+                    frontmatter: fm,
+                    body,
+                });
+            } else {
+                unchanged.push(fm.id);
+            }
+        }
+        let mut data = export::as_export(records);
+        data.projects = projects.into_values().collect();
+        let mut report = self.import_json(&data, strategy, dry_run)?;
+        report.unchanged.extend(unchanged);
+        Ok(report)
+    }
+}
+
+/// `** DOING [#A] Title   :tag:` (tags right-aligned to column 77 like Emacs does)
+fn heading_line(
+    level: usize,
+    keyword: Option<&str>,
+    priority: Option<char>,
+    title: &str,
+    tags: &[String],
+) -> String {
+    let mut s = "*".repeat(level);
+    if let Some(k) = keyword {
+        s += &format!(" {k}");
+    }
+    if let Some(p) = priority {
+        s += &format!(" [#{p}]");
+    }
+    s += &format!(" {title}");
+    if !tags.is_empty() {
+        let tags = format!(
+            ":{}:",
+            tags.iter()
+                .map(|t| org_tag(t))
+                .collect::<Vec<_>>()
+                .join(":")
+        );
+        let pad = 77usize
+            .saturating_sub(s.chars().count() + tags.chars().count())
+            .max(1);
+        s += &format!("{}{tags}", " ".repeat(pad));
+    }
+    s + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{task, vault};
+
+    fn fields(vault: &Vault) -> Vec<(Frontmatter, String)> {
+        let mut out: Vec<_> = vault
+            .records_by_id()
+            .unwrap()
+            .into_values()
+            .map(|r| {
+                let fm = Frontmatter {
+                    created: None,
+                    updated: None,
+                    done_at: None,
+                    key: String::new(),
+                    ..r.frontmatter
+                };
+                (fm, r.body.trim().to_string())
+            })
+            .collect();
+        out.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+        out
+    }
+
+    #[test]
+    fn efforts_and_keywords() {
+        assert_eq!(effort(9000), "2:30");
+        assert_eq!(parse_effort("2:30"), Some(9000));
+        assert_eq!(keyword("in review"), "IN_REVIEW");
+        assert_eq!(org_tag("q4-goals"), "q4_goals");
+    }
+
+    #[test]
+    fn an_export_reads_back_unchanged_and_into_a_new_vault() {
+        let (_dir, vault) = vault();
+        let first = task(&vault, "Book flights");
+        let second = task(&vault, "Write report");
+        let mut record = serde_json::to_value(&vault.records_by_id().unwrap()[&second]).unwrap();
+        let patch = serde_json::json!({
+            "status": "doing", "priority": "high", "tags": ["q4", "writing"],
+            "due": "2026-10-20", "start": "2026-10-14", "estimate": "2h30m", "depends_on": [first],
+            "body": "\nFirst line.\n\n- [ ] outline\n",
+        });
+        record
+            .as_object_mut()
+            .unwrap()
+            .extend(patch.as_object().unwrap().clone());
+        let record = serde_json::from_value(record).unwrap();
+        vault
+            .import_json(&export::as_export(vec![record]), Conflict::Overwrite, false)
+            .unwrap();
+        vault.set_status(&first, "done").unwrap();
+        let text = vault.export_org(&Query::default()).unwrap();
+        assert!(
+            text.starts_with("#+TODO: TODO DOING | DONE CANCELLED\n"),
+            "{text}"
+        );
+        assert!(text.contains("** DOING [#A] Write report"));
+        assert!(text.contains("DEADLINE: <2026-10-20 Tue> SCHEDULED: <2026-10-14 Wed>"));
+
+        let report = vault.import_org(&text, Conflict::Overwrite, false).unwrap();
+        assert!(
+            report.created.is_empty() && report.updated.is_empty(),
+            "{}",
+            report.summary()
+        );
+        assert_eq!(report.unchanged.len(), 2);
+
+        let (_dir2, other) = crate::tests::vault();
+        other.import_org(&text, Conflict::Overwrite, false).unwrap();
+        assert_eq!(fields(&other), fields(&vault));
+    }
+
+    #[test]
+    fn edits_in_emacs_are_merged() {
+        let (_dir, vault) = vault();
+        let id = task(&vault, "Book flights");
+        let text = vault.export_org(&Query::default()).unwrap();
+        let edited = text.replace("** TODO Book flights", "** DONE Book the flights")
+            + "** TODO [#C] Pack\n";
+        let report = vault
+            .import_org(&edited, Conflict::Overwrite, false)
+            .unwrap();
+        assert_eq!(
+            (report.created.len(), report.updated.as_slice()),
+            (1, &[id.clone()][..])
+        );
+        let tasks = vault.list_tasks(None).unwrap();
+        let t = tasks.iter().find(|t| t.id == id).unwrap();
+        assert_eq!(
+            (t.title.as_str(), t.status.as_str()),
+            ("Book the flights", "done")
+        );
+        assert!(t.done_at.is_some());
+        let new = tasks.iter().find(|t| t.title == "Pack").unwrap();
+        assert_eq!(
+            (new.priority.as_str(), new.project.as_str()),
+            ("low", "inbox")
+        );
+        // the new heading has no :ID: yet; importing it again finds it by title
+        let report = vault
+            .import_org(&edited, Conflict::Overwrite, false)
+            .unwrap();
+        assert!(report.created.is_empty());
+    }
+}
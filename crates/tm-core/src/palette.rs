@@ -0,0 +1,473 @@
+//! Global fuzzy palette (`<leader>p`): entries from several sources (commands, tasks,
+//! projects, tags, views, recent filters, plugin sources), ranked by a subsequence matcher.
+//! A leading `>` / `#` / `@` narrows the query to commands / tags / projects.
+
+use crate::{date, CommandDef, Query, QuickAction, Task, Vault, View};
+
+/// What choosing an entry does
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaletteTarget {
+    /// an ex command by name: run it, or open the ex bar when it needs arguments
+    Command(String),
+    /// a full ex line to run as is (plugin entries)
+    Run(String),
+    /// a quick action by id (see `QuickActions`)
+    Quick(String),
+    Task(String),
+    Project(String),
+    Tag(String),
+    /// a filter query
+    Filter(String),
+    /// a named view
+    View(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaletteItem {
+    /// source name (`commands`, `tasks`, ..., or a plugin source)
+    pub source: String,
+    pub label: String,
+    /// dimmed text after the label
+    pub detail: String,
+    /// preview pane text; tasks, projects, tags and filters are previewed from the vault
+    pub preview: Option<String>,
+    pub target: PaletteTarget,
+}
+
+/// A source and the query prefix that selects it alone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaletteSource {
+    pub name: String,
+    pub prefix: Option<char>,
+}
+
+/// One ranked entry: index into the palette items and the matched char positions in its label
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaletteMatch {
+    pub index: usize,
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Palette {
+    pub sources: Vec<PaletteSource>,
+    pub items: Vec<PaletteItem>,
+}
+
+impl Palette {
+    /// Built-in sources: tasks, projects (`@`), tags (`#`), views, recent filters and
+    /// commands (`>`)
+    pub fn new(
+        tasks: &[Task],
+        projects: &[String],
+        commands: &[CommandDef],
+        views: &[View],
+        filters: &[(String, String)],
+    ) -> Self {
+        let mut p = Self::default();
+        p.add_source("tasks", None);
+        p.add_source("projects", Some('@'));
+        p.add_source("tags", Some('#'));
+        p.add_source("views", None);
+        p.add_source("filters", None);
+        p.add_source("commands", Some('>'));
+
+        for t in tasks {
+            p.items.push(PaletteItem {
+                source: "tasks".into(),
+                label: t.title.clone(),
+                detail: format!("[{}] {}", t.status, t.project),
+                preview: None,
+                target: PaletteTarget::Task(t.id.clone()),
+            });
+        }
+        for k in projects {
+            let n = tasks.iter().filter(|t| t.project == *k).count();
+            p.items.push(PaletteItem {
+                source: "projects".into(),
+                label: format!("@{k}"),
+                detail: format!("{n} tasks"),
+                preview: None,
+                target: PaletteTarget::Project(k.clone()),
+            });
+        }
+        let mut tags: Vec<&String> = tasks.iter().flat_map(|t| &t.tags).collect();
+        tags.sort();
+        tags.dedup();
+        for tag in tags {
+            let n = tasks.iter().filter(|t| t.tags.contains(tag)).count();
+            p.items.push(PaletteItem {
+                source: "tags".into(),
+                label: format!("#{tag}"),
+                detail: format!("{n} tasks"),
+                preview: None,
+                target: PaletteTarget::Tag(tag.clone()),
+            });
+        }
+        for v in views {
+            p.items.push(PaletteItem {
+                source: "views".into(),
+                label: v.name.clone(),
+                detail: format!("view: {}", v.query),
+                preview: Some(query_preview(&v.query, tasks)),
+                target: PaletteTarget::View(v.name.clone()),
+            });
+        }
+        for (name, query) in filters {
+            p.items.push(PaletteItem {
+                source: "filters".into(),
+                label: name.clone(),
+                detail: query.clone(),
+                preview: None,
+                target: PaletteTarget::Filter(query.clone()),
+            });
+        }
+        for c in commands {
+            p.items.push(PaletteItem {
+                source: "commands".into(),
+                label: format!(">{}", c.name),
+                detail: c.summary.clone(),
+                preview: Some(format!("{}\n\n{}", c.signature, c.summary)),
+                target: PaletteTarget::Command(c.name.clone()),
+            });
+        }
+        p
+    }
+
+    /// Quick actions that apply right now, listed with the commands (`>`)
+    pub fn add_quick_actions(&mut self, actions: &[&QuickAction]) {
+        for a in actions {
+            self.items.push(PaletteItem {
+                source: "commands".into(),
+                label: format!(">{}", a.label),
+                detail: "quick action".into(),
+                preview: Some(format!("{} (<leader>a)", a.label)),
+                target: PaletteTarget::Quick(a.id.clone()),
+            });
+        }
+    }
+
+    /// Register a source (plugins); a prefix already taken is ignored
+    pub fn add_source(&mut self, name: &str, prefix: Option<char>) {
+        let prefix = prefix.filter(|c| !self.sources.iter().any(|s| s.prefix == Some(*c)));
+        if !self.sources.iter().any(|s| s.name == name) {
+            self.sources.push(PaletteSource {
+                name: name.to_string(),
+                prefix,
+            });
+        }
+    }
+
+    /// Entries for `query`, best first. An empty query lists everything in source order.
+    pub fn filter(&self, query: &str) -> Vec<PaletteMatch> {
+        let (only, pattern) = match query.chars().next() {
+            Some(c) => match self.sources.iter().find(|s| s.prefix == Some(c)) {
+                Some(s) => (Some(s.name.as_str()), &query[c.len_utf8()..]),
+                None => (None, query),
+            },
+            None => (None, query),
+        };
+        let order = |src: &str| {
+            self.sources
+                .iter()
+                .position(|s| s.name == src)
+                .unwrap_or(usize::MAX)
+        };
+        let mut out: Vec<(usize, PaletteMatch)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, it)| only.map(|s| it.source == s).unwrap_or(true))
+            .filter_map(|(index, it)| {
+                // with a prefix query the label's own sigil (`@`, `#`, `>`) is not part of the match
+                let skip = if only.is_some() {
+                    sigil_len(&it.label)
+                } else {
+                    0
+                };
+                let (score, positions) = fuzzy_match(pattern.trim(), &it.label[skip..])?;
+                let positions = positions
+                    .into_iter()
+                    .map(|p| p + usize::from(skip > 0))
+                    .collect();
+                Some((
+                    order(&it.source),
+                    PaletteMatch {
+                        index,
+                        score,
+                        positions,
+                    },
+                ))
+            })
+            .collect();
+        if !pattern.trim().is_empty() {
+            out.sort_by(|(oa, a), (ob, b)| {
+                b.score
+                    .cmp(&a.score)
+                    .then(oa.cmp(ob))
+                    .then(
+                        self.items[a.index]
+                            .label
+                            .len()
+                            .cmp(&self.items[b.index].label.len()),
+                    )
+                    .then(a.index.cmp(&b.index))
+            });
+        } else {
+            out.sort_by_key(|(o, m)| (*o, m.index));
+        }
+        out.into_iter().map(|(_, m)| m).collect()
+    }
+}
+
+fn sigil_len(label: &str) -> usize {
+    match label.chars().next() {
+        Some(c @ ('@' | '#' | '>')) => c.len_utf8(),
+        _ => 0,
+    }
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 12;
+const BONUS_BOUNDARY: i64 = 10;
+const BONUS_FIRST: i64 = 8;
+const PENALTY_GAP: i64 = 2;
+
+/// Fuzzy-match `pattern` against `text`. Every space-separated term must appear in `text`
+/// as a subsequence; smart case (case-sensitive only if the term has uppercase letters).
+/// Returns the score (higher is better) and matched char positions, or None.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut score = 0;
+    let mut positions = Vec::new();
+    for term in pattern.split_whitespace() {
+        let (s, pos) = match_term(term, &chars)?;
+        score += s;
+        positions.extend(pos);
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    // shorter labels win ties
+    Some((score - chars.len() as i64 / 8, positions))
+}
+
+fn match_term(term: &str, text: &[char]) -> Option<(i64, Vec<usize>)> {
+    let case_sensitive = term.chars().any(char::is_uppercase);
+    let norm = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let pat: Vec<char> = term.chars().map(norm).collect();
+    let hay: Vec<char> = text.iter().copied().map(norm).collect();
+
+    // forward: the first window containing the pattern as a subsequence...
+    let mut pi = 0;
+    let mut end = None;
+    for (i, &c) in hay.iter().enumerate() {
+        if c == pat[pi] {
+            pi += 1;
+            if pi == pat.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    // ...then backward from its end for the tightest one
+    let mut positions = vec![0; pat.len()];
+    let mut pi = pat.len();
+    for i in (0..=end).rev() {
+        if hay[i] == pat[pi - 1] {
+            pi -= 1;
+            positions[pi] = i;
+            if pi == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    for (k, &i) in positions.iter().enumerate() {
+        score += SCORE_MATCH;
+        if i == 0 {
+            score += BONUS_FIRST + BONUS_BOUNDARY;
+        } else if is_boundary(text[i - 1], text[i]) {
+            score += BONUS_BOUNDARY;
+        }
+        if k > 0 {
+            let gap = (i - positions[k - 1] - 1) as i64;
+            if gap == 0 {
+                score += BONUS_CONSECUTIVE;
+            } else {
+                score -= PENALTY_GAP * gap.min(8);
+            }
+        }
+    }
+    Some((score, positions))
+}
+
+/// Start of a word: after a separator, or a camelCase hump
+fn is_boundary(prev: char, cur: char) -> bool {
+    matches!(
+        prev,
+        ' ' | '-' | '_' | '/' | ':' | '.' | '@' | '#' | '>' | '[' | '('
+    ) || (prev.is_lowercase() && cur.is_uppercase())
+}
+
+/// The tasks a query selects, one per line (or why it doesn't parse)
+fn query_preview(q: &str, tasks: &[Task]) -> String {
+    let query = match Query::parse(q) {
+        Ok(query) => query,
+        Err(e) => return format!("{e:#}"),
+    };
+    let today = date::today();
+    let lines: Vec<String> = tasks
+        .iter()
+        .filter(|t| query.matches(t, today))
+        .map(|t| format!("[{}] {}", t.status, t.title))
+        .collect();
+    if lines.is_empty() {
+        "(no tasks)".to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// Preview pane text for an entry: the task itself, or the tasks behind a project, tag or filter
+pub fn preview(item: &PaletteItem, tasks: &[Task], vault: &Vault) -> String {
+    if let Some(p) = &item.preview {
+        return p.clone();
+    }
+    let list = |pick: &dyn Fn(&Task) -> bool| {
+        let lines: Vec<String> = tasks
+            .iter()
+            .filter(|t| pick(t))
+            .map(|t| format!("[{}] {}", t.status, t.title))
+            .collect();
+        if lines.is_empty() {
+            "(no tasks)".to_string()
+        } else {
+            lines.join("\n")
+        }
+    };
+    match &item.target {
+        PaletteTarget::Task(id) => {
+            let Some(t) = tasks.iter().find(|t| t.id == *id) else {
+                return String::new();
+            };
+            let mut s = format!(
+                "{}\n\nstatus:  {}\nproject: {}\n",
+                t.title, t.status, t.project
+            );
+            if let Some(due) = &t.due {
+                s.push_str(&format!("due:     {}\n", vault.cfg.display_date(due)));
+            }
+            if !t.tags.is_empty() {
+                s.push_str(&format!("tags:    {}\n", t.tags.join(", ")));
+            }
+            let body = vault.task_body(id).unwrap_or_default();
+            if !body.trim().is_empty() {
+                s.push('\n');
+                s.push_str(body.trim());
+            }
+            s
+        }
+        PaletteTarget::Project(k) => list(&|t| t.project == *k),
+        PaletteTarget::Tag(tag) => list(&|t| t.tags.contains(tag)),
+        PaletteTarget::Filter(q) => query_preview(q, tasks),
+        PaletteTarget::Command(_)
+        | PaletteTarget::Run(_)
+        | PaletteTarget::Quick(_)
+        | PaletteTarget::View(_) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette() -> Palette {
+        let projects = ["work".to_string(), "home-office".to_string()];
+        let commands = [
+            CommandDef::new(":new \"title\"", "create a task"),
+            CommandDef::new(":config.reload", "re-read config.lua and plugins"),
+        ];
+        let filters = [("overdue".to_string(), "due:<today".to_string())];
+        Palette::new(&[], &projects, &commands, &[], &filters)
+    }
+
+    fn labels(p: &Palette, query: &str) -> Vec<String> {
+        p.filter(query)
+            .iter()
+            .map(|m| p.items[m.index].label.clone())
+            .collect()
+    }
+
+    #[test]
+    fn terms_match_as_subsequences_with_smart_case() {
+        assert_eq!(
+            fuzzy_match("cfg", "config").map(|m| m.1),
+            Some(vec![0, 3, 5])
+        );
+        assert!(fuzzy_match("gfc", "config").is_none());
+        assert!(fuzzy_match("Cfg", "config").is_none());
+        assert!(fuzzy_match("cfg", "ConFiG").is_some());
+        // every term has to match, in any order
+        let (_, pos) = fuzzy_match("rel con", "config.reload").unwrap();
+        assert_eq!(pos, [0, 1, 2, 7, 8, 9]);
+        assert!(fuzzy_match("rel xyz", "config.reload").is_none());
+        assert_eq!(fuzzy_match("", "anything").map(|m| m.1), Some(vec![]));
+    }
+
+    #[test]
+    fn word_starts_and_runs_score_higher_than_scattered_letters() {
+        let score = |p, t| fuzzy_match(p, t).unwrap().0;
+        assert!(score("rep", "report") > score("rep", "prepare"));
+        assert!(score("nr", "next-release") > score("nr", "ignore"));
+        assert!(score("tr", "taskRunner") > score("tr", "waterproof"));
+        assert!(score("abc", "abcdef") > score("abc", "axbxcx"));
+        // the tightest window wins: `ab` in `a-xab` picks the later run
+        assert_eq!(fuzzy_match("ab", "a-xab").unwrap().1, [3, 4]);
+    }
+
+    #[test]
+    fn results_are_ranked_best_first_and_listed_by_source_when_empty() {
+        let p = palette();
+        assert_eq!(
+            labels(&p, ""),
+            ["@work", "@home-office", "overdue", ">new", ">config.reload"]
+        );
+        assert_eq!(labels(&p, "re"), [">config.reload", "overdue"]);
+        assert_eq!(labels(&p, "o")[0], "overdue");
+        assert!(labels(&p, "zzz").is_empty());
+    }
+
+    #[test]
+    fn a_prefix_narrows_to_one_source_without_matching_its_sigil() {
+        let p = palette();
+        assert_eq!(labels(&p, "@"), ["@work", "@home-office"]);
+        assert_eq!(labels(&p, ">n"), [">new", ">config.reload"]);
+        assert!(labels(&p, "@>").is_empty());
+        let m = &p.filter("@ho")[0];
+        assert_eq!(p.items[m.index].label, "@home-office");
+        assert_eq!(m.positions, [1, 2]);
+        // a source without entries leaves nothing to show
+        assert!(labels(&p, "#").is_empty());
+    }
+
+    #[test]
+    fn plugin_sources_cannot_take_a_prefix_already_in_use() {
+        let mut p = palette();
+        p.add_source("jira", Some('@'));
+        p.add_source("links", Some('!'));
+        p.add_source("links", Some('?'));
+        let prefix = |name: &str| p.sources.iter().find(|s| s.name == name).unwrap().prefix;
+        assert_eq!(prefix("jira"), None);
+        assert_eq!(prefix("links"), Some('!'));
+        assert_eq!(prefix("projects"), Some('@'));
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+
+/// A plugin declaration: `use({ 'user/repo', version = '>=1.0.0' })` in config.lua,
+/// or `<git-url|path>[@version]` on the command line.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginSpec {
+    /// git URL, local path, or `user/repo` shorthand (GitHub)
+    pub source: String,
+    /// semver constraint (">=1.0.0", "1.2") or a git ref (branch, tag, commit)
+    pub version: Option<String>,
+    /// `opts = { ... }`: settings handed to the plugin as `host.opts`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opts: Option<Json>,
+}
+
+impl PluginSpec {
+    /// Parse `<git-url|path>[@version]`. The `@` of `git@host:repo` URLs is not a version separator.
+    pub fn parse(arg: &str) -> Self {
+        if let Some((src, ver)) = arg.rsplit_once('@') {
+            if !src.is_empty() && !ver.is_empty() && !ver.contains('/') && !ver.contains(':') {
+                return Self {
+                    source: src.to_string(),
+                    version: Some(ver.to_string()),
+                    opts: None,
+                };
+            }
+        }
+        Self {
+            source: arg.to_string(),
+            version: None,
+            opts: None,
+        }
+    }
+
+    /// Clone URL: `user/repo` → GitHub https, `~/x` → home-relative path, an existing local
+    /// path → its absolute path (so the lockfile doesn't depend on the working directory),
+    /// everything else as-is.
+    pub fn url(&self) -> String {
+        let s = self.source.trim();
+        if let Some(rest) = s.strip_prefix("~/") {
+            if let Some(home) = directories::UserDirs::new() {
+                return home.home_dir().join(rest).to_string_lossy().into_owned();
+            }
+        }
+        if !s.contains("://") && !s.starts_with("git@") {
+            if let Ok(abs) = std::fs::canonicalize(s) {
+                return abs.to_string_lossy().into_owned();
+            }
+        }
+        let is_shorthand = !s.contains("://")
+            && !s.starts_with("git@")
+            && !s.starts_with('/')
+            && !s.starts_with('.')
+            && s.split('/').count() == 2
+            && !Path::new(s).exists();
+        if is_shorthand {
+            format!("https://github.com/{s}.git")
+        } else {
+            s.to_string()
+        }
+    }
+
+    /// Directory-friendly name derived from the source (last path segment, without `.git`).
+    pub fn name(&self) -> String {
+        let s = self.source.trim_end_matches('/');
+        let last = s.rsplit(['/', ':']).next().unwrap_or(s);
+        last.trim_end_matches(".git").to_string()
+    }
+}
+
+/// A plugin entry from any config format:
+/// `"user/repo@1.0"`, `{ "user/repo", version = "..." }`, `{ repo = "...", version = "..." }`,
+/// each table optionally with `opts = { ... }`
+pub(crate) fn spec_from_json(v: &Json) -> Option<PluginSpec> {
+    match v {
+        Json::String(s) => Some(PluginSpec::parse(s)),
+        Json::Array(items) => items.first().and_then(spec_from_json),
+        Json::Object(m) => {
+            let source = ["1", "repo", "source"]
+                .iter()
+                .find_map(|k| m.get(*k).and_then(Json::as_str))?;
+            let spec = match m.get("version").and_then(Json::as_str) {
+                Some(ver) => PluginSpec {
+                    source: source.to_string(),
+                    version: Some(ver.to_string()),
+                    opts: None,
+                },
+                None => PluginSpec::parse(source),
+            };
+            Some(PluginSpec {
+                opts: m.get("opts").cloned(),
+                ..spec
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Plugins live in `<config dir>/plugins/<name>`
+pub fn plugins_dir(config_path: &Path) -> PathBuf {
+    config_dir_of(config_path).join("plugins")
+}
+
+/// `tm-lock.json` sits next to config.lua
+pub fn lockfile_path(config_path: &Path) -> PathBuf {
+    config_dir_of(config_path).join("tm-lock.json")
+}
+
+/// Plugin secrets (`host.secrets`) are kept in `secrets.json` next to config.lua
+pub fn secrets_path(config_path: &Path) -> PathBuf {
+    config_dir_of(config_path).join("secrets.json")
+}
+
+fn config_dir_of(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_splits_the_version_but_not_ssh_urls() {
+        let s = PluginSpec::parse("tm-plugins/todoist@>=1.0.0");
+        assert_eq!(
+            (s.source.as_str(), s.version.as_deref()),
+            ("tm-plugins/todoist", Some(">=1.0.0"))
+        );
+        let s = PluginSpec::parse("git@github.com:me/plug.git");
+        assert_eq!(
+            (s.source.as_str(), s.version.as_deref()),
+            ("git@github.com:me/plug.git", None)
+        );
+        assert_eq!(s.name(), "plug");
+    }
+
+    #[test]
+    fn url_expands_shorthand_and_makes_local_paths_absolute() {
+        assert_eq!(
+            PluginSpec::parse("user/repo").url(),
+            "https://github.com/user/repo.git"
+        );
+        let url = PluginSpec::parse("./src").url();
+        assert!(Path::new(&url).is_absolute(), "{url}");
+        assert_eq!(Path::new(&url), std::fs::canonicalize("src").unwrap());
+        assert_eq!(
+            PluginSpec::parse("https://example.com/p.git").url(),
+            "https://example.com/p.git"
+        );
+    }
+}
@@ -0,0 +1,464 @@
+//! Task queries, as typed in the `/` filter, saved in views and passed to `tm ls`:
+//!
+//! ```text
+//! due<=today status:!done +work -someday priority>=med is:overdue "exact words" sort:p,due
+//! ```
+//!
+//! Terms are ANDed. `status:` / `project:` take comma-separated values, `!` negates;
+//! `+tag` / `-tag` require / exclude a tag; `due`, `start`, `created` and `updated` compare
+//! with `: = < <= > >=` against `today`, `tomorrow`, `+3d`, `mon`, `2025-09-01`, or
+//! `none`/`any`; `is:` checks `open` (not closed), `active`, `done`/`closed`, `unknown`
+//! (status not in the workflow), `blocked` (waits for an open task), `ready` (open and not
+//! blocked), `tracking` (its timer runs), `deferred` (start date still ahead), `overdue`,
+//! `today`; `sort:` lists sort keys (most relevant first: priority and dates descending
+//! except `due`; `!key` reverses); `group:` groups the list by `project`, `status`, `due` or
+//! `tag`. Anything else matches text like the plain `/` filter. Deferred tasks are left out
+//! unless the query asks about `start` or `is:deferred`.
+
+use std::cmp::Ordering;
+
+use anyhow::{anyhow, bail, Result};
+use time::Date;
+
+use crate::{
+    date,
+    ex::tokenize,
+    sort::{priority_rank, SortKey, SortSpec},
+    view::GroupBy,
+    Category, Task,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn holds(self, ord: Ordering) -> bool {
+        match self {
+            Cmp::Eq => ord == Ordering::Equal,
+            Cmp::Lt => ord == Ordering::Less,
+            Cmp::Le => ord != Ordering::Greater,
+            Cmp::Gt => ord == Ordering::Greater,
+            Cmp::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DateField {
+    Due,
+    Start,
+    Created,
+    Updated,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum DateValue {
+    None,
+    Any,
+    /// resolved against today when matching, so `today` stays right past midnight
+    Day(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Term {
+    Status(bool, Vec<String>),
+    Project(bool, Vec<String>),
+    Tag(bool, String),
+    Priority(Cmp, u8),
+    Date(DateField, Cmp, DateValue),
+    Is(bool, String),
+    Text(String),
+}
+
+const IS_VALUES: &[&str] = &[
+    "open", "active", "done", "closed", "unknown", "blocked", "ready", "tracking", "deferred",
+    "overdue", "today",
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    terms: Vec<Term>,
+    /// order from `sort:`, if given
+    pub sort: Option<Vec<SortSpec>>,
+    /// `group:`, if given
+    pub group: Option<GroupBy>,
+}
+
+/// `key op value` for the comparison operators (longest first)
+fn split_op(tok: &str) -> Option<(&str, Cmp, &str)> {
+    for (op, cmp) in [
+        ("<=", Cmp::Le),
+        (">=", Cmp::Ge),
+        ("<", Cmp::Lt),
+        (">", Cmp::Gt),
+        ("=", Cmp::Eq),
+        (":", Cmp::Eq),
+    ] {
+        if let Some(i) = tok.find(op) {
+            let key = &tok[..i];
+            if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Some((key, cmp, &tok[i + op.len()..]));
+            }
+        }
+    }
+    None
+}
+
+fn negated(v: &str) -> (bool, &str) {
+    match v.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, v),
+    }
+}
+
+fn list(v: &str) -> Vec<String> {
+    v.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// `sort:p,!due` → keys with their natural direction, `!` reversing it
+pub fn parse_sort_term(v: &str) -> Result<Vec<SortSpec>> {
+    let mut out = Vec::new();
+    for part in v.split(',').filter(|p| !p.is_empty()) {
+        let (rev, name) = negated(part);
+        let key = SortKey::parse(name).ok_or_else(|| anyhow!("unknown sort key '{name}'"))?;
+        out.push(SortSpec {
+            key,
+            desc: key.natural_desc() != rev,
+        });
+    }
+    if out.is_empty() {
+        bail!("sort: needs at least one key");
+    }
+    Ok(out)
+}
+
+impl Query {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut q = Query::default();
+        for tok in tokenize(s) {
+            if let Some(tag) = tok.strip_prefix('+').filter(|t| !t.is_empty()) {
+                q.terms.push(Term::Tag(false, tag.to_lowercase()));
+                continue;
+            }
+            if let Some(tag) = tok
+                .strip_prefix('-')
+                .filter(|t| !t.is_empty() && !t.starts_with(char::is_numeric))
+            {
+                q.terms.push(Term::Tag(true, tag.to_lowercase()));
+                continue;
+            }
+            let Some((key, cmp, value)) = split_op(&tok) else {
+                q.terms.push(Term::Text(tok));
+                continue;
+            };
+            let eq_only = |what: &str| -> Result<()> {
+                if cmp != Cmp::Eq {
+                    bail!("{what} only supports ':'");
+                }
+                Ok(())
+            };
+            match key.to_lowercase().as_str() {
+                "status" | "s" => {
+                    eq_only("status")?;
+                    let (neg, v) = negated(value);
+                    q.terms.push(Term::Status(neg, list(v)));
+                }
+                "project" | "proj" => {
+                    eq_only("project")?;
+                    let (neg, v) = negated(value);
+                    q.terms.push(Term::Project(neg, list(v)));
+                }
+                "tag" => {
+                    eq_only("tag")?;
+                    let (neg, v) = negated(value);
+                    q.terms.push(Term::Tag(neg, v.to_lowercase()));
+                }
+                "priority" | "prio" | "p" => {
+                    if priority_rank(value) == 0 && value != "none" {
+                        bail!("unknown priority '{value}'");
+                    }
+                    q.terms.push(Term::Priority(cmp, priority_rank(value)));
+                }
+                f @ ("due" | "start" | "created" | "updated") => {
+                    let field = match f {
+                        "due" => DateField::Due,
+                        "start" => DateField::Start,
+                        "created" => DateField::Created,
+                        _ => DateField::Updated,
+                    };
+                    let v = match value {
+                        "none" => DateValue::None,
+                        "any" => DateValue::Any,
+                        d if date::parse_relative(d, date::today()).is_some() => {
+                            DateValue::Day(d.to_string())
+                        }
+                        d => bail!("{f}: can't read date '{d}'"),
+                    };
+                    q.terms.push(Term::Date(field, cmp, v));
+                }
+                "is" => {
+                    eq_only("is")?;
+                    let (neg, v) = negated(value);
+                    if !IS_VALUES.contains(&v) {
+                        bail!("is:{v}: expected one of {}", IS_VALUES.join(", "));
+                    }
+                    q.terms.push(Term::Is(neg, v.to_string()));
+                }
+                "sort" => {
+                    eq_only("sort")?;
+                    q.sort = Some(parse_sort_term(value)?);
+                }
+                "group" => {
+                    eq_only("group")?;
+                    q.group = Some(GroupBy::parse(value)?);
+                }
+                _ => q.terms.push(Term::Text(tok)),
+            }
+        }
+        Ok(q)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.sort.is_none() && self.group.is_none()
+    }
+
+    /// Both queries' terms; `other`'s sort and grouping win
+    pub fn and(mut self, other: Query) -> Query {
+        self.terms.extend(other.terms);
+        self.sort = other.sort.or(self.sort);
+        self.group = other.group.or(self.group);
+        self
+    }
+
+    pub fn matches(&self, t: &Task, today: Date) -> bool {
+        (self.about_start() || !t.is_deferred(today))
+            && self.terms.iter().all(|term| term_matches(term, t, today))
+    }
+
+    /// Whether a term looks at start dates (then deferred tasks aren't hidden)
+    fn about_start(&self) -> bool {
+        self.terms.iter().any(|t| match t {
+            Term::Date(DateField::Start, ..) => true,
+            Term::Is(_, v) => v == "deferred",
+            _ => false,
+        })
+    }
+}
+
+fn task_date(t: &Task, field: DateField) -> Option<Date> {
+    let s = match field {
+        DateField::Due => t.due.as_deref()?,
+        DateField::Start => t.start.as_deref()?,
+        DateField::Created => &t.created,
+        DateField::Updated => &t.updated,
+    };
+    date::parse_date(s)
+}
+
+fn term_matches(term: &Term, t: &Task, today: Date) -> bool {
+    match term {
+        Term::Status(neg, values) => {
+            values.iter().any(|v| t.status.eq_ignore_ascii_case(v)) != *neg
+        }
+        Term::Project(neg, values) => {
+            values.iter().any(|v| t.project.eq_ignore_ascii_case(v)) != *neg
+        }
+        Term::Tag(neg, tag) => t.tags.iter().any(|x| x.eq_ignore_ascii_case(tag)) != *neg,
+        Term::Priority(cmp, rank) => cmp.holds(priority_rank(&t.priority).cmp(rank)),
+        Term::Date(field, cmp, value) => {
+            let d = task_date(t, *field);
+            match value {
+                DateValue::None => d.is_none() == (*cmp == Cmp::Eq),
+                DateValue::Any => d.is_some() == (*cmp == Cmp::Eq),
+                DateValue::Day(expr) => match (d, date::parse_relative(expr, today)) {
+                    (Some(d), Some(v)) => cmp.holds(d.cmp(&v)),
+                    _ => false,
+                },
+            }
+        }
+        Term::Is(neg, what) => {
+            let closed = t.is_closed();
+            let due = task_date(t, DateField::Due);
+            let hit = match what.as_str() {
+                "open" => !closed,
+                "active" => t.category == Some(Category::Active),
+                "done" | "closed" => closed,
+                "unknown" => t.category.is_none(),
+                "blocked" => t.blocked,
+                "ready" => !closed && !t.blocked,
+                "tracking" => t.timer_running(),
+                "deferred" => t.is_deferred(today),
+                "overdue" => !closed && due.map(|d| d < today).unwrap_or(false),
+                "today" => due == Some(today),
+                _ => false,
+            };
+            hit != *neg
+        }
+        Term::Text(s) => t.matches_text(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use super::*;
+    use crate::{sort::SortKey, Workflow};
+
+    const TODAY: Date = date!(2026 - 10 - 14);
+
+    fn task(title: &str, status: &str, due: Option<&str>) -> Task {
+        Task {
+            id: title.into(),
+            title: title.into(),
+            status: status.into(),
+            category: Workflow::default().category(status),
+            project: "work".into(),
+            due: due.map(str::to_string),
+            created: "2026-10-01T09:00:00Z".into(),
+            updated: "2026-10-14T09:00:00Z".into(),
+            ..Task::default()
+        }
+    }
+
+    fn tasks() -> Vec<Task> {
+        vec![
+            task("late", "todo", Some("2026-10-13")),
+            task("now", "doing", Some("2026-10-14")),
+            task("later", "todo", Some("2026-10-20")),
+            task("whenever", "todo", None),
+            Task {
+                project: "home".into(),
+                tags: vec!["someday".into(), "Errand".into()],
+                priority: "high".into(),
+                ..task("shipped", "done", Some("2026-10-01"))
+            },
+        ]
+    }
+
+    fn titles(q: &str) -> Vec<String> {
+        let q = Query::parse(q).unwrap();
+        tasks()
+            .into_iter()
+            .filter(|t| q.matches(t, TODAY))
+            .map(|t| t.title)
+            .collect()
+    }
+
+    #[test]
+    fn dates_compare_against_relative_and_absolute_days() {
+        assert_eq!(titles("due<today"), ["late", "shipped"]);
+        assert_eq!(titles("due<=today status:!done"), ["late", "now"]);
+        assert_eq!(titles("due:today"), ["now"]);
+        assert_eq!(titles("due=tomorrow"), Vec::<String>::new());
+        assert_eq!(titles("due>+3d"), ["later"]);
+        assert_eq!(titles("due>=2026-10-20"), ["later"]);
+        assert_eq!(titles("due:none"), ["whenever"]);
+        assert_eq!(titles("due:any").len(), 4);
+        // RFC 3339 stamps compare by their day
+        assert_eq!(titles("created<2026-10-02").len(), 5);
+        assert_eq!(titles("updated>today").len(), 0);
+    }
+
+    #[test]
+    fn negation_and_lists_cover_status_project_tags_and_is() {
+        assert_eq!(titles("status:todo,doing").len(), 4);
+        assert_eq!(titles("status:!todo,doing"), ["shipped"]);
+        assert_eq!(titles("project:!work"), ["shipped"]);
+        assert_eq!(titles("+errand"), ["shipped"]);
+        assert_eq!(titles("-someday").len(), 4);
+        assert_eq!(titles("tag:!someday").len(), 4);
+        assert_eq!(titles("is:overdue"), ["late"]);
+        assert_eq!(titles("is:!open"), ["shipped"]);
+        assert_eq!(titles("is:today"), ["now"]);
+        assert_eq!(titles("priority>=med"), ["shipped"]);
+        assert_eq!(titles("p<high").len(), 4);
+    }
+
+    #[test]
+    fn other_words_match_text() {
+        assert_eq!(titles("LATE"), ["late", "later"]);
+        assert_eq!(titles("\"later\" is:open"), ["later"]);
+        // a leading minus before a digit is not a tag exclusion
+        assert_eq!(Query::parse("-5").unwrap().terms, [Term::Text("-5".into())]);
+        assert_eq!(
+            Query::parse("foo:bar").unwrap().terms,
+            [Term::Text("foo:bar".into())]
+        );
+        assert!(Query::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn deferred_tasks_show_only_when_the_query_asks_about_start() {
+        let start = |title, status, start: Option<&str>| Task {
+            start: start.map(str::to_string),
+            ..task(title, status, None)
+        };
+        let tasks = [
+            start("snoozed", "todo", Some("2026-10-20")),
+            start("begun", "todo", Some("2026-10-10")),
+            start("dropped", "done", Some("2026-10-20")),
+            start("plain", "todo", None),
+        ];
+        let titles = |q: &str| {
+            let q = Query::parse(q).unwrap();
+            tasks
+                .iter()
+                .filter(|t| q.matches(t, TODAY))
+                .map(|t| t.title.as_str())
+                .collect::<Vec<_>>()
+        };
+        // closed tasks aren't deferred, whatever their start date
+        assert_eq!(titles(""), ["begun", "dropped", "plain"]);
+        assert_eq!(titles("project:work"), ["begun", "dropped", "plain"]);
+        assert_eq!(titles("is:deferred"), ["snoozed"]);
+        assert_eq!(titles("is:!deferred"), ["begun", "dropped", "plain"]);
+        assert_eq!(titles("start>today"), ["snoozed", "dropped"]);
+        assert_eq!(titles("start<=today"), ["begun"]);
+        assert_eq!(titles("start:none"), ["plain"]);
+        assert_eq!(titles("start:any is:open"), ["snoozed", "begun"]);
+    }
+
+    #[test]
+    fn bad_terms_are_parse_errors() {
+        for (q, msg) in [
+            ("due:someday", "can't read date 'someday'"),
+            ("due:+é", "can't read date '+é'"),
+            ("due:+200000000000000d", "can't read date"),
+            ("is:later", "is:later: expected one of"),
+            ("status>todo", "status only supports ':'"),
+            ("priority:urgentest", "unknown priority"),
+            ("sort:bogus", "unknown sort key 'bogus'"),
+            ("sort:", "needs at least one key"),
+        ] {
+            let err = Query::parse(q).unwrap_err().to_string();
+            assert!(err.contains(msg), "{q}: {err}");
+        }
+    }
+
+    #[test]
+    fn sort_terms_use_natural_directions_and_the_last_query_wins() {
+        let q = Query::parse("sort:p,!due").unwrap();
+        let keys: Vec<_> = q.sort.unwrap().iter().map(|s| (s.key, s.desc)).collect();
+        assert_eq!(keys, [(SortKey::Priority, true), (SortKey::Due, true)]);
+
+        let view = Query::parse("is:open sort:due").unwrap();
+        let both = view.and(Query::parse("+work sort:title").unwrap());
+        assert_eq!(both.terms.len(), 2);
+        assert_eq!(both.sort.unwrap()[0].key, SortKey::Title);
+        let kept = Query::parse("sort:due")
+            .unwrap()
+            .and(Query::parse("x").unwrap());
+        assert_eq!(kept.sort.unwrap()[0].key, SortKey::Due);
+    }
+}
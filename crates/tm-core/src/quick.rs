@@ -0,0 +1,342 @@
+//! Quick actions (`<leader>a`): the verbs that apply to the selected task or the current
+//! view. One registry feeds the TUI popup, the GUI context menu and the palette's `>`
+//! source; plugins add entries with a predicate (`host.register_quick_action`).
+
+use std::rc::Rc;
+
+use crate::{Action, Category, Task};
+
+/// What the entry applies to: the selected task (if any) and the project being viewed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuickContext<'a> {
+    pub task: Option<&'a Task>,
+    pub project: Option<&'a str>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QuickRun {
+    /// a keymap action, as if its key was pressed
+    Action(Action),
+    /// an ex line, run as is
+    Ex(String),
+    /// open the ex bar with this text for the user to finish
+    Prompt(String),
+}
+
+/// When an entry is listed
+#[derive(Clone)]
+pub enum When {
+    Always,
+    /// a task is selected and the check passes
+    Task(fn(&Task) -> bool),
+    /// a single project is being viewed
+    Project,
+    /// plugin predicates
+    Custom(Rc<dyn Fn(&QuickContext) -> bool>),
+}
+
+impl When {
+    pub fn applies(&self, ctx: &QuickContext) -> bool {
+        match self {
+            When::Always => true,
+            When::Task(check) => ctx.task.map(check).unwrap_or(false),
+            When::Project => ctx.project.is_some(),
+            When::Custom(f) => f(ctx),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct QuickAction {
+    pub id: String,
+    pub label: String,
+    pub when: When,
+    pub run: QuickRun,
+}
+
+impl QuickAction {
+    pub fn new(id: &str, label: &str, when: When, run: QuickRun) -> Self {
+        Self {
+            id: id.to_string(),
+            label: label.to_string(),
+            when,
+            run,
+        }
+    }
+
+    /// `run` with `{id}` and `{project}` filled in (the task's project, else the viewed one)
+    pub fn resolve(&self, ctx: &QuickContext) -> QuickRun {
+        let fill = |s: &str| {
+            let project = ctx
+                .task
+                .map(|t| t.project.as_str())
+                .or(ctx.project)
+                .unwrap_or_default();
+            s.replace("{id}", ctx.task.map(|t| t.id.as_str()).unwrap_or_default())
+                .replace("{project}", project)
+        };
+        match &self.run {
+            QuickRun::Action(a) => QuickRun::Action(*a),
+            QuickRun::Ex(line) => QuickRun::Ex(fill(line)),
+            QuickRun::Prompt(line) => QuickRun::Prompt(fill(line)),
+        }
+    }
+}
+
+/// Built-in entries first, then plugin ones in registration order
+#[derive(Clone)]
+pub struct QuickActions {
+    entries: Vec<QuickAction>,
+}
+
+impl Default for QuickActions {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl QuickActions {
+    pub fn builtin() -> Self {
+        use Action::*;
+        use QuickRun::{Action as Act, Ex, Prompt};
+        let task = When::Task(|_| true);
+        let entries = vec![
+            QuickAction::new(
+                "complete",
+                "Complete",
+                When::Task(|t| !t.is_closed()),
+                Act(SetDone),
+            ),
+            QuickAction::new(
+                "start",
+                "Start",
+                When::Task(|t| t.category == Some(Category::Open)),
+                Act(SetDoing),
+            ),
+            QuickAction::new(
+                "start_timer",
+                "Start timer",
+                When::Task(|t| !t.is_closed() && !t.timer_running()),
+                Act(ToggleTimer),
+            ),
+            QuickAction::new(
+                "stop_timer",
+                "Stop timer",
+                When::Task(|t| t.timer_running()),
+                Act(ToggleTimer),
+            ),
+            QuickAction::new(
+                "set_estimate",
+                "Set estimate",
+                task.clone(),
+                Prompt("estimate ".into()),
+            ),
+            QuickAction::new(
+                "cancel",
+                "Cancel",
+                When::Task(|t| !t.is_closed()),
+                Act(SetCancelled),
+            ),
+            QuickAction::new(
+                "reopen",
+                "Reopen",
+                When::Task(|t| t.is_closed()),
+                Act(SetTodo),
+            ),
+            QuickAction::new("edit_due", "Edit due", task.clone(), Act(EditDue)),
+            QuickAction::new(
+                "snooze",
+                "Snooze",
+                When::Task(|t| !t.is_closed()),
+                Act(Snooze),
+            ),
+            QuickAction::new("rename", "Rename", task.clone(), Act(EditTitle)),
+            QuickAction::new("edit_tags", "Edit tags", task.clone(), Act(EditTags)),
+            QuickAction::new(
+                "set_priority",
+                "Set priority",
+                task.clone(),
+                Prompt("priority ".into()),
+            ),
+            QuickAction::new(
+                "move",
+                "Move to project",
+                task.clone(),
+                Prompt("move project:".into()),
+            ),
+            QuickAction::new(
+                "subtask",
+                "Add subtask",
+                task.clone(),
+                Prompt("new parent:{id} project:{project} ".into()),
+            ),
+            QuickAction::new(
+                "add_dep",
+                "Add dependency",
+                task.clone(),
+                Prompt("dep add ".into()),
+            ),
+            QuickAction::new("open_in_editor", "Open in editor", task, Act(OpenInEditor)),
+            QuickAction::new(
+                "new_task_here",
+                "New task in project",
+                When::Project,
+                Prompt("new project:{project} ".into()),
+            ),
+            QuickAction::new(
+                "pick_project",
+                "Pick project",
+                When::Always,
+                Act(PickProject),
+            ),
+            QuickAction::new("new_project", "New project", When::Always, Act(NewProject)),
+            QuickAction::new(
+                "reload_config",
+                "Reload config",
+                When::Always,
+                Ex("config.reload".into()),
+            ),
+        ];
+        Self { entries }
+    }
+
+    /// Add an entry; an existing id is replaced
+    pub fn register(&mut self, action: QuickAction) {
+        match self.entries.iter_mut().find(|e| e.id == action.id) {
+            Some(e) => *e = action,
+            None => self.entries.push(action),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&QuickAction> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Entries whose predicate holds for `ctx`, in menu order
+    pub fn applicable(&self, ctx: &QuickContext) -> Vec<&QuickAction> {
+        self.entries
+            .iter()
+            .filter(|e| e.when.applies(ctx))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Workflow;
+
+    fn task(status: &str) -> Task {
+        Task {
+            id: "01J9ZQ".into(),
+            title: "Write report".into(),
+            status: status.into(),
+            category: Workflow::default().category(status),
+            project: "work".into(),
+            ..Task::default()
+        }
+    }
+
+    fn ids(qa: &QuickActions, ctx: &QuickContext) -> Vec<String> {
+        qa.applicable(ctx).iter().map(|e| e.id.clone()).collect()
+    }
+
+    #[test]
+    fn task_entries_follow_the_selected_tasks_status() {
+        let qa = QuickActions::builtin();
+        let todo = task("todo");
+        let done = task("done");
+        let listed = |t| {
+            ids(
+                &qa,
+                &QuickContext {
+                    task: Some(t),
+                    project: None,
+                },
+            )
+        };
+        let for_todo = listed(&todo);
+        assert!(for_todo.contains(&"complete".into()));
+        assert!(for_todo.contains(&"start".into()));
+        assert!(!for_todo.contains(&"reopen".into()));
+        let for_done = listed(&done);
+        assert!(for_done.contains(&"reopen".into()));
+        assert!(!for_done.contains(&"complete".into()));
+        assert!(!for_done.contains(&"start".into()));
+    }
+
+    #[test]
+    fn without_a_task_only_view_entries_are_listed() {
+        let qa = QuickActions::builtin();
+        let none = ids(&qa, &QuickContext::default());
+        assert_eq!(none, ["pick_project", "new_project", "reload_config"]);
+        let in_project = ids(
+            &qa,
+            &QuickContext {
+                task: None,
+                project: Some("work"),
+            },
+        );
+        assert_eq!(in_project[0], "new_task_here");
+        assert_eq!(in_project.len(), 4);
+    }
+
+    #[test]
+    fn plugin_predicates_see_the_context_and_replace_entries_by_id() {
+        let mut qa = QuickActions::builtin();
+        qa.register(QuickAction::new(
+            "jira",
+            "Open in Jira",
+            When::Custom(Rc::new(|ctx| ctx.task.is_some_and(|t| t.project == "work"))),
+            QuickRun::Ex("jira.open {id}".into()),
+        ));
+        qa.register(QuickAction::new(
+            "reload_config",
+            "Reload everything",
+            When::Always,
+            QuickRun::Ex("reload".into()),
+        ));
+        let work = task("todo");
+        let home = Task {
+            project: "home".into(),
+            ..task("todo")
+        };
+        let ctx = |t| QuickContext {
+            task: Some(t),
+            project: None,
+        };
+        assert!(ids(&qa, &ctx(&work)).contains(&"jira".into()));
+        assert!(!ids(&qa, &ctx(&home)).contains(&"jira".into()));
+        assert_eq!(qa.get("reload_config").unwrap().label, "Reload everything");
+        assert_eq!(ids(&qa, &ctx(&work)).last().unwrap(), "jira");
+    }
+
+    #[test]
+    fn placeholders_fill_from_the_task_or_the_viewed_project() {
+        let qa = QuickActions::builtin();
+        let t = task("todo");
+        let subtask = qa.get("subtask").unwrap();
+        let with_task = QuickContext {
+            task: Some(&t),
+            project: Some("home"),
+        };
+        assert_eq!(
+            subtask.resolve(&with_task),
+            QuickRun::Prompt("new parent:01J9ZQ project:work ".into())
+        );
+        let here = qa.get("new_task_here").unwrap();
+        let in_project = QuickContext {
+            task: None,
+            project: Some("home"),
+        };
+        assert_eq!(
+            here.resolve(&in_project),
+            QuickRun::Prompt("new project:home ".into())
+        );
+        let complete = qa.get("complete").unwrap();
+        assert_eq!(
+            complete.resolve(&with_task),
+            QuickRun::Action(Action::SetDone)
+        );
+    }
+}
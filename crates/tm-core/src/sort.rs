@@ -0,0 +1,289 @@
+//! Task list ordering: `sort = "due asc, priority desc"`
+
+use std::cmp::Ordering;
+
+use anyhow::{bail, Result};
+
+use crate::{Category, Task};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Updated,
+    Created,
+    Due,
+    Priority,
+    Status,
+    Title,
+    Project,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 7] = [
+        SortKey::Updated,
+        SortKey::Created,
+        SortKey::Due,
+        SortKey::Priority,
+        SortKey::Status,
+        SortKey::Title,
+        SortKey::Project,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Updated => "updated",
+            SortKey::Created => "created",
+            SortKey::Due => "due",
+            SortKey::Priority => "priority",
+            SortKey::Status => "status",
+            SortKey::Title => "title",
+            SortKey::Project => "project",
+        }
+    }
+
+    /// Most useful first: priority and timestamps high to low, the rest ascending
+    pub fn natural_desc(&self) -> bool {
+        matches!(
+            self,
+            SortKey::Priority | SortKey::Updated | SortKey::Created
+        )
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "updated" => SortKey::Updated,
+            "created" => SortKey::Created,
+            "due" => SortKey::Due,
+            "priority" | "p" | "prio" => SortKey::Priority,
+            "status" => SortKey::Status,
+            "title" => SortKey::Title,
+            "project" => SortKey::Project,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SortSpec {
+    pub key: SortKey,
+    pub desc: bool,
+}
+
+/// `"updated desc"`, `"due, priority desc"` → ordered sort keys (asc by default)
+pub fn parse_sort(s: &str) -> Result<Vec<SortSpec>> {
+    let mut out = Vec::new();
+    for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let mut words = part.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let Some(key) = SortKey::parse(name) else {
+            bail!("unknown sort key '{name}'");
+        };
+        let desc = match words.next() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(other) => bail!("sort direction must be asc|desc, got '{other}'"),
+        };
+        out.push(SortSpec { key, desc });
+    }
+    if out.is_empty() {
+        bail!("empty sort order");
+    }
+    Ok(out)
+}
+
+/// Back to config syntax: `due, priority desc`
+pub fn format_sort(spec: &[SortSpec]) -> String {
+    spec.iter()
+        .map(|s| {
+            if s.desc {
+                format!("{} desc", s.key.as_str())
+            } else {
+                s.key.as_str().to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Column-header style toggling: sorting by the primary key again flips it; `add` keeps the
+/// other keys (a new key goes last, an existing one flips), otherwise `key` sorts alone.
+/// New keys start in their natural direction.
+pub fn toggle_sort(spec: &mut Vec<SortSpec>, key: SortKey, add: bool) {
+    if let Some(i) = spec.iter().position(|s| s.key == key) {
+        if add || i == 0 {
+            spec[i].desc = !spec[i].desc;
+            return;
+        }
+    }
+    if add {
+        spec.push(SortSpec {
+            key,
+            desc: key.natural_desc(),
+        });
+    } else {
+        *spec = vec![SortSpec {
+            key,
+            desc: key.natural_desc(),
+        }];
+    }
+}
+
+/// `:sort <arg>` on top of the current order: a bare key toggles it as the only key (or
+/// flips it when it already leads), `+key` adds or flips a secondary key, and a list in
+/// query syntax (`due,!p`) replaces the order
+pub fn apply_sort_arg(current: &[SortSpec], arg: &str) -> Result<Vec<SortSpec>> {
+    let mut spec = current.to_vec();
+    let (add, name) = match arg.strip_prefix('+') {
+        Some(rest) => (true, rest),
+        None => (false, arg),
+    };
+    if name.contains(',') || name.starts_with('!') {
+        return crate::query::parse_sort_term(arg);
+    }
+    let Some(key) = SortKey::parse(name) else {
+        bail!("unknown sort key '{name}'");
+    };
+    toggle_sort(&mut spec, key, add);
+    Ok(spec)
+}
+
+/// `o`: lead with the next key (in `SortKey::ALL` order), keeping the others behind it
+pub fn cycle_sort(current: &[SortSpec]) -> Vec<SortSpec> {
+    let lead = current.first().map(|s| s.key).unwrap_or(SortKey::Updated);
+    let i = SortKey::ALL.iter().position(|k| *k == lead).unwrap_or(0);
+    let key = SortKey::ALL[(i + 1) % SortKey::ALL.len()];
+    let mut out = vec![SortSpec {
+        key,
+        desc: key.natural_desc(),
+    }];
+    out.extend(current.iter().filter(|s| s.key != key).copied());
+    out
+}
+
+pub fn priority_rank(p: &str) -> u8 {
+    match p {
+        "urgent" => 4,
+        "high" | "h" => 3,
+        "med" | "medium" | "m" => 2,
+        "low" | "l" => 1,
+        _ => 0,
+    }
+}
+
+/// Open, active, closed, then unknown statuses
+pub(crate) fn status_rank(t: &Task) -> u8 {
+    match t.category {
+        Some(Category::Open) => 0,
+        Some(Category::Active) => 1,
+        Some(Category::Closed) => 2,
+        None => 3,
+    }
+}
+
+fn cmp_key(key: SortKey, a: &Task, b: &Task) -> Ordering {
+    match key {
+        SortKey::Updated => a.updated.cmp(&b.updated),
+        SortKey::Created => a.created.cmp(&b.created),
+        SortKey::Due => a.due.cmp(&b.due),
+        SortKey::Priority => priority_rank(&a.priority).cmp(&priority_rank(&b.priority)),
+        SortKey::Status => status_rank(a).cmp(&status_rank(b)),
+        SortKey::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+        SortKey::Project => a.project.cmp(&b.project),
+    }
+}
+
+/// Stable multi-key sort. Tasks without a due date always sort after dated ones.
+pub fn sort_tasks(tasks: &mut [Task], spec: &[SortSpec]) {
+    tasks.sort_by(|a, b| {
+        for s in spec {
+            let ord = match (s.key, &a.due, &b.due) {
+                (SortKey::Due, Some(_), None) => Ordering::Less,
+                (SortKey::Due, None, Some(_)) => Ordering::Greater,
+                _ if s.desc => cmp_key(s.key, a, b).reverse(),
+                _ => cmp_key(s.key, a, b),
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(s: &str) -> Vec<SortSpec> {
+        parse_sort(s).unwrap()
+    }
+
+    fn task(title: &str, priority: &str, due: Option<&str>) -> Task {
+        Task {
+            title: title.into(),
+            priority: priority.into(),
+            due: due.map(str::to_string),
+            ..Task::default()
+        }
+    }
+
+    #[test]
+    fn sort_orders_parse_and_format_back() {
+        let s = spec("due, priority desc ,title asc");
+        assert_eq!(format_sort(&s), "due, priority desc, title");
+        assert_eq!(spec("p desc")[0].key, SortKey::Priority);
+        assert!(parse_sort("").is_err());
+        assert!(parse_sort("due sideways").is_err());
+        assert!(parse_sort("size").is_err());
+    }
+
+    #[test]
+    fn toggling_flips_the_lead_or_adds_secondary_keys() {
+        let mut s = spec("due");
+        toggle_sort(&mut s, SortKey::Due, false);
+        assert_eq!(format_sort(&s), "due desc");
+        toggle_sort(&mut s, SortKey::Priority, true);
+        assert_eq!(format_sort(&s), "due desc, priority desc");
+        toggle_sort(&mut s, SortKey::Priority, true);
+        assert_eq!(format_sort(&s), "due desc, priority");
+        // without `add` another key sorts alone, in its natural direction
+        toggle_sort(&mut s, SortKey::Updated, false);
+        assert_eq!(format_sort(&s), "updated desc");
+    }
+
+    #[test]
+    fn sort_arguments_toggle_add_or_replace() {
+        let cur = spec("due");
+        let fmt = |arg| format_sort(&apply_sort_arg(&cur, arg).unwrap());
+        assert_eq!(fmt("due"), "due desc");
+        assert_eq!(fmt("title"), "title");
+        assert_eq!(fmt("+p"), "due, priority desc");
+        assert_eq!(fmt("title,!p"), "title, priority");
+        assert!(apply_sort_arg(&cur, "+size").is_err());
+    }
+
+    #[test]
+    fn cycling_leads_with_the_next_key_and_keeps_the_rest() {
+        let s = cycle_sort(&spec("due, title"));
+        assert_eq!(format_sort(&s), "priority desc, due, title");
+        let s = cycle_sort(&spec("project"));
+        assert_eq!(format_sort(&s), "updated desc, project");
+        assert_eq!(format_sort(&cycle_sort(&[])), "created desc");
+    }
+
+    #[test]
+    fn later_keys_break_ties_and_undated_tasks_go_last() {
+        let mut tasks = vec![
+            task("c", "low", None),
+            task("b", "high", Some("2026-10-20")),
+            task("a", "low", Some("2026-10-20")),
+            task("d", "urgent", Some("2026-10-01")),
+        ];
+        sort_tasks(&mut tasks, &spec("due, priority desc"));
+        let order: Vec<_> = tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(order, ["d", "b", "a", "c"]);
+        // reversing due still keeps undated tasks at the end
+        sort_tasks(&mut tasks, &spec("due desc, title"));
+        let order: Vec<_> = tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(order, ["a", "b", "d", "c"]);
+    }
+}
@@ -0,0 +1,133 @@
+//! What the two-way syncs with remote services (`tm sync caldav`, ...) have in common:
+//! the report they print, the per-task state they keep under `.tm/` between runs and the
+//! tombstones `tm rm` leaves so a deletion can reach the remote side.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{find_task_file_by_id, notes, Task, Vault};
+
+/// `.tm/deleted.json`: id → when it was deleted
+const TOMBSTONES: &str = "deleted";
+
+/// What a sync did, as task titles
+#[derive(Clone, Debug, Default)]
+pub struct SyncReport {
+    /// created or updated from the remote side
+    pub pulled: Vec<String>,
+    /// closed because they were deleted remotely
+    pub closed: Vec<String>,
+    /// created or updated remotely
+    pub pushed: Vec<String>,
+    /// deleted remotely because they were deleted (or moved away) locally
+    pub deleted: Vec<String>,
+    /// changed on both sides: what was kept
+    pub conflicts: Vec<String>,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.pulled.is_empty()
+            && self.closed.is_empty()
+            && self.pushed.is_empty()
+            && self.deleted.is_empty()
+            && self.conflicts.is_empty()
+    }
+
+    pub fn summary(&self, service: &str) -> String {
+        format!(
+            "{service}: {} pulled, {} closed, {} pushed, {} deleted remotely, {} conflict(s)",
+            self.pulled.len(),
+            self.closed.len(),
+            self.pushed.len(),
+            self.deleted.len(),
+            self.conflicts.len()
+        )
+    }
+}
+
+impl Vault {
+    fn sync_state_path(&self, service: &str) -> PathBuf {
+        self.state_dir().join(format!("{service}.json"))
+    }
+
+    /// The per-task state `service` saved last time (empty before the first sync)
+    pub(crate) fn load_sync_state<T: DeserializeOwned>(
+        &self,
+        service: &str,
+    ) -> Result<BTreeMap<String, T>> {
+        let path = self.sync_state_path(service);
+        match fs::read_to_string(&path) {
+            Ok(s) => {
+                serde_json::from_str(&s).with_context(|| format!("reading {}", path.display()))
+            }
+            Err(_) => Ok(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn save_sync_state<T: Serialize>(
+        &self,
+        service: &str,
+        state: &BTreeMap<String, T>,
+    ) -> Result<()> {
+        fs::create_dir_all(self.state_dir())?;
+        fs::write(
+            self.sync_state_path(service),
+            serde_json::to_string_pretty(state)?,
+        )?;
+        Ok(())
+    }
+
+    /// Delete a task's file and leave a tombstone, so syncs delete it remotely too; a
+    /// task file removed by hand stays on the remote side. Returns the title.
+    pub fn delete_task(&self, id: &str) -> Result<String> {
+        if notes::is_note_id(id) {
+            bail!("{id} is a checkbox in a note: delete it there");
+        }
+        let path = find_task_file_by_id(&self.cfg.vault_path, id)
+            .with_context(|| format!("task {id} not found"))?;
+        let title = Task::from_md_file(&path)?.title;
+        let mut tombstones = self.deleted_tasks()?;
+        tombstones.insert(id.to_string(), OffsetDateTime::now_utc().format(&Rfc3339)?);
+        self.save_sync_state(TOMBSTONES, &tombstones)?;
+        fs::remove_file(&path).with_context(|| format!("deleting {}", path.display()))?;
+        Ok(title)
+    }
+
+    /// Tasks deleted with `delete_task`: id → when (RFC 3339)
+    pub fn deleted_tasks(&self) -> Result<BTreeMap<String, String>> {
+        self.load_sync_state(TOMBSTONES)
+    }
+
+    /// Drop a tombstone once the deletion reached the remote side
+    pub fn forget_deleted(&self, id: &str) -> Result<()> {
+        let mut tombstones = self.deleted_tasks()?;
+        if tombstones.remove(id).is_some() {
+            self.save_sync_state(TOMBSTONES, &tombstones)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{task, vault};
+
+    #[test]
+    fn deleting_a_task_leaves_a_tombstone() {
+        let (_dir, vault) = vault();
+        let id = task(&vault, "Old");
+        let kept = task(&vault, "Kept");
+        assert_eq!(vault.delete_task(&id).unwrap(), "Old");
+        assert!(vault.task_path(&id).is_err());
+        assert!(vault.task_path(&kept).is_ok());
+        let tombstones = vault.deleted_tasks().unwrap();
+        assert_eq!(tombstones.keys().collect::<Vec<_>>(), [&id]);
+        assert!(vault.delete_task(&id).is_err());
+        vault.forget_deleted(&id).unwrap();
+        assert!(vault.deleted_tasks().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,574 @@
+//! Taskwarrior interchange: `task export` JSON in (`tm import --from taskwarrior`) and out
+//! (`tm export --to taskwarrior`, for `task import`). A Taskwarrior uuid and a tm ULID are
+//! the same 128 bits, so ids (and `depends`) survive both ways and re-imports upsert.
+//!
+//! | Taskwarrior | tm |
+//! |---|---|
+//! | description | title |
+//! | status pending / waiting / completed / deleted | first open status / open with `start` / first closed / the cancelled one |
+//! | start (active) | first active status |
+//! | project, tags | project (slugified), tags |
+//! | priority H / M / L | high / med / low |
+//! | due | due (local date) |
+//! | wait, scheduled | start |
+//! | entry, modified, end | created, updated, done_at |
+//! | depends | depends_on |
+//! | annotations | `## Annotations` list in the body |
+//! | recur | kept as `recur:` (tm doesn't repeat tasks) |
+//!
+//! Everything else is counted in a mapping report.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, OffsetDateTime,
+    PrimitiveDateTime, UtcOffset,
+};
+use ulid::Ulid;
+
+use crate::{
+    date,
+    export::{ProjectRecord, TaskRecord, VaultExport, SCHEMA, VERSION},
+    Category, Frontmatter, ProjectFrontmatter, Workflow,
+};
+
+/// Fields that couldn't be carried over as-is, with how many records had them
+#[derive(Clone, Debug, Default)]
+pub struct MappingReport {
+    pub notes: BTreeMap<String, usize>,
+}
+
+impl MappingReport {
+    fn note(&mut self, what: impl Into<String>) {
+        *self.notes.entry(what.into()).or_default() += 1;
+    }
+
+    /// One line per note: `estimate: not converted (12)`
+    pub fn lines(&self) -> Vec<String> {
+        self.notes
+            .iter()
+            .map(|(what, n)| format!("{what} ({n})"))
+            .collect()
+    }
+}
+
+const ANNOTATIONS: &str = "## Annotations";
+
+/// Fields derived by Taskwarrior itself; dropped without a note
+const DERIVED: &[&str] = &["id", "urgency", "mask", "imask"];
+
+fn tw_time(s: &str) -> Option<OffsetDateTime> {
+    let fmt = format_description!("[year][month][day]T[hour][minute][second]Z");
+    PrimitiveDateTime::parse(s, fmt)
+        .ok()
+        .map(|t| t.assume_utc())
+}
+
+fn to_tw_time(t: OffsetDateTime) -> String {
+    let t = t.to_offset(UtcOffset::UTC);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        t.year(),
+        u8::from(t.month()),
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second()
+    )
+}
+
+fn rfc3339(t: OffsetDateTime) -> String {
+    t.format(&Rfc3339).unwrap()
+}
+
+/// A Taskwarrior timestamp as a local `YYYY-MM-DD`
+fn local_date(s: &str) -> Option<String> {
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    tw_time(s).map(|t| date::format_date("YYYY-MM-DD", t.to_offset(offset).date()))
+}
+
+pub fn uuid_to_ulid(uuid: &str) -> Option<String> {
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16)
+        .ok()
+        .map(|n| Ulid::from(n).to_string())
+}
+
+pub fn ulid_to_uuid(id: &str) -> Option<String> {
+    let h = format!("{:032x}", u128::from(Ulid::from_string(id).ok()?));
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &h[..8],
+        &h[8..12],
+        &h[12..16],
+        &h[16..20],
+        &h[20..]
+    ))
+}
+
+/// `task export` output as tm records (new projects included), ready for `import_json`
+pub fn from_taskwarrior(
+    json: &str,
+    wf: &Workflow,
+    known_projects: &[String],
+) -> Result<(VaultExport, MappingReport)> {
+    let items: Vec<Map<String, Value>> =
+        serde_json::from_str(json).context("not a Taskwarrior export (a JSON array)")?;
+    let mut report = MappingReport::default();
+    let mut tasks = Vec::new();
+    let mut projects = BTreeMap::new();
+    let first = |c| wf.first(c).map(str::to_string);
+    for mut item in items {
+        let mut take = |k: &str| item.remove(k);
+        let str_of = |v: Option<Value>| v.and_then(|v| v.as_str().map(str::to_string));
+        let Some(id) = str_of(take("uuid")).as_deref().and_then(uuid_to_ulid) else {
+            report.note("task without a valid uuid: skipped");
+            continue;
+        };
+        let title = str_of(take("description")).unwrap_or_default();
+        let tw_status = str_of(take("status")).unwrap_or_else(|| "pending".into());
+        let started = take("start").is_some();
+        let status = match tw_status.as_str() {
+            "completed" => first(Category::Closed)?,
+            "deleted" => match wf.cancelled() {
+                Some(s) => s.to_string(),
+                None => {
+                    report.note("status deleted: no cancelled status, imported as closed");
+                    first(Category::Closed)?
+                }
+            },
+            "recurring" => {
+                report.note("status recurring: imported as an open task (the template)");
+                first(Category::Open)?
+            }
+            _ if started => first(Category::Active)?,
+            _ => first(Category::Open)?,
+        };
+        let tw_project = str_of(take("project"));
+        let project = match &tw_project {
+            Some(p) => {
+                let key = slug::slugify(p);
+                if key != *p {
+                    report.note(format!("project '{p}' became '{key}'"));
+                }
+                projects.entry(key.clone()).or_insert_with(|| p.clone());
+                key
+            }
+            None => "inbox".to_string(),
+        };
+        let tags = take("tags")
+            .and_then(|v| v.as_array().cloned())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect();
+        let priority = match str_of(take("priority")).as_deref() {
+            Some("H") => "high",
+            Some("M") => "med",
+            Some("L") => "low",
+            Some(p) => {
+                report.note(format!("priority '{p}': not H/M/L, dropped"));
+                "none"
+            }
+            None => "none",
+        };
+        let due = str_of(take("due")).and_then(|d| local_date(&d));
+        let wait = str_of(take("wait")).and_then(|d| local_date(&d));
+        let scheduled = str_of(take("scheduled")).and_then(|d| local_date(&d));
+        if wait.is_some() && scheduled.is_some() && wait != scheduled {
+            report.note("wait and scheduled differ: the later one is the start date");
+        }
+        let start = wait.into_iter().chain(scheduled).max();
+        let ts = |v: Option<String>| v.as_deref().and_then(tw_time).map(rfc3339);
+        let created = ts(str_of(take("entry")));
+        let updated = ts(str_of(take("modified"))).or_else(|| created.clone());
+        let done_at = ts(str_of(take("end")))
+            .filter(|_| matches!(tw_status.as_str(), "completed" | "deleted"));
+        // an array, or a comma-separated string in Taskwarrior < 2.5
+        let depends_on = match take("depends") {
+            Some(Value::Array(a)) => a
+                .iter()
+                .filter_map(|v| v.as_str().and_then(uuid_to_ulid))
+                .collect(),
+            Some(Value::String(s)) => s
+                .split(',')
+                .filter_map(|u| uuid_to_ulid(u.trim()))
+                .collect(),
+            _ => vec![],
+        };
+        let mut body = String::new();
+        if let Some(Value::Array(notes)) = take("annotations") {
+            body.push_str(ANNOTATIONS);
+            body.push('\n');
+            for n in notes {
+                let when = n
+                    .get("entry")
+                    .and_then(|e| e.as_str())
+                    .and_then(local_date)
+                    .unwrap_or_default();
+                let text = n
+                    .get("description")
+                    .and_then(|d| d.as_str())
+                    .unwrap_or_default();
+                body.push_str(&format!("- {when} {text}\n"));
+            }
+        }
+        let mut extra = BTreeMap::new();
+        if let Some(Value::String(r)) = take("recur") {
+            report.note("recur: kept as `recur:`, tm doesn't repeat tasks");
+            extra.insert("recur".to_string(), serde_yaml::Value::String(r));
+        }
+        for key in item.keys().filter(|k| !DERIVED.contains(&k.as_str())) {
+            report.note(format!("{key}: not converted"));
+        }
+        tasks.push(TaskRecord {
+            frontmatter: Frontmatter {
+                id,
+                key: slug::slugify(&title),
+                title,
+                status,
+                project,
+                tags,
+                priority: priority.into(),
+                due,
+                start,
+                created,
+                updated,
+                done_at,
+                parent: None,
+                depends_on,
+                estimate: None,
+                time: vec![],
+                extra,
+            },
+            body,
+        });
+    }
+    let projects = projects
+        .into_iter()
+        .filter(|(key, _)| !known_projects.contains(key))
+        .map(|(key, title)| ProjectRecord {
+            frontmatter: ProjectFrontmatter {
+                key,
+                title,
+                status: "active".into(),
+                tags: vec![],
+                created: None,
+                updated: None,
+                description: None,
+                extra: BTreeMap::new(),
+            },
+            body: String::new(),
+        })
+        .collect();
+    let data = VaultExport {
+        schema: SCHEMA.into(),
+        version: VERSION,
+        exported: crate::track::now_rfc3339(),
+        projects,
+        tasks,
+    };
+    Ok((data, report))
+}
+
+/// tm records as `task import` JSON
+pub fn to_taskwarrior(data: &VaultExport, wf: &Workflow) -> Result<(String, MappingReport)> {
+    let mut report = MappingReport::default();
+    let mut out = Vec::new();
+    let ids: BTreeSet<&str> = data
+        .tasks
+        .iter()
+        .map(|t| t.frontmatter.id.as_str())
+        .collect();
+    let today = date::today();
+    // the statuses an import maps Taskwarrior's back to; others come back as one of these
+    let mut round_trips: Vec<&str> = [Category::Open, Category::Active, Category::Closed]
+        .into_iter()
+        .filter_map(|c| wf.first(c).ok())
+        .collect();
+    round_trips.extend(wf.cancelled());
+    for t in &data.tasks {
+        let fm = &t.frontmatter;
+        let Some(uuid) = ulid_to_uuid(&fm.id) else {
+            report.note("task id is not a ULID: skipped");
+            continue;
+        };
+        let mut o = Map::new();
+        o.insert("uuid".into(), json!(uuid));
+        o.insert("description".into(), json!(fm.title));
+        let category = wf.category(&fm.status);
+        let status = match category {
+            Some(Category::Closed) if wf.is_cancelled(&fm.status) => "deleted",
+            Some(Category::Closed) => "completed",
+            _ => "pending",
+        };
+        if !round_trips.contains(&fm.status.as_str()) {
+            report.note(format!("status '{}': exported as {status}", fm.status));
+        }
+        let ts = |s: &Option<String>| {
+            s.as_deref()
+                .and_then(|s| OffsetDateTime::parse(s, &Rfc3339).ok())
+                .map(to_tw_time)
+        };
+        let day = |s: &Option<String>| {
+            let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+            s.as_deref()
+                .and_then(date::parse_date)
+                .map(|d| to_tw_time(d.midnight().assume_offset(offset)))
+        };
+        let deferred = fm
+            .start
+            .as_deref()
+            .and_then(date::parse_date)
+            .is_some_and(|s| s > today);
+        o.insert(
+            "status".into(),
+            json!(if status == "pending" && deferred {
+                "waiting"
+            } else {
+                status
+            }),
+        );
+        if category == Some(Category::Active) {
+            if let Some(u) = ts(&fm.updated) {
+                o.insert("start".into(), json!(u));
+            }
+        }
+        o.insert("project".into(), json!(fm.project));
+        if !fm.tags.is_empty() {
+            o.insert("tags".into(), json!(fm.tags));
+        }
+        let priority = match fm.priority.as_str() {
+            "high" => Some("H"),
+            "med" => Some("M"),
+            "low" => Some("L"),
+            _ => None,
+        };
+        if let Some(p) = priority {
+            o.insert("priority".into(), json!(p));
+        }
+        for (key, value) in [
+            ("due", day(&fm.due)),
+            ("wait", day(&fm.start)),
+            ("entry", ts(&fm.created)),
+            ("modified", ts(&fm.updated)),
+        ] {
+            if let Some(v) = value {
+                o.insert(key.into(), json!(v));
+            }
+        }
+        if status != "pending" {
+            if let Some(end) = ts(&fm.done_at).or_else(|| ts(&fm.updated)) {
+                o.insert("end".into(), json!(end));
+            }
+        }
+        let depends: Vec<String> = fm
+            .depends_on
+            .iter()
+            .filter_map(|d| ulid_to_uuid(d))
+            .collect();
+        if fm.depends_on.iter().any(|d| !ids.contains(d.as_str())) {
+            report.note("depends_on: a task outside the export (kept, Taskwarrior may drop it)");
+        }
+        if !depends.is_empty() {
+            o.insert("depends".into(), json!(depends));
+        }
+        let annotations = body_annotations(&t.body, fm.created.as_deref());
+        if !annotations.is_empty() {
+            o.insert("annotations".into(), Value::Array(annotations));
+        }
+        if fm.parent.is_some() {
+            report.note("parent: Taskwarrior has no subtasks, dropped");
+        }
+        if fm.estimate.is_some() {
+            report.note("estimate: not converted");
+        }
+        if !fm.time.is_empty() {
+            report.note("time: tracked intervals not converted");
+        }
+        for (key, value) in &fm.extra {
+            match (key.as_str(), value) {
+                ("recur", serde_yaml::Value::String(r)) => {
+                    o.insert("recur".into(), json!(r));
+                }
+                _ => report.note(format!("{key}: not converted")),
+            }
+        }
+        out.push(Value::Object(o));
+    }
+    if !data.projects.is_empty() {
+        report.notes.insert(
+            "projects: Taskwarrior has no project records, only names".into(),
+            data.projects.len(),
+        );
+    }
+    Ok((serde_json::to_string_pretty(&out)? + "\n", report))
+}
+
+/// Annotations from the body: the `## Annotations` list (`- YYYY-MM-DD text`) and the rest
+/// of the text as one more, dated `created`
+fn body_annotations(body: &str, created: Option<&str>) -> Vec<Value> {
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    let entry = |d: Option<time::Date>| d.map(|d| to_tw_time(d.midnight().assume_offset(offset)));
+    let (text, list) = match body.split_once(ANNOTATIONS) {
+        Some((text, list)) => (text, list),
+        None => (body, ""),
+    };
+    let mut out = Vec::new();
+    let text = text.trim();
+    if !text.is_empty() {
+        out.push(
+            json!({ "entry": entry(created.and_then(date::parse_date)), "description": text }),
+        );
+    }
+    for line in list.lines().filter_map(|l| l.strip_prefix("- ")) {
+        let (day, note) = match line.split_once(' ') {
+            Some((d, rest)) if date::parse_date(d).is_some() => (date::parse_date(d), rest),
+            _ => (created.and_then(date::parse_date), line),
+        };
+        out.push(json!({ "entry": entry(day), "description": note }));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: &str = "0b5c1a6e-7d1f-4c0e-9a53-3f1c2b4d5e6f";
+    const DEP: &str = "7f0e4d2c-1b3a-4c5d-8e9f-0a1b2c3d4e5f";
+
+    fn export() -> String {
+        json!([
+            {
+                "uuid": UUID,
+                "description": "Renew passport",
+                "status": "pending",
+                "project": "Home Admin",
+                "tags": ["errand"],
+                "priority": "H",
+                "due": "20261020T000000Z",
+                "entry": "20261001T080000Z",
+                "modified": "20261002T090000Z",
+                "depends": [DEP],
+                "annotations": [{ "entry": "20261002T000000Z", "description": "photo booth first" }],
+                "recur": "yearly",
+                "urgency": 9.1,
+                "color": "red"
+            },
+            { "uuid": DEP, "description": "Find old passport", "status": "deleted", "end": "20261003T000000Z" }
+        ])
+        .to_string()
+    }
+
+    #[test]
+    fn uuids_and_ulids_are_the_same_bits() {
+        let id = uuid_to_ulid(UUID).unwrap();
+        assert_eq!(ulid_to_uuid(&id).unwrap(), UUID);
+        assert!(uuid_to_ulid("not-a-uuid").is_none());
+    }
+
+    #[test]
+    fn a_taskwarrior_export_maps_to_tm_records() {
+        let wf = Workflow::default();
+        let (data, report) = from_taskwarrior(&export(), &wf, &[]).unwrap();
+        let fm = &data.tasks[0].frontmatter;
+        assert_eq!(
+            (fm.title.as_str(), fm.key.as_str()),
+            ("Renew passport", "renew-passport")
+        );
+        assert_eq!(
+            (
+                fm.status.as_str(),
+                fm.project.as_str(),
+                fm.priority.as_str()
+            ),
+            ("todo", "home-admin", "high")
+        );
+        assert_eq!(fm.due.as_deref(), Some("2026-10-20"));
+        assert_eq!(fm.depends_on, [uuid_to_ulid(DEP).unwrap()]);
+        assert!(data.tasks[0]
+            .body
+            .contains("- 2026-10-02 photo booth first"));
+        assert_eq!(data.tasks[1].frontmatter.status, "cancelled");
+        assert_eq!(data.projects[0].frontmatter.key, "home-admin");
+        let lines = report.lines();
+        assert!(
+            lines.contains(&"color: not converted (1)".to_string()),
+            "{lines:?}"
+        );
+        assert!(!lines.iter().any(|l| l.starts_with("urgency")));
+    }
+
+    #[test]
+    fn records_survive_a_round_trip() {
+        let wf = Workflow::default();
+        let (data, _) = from_taskwarrior(&export(), &wf, &[]).unwrap();
+        let (json, _) = to_taskwarrior(&data, &wf).unwrap();
+        let (again, _) = from_taskwarrior(&json, &wf, &[]).unwrap();
+        for (a, b) in data.tasks.iter().zip(&again.tasks) {
+            let (a, b) = (&a.frontmatter, &b.frontmatter);
+            assert_eq!(
+                (&a.id, &a.title, &a.status, &a.priority),
+                (&b.id, &b.title, &b.status, &b.priority)
+            );
+            assert_eq!(
+                (&a.due, &a.tags, &a.depends_on, &a.extra),
+                (&b.due, &b.tags, &b.depends_on, &b.extra)
+            );
+            assert_eq!(
+                (&a.created, &a.updated, &a.done_at),
+                (&b.created, &b.updated, &b.done_at)
+            );
+        }
+        assert_eq!(data.tasks[0].body, again.tasks[0].body);
+
+        let items: Vec<Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(items[0]["uuid"], UUID);
+        assert_eq!(items[0]["recur"], "yearly");
+        assert_eq!(items[1]["status"], "deleted");
+    }
+
+    #[test]
+    fn only_statuses_an_import_would_not_restore_are_reported() {
+        use Category::*;
+        let wf = Workflow {
+            statuses: [
+                ("todo", Open),
+                ("blocked", Open),
+                ("wip", Active),
+                ("shipped", Closed),
+                ("wontfix", Closed),
+            ]
+            .into_iter()
+            .map(|(s, c)| (s.to_string(), c))
+            .collect(),
+            order: ["todo", "wip", "shipped"].map(String::from).to_vec(),
+            cancelled: Some("wontfix".into()),
+            ..Workflow::default()
+        };
+        let (mut data, _) = from_taskwarrior(&export(), &wf, &[]).unwrap();
+        assert_eq!(data.tasks[1].frontmatter.status, "wontfix");
+        let notes = |data: &VaultExport| {
+            let (_, report) = to_taskwarrior(data, &wf).unwrap();
+            report
+                .lines()
+                .into_iter()
+                .filter(|l| l.starts_with("status"))
+                .collect::<Vec<_>>()
+        };
+        for status in ["todo", "wip", "shipped"] {
+            data.tasks[0].frontmatter.status = status.into();
+            assert!(notes(&data).is_empty(), "{status}");
+        }
+        data.tasks[0].frontmatter.status = "blocked".into();
+        assert_eq!(notes(&data), ["status 'blocked': exported as pending (1)"]);
+        data.tasks[0].frontmatter.status = "done".into();
+        assert_eq!(notes(&data), ["status 'done': exported as pending (1)"]);
+    }
+}
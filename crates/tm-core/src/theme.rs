@@ -0,0 +1,542 @@
+//! Themes: semantic style roles (`status_done`, `overdue`, `selection`, ...) shared by the
+//! TUI and GUI. Built-ins: `gruvbox-dark` (default), `gruvbox-light` and `mono`; config adds
+//! more on top of a base:
+//!
+//! ```lua
+//! theme = 'mine',
+//! themes = {
+//!   mine = { base = 'gruvbox-dark', overdue = '#ff5f5f bold', selection = { bg = '#504945' } },
+//! }
+//! ```
+//!
+//! A style is `"<fg> [on <bg>] [bold] [dim] [italic] [underline] [reverse]"` or a table with
+//! the same keys; colours are `#rrggbb`. Setting a role replaces the base's style for it.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::workflow::Category;
+
+pub const DEFAULT_THEME: &str = "gruvbox-dark";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    /// default text and background
+    Text,
+    Muted,
+    Border,
+    /// pane titles
+    Title,
+    /// table header
+    Header,
+    Selection,
+    /// the active view tab
+    TabActive,
+    StatusTodo,
+    StatusDoing,
+    StatusDone,
+    Overdue,
+    DueToday,
+    /// tasks waiting for others
+    Blocked,
+    PriorityHigh,
+    PriorityMed,
+    PriorityLow,
+    Tag,
+    Project,
+    /// group headers in the list
+    Group,
+    Success,
+    Error,
+    /// inline hints (ex signatures, pending keys)
+    Hint,
+    /// fuzzy match highlights
+    Match,
+}
+
+impl Role {
+    pub const ALL: [Role; 23] = [
+        Role::Text,
+        Role::Muted,
+        Role::Border,
+        Role::Title,
+        Role::Header,
+        Role::Selection,
+        Role::TabActive,
+        Role::StatusTodo,
+        Role::StatusDoing,
+        Role::StatusDone,
+        Role::Overdue,
+        Role::DueToday,
+        Role::Blocked,
+        Role::PriorityHigh,
+        Role::PriorityMed,
+        Role::PriorityLow,
+        Role::Tag,
+        Role::Project,
+        Role::Group,
+        Role::Success,
+        Role::Error,
+        Role::Hint,
+        Role::Match,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Text => "text",
+            Role::Muted => "muted",
+            Role::Border => "border",
+            Role::Title => "title",
+            Role::Header => "header",
+            Role::Selection => "selection",
+            Role::TabActive => "tab_active",
+            Role::StatusTodo => "status_todo",
+            Role::StatusDoing => "status_doing",
+            Role::StatusDone => "status_done",
+            Role::Overdue => "overdue",
+            Role::DueToday => "due_today",
+            Role::Blocked => "blocked",
+            Role::PriorityHigh => "priority_high",
+            Role::PriorityMed => "priority_med",
+            Role::PriorityLow => "priority_low",
+            Role::Tag => "tag",
+            Role::Project => "project",
+            Role::Group => "group",
+            Role::Success => "success",
+            Role::Error => "error",
+            Role::Hint => "hint",
+            Role::Match => "match",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Role::ALL.into_iter().find(|r| r.as_str() == s)
+    }
+
+    /// The role for a status category (statuses the workflow doesn't know are errors)
+    pub fn for_status(category: Option<Category>) -> Self {
+        match category {
+            Some(Category::Open) => Role::StatusTodo,
+            Some(Category::Active) => Role::StatusDoing,
+            Some(Category::Closed) => Role::StatusDone,
+            None => Role::Error,
+        }
+    }
+
+    /// The role for a priority, if it is styled at all
+    pub fn for_priority(priority: &str) -> Option<Self> {
+        match crate::sort::priority_rank(priority) {
+            0 => None,
+            1 => Some(Role::PriorityLow),
+            2 => Some(Role::PriorityMed),
+            _ => Some(Role::PriorityHigh),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+/// xterm's values for the 16 ANSI colours (black, red, green, yellow, blue, magenta, cyan,
+/// white, then the bright ones)
+const ANSI16: [Rgb; 16] = [
+    Rgb(0, 0, 0),
+    Rgb(205, 0, 0),
+    Rgb(0, 205, 0),
+    Rgb(205, 205, 0),
+    Rgb(0, 0, 238),
+    Rgb(205, 0, 205),
+    Rgb(0, 205, 205),
+    Rgb(229, 229, 229),
+    Rgb(127, 127, 127),
+    Rgb(255, 0, 0),
+    Rgb(0, 255, 0),
+    Rgb(255, 255, 0),
+    Rgb(92, 92, 255),
+    Rgb(255, 0, 255),
+    Rgb(0, 255, 255),
+    Rgb(255, 255, 255),
+];
+
+impl Rgb {
+    /// `#rrggbb` or `#rgb`
+    pub fn parse(s: &str) -> Option<Self> {
+        let hex = s.strip_prefix('#')?;
+        let v = |h: &str| u8::from_str_radix(h, 16).ok();
+        match hex.len() {
+            6 => Some(Rgb(v(&hex[0..2])?, v(&hex[2..4])?, v(&hex[4..6])?)),
+            3 => {
+                let d = |i: usize| v(&hex[i..=i]).map(|x| x * 17);
+                Some(Rgb(d(0)?, d(1)?, d(2)?))
+            }
+            _ => None,
+        }
+    }
+
+    fn dist(self, o: Rgb) -> u32 {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+        // weighted for perceived brightness
+        2 * d(self.0, o.0) + 4 * d(self.1, o.1) + 3 * d(self.2, o.2)
+    }
+
+    /// Nearest entry of the xterm 256-colour palette (6×6×6 cube or grey ramp)
+    pub fn to_ansi256(self) -> u8 {
+        let level = |c: u8| {
+            if c < 48 {
+                0
+            } else if c < 115 {
+                1
+            } else {
+                (c as u16 - 35) as u8 / 40
+            }
+        };
+        let (r, g, b) = (level(self.0), level(self.1), level(self.2));
+        let steps = [0u8, 95, 135, 175, 215, 255];
+        let cube = Rgb(steps[r as usize], steps[g as usize], steps[b as usize]);
+        let cube_idx = 16 + 36 * r + 6 * g + b;
+        let avg = (self.0 as u16 + self.1 as u16 + self.2 as u16) / 3;
+        let grey_level = if avg > 238 {
+            23
+        } else {
+            (avg.saturating_sub(3) / 10) as u8
+        };
+        let grey_v = 8 + 10 * grey_level;
+        let grey = Rgb(grey_v, grey_v, grey_v);
+        if self.dist(grey) < self.dist(cube) {
+            232 + grey_level
+        } else {
+            cube_idx
+        }
+    }
+
+    /// Nearest of the 16 ANSI colours (0–15)
+    pub fn to_ansi16(self) -> u8 {
+        (0..16u8)
+            .min_by_key(|&i| self.dist(ANSI16[i as usize]))
+            .unwrap_or(7)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Rgb>,
+    pub bg: Option<Rgb>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Style {
+    /// `"#fb4934 on #282828 bold"`
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut st = Style::default();
+        let mut words = s.split_whitespace().peekable();
+        while let Some(w) = words.next() {
+            match w {
+                "bold" => st.bold = true,
+                "dim" => st.dim = true,
+                "italic" => st.italic = true,
+                "underline" => st.underline = true,
+                "reverse" => st.reverse = true,
+                "on" => {
+                    let c = words.next().ok_or_else(|| anyhow!("'on' needs a colour"))?;
+                    st.bg = Some(
+                        Rgb::parse(c).ok_or_else(|| anyhow!("bad colour '{c}' (use #rrggbb)"))?,
+                    );
+                }
+                c => {
+                    st.fg = Some(
+                        Rgb::parse(c).ok_or_else(|| anyhow!("bad colour or attribute '{c}'"))?,
+                    )
+                }
+            }
+        }
+        Ok(st)
+    }
+}
+
+/// A style as written in config: a string or a table
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StyleSpec {
+    Str(String),
+    Table {
+        #[serde(default)]
+        fg: Option<String>,
+        #[serde(default)]
+        bg: Option<String>,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default)]
+        dim: bool,
+        #[serde(default)]
+        italic: bool,
+        #[serde(default)]
+        underline: bool,
+        #[serde(default)]
+        reverse: bool,
+    },
+}
+
+impl StyleSpec {
+    fn to_style(&self) -> Result<Style> {
+        match self {
+            StyleSpec::Str(s) => Style::parse(s),
+            StyleSpec::Table {
+                fg,
+                bg,
+                bold,
+                dim,
+                italic,
+                underline,
+                reverse,
+            } => {
+                let color = |c: &Option<String>| -> Result<Option<Rgb>> {
+                    c.as_deref()
+                        .map(|c| {
+                            Rgb::parse(c).ok_or_else(|| anyhow!("bad colour '{c}' (use #rrggbb)"))
+                        })
+                        .transpose()
+                };
+                Ok(Style {
+                    fg: color(fg)?,
+                    bg: color(bg)?,
+                    bold: *bold,
+                    dim: *dim,
+                    italic: *italic,
+                    underline: *underline,
+                    reverse: *reverse,
+                })
+            }
+        }
+    }
+}
+
+/// `themes.<name>` in config: `base` plus role → style
+pub type ThemeSpec = BTreeMap<String, StyleSpec>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub name: String,
+    styles: BTreeMap<Role, Style>,
+}
+
+fn builtin_spec(name: &str) -> Option<&'static [(Role, &'static str)]> {
+    use Role::*;
+    Some(match name {
+        "gruvbox-dark" => &[
+            (Text, "#ebdbb2 on #282828"),
+            (Muted, "#928374"),
+            (Border, "#504945"),
+            (Title, "#fabd2f bold"),
+            (Header, "#83a598 bold"),
+            (Selection, "on #3c3836 bold"),
+            (TabActive, "#282828 on #fabd2f bold"),
+            (StatusTodo, "#83a598"),
+            (StatusDoing, "#fabd2f"),
+            (StatusDone, "#928374"),
+            (Overdue, "#fb4934 bold"),
+            (DueToday, "#fe8019"),
+            (Blocked, "#a89984 italic"),
+            (PriorityHigh, "#fb4934 bold"),
+            (PriorityMed, "#fabd2f"),
+            (PriorityLow, "#8ec07c"),
+            (Tag, "#d3869b"),
+            (Project, "#8ec07c"),
+            (Group, "#fabd2f bold"),
+            (Success, "#b8bb26"),
+            (Error, "#fb4934"),
+            (Hint, "#928374 italic"),
+            (Match, "#fe8019 bold"),
+        ],
+        "gruvbox-light" => &[
+            (Text, "#3c3836 on #fbf1c7"),
+            (Muted, "#928374"),
+            (Border, "#d5c4a1"),
+            (Title, "#b57614 bold"),
+            (Header, "#076678 bold"),
+            (Selection, "on #ebdbb2 bold"),
+            (TabActive, "#fbf1c7 on #b57614 bold"),
+            (StatusTodo, "#076678"),
+            (StatusDoing, "#b57614"),
+            (StatusDone, "#928374"),
+            (Overdue, "#9d0006 bold"),
+            (DueToday, "#af3a03"),
+            (Blocked, "#7c6f64 italic"),
+            (PriorityHigh, "#9d0006 bold"),
+            (PriorityMed, "#b57614"),
+            (PriorityLow, "#427b58"),
+            (Tag, "#8f3f71"),
+            (Project, "#427b58"),
+            (Group, "#b57614 bold"),
+            (Success, "#79740e"),
+            (Error, "#9d0006"),
+            (Hint, "#928374 italic"),
+            (Match, "#af3a03 bold"),
+        ],
+        // terminal colours only: attributes, no palette
+        "mono" => &[
+            (Muted, "dim"),
+            (Title, "bold"),
+            (Header, "bold underline"),
+            (Selection, "reverse"),
+            (TabActive, "reverse"),
+            (StatusDone, "dim"),
+            (Overdue, "bold"),
+            (PriorityHigh, "bold"),
+            (Blocked, "italic"),
+            (Group, "bold"),
+            (Error, "bold"),
+            (Hint, "dim"),
+            (Match, "underline"),
+        ],
+        _ => return None,
+    })
+}
+
+pub const BUILTIN_THEMES: &[&str] = &["gruvbox-dark", "gruvbox-light", "mono"];
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::builtin(DEFAULT_THEME).expect("default theme")
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let spec = builtin_spec(name)?;
+        let styles = spec
+            .iter()
+            .map(|(role, s)| (*role, Style::parse(s).expect("built-in theme style")))
+            .collect();
+        Some(Self {
+            name: name.to_string(),
+            styles,
+        })
+    }
+
+    /// `name` from the user's `themes` (following `base`, default gruvbox-dark) or the built-ins
+    pub fn resolve(name: &str, user: &BTreeMap<String, ThemeSpec>) -> Result<Self> {
+        Self::resolve_depth(name, user, 0)
+    }
+
+    fn resolve_depth(name: &str, user: &BTreeMap<String, ThemeSpec>, depth: usize) -> Result<Self> {
+        if depth > 8 {
+            bail!("theme {name}: 'base' chain too long (a cycle?)");
+        }
+        let Some(spec) = user.get(name) else {
+            return Self::builtin(name).ok_or_else(|| {
+                let mut names: Vec<&str> = BUILTIN_THEMES.to_vec();
+                names.extend(user.keys().map(String::as_str));
+                anyhow!("unknown theme '{name}' (themes: {})", names.join(", "))
+            });
+        };
+        let base = match spec.get("base") {
+            Some(StyleSpec::Str(b)) if b != name => b.as_str(),
+            Some(StyleSpec::Str(_)) => bail!("theme {name}: can't be its own base"),
+            Some(_) => bail!("theme {name}: 'base' must be a theme name"),
+            None => DEFAULT_THEME,
+        };
+        let mut theme = Self::resolve_depth(base, user, depth + 1)?;
+        theme.name = name.to_string();
+        for (key, style) in spec.iter().filter(|(k, _)| *k != "base") {
+            let role = Role::parse(key).ok_or_else(|| {
+                let roles: Vec<&str> = Role::ALL.iter().map(|r| r.as_str()).collect();
+                anyhow!(
+                    "theme {name}: unknown role '{key}' (roles: {})",
+                    roles.join(", ")
+                )
+            })?;
+            theme.styles.insert(
+                role,
+                style
+                    .to_style()
+                    .with_context(|| format!("theme {name}.{key}"))?,
+            );
+        }
+        Ok(theme)
+    }
+
+    /// The style of a role (unstyled if the theme leaves it out)
+    pub fn get(&self, role: Role) -> Style {
+        self.styles.get(&role).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_parse_in_long_and_short_hex() {
+        assert_eq!(Rgb::parse("#fb4934"), Some(Rgb(0xfb, 0x49, 0x34)));
+        assert_eq!(Rgb::parse("#f80"), Some(Rgb(0xff, 0x88, 0x00)));
+        assert_eq!(Rgb::parse("fb4934"), None);
+        assert_eq!(Rgb::parse("#fb493"), None);
+        assert_eq!(Rgb::parse("#ggg"), None);
+    }
+
+    #[test]
+    fn colours_degrade_to_the_nearest_palette_entry() {
+        // cube corners, and greys that land on the grey ramp rather than the cube
+        assert_eq!(Rgb(255, 0, 0).to_ansi256(), 196);
+        assert_eq!(Rgb(0, 0, 0).to_ansi256(), 16);
+        assert_eq!(Rgb(255, 255, 255).to_ansi256(), 231);
+        assert_eq!(Rgb(0x28, 0x28, 0x28).to_ansi256(), 235);
+        assert_eq!(Rgb(128, 128, 128).to_ansi256(), 244);
+
+        assert_eq!(Rgb(0xfb, 0x49, 0x34).to_ansi16(), 9);
+        assert_eq!(Rgb(0x28, 0x28, 0x28).to_ansi16(), 0);
+        assert_eq!(Rgb(0x80, 0x80, 0x80).to_ansi16(), 8);
+        assert_eq!(Rgb(250, 250, 250).to_ansi16(), 15);
+    }
+
+    #[test]
+    fn styles_parse_colours_and_attributes() {
+        let s = Style::parse("#fb4934 on #282828 bold italic").unwrap();
+        assert_eq!(s.fg, Some(Rgb(0xfb, 0x49, 0x34)));
+        assert_eq!(s.bg, Some(Rgb(0x28, 0x28, 0x28)));
+        assert!(s.bold && s.italic && !s.dim);
+        assert_eq!(Style::parse("").unwrap(), Style::default());
+        assert!(Style::parse("on").is_err());
+        assert!(Style::parse("red").is_err());
+    }
+
+    #[test]
+    fn user_themes_override_roles_over_their_base() {
+        let user: BTreeMap<String, ThemeSpec> = [
+            (
+                "mine".to_string(),
+                BTreeMap::from([
+                    ("base".to_string(), StyleSpec::Str("mono".into())),
+                    ("tag".to_string(), StyleSpec::Str("#d3869b".into())),
+                ]),
+            ),
+            (
+                "loop".to_string(),
+                BTreeMap::from([("base".to_string(), StyleSpec::Str("loop2".into()))]),
+            ),
+            (
+                "loop2".to_string(),
+                BTreeMap::from([("base".to_string(), StyleSpec::Str("loop".into()))]),
+            ),
+            (
+                "typo".to_string(),
+                BTreeMap::from([("tags".to_string(), StyleSpec::Str("bold".into()))]),
+            ),
+        ]
+        .into();
+        let mine = Theme::resolve("mine", &user).unwrap();
+        assert_eq!(mine.name, "mine");
+        assert_eq!(mine.get(Role::Tag).fg, Some(Rgb(0xd3, 0x86, 0x9b)));
+        assert!(mine.get(Role::Selection).reverse);
+        assert_eq!(mine.get(Role::Text), Style::default());
+        assert!(Theme::resolve("loop", &user).is_err());
+        assert!(Theme::resolve("typo", &user).is_err());
+        assert!(Theme::resolve("solarized", &user).is_err());
+    }
+}
@@ -0,0 +1,615 @@
+//! todo.txt: one task per line, for one-shot `tm import --from todotxt` / `tm export --to
+//! todotxt` and for the `todotxt_mirror` file kept in sync with the vault both ways.
+//!
+//! ```text
+//! (A) 2026-10-01 Write report +work @office due:2026-10-20 tm:01J8...
+//! x 2026-10-05 2026-09-30 Pay rent +home pri:B tm:01J9...
+//! Buy milk @shop
+//! ```
+//!
+//! `(A)`/`(B)`/`(C)` are high/med/low (D–Z low), `x <date>` closes a task, the first
+//! `+project` is its project (more become tags, none is `default_project`), `@contexts`
+//! are tags, `due:` and `t:` (threshold) are the due and start dates, and `tm:` carries
+//! the ULID. Other `key:value` pairs are kept under `todotxt:` in the frontmatter and
+//! written back.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use ulid::Ulid;
+
+use crate::{
+    date,
+    export::{as_export, Conflict, ImportReport, TaskRecord},
+    track, Category, Config, FileWatcher, Frontmatter, Query, Vault,
+};
+
+/// How often the TUI and GUI look for edits made to `todotxt_mirror` outside tm
+pub const MIRROR_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The frontmatter key holding `key:value` pairs tm has no field for
+const EXTRA_KEY: &str = "todotxt";
+
+/// One todo.txt line
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Item {
+    pub done: bool,
+    pub completed: Option<String>,
+    pub priority: Option<char>,
+    pub created: Option<String>,
+    pub title: String,
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    pub due: Option<String>,
+    pub threshold: Option<String>,
+    pub id: Option<String>,
+    /// other `key:value` pairs, in line order
+    pub other: Vec<(String, String)>,
+}
+
+fn is_date(s: &str) -> bool {
+    s.len() == 10 && date::parse_date(s).is_some()
+}
+
+/// `key:value` with no colon on either side (`http://…` stays text)
+fn key_value(word: &str) -> Option<(&str, &str)> {
+    let (k, v) = word.split_once(':')?;
+    let plain = |s: &str| !s.is_empty() && !s.contains(':') && !s.starts_with("//");
+    (plain(k) && plain(v)).then_some((k, v))
+}
+
+impl Item {
+    /// None for a blank line
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace().peekable();
+        words.peek()?;
+        let mut item = Item::default();
+        if words.peek() == Some(&"x") {
+            item.done = true;
+            words.next();
+            if let Some(d) = words.next_if(|w| is_date(w)) {
+                item.completed = Some(d.to_string());
+            }
+        }
+        if let Some(p) = words.next_if(|w| w.len() == 3 && w.starts_with('(') && w.ends_with(')')) {
+            match p.chars().nth(1).filter(char::is_ascii_uppercase) {
+                Some(c) => item.priority = Some(c),
+                None => item.title = p.to_string(),
+            }
+        }
+        if item.title.is_empty() {
+            if let Some(d) = words.next_if(|w| is_date(w)) {
+                item.created = Some(d.to_string());
+            }
+        }
+        let mut title: Vec<&str> = item.title.split_whitespace().collect();
+        for w in words {
+            if let Some(p) = w.strip_prefix('+').filter(|p| !p.is_empty()) {
+                item.projects.push(p.to_string());
+            } else if let Some(c) = w.strip_prefix('@').filter(|c| !c.is_empty()) {
+                item.contexts.push(c.to_string());
+            } else if let Some((k, v)) = key_value(w) {
+                match k {
+                    "due" if is_date(v) => item.due = Some(v.to_string()),
+                    "t" if is_date(v) => item.threshold = Some(v.to_string()),
+                    "tm" => item.id = Some(v.to_string()),
+                    "pri" if item.priority.is_none() && v.len() == 1 => {
+                        item.priority = v.chars().next()
+                    }
+                    _ => item.other.push((k.to_string(), v.to_string())),
+                }
+            } else {
+                title.push(w);
+            }
+        }
+        item.title = title.join(" ");
+        Some(item)
+    }
+
+    /// The line, with the priority as `pri:` on completed tasks (the todo.txt convention)
+    pub fn format(&self) -> String {
+        let mut words: Vec<String> = vec![];
+        if self.done {
+            words.push("x".into());
+            words.extend(self.completed.clone());
+        } else if let Some(p) = self.priority {
+            words.push(format!("({p})"));
+        }
+        if self.completed.is_some() || !self.done {
+            words.extend(self.created.clone());
+        }
+        if !self.title.is_empty() {
+            words.push(self.title.clone());
+        }
+        words.extend(self.projects.iter().map(|p| format!("+{p}")));
+        words.extend(self.contexts.iter().map(|c| format!("@{c}")));
+        words.extend(self.due.as_ref().map(|d| format!("due:{d}")));
+        words.extend(self.threshold.as_ref().map(|t| format!("t:{t}")));
+        if self.done {
+            words.extend(self.priority.map(|p| format!("pri:{p}")));
+        }
+        words.extend(self.other.iter().map(|(k, v)| format!("{k}:{v}")));
+        words.extend(self.id.as_ref().map(|id| format!("tm:{id}")));
+        words.join(" ")
+    }
+
+    /// The line for a task
+    pub fn from_task(fm: &Frontmatter, cfg: &Config) -> Self {
+        let day = |s: &Option<String>| {
+            s.as_deref()
+                .and_then(date::parse_date)
+                .map(|d| date::format_date("YYYY-MM-DD", d))
+        };
+        let done = cfg.workflow.category(&fm.status) == Some(Category::Closed);
+        let other = match fm.extra.get(EXTRA_KEY) {
+            Some(serde_yaml::Value::Mapping(m)) => m
+                .iter()
+                .filter_map(|(k, v)| Some((k.as_str()?.to_string(), yaml_scalar(v)?)))
+                .collect(),
+            _ => vec![],
+        };
+        Item {
+            done,
+            completed: if done {
+                day(&fm.done_at).or_else(|| day(&fm.updated))
+            } else {
+                None
+            },
+            priority: match fm.priority.as_str() {
+                "high" => Some('A'),
+                "med" => Some('B'),
+                "low" => Some('C'),
+                _ => None,
+            },
+            created: day(&fm.created),
+            title: fm.title.clone(),
+            projects: if fm.project == cfg.default_project {
+                vec![]
+            } else {
+                vec![fm.project.clone()]
+            },
+            contexts: fm
+                .tags
+                .iter()
+                .map(|t| t.replace(char::is_whitespace, "-"))
+                .collect(),
+            due: day(&fm.due),
+            threshold: day(&fm.start),
+            id: Some(fm.id.clone()),
+            other,
+        }
+    }
+
+    /// Carry the line's fields over to a task (title, priority, project, tags, dates,
+    /// open/closed); everything todo.txt can't express is left alone
+    pub fn apply(&self, fm: &mut Frontmatter, cfg: &Config) -> Result<()> {
+        let wf = &cfg.workflow;
+        fm.title = self.title.clone();
+        fm.priority = match self.priority {
+            Some('A') => "high",
+            Some('B') => "med",
+            Some(_) => "low",
+            None => "none",
+        }
+        .into();
+        let mut projects = self.projects.iter();
+        fm.project = projects
+            .next()
+            .map(slug::slugify)
+            .unwrap_or_else(|| cfg.default_project.clone());
+        fm.tags = self.contexts.iter().chain(projects).cloned().collect();
+        fm.due = self.due.clone();
+        fm.start = self.threshold.clone();
+        let closed = wf.category(&fm.status) == Some(Category::Closed);
+        if self.done && !closed {
+            fm.status = wf.first(Category::Closed)?.to_string();
+            track::stop(&mut fm.time);
+            fm.done_at = Some(match self.completed.as_deref().and_then(date::parse_date) {
+                Some(d) if d != date::today() => date::local_midnight(d),
+                _ => track::now_rfc3339(),
+            });
+        } else if !self.done && closed {
+            fm.status = wf.first(Category::Open)?.to_string();
+            fm.done_at = None;
+        }
+        if self.other.is_empty() {
+            fm.extra.remove(EXTRA_KEY);
+        } else {
+            let map = self
+                .other
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        serde_yaml::Value::from(k.as_str()),
+                        serde_yaml::Value::from(v.as_str()),
+                    )
+                })
+                .collect();
+            fm.extra
+                .insert(EXTRA_KEY.into(), serde_yaml::Value::Mapping(map));
+        }
+        Ok(())
+    }
+}
+
+fn yaml_scalar(v: &serde_yaml::Value) -> Option<String> {
+    match v {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// A task that doesn't exist yet, for a line without a (known) `tm:` id
+fn new_task(id: String, item: &Item, cfg: &Config) -> Result<Frontmatter> {
+    let now = track::now_rfc3339();
+    let created = match item.created.as_deref().and_then(date::parse_date) {
+        Some(d) if d != date::today() => date::local_midnight(d),
+        _ => now.clone(),
+    };
+    Ok(Frontmatter {
+        id,
+        key: slug::slugify(&item.title),
+        title: String::new(),
+        status: cfg.workflow.first(Category::Open)?.to_string(),
+        project: cfg.default_project.clone(),
+        tags: vec![],
+        priority: "none".into(),
+        due: None,
+        start: None,
+        created: Some(created),
+        updated: Some(now),
+        done_at: None,
+        parent: None,
+        depends_on: vec![],
+        estimate: None,
+        time: vec![],
+        extra: BTreeMap::new(),
+    })
+}
+
+/// Records for the lines that change something: existing tasks (by `tm:` id) with the
+/// line applied and `updated` bumped, and new ones. Returns the id each line ended up with.
+fn records_for(
+    items: &[Item],
+    existing: &HashMap<String, TaskRecord>,
+    cfg: &Config,
+) -> Result<(Vec<TaskRecord>, Vec<String>)> {
+    let mut records = vec![];
+    let mut ids = vec![];
+    for item in items {
+        let known = item.id.as_ref().filter(|id| Ulid::from_string(id).is_ok());
+        let (mut fm, body) = match known.and_then(|id| existing.get(id)) {
+            Some(r) => (r.frontmatter.clone(), r.body.clone()),
+            None => {
+                let id = known.cloned().unwrap_or_else(|| Ulid::new().to_string());
+                (new_task(id, item, cfg)?, String::new())
+            }
+        };
+        let before = fm.clone();
+        item.apply(&mut fm, cfg)?;
+        ids.push(fm.id.clone());
+        if fm != before || !existing.contains_key(&fm.id) {
+            fm.updated = Some(track::now_rfc3339());
+            records.push(TaskRecord {
+                frontmatter: fm,
+                body,
+            });
+        }
+    }
+    Ok((records, ids))
+}
+
+/// When the TUI and GUI sync `todotxt_mirror` (and rewrite the `ics` feed): as soon as a
+/// task file changes, and when the todo.txt was edited outside tm (looked at every
+/// `MIRROR_INTERVAL`)
+pub struct MirrorTrigger {
+    tasks: Option<FileWatcher>,
+    checked_at: Option<Instant>,
+}
+
+impl MirrorTrigger {
+    pub fn new(vault_path: &Path) -> Self {
+        Self {
+            tasks: FileWatcher::for_tasks(vault_path).ok(),
+            checked_at: None,
+        }
+    }
+
+    /// Whether task files changed (always the first time, and every `MIRROR_INTERVAL` when
+    /// they can't be watched) and whether the todo.txt at `mirror` was edited since the last sync
+    pub fn poll(&mut self, vault: &Vault, mirror: Option<&Path>) -> (bool, bool) {
+        let first = self.checked_at.is_none();
+        let due = self
+            .checked_at
+            .is_none_or(|t| t.elapsed() >= MIRROR_INTERVAL);
+        if due {
+            self.checked_at = Some(Instant::now());
+        }
+        let tasks = match &self.tasks {
+            Some(w) => w.changed() || first,
+            None => due,
+        };
+        let edited = due && !tasks && mirror.is_some_and(|p| vault.todotxt_edited(p));
+        (tasks, edited)
+    }
+}
+
+/// What a mirror sync did
+#[derive(Clone, Debug, Default)]
+pub struct MirrorReport {
+    /// tasks created / updated from lines added or edited in the file
+    pub import: ImportReport,
+    /// tasks closed because their line was deleted from the file
+    pub closed: Vec<String>,
+    /// whether the file was rewritten from the vault
+    pub written: bool,
+}
+
+impl MirrorReport {
+    /// Whether lines from the file changed the vault
+    pub fn changed_vault(&self) -> bool {
+        !(self.import.created.is_empty() && self.import.updated.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.changed_vault() && !self.written
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "todo.txt: {} created, {} updated, {} closed from the file{}",
+            self.import.created.len(),
+            // closing is an update too
+            self.import.updated.len().saturating_sub(self.closed.len()),
+            self.closed.len(),
+            if self.written { ", file rewritten" } else { "" }
+        )
+    }
+}
+
+impl Vault {
+    /// Matching tasks (all for an empty query) as todo.txt lines
+    pub fn export_todotxt(&self, query: &Query) -> Result<String> {
+        let data = self.export_json(query)?;
+        Ok(data
+            .tasks
+            .iter()
+            .map(|t| Item::from_task(&t.frontmatter, &self.cfg).format() + "\n")
+            .collect())
+    }
+
+    /// Upsert the lines of a todo.txt: `tm:` ids update their task, other lines are new tasks
+    pub fn import_todotxt(
+        &self,
+        text: &str,
+        strategy: Conflict,
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        let existing = self.records_by_id()?;
+        let items: Vec<Item> = text.lines().filter_map(Item::parse).collect();
+        let (records, _) = records_for(&items, &existing, &self.cfg)?;
+        self.import_json(&as_export(records), strategy, dry_run)
+    }
+
+    /// The copy of the mirror written by the last sync
+    fn mirror_state(&self) -> PathBuf {
+        self.state_dir().join("todotxt-mirror.txt")
+    }
+
+    /// Whether the todo.txt at `path` changed since the last `sync_todotxt` (or is gone)
+    pub fn todotxt_edited(&self, path: &Path) -> bool {
+        fs::read_to_string(path).ok() != fs::read_to_string(self.mirror_state()).ok()
+    }
+
+    /// Two-way sync with a todo.txt against the copy written last time (`.tm/todotxt-mirror.txt`):
+    /// lines added or edited in the file go to the vault, open tasks whose line was deleted are
+    /// closed (completed lines may be archived away freely), then the file is rewritten from
+    /// the vault: every open task, plus closed ones still listed, in file order.
+    pub fn sync_todotxt(&self, path: &Path) -> Result<MirrorReport> {
+        let state = self.mirror_state();
+        let last = fs::read_to_string(&state).unwrap_or_default();
+        // a missing file (mid-sync on the phone, say) is rewritten, not read as "all deleted"
+        let current = fs::read_to_string(path).ok();
+        let mut report = MirrorReport::default();
+        let mut order: Vec<String> = vec![];
+        if let Some(text) = current.as_deref().filter(|t| *t != last) {
+            let existing = self.records_by_id()?;
+            let last_items: HashMap<String, Item> = last
+                .lines()
+                .filter_map(Item::parse)
+                .filter_map(|i| Some((i.id.clone()?, i)))
+                .collect();
+            let items: Vec<Item> = text.lines().filter_map(Item::parse).collect();
+            let changed: Vec<Item> = items
+                .iter()
+                .filter(|i| i.id.as_ref().and_then(|id| last_items.get(id)) != Some(*i))
+                .cloned()
+                .collect();
+            let (mut records, changed_ids) = records_for(&changed, &existing, &self.cfg)?;
+            let mut changed_ids = changed_ids.into_iter();
+            for i in &items {
+                match i.id.as_ref().and_then(|id| last_items.get(id)) {
+                    Some(prev) if prev == i => order.push(i.id.clone().unwrap_or_default()),
+                    _ => order.extend(changed_ids.next()),
+                }
+            }
+            let listed: HashSet<&str> = items.iter().filter_map(|i| i.id.as_deref()).collect();
+            for (id, prev) in &last_items {
+                if listed.contains(id.as_str()) || prev.done {
+                    continue;
+                }
+                let Some(r) = existing.get(id) else { continue };
+                if self.cfg.workflow.category(&r.frontmatter.status) == Some(Category::Closed) {
+                    continue;
+                }
+                let mut fm = r.frontmatter.clone();
+                fm.status = self.cfg.workflow.cancel_status()?.to_string();
+                track::stop(&mut fm.time);
+                fm.done_at = Some(track::now_rfc3339());
+                fm.updated = fm.done_at.clone();
+                report.closed.push(id.clone());
+                records.push(TaskRecord {
+                    frontmatter: fm,
+                    body: r.body.clone(),
+                });
+            }
+            report.import = self.import_json(&as_export(records), Conflict::Overwrite, false)?;
+        } else if let Some(text) = &current {
+            order = text
+                .lines()
+                .filter_map(Item::parse)
+                .filter_map(|i| i.id)
+                .collect();
+        }
+
+        let tasks = self.records_by_id()?;
+        let in_file: HashSet<&str> = order.iter().map(String::as_str).collect();
+        let mut rest: Vec<&TaskRecord> = tasks
+            .values()
+            .filter(|t| !in_file.contains(t.frontmatter.id.as_str()))
+            .filter(|t| self.cfg.workflow.category(&t.frontmatter.status) != Some(Category::Closed))
+            .collect();
+        rest.sort_by(|a, b| a.frontmatter.id.cmp(&b.frontmatter.id));
+        let mut seen = HashSet::new();
+        let text: String = order
+            .iter()
+            .filter_map(|id| tasks.get(id))
+            .chain(rest)
+            .filter(|t| seen.insert(t.frontmatter.id.clone()))
+            .map(|t| Item::from_task(&t.frontmatter, &self.cfg).format() + "\n")
+            .collect();
+        if current.as_deref() != Some(text.as_str()) {
+            fs::write(path, &text)?;
+            report.written = true;
+        }
+        if last != text {
+            fs::create_dir_all(self.state_dir())?;
+            fs::write(&state, &text)?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{task, vault};
+
+    fn by_title(vault: &Vault, title: &str) -> crate::Task {
+        vault
+            .list_tasks(None)
+            .unwrap()
+            .into_iter()
+            .find(|t| t.title == title)
+            .unwrap()
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        for line in [
+            "(A) 2026-10-01 Write report +work @office due:2026-10-20 t:2026-10-15 rec:1w tm:01J8ZQ4N6Y7Z8A9B0C1D2E3F4G",
+            "x 2026-10-05 2026-09-30 Pay rent +home pri:B",
+            "Read http://example.com/a:b later",
+        ] {
+            assert_eq!(Item::parse(line).unwrap().format(), line);
+        }
+        let item = Item::parse("x 2026-10-05 Pay rent +home +bills @desk").unwrap();
+        assert!(item.done);
+        assert_eq!((item.projects.len(), item.contexts.len()), (2, 1));
+        assert!(Item::parse("   ").is_none());
+    }
+
+    #[test]
+    fn tasks_survive_export_and_import() {
+        let (_dir, vault) = vault();
+        let id = task(&vault, "Write report");
+        vault.set_priority(&id, "high").unwrap();
+        let text = vault.export_todotxt(&Query::default()).unwrap();
+        assert!(text.starts_with("(A) "), "{text}");
+        assert!(text.contains(&format!("tm:{id}")));
+
+        let edited = text
+            .replace("(A)", "(C)")
+            .replace("Write report", "Write the report @desk");
+        let report = vault
+            .import_todotxt(
+                &(edited + "Buy milk +errands\n"),
+                Conflict::Overwrite,
+                false,
+            )
+            .unwrap();
+        assert_eq!((report.created.len(), report.updated.len()), (1, 1));
+        let t = by_title(&vault, "Write the report");
+        assert_eq!(
+            (t.id.as_str(), t.priority.as_str(), t.tags.as_slice()),
+            (id.as_str(), "low", &["desk".to_string()][..])
+        );
+        assert_eq!(by_title(&vault, "Buy milk").project, "errands");
+
+        let again = vault.export_todotxt(&Query::default()).unwrap();
+        let report = vault
+            .import_todotxt(&again, Conflict::Overwrite, false)
+            .unwrap();
+        assert!(report.created.is_empty() && report.updated.is_empty());
+    }
+
+    #[test]
+    fn the_mirror_syncs_both_ways() {
+        let (dir, vault) = vault();
+        let path = dir.path().join("todo.txt");
+        let keep = task(&vault, "Keep me");
+        let drop = task(&vault, "Drop me");
+        assert!(vault.sync_todotxt(&path).unwrap().written);
+        assert!(!vault.todotxt_edited(&path));
+
+        let text = fs::read_to_string(&path).unwrap();
+        let text: String = text
+            .lines()
+            .filter(|l| !l.contains(&drop))
+            .map(|l| l.replace("Keep me", "Kept") + "\n")
+            .collect();
+        fs::write(&path, text + "New one\n").unwrap();
+        assert!(vault.todotxt_edited(&path));
+        let report = vault.sync_todotxt(&path).unwrap();
+        assert_eq!(
+            (report.import.created.len(), report.closed.as_slice()),
+            (1, &[drop.clone()][..])
+        );
+        assert_eq!(by_title(&vault, "Kept").id, keep);
+        assert_eq!(by_title(&vault, "Drop me").status, "cancelled");
+
+        vault.set_status(&keep, "done").unwrap();
+        vault.sync_todotxt(&path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(
+            text.lines()
+                .any(|l| l.starts_with("x ") && l.contains(&keep)),
+            "{text}"
+        );
+        assert!(!text.contains(&drop));
+    }
+
+    #[test]
+    fn the_trigger_fires_on_task_file_changes() {
+        let (dir, vault) = vault();
+        let path = dir.path().join("todo.txt");
+        let mut trigger = MirrorTrigger::new(&vault.cfg.vault_path);
+        assert!(trigger.poll(&vault, Some(&path)).0);
+        vault.sync_todotxt(&path).unwrap();
+        assert_eq!(trigger.poll(&vault, Some(&path)), (false, false));
+
+        task(&vault, "Watched");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !trigger.poll(&vault, Some(&path)).0 {
+            assert!(Instant::now() < deadline, "no change seen");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
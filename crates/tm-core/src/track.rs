@@ -0,0 +1,253 @@
+//! Time tracking: `time: [{start, end}]` intervals in the frontmatter (RFC 3339; an
+//! interval without `end` is the running timer, at most one in the vault), an `estimate`
+//! like `2h30m`, and timesheets over a date range (`tm report time`).
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, Date, OffsetDateTime, Time, UtcOffset};
+
+use crate::Task;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interval {
+    pub start: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+}
+
+impl Interval {
+    pub fn now() -> Self {
+        Self {
+            start: now_rfc3339(),
+            end: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.end.is_none()
+    }
+
+    /// Start and end (`now` while running); None if a timestamp doesn't parse
+    fn bounds(&self, now: OffsetDateTime) -> Option<(OffsetDateTime, OffsetDateTime)> {
+        let start = OffsetDateTime::parse(&self.start, &Rfc3339).ok()?;
+        let end = match &self.end {
+            Some(e) => OffsetDateTime::parse(e, &Rfc3339).ok()?,
+            None => now,
+        };
+        Some((start, end.max(start)))
+    }
+
+    /// Seconds of this interval inside `[from, to)`
+    fn seconds_within(&self, from: OffsetDateTime, to: OffsetDateTime, now: OffsetDateTime) -> i64 {
+        match self.bounds(now) {
+            Some((s, e)) => (e.min(to) - s.max(from)).whole_seconds().max(0),
+            None => 0,
+        }
+    }
+}
+
+/// Now, to the second
+pub(crate) fn now_rfc3339() -> String {
+    let now = OffsetDateTime::now_utc();
+    now.replace_nanosecond(0)
+        .unwrap_or(now)
+        .format(&Rfc3339)
+        .unwrap()
+}
+
+/// Close the running interval, if any; returns its length in seconds
+pub(crate) fn stop(intervals: &mut [Interval]) -> Option<i64> {
+    let now = OffsetDateTime::now_utc();
+    let running = intervals.iter_mut().find(|i| i.is_running())?;
+    running.end = Some(now_rfc3339());
+    Some(
+        running
+            .bounds(now)
+            .map(|(s, e)| (e - s).whole_seconds())
+            .unwrap_or(0),
+    )
+}
+
+/// Total tracked seconds (a running interval counts up to now)
+pub fn tracked(intervals: &[Interval]) -> i64 {
+    let now = OffsetDateTime::now_utc();
+    let all = (OffsetDateTime::UNIX_EPOCH, now);
+    intervals
+        .iter()
+        .map(|i| i.seconds_within(all.0, all.1, now))
+        .sum()
+}
+
+/// `90m`, `1h30m`, `2h`, `1.5h`, `45` (minutes) → seconds
+pub fn parse_duration(s: &str) -> Result<i64> {
+    let s = s.trim().to_lowercase();
+    let secs = match s.parse::<f64>() {
+        Ok(min) => min * 60.0,
+        Err(_) => parse_units(&s)?,
+    };
+    // `-5`, `nan` and `inf` parse as numbers too
+    if !secs.is_finite() || secs.round() < 1.0 || secs >= i64::MAX as f64 {
+        bail!("bad duration '{s}': it must be a positive length of time");
+    }
+    Ok(secs.round() as i64)
+}
+
+/// `1h30m` → seconds
+fn parse_units(s: &str) -> Result<f64> {
+    let mut total = 0.0;
+    let mut num = String::new();
+    for c in s.chars() {
+        match c {
+            '0'..='9' | '.' => num.push(c),
+            'h' | 'm' | 's' if !num.is_empty() => {
+                let n: f64 = num
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("bad number in duration '{s}'"))?;
+                total += n * match c {
+                    'h' => 3600.0,
+                    'm' => 60.0,
+                    _ => 1.0,
+                };
+                num.clear();
+            }
+            ' ' => {}
+            _ => bail!("bad duration '{s}' (e.g. 45m, 1h30m, 1.5h)"),
+        }
+    }
+    if !num.is_empty() || s.is_empty() {
+        bail!("bad duration '{s}' (e.g. 45m, 1h30m, 1.5h)");
+    }
+    Ok(total)
+}
+
+/// Seconds as `1h05m` / `45m` (rounded down to minutes)
+pub fn format_duration(secs: i64) -> String {
+    let min = secs.max(0) / 60;
+    match (min / 60, min % 60) {
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h{m:02}m"),
+    }
+}
+
+/// What `tm report time --by` groups on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportBy {
+    Project,
+    /// a task with several tags counts under each
+    Tag,
+}
+
+impl ReportBy {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "project" => Ok(Self::Project),
+            "tag" => Ok(Self::Tag),
+            _ => bail!("unknown report grouping '{s}' (use: project|tag)"),
+        }
+    }
+}
+
+/// One task's time within one group
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportRow {
+    pub group: String,
+    pub id: String,
+    pub title: String,
+    pub seconds: i64,
+}
+
+/// Time tracked from the start of `from` to the end of `to` (local days), by group, then
+/// by task; tasks without time in the range are left out
+pub fn time_report(tasks: &[Task], from: Date, to: Date, by: ReportBy) -> Vec<ReportRow> {
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    let start = from.with_time(Time::MIDNIGHT).assume_offset(offset);
+    let end = to
+        .next_day()
+        .unwrap_or(to)
+        .with_time(Time::MIDNIGHT)
+        .assume_offset(offset);
+    let now = OffsetDateTime::now_utc();
+    let mut groups: BTreeMap<String, Vec<ReportRow>> = BTreeMap::new();
+    for t in tasks {
+        let seconds: i64 = t
+            .time
+            .iter()
+            .map(|i| i.seconds_within(start, end, now))
+            .sum();
+        if seconds == 0 {
+            continue;
+        }
+        let keys = match by {
+            ReportBy::Project => vec![t.project.clone()],
+            ReportBy::Tag if t.tags.is_empty() => vec!["(untagged)".to_string()],
+            ReportBy::Tag => t.tags.clone(),
+        };
+        for group in keys {
+            let row = ReportRow {
+                group: group.clone(),
+                id: t.id.clone(),
+                title: t.title.clone(),
+                seconds,
+            };
+            groups.entry(group).or_default().push(row);
+        }
+    }
+    groups
+        .into_values()
+        .flat_map(|mut rows| {
+            rows.sort_by(|a, b| {
+                b.seconds
+                    .cmp(&a.seconds)
+                    .then_with(|| a.title.cmp(&b.title))
+            });
+            rows
+        })
+        .collect()
+}
+
+/// `group,id,title,hours` with a header line
+pub fn report_csv(rows: &[ReportRow]) -> String {
+    let field = |s: &str| {
+        if s.contains([',', '"', '\n']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let mut out = String::from("group,id,title,hours\n");
+    for r in rows {
+        out.push_str(&format!(
+            "{},{},{},{:.2}\n",
+            field(&r.group),
+            r.id,
+            field(&r.title),
+            r.seconds as f64 / 3600.0
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse_to_seconds() {
+        assert_eq!(parse_duration("45").unwrap(), 45 * 60);
+        assert_eq!(parse_duration("1h30m").unwrap(), 5400);
+        assert_eq!(parse_duration("1.5h").unwrap(), 5400);
+        assert_eq!(parse_duration(" 2h 5s ").unwrap(), 7205);
+        assert_eq!(format_duration(parse_duration("65m").unwrap()), "1h05m");
+    }
+
+    #[test]
+    fn durations_must_be_finite_and_positive() {
+        for bad in [
+            "-5", "-1h", "0", "0m", "nan", "NaN", "inf", "-inf", "infinity", "1e300", "", "h", "5x",
+        ] {
+            assert!(parse_duration(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,769 @@
+//! How the task list is shown: columns (with widths), grouping, and named views — a saved
+//! query plus its own columns, sort and grouping. Defined in config
+//!
+//! ```lua
+//! columns = { 'status', 'title', 'project', 'due:10' },   -- the list without a view
+//! group = 'project',
+//! views = {
+//!   today = 'due<=today status:!done sort:p',
+//!   work  = { query = '+work is:open', columns = { 'priority', 'title:40', 'due' }, sort = 'due, priority desc', group = 'status' },
+//! }
+//! ```
+//!
+//! or saved from the UI with `:view.save <name>` into `<vault>/.tm/views.json`.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use time::{Date, Duration};
+
+use crate::{
+    checklist, date,
+    query::Query,
+    sort::{format_sort, parse_sort, status_rank, SortKey, SortSpec},
+    theme::Role,
+    track::format_duration,
+    Config, Task, Vault,
+};
+
+/// A task list column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Column {
+    Id,
+    Status,
+    Priority,
+    Title,
+    Project,
+    Tags,
+    Due,
+    /// deferred until
+    Start,
+    Created,
+    Updated,
+    /// planned effort
+    Estimate,
+    /// tracked time
+    Actual,
+}
+
+/// Before the title of a task that waits for others
+pub const BLOCKED_MARKER: &str = "⊘";
+
+/// The list as it always looked: `[status] title · project`
+pub const DEFAULT_COLUMNS: &[Column] = &[Column::Status, Column::Title, Column::Project];
+
+/// A column and its width in cells (`title:40`); without one it fits its content
+/// (the title takes the remaining space)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColumnSpec {
+    pub column: Column,
+    pub width: Option<u16>,
+}
+
+impl ColumnSpec {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (name, width) = match s.split_once(':') {
+            Some((n, w)) => {
+                let w: u16 = w
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow!("column {n}: width must be a number, got '{w}'"))?;
+                if !(1..=200).contains(&w) {
+                    bail!("column {n}: width must be between 1 and 200");
+                }
+                (n.trim(), Some(w))
+            }
+            None => (s.trim(), None),
+        };
+        let column = Column::parse(name).ok_or_else(|| {
+            let names: Vec<&str> = Column::ALL.iter().map(|c| c.as_str()).collect();
+            anyhow!("unknown column '{name}' (columns: {})", names.join(", "))
+        })?;
+        Ok(Self { column, width })
+    }
+
+    pub fn parse_list(items: &[String]) -> Result<Vec<Self>> {
+        let out = items
+            .iter()
+            .map(|c| Self::parse(c))
+            .collect::<Result<Vec<_>>>()?;
+        if out.is_empty() {
+            bail!("no columns");
+        }
+        Ok(out)
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        DEFAULT_COLUMNS
+            .iter()
+            .map(|&column| Self {
+                column,
+                width: None,
+            })
+            .collect()
+    }
+
+    /// Back to config syntax (`title:40`)
+    pub fn to_config(self) -> String {
+        match self.width {
+            Some(w) => format!("{}:{w}", self.column.as_str()),
+            None => self.column.as_str().to_string(),
+        }
+    }
+}
+
+impl Column {
+    pub const ALL: [Column; 12] = [
+        Column::Id,
+        Column::Status,
+        Column::Priority,
+        Column::Title,
+        Column::Project,
+        Column::Tags,
+        Column::Due,
+        Column::Start,
+        Column::Created,
+        Column::Updated,
+        Column::Estimate,
+        Column::Actual,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Status => "status",
+            Column::Priority => "priority",
+            Column::Title => "title",
+            Column::Project => "project",
+            Column::Tags => "tags",
+            Column::Due => "due",
+            Column::Start => "start",
+            Column::Created => "created",
+            Column::Updated => "updated",
+            Column::Estimate => "estimate",
+            Column::Actual => "actual",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Column::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+
+    /// Header label
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Id => "ID",
+            Column::Status => "Status",
+            Column::Priority => "Pri",
+            Column::Title => "Title",
+            Column::Project => "Project",
+            Column::Tags => "Tags",
+            Column::Due => "Due",
+            Column::Start => "Start",
+            Column::Created => "Created",
+            Column::Updated => "Updated",
+            Column::Estimate => "Est",
+            Column::Actual => "Actual",
+        }
+    }
+
+    /// The sort key behind a column header (tags and ids don't sort)
+    pub fn sort_key(&self) -> Option<SortKey> {
+        Some(match self {
+            Column::Status => SortKey::Status,
+            Column::Priority => SortKey::Priority,
+            Column::Title => SortKey::Title,
+            Column::Project => SortKey::Project,
+            Column::Due => SortKey::Due,
+            Column::Created => SortKey::Created,
+            Column::Updated => SortKey::Updated,
+            Column::Id | Column::Tags | Column::Start | Column::Estimate | Column::Actual => {
+                return None
+            }
+        })
+    }
+
+    /// The cell text for `t` (dates with `date_format`, ids shortened)
+    pub fn cell(&self, t: &Task, cfg: &Config) -> String {
+        match self {
+            Column::Id => t.id.chars().take(8).collect(),
+            Column::Status => format!("[{}]", t.status),
+            Column::Priority => t.priority.clone(),
+            Column::Title => {
+                let mut s = if t.blocked {
+                    format!("{BLOCKED_MARKER} {}", t.title)
+                } else {
+                    t.title.clone()
+                };
+                if !t.checklist.is_empty() {
+                    let (done, total) = checklist::progress(&t.checklist);
+                    s += &format!(" {done}/{total}");
+                }
+                s
+            }
+            Column::Project => t.project.clone(),
+            Column::Tags => t
+                .tags
+                .iter()
+                .map(|x| format!("+{x}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Column::Due => t
+                .due
+                .as_deref()
+                .map(|d| cfg.display_date(d))
+                .unwrap_or_default(),
+            Column::Start => t
+                .start
+                .as_deref()
+                .map(|d| cfg.display_date(d))
+                .unwrap_or_default(),
+            Column::Created => cfg.display_date(&t.created),
+            Column::Updated => cfg.display_date(&t.updated),
+            Column::Estimate => t.estimate.map(format_duration).unwrap_or_default(),
+            Column::Actual if t.actual == 0 => String::new(),
+            Column::Actual => format!(
+                "{}{}",
+                format_duration(t.actual),
+                if t.timer_running() { " ⏱" } else { "" }
+            ),
+        }
+    }
+
+    /// The theme role of this column's cell for `t`, if it is styled
+    pub fn role(&self, t: &Task, today: Date) -> Option<Role> {
+        match self {
+            Column::Status => Some(Role::for_status(t.category)),
+            Column::Priority => Role::for_priority(&t.priority),
+            Column::Project => Some(Role::Project),
+            Column::Tags => Some(Role::Tag),
+            Column::Due if !t.is_closed() => {
+                let due = t.due.as_deref().and_then(date::parse_date)?;
+                if due < today {
+                    Some(Role::Overdue)
+                } else {
+                    (due == today).then_some(Role::DueToday)
+                }
+            }
+            Column::Title if t.blocked => Some(Role::Blocked),
+            Column::Actual if t.estimate.is_some_and(|e| t.actual > e) => Some(Role::Overdue),
+            Column::Start if t.is_deferred(today) => Some(Role::Hint),
+            Column::Start | Column::Estimate | Column::Actual => Some(Role::Muted),
+            Column::Id | Column::Created | Column::Updated => Some(Role::Muted),
+            _ => None,
+        }
+    }
+}
+
+/// Fit `s` into `width` cells: cut with `…` or pad with spaces
+pub fn fit(s: &str, width: usize) -> String {
+    let n = s.chars().count();
+    if n > width {
+        let mut out: String = s.chars().take(width.saturating_sub(1)).collect();
+        out.push('…');
+        out
+    } else {
+        format!("{s}{}", " ".repeat(width - n))
+    }
+}
+
+/// One plain-text row (`tm ls`): the cells of `columns`, fixed widths padded
+pub fn format_row(t: &Task, columns: &[ColumnSpec], cfg: &Config) -> String {
+    let cells: Vec<String> = columns
+        .iter()
+        .map(|c| {
+            let cell = c.column.cell(t, cfg);
+            match c.width {
+                Some(w) => fit(&cell, w as usize),
+                None => cell,
+            }
+        })
+        .collect();
+    cells.join("  ").trim_end().to_string()
+}
+
+/// `group = 'project' | 'status' | 'due' | 'tag'`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    None,
+    Project,
+    Status,
+    /// overdue, today, tomorrow, this week, later, no due date
+    Due,
+    /// a task shows under each of its tags
+    Tag,
+}
+
+impl GroupBy {
+    pub const ALL: [GroupBy; 5] = [
+        GroupBy::None,
+        GroupBy::Project,
+        GroupBy::Status,
+        GroupBy::Due,
+        GroupBy::Tag,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupBy::None => "none",
+            GroupBy::Project => "project",
+            GroupBy::Status => "status",
+            GroupBy::Due => "due",
+            GroupBy::Tag => "tag",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        GroupBy::ALL
+            .into_iter()
+            .find(|g| g.as_str() == s || (s == "tags" && *g == GroupBy::Tag))
+            .ok_or_else(|| anyhow!("unknown grouping '{s}' (none, project, status, due, tag)"))
+    }
+}
+
+/// A group header and the tasks under it (indexes into the task list, in list order)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Group {
+    /// stable id for folding: `project:work`, `due:today`, ...
+    pub key: String,
+    pub label: String,
+    pub tasks: Vec<usize>,
+}
+
+const DUE_BUCKETS: [&str; 6] = [
+    "overdue",
+    "today",
+    "tomorrow",
+    "this week",
+    "later",
+    "no due date",
+];
+
+fn due_bucket(t: &Task, today: Date) -> usize {
+    let Some(d) = t.due.as_deref().and_then(date::parse_date) else {
+        return 5;
+    };
+    match d - today {
+        n if n < Duration::ZERO => 0,
+        n if n == Duration::ZERO => 1,
+        n if n == Duration::days(1) => 2,
+        n if n <= Duration::days(7) => 3,
+        _ => 4,
+    }
+}
+
+/// Split `visible` (indexes into `tasks`) into groups, keeping the list order inside each.
+/// Groups come in a fixed order: statuses by workflow, due buckets by time, projects and
+/// tags by name (untagged last).
+pub fn group_tasks(tasks: &[Task], visible: &[usize], by: GroupBy, today: Date) -> Vec<Group> {
+    // (order, key, label) → tasks
+    let mut groups: BTreeMap<(u32, String), (String, Vec<usize>)> = BTreeMap::new();
+    for &i in visible {
+        let t = &tasks[i];
+        let keys: Vec<(u32, String, String)> = match by {
+            GroupBy::None => vec![(0, String::new(), "All".into())],
+            GroupBy::Project => vec![(0, t.project.clone(), t.project.clone())],
+            GroupBy::Status => vec![(status_rank(t) as u32, t.status.clone(), t.status.clone())],
+            GroupBy::Due => {
+                let b = due_bucket(t, today);
+                vec![(b as u32, DUE_BUCKETS[b].into(), DUE_BUCKETS[b].into())]
+            }
+            GroupBy::Tag if t.tags.is_empty() => vec![(1, String::new(), "untagged".into())],
+            GroupBy::Tag => t
+                .tags
+                .iter()
+                .map(|x| (0, x.to_lowercase(), format!("+{x}")))
+                .collect(),
+        };
+        for (order, key, label) in keys {
+            groups
+                .entry((order, key))
+                .or_insert_with(|| (label, vec![]))
+                .1
+                .push(i);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|((_, key), (label, tasks))| Group {
+            key: format!("{}:{key}", by.as_str()),
+            label,
+            tasks,
+        })
+        .collect()
+}
+
+/// How a view is written in config or views.json: a bare query or a table
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ViewSpec {
+    Query(String),
+    Full {
+        query: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        columns: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sort: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct View {
+    pub name: String,
+    pub query: String,
+    /// None: the configured `columns`
+    pub columns: Option<Vec<ColumnSpec>>,
+    /// `sort` in config syntax (`due, priority desc`); overrides the query's `sort:`
+    pub sort: Option<String>,
+    /// overrides the query's `group:` and the configured `group`
+    pub group: Option<GroupBy>,
+    /// from views.json (`:view.save`), not config
+    pub saved: bool,
+}
+
+impl View {
+    pub fn from_spec(name: &str, spec: &ViewSpec, saved: bool) -> Result<Self> {
+        let (query, columns, sort, group) = match spec {
+            ViewSpec::Query(q) => (q.clone(), None, None, None),
+            ViewSpec::Full {
+                query,
+                columns,
+                sort,
+                group,
+            } => (query.clone(), columns.clone(), sort.clone(), group.clone()),
+        };
+        Query::parse(&query).with_context(|| format!("view {name}"))?;
+        if let Some(s) = &sort {
+            parse_sort(s).with_context(|| format!("view {name}: sort"))?;
+        }
+        let columns = match columns {
+            Some(cols) => {
+                Some(ColumnSpec::parse_list(&cols).with_context(|| format!("view {name}"))?)
+            }
+            None => None,
+        };
+        let group = match group {
+            Some(g) => Some(GroupBy::parse(&g).with_context(|| format!("view {name}"))?),
+            None => None,
+        };
+        Ok(Self {
+            name: name.to_string(),
+            query,
+            columns,
+            sort,
+            group,
+            saved,
+        })
+    }
+
+    fn spec(&self) -> ViewSpec {
+        let columns = self
+            .columns
+            .as_ref()
+            .map(|cols| cols.iter().map(|c| c.to_config()).collect());
+        if columns.is_none() && self.sort.is_none() && self.group.is_none() {
+            return ViewSpec::Query(self.query.clone());
+        }
+        ViewSpec::Full {
+            query: self.query.clone(),
+            columns,
+            sort: self.sort.clone(),
+            group: self.group.map(|g| g.as_str().to_string()),
+        }
+    }
+
+    /// What `:view.save <name>` stores: the active view's query narrowed by the `/`
+    /// filter, with what is shown now (`layout`)
+    pub fn save_current(
+        name: &str,
+        active: Option<&View>,
+        filter: &str,
+        layout: &ListLayout,
+    ) -> View {
+        let query = [
+            active.map(|v| v.query.trim()).unwrap_or_default(),
+            filter.trim(),
+        ]
+        .into_iter()
+        .filter(|q| !q.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+        View {
+            name: name.to_string(),
+            query,
+            columns: Some(layout.columns.clone()),
+            sort: layout.sort.as_deref().map(format_sort),
+            group: Some(layout.group),
+            saved: true,
+        }
+    }
+
+    /// The query with the view's `sort` applied over the query's own `sort:`
+    pub fn parsed(&self) -> Result<Query> {
+        let mut q = Query::parse(&self.query)?;
+        if let Some(s) = &self.sort {
+            q.sort = Some(parse_sort(s)?);
+        }
+        Ok(q)
+    }
+}
+
+/// What the list shows, after config, view, query and the session's `:sort` / `:group`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListLayout {
+    pub columns: Vec<ColumnSpec>,
+    /// None: the configured `sort` (tasks come sorted from the vault)
+    pub sort: Option<Vec<SortSpec>>,
+    pub group: GroupBy,
+}
+
+impl ListLayout {
+    /// Later sources win: config, then the view, then the query (view query and `/` filter)
+    pub fn resolve(cfg: &Config, view: Option<&View>, query: &Query) -> Self {
+        let columns = view
+            .and_then(|v| v.columns.clone())
+            .unwrap_or_else(|| cfg.list_columns());
+        let group = query
+            .group
+            .or(view.and_then(|v| v.group))
+            .unwrap_or_else(|| cfg.list_group());
+        Self {
+            columns,
+            sort: query.sort.clone(),
+            group,
+        }
+    }
+}
+
+fn views_file(vault: &Vault) -> PathBuf {
+    vault.state_dir().join("views.json")
+}
+
+fn load_saved(vault: &Vault) -> Result<BTreeMap<String, ViewSpec>> {
+    let path = views_file(vault);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let s = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&s).with_context(|| format!("invalid {}", path.display()))
+}
+
+fn store_saved(vault: &Vault, views: &BTreeMap<String, ViewSpec>) -> Result<()> {
+    let path = views_file(vault);
+    fs::create_dir_all(vault.state_dir())?;
+    fs::write(&path, serde_json::to_string_pretty(views)? + "\n")
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+impl Vault {
+    /// Views from config, then the saved ones (a saved view can't shadow a config one)
+    pub fn views(&self) -> Result<Vec<View>> {
+        let mut out = Vec::new();
+        for (name, spec) in &self.cfg.views {
+            out.push(View::from_spec(name, spec, false)?);
+        }
+        for (name, spec) in load_saved(self)? {
+            if !self.cfg.views.contains_key(&name) {
+                out.push(View::from_spec(&name, &spec, true)?);
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn view(&self, name: &str) -> Result<View> {
+        let views = self.views()?;
+        match views.iter().find(|v| v.name == name) {
+            Some(v) => Ok(v.clone()),
+            None => {
+                let names: Vec<&str> = views.iter().map(|v| v.name.as_str()).collect();
+                bail!(
+                    "unknown view '{name}' (views: {})",
+                    if names.is_empty() {
+                        "none".into()
+                    } else {
+                        names.join(", ")
+                    }
+                )
+            }
+        }
+    }
+
+    /// Save (or replace) a view in views.json
+    pub fn save_view(&self, view: &View) -> Result<()> {
+        if view.name.is_empty() || view.name.contains(char::is_whitespace) {
+            bail!("view names are one word");
+        }
+        if self.cfg.views.contains_key(&view.name) {
+            bail!("view '{}' is defined in config", view.name);
+        }
+        let mut saved = load_saved(self)?;
+        saved.insert(view.name.clone(), view.spec());
+        store_saved(self, &saved)
+    }
+
+    pub fn delete_view(&self, name: &str) -> Result<()> {
+        if self.cfg.views.contains_key(name) {
+            bail!("view '{name}' is defined in config; remove it there");
+        }
+        let mut saved = load_saved(self)?;
+        if saved.remove(name).is_none() {
+            bail!("no saved view '{name}'");
+        }
+        store_saved(self, &saved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use super::*;
+    use crate::Workflow;
+
+    const TODAY: Date = date!(2026 - 10 - 14);
+
+    fn task(project: &str, status: &str, due: Option<&str>, tags: &[&str]) -> Task {
+        Task {
+            title: format!("{project} {status}"),
+            project: project.into(),
+            status: status.into(),
+            category: Workflow::default().category(status),
+            due: due.map(str::to_string),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Task::default()
+        }
+    }
+
+    fn tasks() -> Vec<Task> {
+        vec![
+            task("work", "done", Some("2026-10-13"), &["q4"]),
+            task("home", "todo", Some("2026-10-14"), &[]),
+            task("work", "todo", Some("2026-10-19"), &["q4", "Writing"]),
+            task("home", "doing", None, &["writing"]),
+            task("work", "doing", Some("2026-12-01"), &[]),
+        ]
+    }
+
+    fn groups(by: GroupBy, visible: &[usize]) -> Vec<(String, Vec<usize>)> {
+        group_tasks(&tasks(), visible, by, TODAY)
+            .into_iter()
+            .map(|g| (g.label, g.tasks))
+            .collect()
+    }
+
+    #[test]
+    fn groups_come_in_a_fixed_order_and_keep_list_order_inside() {
+        let all = [4, 3, 2, 1, 0];
+        let by_project = groups(GroupBy::Project, &all);
+        assert_eq!(
+            by_project,
+            [("home".into(), vec![3, 1]), ("work".into(), vec![4, 2, 0])]
+        );
+        let by_status: Vec<_> = groups(GroupBy::Status, &all)
+            .into_iter()
+            .map(|g| g.0)
+            .collect();
+        assert_eq!(by_status, ["todo", "doing", "done"]);
+        assert_eq!(groups(GroupBy::None, &[1, 0]), [("All".into(), vec![1, 0])]);
+        // only visible tasks are grouped
+        assert_eq!(groups(GroupBy::Project, &[0]), [("work".into(), vec![0])]);
+    }
+
+    #[test]
+    fn due_groups_bucket_by_distance_from_today() {
+        let by_due = groups(GroupBy::Due, &[0, 1, 2, 3, 4]);
+        assert_eq!(
+            by_due,
+            [
+                ("overdue".into(), vec![0]),
+                ("today".into(), vec![1]),
+                ("this week".into(), vec![2]),
+                ("later".into(), vec![4]),
+                ("no due date".into(), vec![3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn tag_groups_list_a_task_under_each_tag_and_untagged_last() {
+        let by_tag = group_tasks(&tasks(), &[0, 1, 2, 3], GroupBy::Tag, TODAY);
+        let keys: Vec<_> = by_tag
+            .iter()
+            .map(|g| (g.key.as_str(), g.tasks.clone()))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("tag:q4", vec![0, 2]),
+                ("tag:writing", vec![2, 3]),
+                ("tag:", vec![1]),
+            ]
+        );
+        assert_eq!(by_tag[1].label, "+Writing");
+        assert_eq!(by_tag[2].label, "untagged");
+    }
+
+    #[test]
+    fn columns_parse_with_optional_widths() {
+        let cols = ColumnSpec::parse_list(&["status".into(), "title:40".into()]).unwrap();
+        assert_eq!(cols[1].width, Some(40));
+        assert_eq!(cols[1].to_config(), "title:40");
+        assert!(ColumnSpec::parse("title:0").is_err());
+        assert!(ColumnSpec::parse("title:wide").is_err());
+        assert!(ColumnSpec::parse("size").is_err());
+        assert!(ColumnSpec::parse_list(&[]).is_err());
+        assert_eq!(fit("report", 4), "rep…");
+        assert_eq!(fit("re", 4), "re  ");
+    }
+
+    #[test]
+    fn the_query_beats_the_view_which_beats_config() {
+        let cfg = Config {
+            columns: vec!["title".into()],
+            group: "status".into(),
+            ..Config::defaults()
+        };
+        let view = View::from_spec(
+            "work",
+            &ViewSpec::Full {
+                query: "+work".into(),
+                columns: Some(vec!["due".into(), "title".into()]),
+                sort: Some("due, priority desc".into()),
+                group: Some("project".into()),
+            },
+            false,
+        )
+        .unwrap();
+        let plain = ListLayout::resolve(&cfg, None, &Query::default());
+        assert_eq!(plain.group, GroupBy::Status);
+        assert_eq!(plain.columns[0].column, Column::Title);
+        assert_eq!(plain.sort, None);
+
+        let q = view.parsed().unwrap();
+        let in_view = ListLayout::resolve(&cfg, Some(&view), &q);
+        assert_eq!(in_view.group, GroupBy::Project);
+        assert_eq!(in_view.columns[0].column, Column::Due);
+        assert_eq!(
+            format_sort(in_view.sort.as_deref().unwrap()),
+            "due, priority desc"
+        );
+
+        let q = q.and(Query::parse("group:tag sort:title").unwrap());
+        let narrowed = ListLayout::resolve(&cfg, Some(&view), &q);
+        assert_eq!(narrowed.group, GroupBy::Tag);
+        assert_eq!(format_sort(narrowed.sort.as_deref().unwrap()), "title");
+
+        // a saved view keeps what is shown, and a bare query stays a string
+        let saved = View::save_current("mine", Some(&view), "is:open", &narrowed);
+        assert_eq!(saved.query, "+work is:open");
+        assert_eq!(
+            saved.spec(),
+            View::from_spec("mine", &saved.spec(), true).unwrap().spec()
+        );
+        let bare = View::from_spec("t", &ViewSpec::Query("due:today".into()), false).unwrap();
+        assert_eq!(bare.spec(), ViewSpec::Query("due:today".into()));
+    }
+}
@@ -0,0 +1,126 @@
+//! File-change notifications: config.lua and tm-lock.json for hot reload, task files for
+//! the `todotxt_mirror` and `ics` feed
+
+use std::{
+    ffi::OsString,
+    path::Path,
+    sync::mpsc::{channel, Receiver},
+};
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Watches a directory for changes to the files it cares about.
+/// Editors often save by writing a temp file and renaming it over the original, so the
+/// directory is watched rather than the files themselves.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+    rx: Receiver<()>,
+}
+
+impl FileWatcher {
+    pub fn new(dir: &Path, names: &[&str]) -> Result<Self> {
+        let names: Vec<OsString> = names.iter().map(OsString::from).collect();
+        Self::watch(dir, RecursiveMode::NonRecursive, move |p| {
+            p.file_name().is_some_and(|n| names.iter().any(|w| w == n))
+        })
+    }
+
+    fn watch(
+        dir: &Path,
+        mode: RecursiveMode,
+        relevant: impl Fn(&Path) -> bool + Send + 'static,
+    ) -> Result<Self> {
+        let (tx, rx) = channel();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(ev) = res else { return };
+            if matches!(ev.kind, EventKind::Access(_)) {
+                return;
+            }
+            if ev.paths.iter().any(|p| relevant(p)) {
+                let _ = tx.send(());
+            }
+        })
+        .context("creating file watcher")?;
+        watcher
+            .watch(dir, mode)
+            .with_context(|| format!("watching {}", dir.display()))?;
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    /// Watch the config file and the plugin lockfile next to it
+    pub fn for_config(config_path: &Path) -> Result<Self> {
+        let dir = config_path.parent().context("config path has no parent")?;
+        let name = config_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .context("config path has no file name")?;
+        Self::new(dir, &[name, "tm-lock.json"])
+    }
+
+    /// Watch the vault's task files, whoever writes them (tm, another tm, an editor)
+    pub fn for_tasks(vault_path: &Path) -> Result<Self> {
+        Self::watch(&vault_path.join("tasks"), RecursiveMode::Recursive, |p| {
+            // a file written right after its month directory is created can go unseen
+            p.extension().is_some_and(|e| e == "md") || p.is_dir()
+        })
+    }
+
+    /// True if anything changed since the last call (drains pending events)
+    pub fn changed(&self) -> bool {
+        let mut any = false;
+        while self.rx.try_recv().is_ok() {
+            any = true;
+        }
+        any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    /// Events arrive on the watcher's thread; poll for a while before giving up
+    fn changed_within(w: &FileWatcher, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if w.changed() {
+                return true;
+            }
+            sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn only_watched_names_count_as_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let w = FileWatcher::for_config(&dir.path().join("config.lua")).unwrap();
+
+        fs::write(dir.path().join("notes.txt"), "x").unwrap();
+        assert!(!changed_within(&w, Duration::from_millis(300)));
+
+        fs::write(dir.path().join("config.lua"), "return {}").unwrap();
+        assert!(changed_within(&w, Duration::from_secs(5)));
+
+        // saved by writing a temp file and renaming it over the lockfile
+        fs::write(dir.path().join(".tm-lock.json.tmp"), "{}").unwrap();
+        sleep(Duration::from_millis(100));
+        w.changed();
+        fs::rename(
+            dir.path().join(".tm-lock.json.tmp"),
+            dir.path().join("tm-lock.json"),
+        )
+        .unwrap();
+        assert!(changed_within(&w, Duration::from_secs(5)));
+    }
+}
@@ -0,0 +1,376 @@
+//! Task statuses: named, each in a category (open, active or closed), with the order `x`
+//! and `:status next|prev` step through and optional allowed transitions. In config:
+//!
+//! ```lua
+//! workflow = {
+//!   statuses = { todo = 'open', blocked = 'open', doing = 'active', done = 'closed', cancelled = 'closed' },
+//!   order = { 'todo', 'doing', 'done' },
+//!   transitions = { blocked = { 'todo', 'cancelled' } },   -- unlisted statuses go anywhere
+//!   cancelled = 'cancelled',   -- closed, but dropped rather than done (for sync and export)
+//! }
+//! ```
+//!
+//! Statuses in task files that the workflow doesn't know are kept as they are and reported.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::Task;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    /// not started
+    Open,
+    /// in progress
+    Active,
+    /// finished: done, cancelled, ...
+    Closed,
+}
+
+impl Category {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Open => "open",
+            Category::Active => "active",
+            Category::Closed => "closed",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Workflow {
+    /// status name → category
+    pub statuses: BTreeMap<String, Category>,
+    /// `x` / `:status next|prev` cycle through these (empty: all, open → active → closed)
+    #[serde(default)]
+    pub order: Vec<String>,
+    /// status → the statuses it may move to (unlisted: any)
+    pub transitions: BTreeMap<String, Vec<String>>,
+    /// the closed status for dropped work (unset: a closed status other than the first)
+    #[serde(default)]
+    pub cancelled: Option<String>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        let statuses = [
+            ("todo", Category::Open),
+            ("doing", Category::Active),
+            ("done", Category::Closed),
+            ("cancelled", Category::Closed),
+        ];
+        Self {
+            statuses: statuses
+                .into_iter()
+                .map(|(s, c)| (s.to_string(), c))
+                .collect(),
+            order: ["todo", "doing", "done"].map(String::from).to_vec(),
+            transitions: BTreeMap::new(),
+            cancelled: Some("cancelled".into()),
+        }
+    }
+}
+
+impl Workflow {
+    pub fn validate(&self) -> Result<()> {
+        for name in self.statuses.keys() {
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ',') {
+                bail!("'{name}' is not a one-word status");
+            }
+        }
+        for c in [Category::Open, Category::Closed] {
+            if !self.statuses.values().any(|v| *v == c) {
+                bail!("needs at least one {} status", c.as_str());
+            }
+        }
+        for s in &self.order {
+            self.known(s).map_err(|e| anyhow!("order: {e}"))?;
+        }
+        for (from, to) in &self.transitions {
+            self.known(from).map_err(|e| anyhow!("transitions: {e}"))?;
+            for s in to {
+                self.known(s)
+                    .map_err(|e| anyhow!("transitions.{from}: {e}"))?;
+            }
+        }
+        if let Some(s) = &self.cancelled {
+            self.known(s).map_err(|e| anyhow!("cancelled: {e}"))?;
+            if self.category(s) != Some(Category::Closed) {
+                bail!("cancelled: '{s}' is not a closed status");
+            }
+        }
+        Ok(())
+    }
+
+    fn known(&self, s: &str) -> Result<()> {
+        if self.statuses.contains_key(s) {
+            Ok(())
+        } else {
+            bail!(
+                "unknown status '{s}' (statuses: {})",
+                self.names().join(", ")
+            )
+        }
+    }
+
+    /// The cycle of `x` / `:status next|prev`
+    pub fn order(&self) -> Vec<String> {
+        if !self.order.is_empty() {
+            return self.order.clone();
+        }
+        let mut all: Vec<(&Category, &String)> =
+            self.statuses.iter().map(|(s, c)| (c, s)).collect();
+        all.sort();
+        all.into_iter().map(|(_, s)| s.clone()).collect()
+    }
+
+    /// Status names: the order first, then the rest
+    pub fn names(&self) -> Vec<String> {
+        let mut out = self.order();
+        out.extend(
+            self.statuses
+                .keys()
+                .filter(|s| !out.contains(s))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        out
+    }
+
+    pub fn category(&self, status: &str) -> Option<Category> {
+        self.statuses.get(status).copied()
+    }
+
+    /// The status `s` names (`in-progress` is the first active one)
+    pub fn resolve(&self, s: &str) -> Result<String> {
+        let s = s.trim().to_lowercase();
+        if self.statuses.contains_key(&s) {
+            return Ok(s);
+        }
+        if matches!(s.as_str(), "in-progress" | "in_progress") {
+            return self.first(Category::Active).map(str::to_string);
+        }
+        self.known(&s)?;
+        Ok(s)
+    }
+
+    /// The first status of a category, in `order` if it has one (what `1`/`2`/`3` set)
+    pub fn first(&self, c: Category) -> Result<&str> {
+        let in_order = self
+            .order()
+            .into_iter()
+            .find(|s| self.category(s) == Some(c));
+        in_order
+            .and_then(|s| self.statuses.get_key_value(&s).map(|(k, _)| k))
+            .or_else(|| self.statuses.iter().find(|(_, v)| **v == c).map(|(k, _)| k))
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("the workflow has no {} status", c.as_str()))
+    }
+
+    /// The closed status for work dropped rather than done, if the workflow tells them apart
+    pub fn cancelled(&self) -> Option<&str> {
+        if let Some(s) = &self.cancelled {
+            return Some(s);
+        }
+        let done = self.first(Category::Closed).ok()?;
+        let names = self.names();
+        let other = names
+            .iter()
+            .find(|s| *s != done && self.category(s) == Some(Category::Closed))?;
+        self.statuses.get_key_value(other).map(|(k, _)| k.as_str())
+    }
+
+    pub fn is_cancelled(&self, status: &str) -> bool {
+        self.cancelled() == Some(status)
+    }
+
+    /// What a dropped task becomes: the cancelled status, else the first closed one
+    pub fn cancel_status(&self) -> Result<&str> {
+        match self.cancelled() {
+            Some(s) => Ok(s),
+            None => self.first(Category::Closed),
+        }
+    }
+
+    /// The status after (`dir` ≥ 0) or before `cur` in `order`, wrapping around; statuses
+    /// outside the order step to its first / last entry
+    pub fn step(&self, cur: &str, dir: i8) -> Result<String> {
+        self.known(cur)?;
+        let order = self.order();
+        let n = order.len();
+        let i = match (order.iter().position(|s| s == cur), dir >= 0) {
+            (Some(i), true) => (i + 1) % n,
+            (Some(i), false) => (i + n - 1) % n,
+            (None, true) => 0,
+            (None, false) => n - 1,
+        };
+        Ok(order[i].clone())
+    }
+
+    /// Whether `from` may move to `to` (leaving an unknown status is always allowed)
+    pub fn check_transition(&self, from: &str, to: &str) -> Result<()> {
+        self.known(to)?;
+        match self.transitions.get(from) {
+            Some(allowed) if from != to && !allowed.iter().any(|s| s == to) => {
+                bail!(
+                    "can't go from {from} to {to} (allowed: {})",
+                    allowed.join(", ")
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// `status (category), ...` for `tm config show`
+    pub fn summary(&self) -> String {
+        self.names()
+            .iter()
+            .map(|s| format!("{s} ({})", self.statuses[s].as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Statuses of `tasks` the workflow doesn't know
+pub fn unknown_statuses(tasks: &[Task]) -> Vec<String> {
+    let set: BTreeSet<&str> = tasks
+        .iter()
+        .filter(|t| t.category.is_none())
+        .map(|t| t.status.as_str())
+        .collect();
+    set.into_iter().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workflow(statuses: &[(&str, Category)], cancelled: Option<&str>) -> Workflow {
+        Workflow {
+            statuses: statuses.iter().map(|(s, c)| (s.to_string(), *c)).collect(),
+            order: vec![],
+            transitions: BTreeMap::new(),
+            cancelled: cancelled.map(String::from),
+        }
+    }
+
+    #[test]
+    fn the_cancelled_status_comes_from_the_workflow() {
+        use Category::*;
+        assert_eq!(Workflow::default().cancel_status().unwrap(), "cancelled");
+        let wf = workflow(
+            &[("todo", Open), ("wontfix", Closed), ("done", Closed)],
+            Some("wontfix"),
+        );
+        assert!(wf.is_cancelled("wontfix") && !wf.is_cancelled("done"));
+        // unset: the closed status that isn't the first
+        let wf = workflow(
+            &[("todo", Open), ("done", Closed), ("wontdo", Closed)],
+            None,
+        );
+        assert_eq!(
+            (wf.first(Closed).unwrap(), wf.cancelled()),
+            ("done", Some("wontdo"))
+        );
+        let wf = workflow(&[("todo", Open), ("done", Closed)], None);
+        assert_eq!(
+            (wf.cancelled(), wf.cancel_status().unwrap()),
+            (None, "done")
+        );
+        assert!(workflow(&[("todo", Open), ("done", Closed)], Some("todo"))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn stepping_follows_the_order_and_wraps() {
+        use Category::*;
+        let wf = Workflow::default();
+        assert_eq!(wf.step("todo", 1).unwrap(), "doing");
+        assert_eq!(wf.step("done", 1).unwrap(), "todo");
+        assert_eq!(wf.step("todo", -1).unwrap(), "done");
+        // outside the order: onto its ends
+        assert_eq!(wf.step("cancelled", 1).unwrap(), "todo");
+        assert_eq!(wf.step("cancelled", -1).unwrap(), "done");
+        assert!(wf.step("blocked", 1).is_err());
+        // no order: open, active, closed, by name within a category
+        let wf = workflow(
+            &[
+                ("todo", Open),
+                ("review", Active),
+                ("done", Closed),
+                ("doing", Active),
+            ],
+            None,
+        );
+        assert_eq!(wf.order(), ["todo", "doing", "review", "done"]);
+        assert_eq!(wf.step("doing", 1).unwrap(), "review");
+    }
+
+    #[test]
+    fn transitions_limit_only_the_statuses_they_list() {
+        use Category::*;
+        let mut wf = workflow(
+            &[
+                ("todo", Open),
+                ("blocked", Open),
+                ("done", Closed),
+                ("cancelled", Closed),
+            ],
+            None,
+        );
+        wf.transitions
+            .insert("blocked".into(), vec!["todo".into(), "cancelled".into()]);
+        assert!(wf.validate().is_ok());
+        assert!(wf.check_transition("blocked", "todo").is_ok());
+        assert!(wf.check_transition("blocked", "blocked").is_ok());
+        let err = wf.check_transition("blocked", "done").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "can't go from blocked to done (allowed: todo, cancelled)"
+        );
+        assert!(wf.check_transition("todo", "blocked").is_ok());
+        // leaving a status the workflow doesn't know is fine, entering one isn't
+        assert!(wf.check_transition("waiting", "todo").is_ok());
+        assert!(wf.check_transition("todo", "waiting").is_err());
+
+        wf.transitions.insert("todo".into(), vec!["waiting".into()]);
+        assert!(wf.validate().is_err());
+    }
+
+    #[test]
+    fn names_resolve_case_insensitively_with_an_in_progress_alias() {
+        use Category::*;
+        let wf = Workflow::default();
+        assert_eq!(wf.resolve(" Done ").unwrap(), "done");
+        assert_eq!(wf.resolve("in-progress").unwrap(), "doing");
+        let err = wf.resolve("waiting").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown status 'waiting' (statuses: todo, doing, done, cancelled)"
+        );
+        assert_eq!(wf.first(Closed).unwrap(), "done");
+        assert!(workflow(&[("todo", Open)], None).validate().is_err());
+        assert!(workflow(&[("to do", Open), ("done", Closed)], None)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn statuses_outside_the_workflow_are_reported_once() {
+        let wf = Workflow::default();
+        let tasks: Vec<Task> = ["todo", "waiting", "later", "waiting", "done"]
+            .into_iter()
+            .map(|s| Task {
+                status: s.into(),
+                category: wf.category(s),
+                ..Task::default()
+            })
+            .collect();
+        assert_eq!(unknown_statuses(&tasks), ["later", "waiting"]);
+    }
+}
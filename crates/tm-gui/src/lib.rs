@@ -1,26 +1,77 @@
 use anyhow::Result;
 use eframe::{
-    egui::{self, Event, Key, Modifiers, RichText, ScrollArea},
+    egui::{self, text::LayoutJob, Align, Event, Key, Modifiers, RichText, ScrollArea},
     NativeOptions,
 };
+use egui_extras::{Column as TableColumn, TableBuilder};
 use slug::slugify;
-use tm_core::{load_keymap_from_user, Action, Keymap, Status, Vault};
+use std::collections::HashSet;
+use std::time::Instant;
+use tm_core::{
+    apply_sort_arg, builtin_commands, cycle_sort, date, ex::tokenize, format_duration, format_sort,
+    group_tasks, motion_target, parse_ex, parse_sort, preview, run_ex, sort::sort_tasks,
+    toggle_sort, Action, Category, CommandDef, Dispatch, ExEffect, FileWatcher, GroupBy,
+    KeyDispatcher, KeyOutcome, Keymap, ListLayout, MirrorTrigger, Mode, Palette, PaletteMatch,
+    PaletteTarget, PanePosition, Query, QuickAction, QuickActions, QuickContext, QuickRun, Role,
+    SortSpec, Task, Theme, Vault, View,
+};
+use tm_plugin_host::PluginHost;
+
+mod theme;
 
 pub fn run_gui(vault: Vault) -> Result<()> {
     let native_options = NativeOptions::default();
     eframe::run_native(
         "tm — GUI",
         native_options,
-        Box::new(move |_cc| {
+        Box::new(move |cc| {
+            let keymap = Keymap::from_config(&vault.cfg);
+            let theme = vault.cfg.theme();
+            cc.egui_ctx.set_visuals(theme::visuals(&theme));
+            let (plugins, plugin_errors) = PluginHost::load(&vault.cfg);
+            let watcher = FileWatcher::for_config(&vault.cfg.config_path).ok();
+            let mirror = MirrorTrigger::new(&vault.cfg.vault_path);
+            let mut status = (!plugin_errors.is_empty())
+                .then(|| (true, format!("plugin errors: {}", plugin_errors.join("; "))));
+            let layout = ListLayout::resolve(&vault.cfg, None, &Query::default());
+            let views = vault.views().unwrap_or_else(|e| {
+                status = Some((true, format!("{e:#}")));
+                vec![]
+            });
+            let mut quick = QuickActions::builtin();
+            plugins.extend_quick_actions(&mut quick);
             Box::new(App {
                 vault,
                 selected: 0,
                 filter: String::new(),
-                last_key_g: false,
+                views,
+                cur_view: None,
+                layout,
+                sort_override: None,
+                group_override: None,
+                collapsed: HashSet::new(),
+                check_focus: false,
+                check_idx: 0,
+                check_task: None,
+                keys: KeyDispatcher::new(),
+                scroll_to: None,
                 project_filter: None,
                 new_project_title: String::new(),
                 focus_new_project: false,
-                keymap: load_keymap_from_user(),
+                picking_project: false,
+                editing: None,
+                command: String::new(),
+                focus_command: false,
+                palette: None,
+                quick,
+                quick_open: false,
+                queued: vec![],
+                keymap,
+                theme,
+                plugins,
+                watcher,
+                status,
+                mirror,
             })
         }),
     )
@@ -32,138 +83,1135 @@ struct App {
     vault: Vault,
     selected: usize,
     filter: String,
-    last_key_g: bool, // for 'gg'
+    /// named views shown as tabs (gt/gT); None is "All"
+    views: Vec<View>,
+    cur_view: Option<String>,
+    /// columns, sort and grouping in effect (config ← view ← query ← overrides below)
+    layout: ListLayout,
+    /// header clicks, `:sort`, `o`, `~` and `:group`; cleared when the view changes
+    sort_override: Option<Vec<SortSpec>>,
+    group_override: Option<GroupBy>,
+    /// folded group keys
+    collapsed: HashSet<String>,
+    /// the selected task's checklist: Tab focuses it (j/k move the cursor), Space ticks;
+    /// both reset when another task is selected
+    check_focus: bool,
+    check_idx: usize,
+    check_task: Option<String>,
+    /// multi-key sequences and counts, same state machine as the TUI
+    keys: KeyDispatcher,
+    /// zt/zz/zb: scroll the selected row to this edge on the next frame
+    scroll_to: Option<Align>,
     project_filter: Option<String>,
     new_project_title: String,
     focus_new_project: bool,
+    /// project picker window (pick_project, `O`)
+    picking_project: bool,
+    /// inline edit in the detail pane (edit_due / edit_title / edit_tags) and its text
+    editing: Option<(Action, String)>,
+    /// ex command field (enter_command, `:`)
+    command: String,
+    focus_command: bool,
+    /// `<leader>p` window: entries, query, highlighted row
+    palette: Option<(Palette, String, usize)>,
+    /// quick actions (built-ins + plugins) for the `<leader>a` window and row context menus
+    quick: QuickActions,
+    quick_open: bool,
+    /// actions chosen from menus, run with the next frame's key actions
+    queued: Vec<Action>,
     keymap: Keymap,
+    /// `theme` from config; visuals are set when it changes
+    theme: Theme,
+    plugins: PluginHost,
+    watcher: Option<FileWatcher>,
+    /// last reload/plugin message; (is_error, text)
+    status: Option<(bool, String)>,
+    /// when to sync `todotxt_mirror` and rewrite the `ics` feed
+    mirror: MirrorTrigger,
+}
+
+impl App {
+    /// Sync `todotxt_mirror` and rewrite the `ics` feed when task files change, and read
+    /// back the todo.txt when it was edited outside tm; only edits that came from the
+    /// todo.txt (and errors) are reported
+    fn poll_mirror(&mut self) {
+        let cfg = &self.vault.cfg;
+        if cfg.todotxt_mirror.is_none() && cfg.ics.is_none() {
+            return;
+        }
+        let (tasks_changed, edited) = self.mirror.poll(&self.vault, cfg.todotxt_mirror.as_deref());
+        if !(tasks_changed || edited) {
+            return;
+        }
+        if let Some(path) = self.vault.cfg.todotxt_mirror.clone() {
+            match self.vault.sync_todotxt(&path) {
+                Ok(r) if r.changed_vault() => self.status = Some((false, r.summary())),
+                Ok(_) => {}
+                Err(e) => self.status = Some((true, format!("todo.txt mirror: {e:#}"))),
+            }
+        }
+        if let Some(feed) = self.vault.cfg.ics.as_ref().filter(|_| tasks_changed) {
+            if let Err(e) = self.vault.write_ics_feed(feed) {
+                self.status = Some((true, format!("ics feed: {e:#}")));
+            }
+        }
+    }
+
+    /// Re-read config.lua and swap in the new vault, keymap and plugin set.
+    /// If the new config does not validate, the running one stays in place.
+    fn reload_config(&mut self) {
+        let cfg = match self.vault.cfg.reload() {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                self.status = Some((true, format!("config not reloaded: {e:#}")));
+                return;
+            }
+        };
+        if cfg.vault_path != self.vault.cfg.vault_path {
+            self.selected = 0;
+            self.project_filter = None;
+        }
+        self.keymap = Keymap::from_config(&cfg);
+        self.theme = cfg.theme();
+        let errors = self.plugins.reload(&cfg);
+        self.quick = QuickActions::builtin();
+        self.plugins.extend_quick_actions(&mut self.quick);
+        self.vault.cfg = cfg;
+        self.mirror = MirrorTrigger::new(&self.vault.cfg.vault_path);
+        self.status = Some(if errors.is_empty() {
+            (
+                false,
+                format!("config reloaded ({} plugins)", self.plugins.plugins().len()),
+            )
+        } else {
+            (
+                true,
+                format!("config reloaded; plugin errors: {}", errors.join("; ")),
+            )
+        });
+        self.load_views();
+    }
+
+    /// Re-read the views; a view that no longer exists falls back to "All"
+    fn load_views(&mut self) {
+        match self.vault.views() {
+            Ok(v) => self.views = v,
+            Err(e) => self.status = Some((true, format!("{e:#}"))),
+        }
+        if self.active_view().is_none() {
+            self.cur_view = None;
+        }
+    }
+
+    fn active_view(&self) -> Option<&View> {
+        let name = self.cur_view.as_ref()?;
+        self.views.iter().find(|v| v.name == *name)
+    }
+
+    fn open_view(&mut self, name: Option<String>) {
+        self.cur_view = name;
+        self.filter.clear();
+        self.sort_override = None;
+        self.group_override = None;
+        self.selected = 0;
+    }
+
+    /// The order in effect (the configured `sort` when nothing overrides it)
+    fn current_sort(&self) -> Vec<SortSpec> {
+        self.layout
+            .sort
+            .clone()
+            .unwrap_or_else(|| parse_sort(&self.vault.cfg.sort).unwrap_or_default())
+    }
+
+    /// next_view / prev_view (`gt` / `gT`), "All" included
+    fn cycle_view(&mut self, forward: bool) {
+        let n = self.views.len() + 1;
+        let cur = self
+            .cur_view
+            .as_ref()
+            .and_then(|name| self.views.iter().position(|v| v.name == *name))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let next = if forward {
+            (cur + 1) % n
+        } else {
+            (cur + n - 1) % n
+        };
+        self.open_view(next.checked_sub(1).map(|i| self.views[i].name.clone()));
+    }
+
+    /// The active view's query narrowed by the filter field
+    fn query(&self) -> Result<Query, String> {
+        let view = match self.active_view() {
+            Some(v) => v.parsed().map_err(|e| format!("view {}: {e:#}", v.name))?,
+            None => Query::default(),
+        };
+        let filter = Query::parse(&self.filter).map_err(|e| format!("{e:#}"))?;
+        Ok(view.and(filter))
+    }
+
+    /// Inline field opened by edit_due / edit_title / edit_tags (Enter saves, Esc cancels)
+    fn edit_ui(&mut self, ui: &mut egui::Ui, t: &tm_core::Task) {
+        let Some((action, text)) = &mut self.editing else {
+            return;
+        };
+        let label = match action {
+            Action::EditDue => "due:",
+            Action::EditTitle => "title:",
+            Action::Snooze => "snooze until (1d, mon, next week, none):",
+            _ => "tags:",
+        };
+        let mut save = false;
+        ui.horizontal(|ui| {
+            ui.label(label);
+            let resp = ui.add(egui::TextEdit::singleline(text).id_source("detail_edit"));
+            if !resp.has_focus() && !resp.lost_focus() {
+                resp.request_focus();
+            }
+            save = resp.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+        });
+        if ui.input(|i| i.key_pressed(Key::Escape)) {
+            self.editing = None;
+            return;
+        }
+        if save {
+            let res = match action {
+                Action::EditDue => self.vault.set_due(&t.id, text).map(|_| "saved".to_string()),
+                Action::EditTitle => self
+                    .vault
+                    .rename_title(&t.id, text)
+                    .map(|_| "saved".to_string()),
+                Action::Snooze => self.vault.snooze(&t.id, text).map(|d| match d {
+                    Some(d) => format!("snoozed until {}", self.vault.cfg.display_date(&d)),
+                    None => "no longer deferred".to_string(),
+                }),
+                _ => self
+                    .vault
+                    .set_tags_csv(&t.id, text)
+                    .map(|_| "saved".to_string()),
+            };
+            self.status = Some(match res {
+                Ok(msg) => (false, msg),
+                Err(e) => (true, e.to_string()),
+            });
+            self.editing = None;
+        }
+        ui.add_space(8.0);
+    }
+
+    /// Built-in ex commands plus those registered by plugins
+    fn command_defs(&self) -> Vec<CommandDef> {
+        let mut defs = builtin_commands();
+        defs.extend(self.plugins.commands());
+        defs
+    }
+
+    /// Run an ex line (without `:`): plugin commands first, then the built-ins
+    fn run_command(&mut self, line: &str, selected: Option<&str>) {
+        let mut args = tokenize(line);
+        if args.is_empty() {
+            return;
+        }
+        let name = args.remove(0);
+        self.status = Some(match self.plugins.run_command(&name, &args) {
+            Ok(Some(msg)) => (
+                false,
+                if msg.is_empty() {
+                    format!(":{name} done")
+                } else {
+                    msg
+                },
+            ),
+            Err(e) => (true, format!("{e:#}")),
+            Ok(None) => match parse_ex(line).and_then(|cmd| run_ex(&self.vault, cmd, selected)) {
+                Ok(ExEffect::Done(msg)) => (false, msg),
+                Ok(ExEffect::Warning(msg)) => (true, msg),
+                Ok(ExEffect::OpenProject(key)) => {
+                    self.project_filter = key;
+                    self.selected = 0;
+                    return;
+                }
+                Ok(ExEffect::ProjectCreated(key)) => {
+                    self.project_filter = Some(key.clone());
+                    (false, format!("created project {key}"))
+                }
+                Ok(ExEffect::Sort(None)) => {
+                    self.sort_override = None;
+                    (false, "sort reset".into())
+                }
+                Ok(ExEffect::Sort(Some(arg))) => match apply_sort_arg(&self.current_sort(), &arg) {
+                    Ok(spec) => {
+                        let msg = format!("sort: {}", format_sort(&spec));
+                        self.sort_override = Some(spec);
+                        (false, msg)
+                    }
+                    Err(e) => (true, format!("{e:#}")),
+                },
+                Ok(ExEffect::Group(by)) => {
+                    self.group_override = by;
+                    (
+                        false,
+                        format!("group: {}", by.unwrap_or(self.layout.group).as_str()),
+                    )
+                }
+                Ok(ExEffect::OpenView(name)) => {
+                    self.load_views();
+                    self.open_view(name);
+                    return;
+                }
+                Ok(ExEffect::SaveView(name)) => {
+                    let view =
+                        View::save_current(&name, self.active_view(), &self.filter, &self.layout);
+                    match self.vault.save_view(&view) {
+                        Ok(()) => {
+                            self.load_views();
+                            self.open_view(Some(name.clone()));
+                            (false, format!("saved view {name}"))
+                        }
+                        Err(e) => (true, format!("{e:#}")),
+                    }
+                }
+                Ok(ExEffect::ViewsChanged(msg)) => {
+                    self.load_views();
+                    (false, msg)
+                }
+                Ok(ExEffect::ReloadConfig) => {
+                    self.reload_config();
+                    return;
+                }
+                Err(e) => (true, e.to_string()),
+            },
+        });
+    }
+
+    /// `<leader>p`: collect entries from every source (plugins included)
+    fn open_palette(&mut self, tasks: &[Task]) {
+        let mut palette = Palette::new(
+            tasks,
+            &self.project_keys(),
+            &self.command_defs(),
+            &self.views,
+            &[],
+        );
+        let ctx = QuickContext {
+            task: tasks.get(self.selected),
+            project: self.project_filter.as_deref(),
+        };
+        palette.add_quick_actions(&self.quick.applicable(&ctx));
+        let errors = self.plugins.extend_palette(&mut palette);
+        if !errors.is_empty() {
+            self.status = Some((true, errors.join("; ")));
+        }
+        self.palette = Some((palette, String::new(), 0));
+    }
+
+    /// Jump to / run a palette entry
+    fn choose_palette(&mut self, target: PaletteTarget, tasks: &[Task]) {
+        match target {
+            PaletteTarget::Task(id) => {
+                self.project_filter = None;
+                self.filter.clear();
+                if let Some(i) = tasks.iter().position(|t| t.id == id) {
+                    self.selected = i;
+                    self.scroll_to = Some(Align::Center);
+                }
+            }
+            PaletteTarget::Project(k) => {
+                self.project_filter = Some(k);
+                self.selected = 0;
+            }
+            PaletteTarget::Tag(tag) => {
+                self.project_filter = None;
+                self.filter = format!("+{tag}");
+            }
+            PaletteTarget::Filter(q) => self.filter = q,
+            PaletteTarget::View(name) => self.open_view(Some(name)),
+            PaletteTarget::Command(name) => {
+                let needs_args = self
+                    .command_defs()
+                    .iter()
+                    .find(|c| c.name == name)
+                    .map(|c| c.params().iter().any(|p| !p.optional))
+                    .unwrap_or(false);
+                if needs_args {
+                    self.command = format!("{name} ");
+                    self.focus_command = true;
+                } else {
+                    let selected = tasks.get(self.selected).map(|t| t.id.clone());
+                    self.run_command(&name, selected.as_deref());
+                }
+            }
+            PaletteTarget::Run(line) => {
+                let selected = tasks.get(self.selected).map(|t| t.id.clone());
+                self.run_command(&line, selected.as_deref());
+            }
+            PaletteTarget::Quick(id) => {
+                if let Some(qa) = self.quick.get(&id).cloned() {
+                    self.run_quick(&qa, tasks);
+                }
+            }
+        }
+    }
+
+    /// Run a quick action for the selected task / current project
+    fn run_quick(&mut self, qa: &QuickAction, tasks: &[Task]) {
+        let task = tasks.get(self.selected);
+        let ctx = QuickContext {
+            task,
+            project: self.project_filter.as_deref(),
+        };
+        match qa.resolve(&ctx) {
+            QuickRun::Action(a) => self.queued.push(a),
+            QuickRun::Ex(line) => {
+                let selected = task.map(|t| t.id.clone());
+                self.run_command(&line, selected.as_deref());
+            }
+            QuickRun::Prompt(text) => {
+                self.command = text;
+                self.focus_command = true;
+            }
+        }
+    }
+
+    /// Entries of a quick actions menu; returns the chosen one
+    fn quick_menu_ui(&self, ui: &mut egui::Ui, task: Option<&Task>) -> Option<QuickAction> {
+        let ctx = QuickContext {
+            task,
+            project: self.project_filter.as_deref(),
+        };
+        let mut chosen = None;
+        for qa in self.quick.applicable(&ctx) {
+            if ui.button(&qa.label).clicked() {
+                chosen = Some(qa.clone());
+                ui.close_menu();
+            }
+        }
+        chosen
+    }
+
+    /// Palette window: query, ranked entries with matched chars highlighted, preview
+    fn palette_ui(&mut self, ctx: &egui::Context, tasks: &[Task]) {
+        let Some((palette, query, idx)) = &mut self.palette else {
+            return;
+        };
+        let (up, down, enter, esc) = ctx.input(|i| {
+            (
+                i.key_pressed(Key::ArrowUp),
+                i.key_pressed(Key::ArrowDown),
+                i.key_pressed(Key::Enter),
+                i.key_pressed(Key::Escape),
+            )
+        });
+        let matches: Vec<PaletteMatch> = palette.filter(query);
+        if down && *idx + 1 < matches.len() {
+            *idx += 1;
+        }
+        if up {
+            *idx = idx.saturating_sub(1);
+        }
+        let mut chosen = None;
+        let mut open = true;
+        egui::Window::new("Palette")
+            .open(&mut open)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+            .default_width(720.0)
+            .show(ctx, |ui| {
+                let resp = ui.add(
+                    egui::TextEdit::singleline(query)
+                        .id_source("palette_query")
+                        .hint_text("fuzzy · >commands #tags @projects")
+                        .desired_width(f32::INFINITY),
+                );
+                resp.request_focus();
+                if resp.changed() {
+                    *idx = 0;
+                }
+                ui.columns(2, |cols| {
+                    ScrollArea::vertical()
+                        .id_source("palette_list")
+                        .max_height(360.0)
+                        .show(&mut cols[0], |ui| {
+                            for (row, m) in matches.iter().enumerate() {
+                                let it = &palette.items[m.index];
+                                let mut job = LayoutJob::default();
+                                let (plain, hl) = (
+                                    theme::format(&self.theme, Role::Text, ui),
+                                    theme::format(&self.theme, Role::Match, ui),
+                                );
+                                for (i, c) in it.label.chars().enumerate() {
+                                    let fmt = if m.positions.contains(&i) {
+                                        hl.clone()
+                                    } else {
+                                        plain.clone()
+                                    };
+                                    job.append(&c.to_string(), 0.0, fmt);
+                                }
+                                job.append(
+                                    &format!("  {}", it.detail),
+                                    0.0,
+                                    theme::format(&self.theme, Role::Muted, ui),
+                                );
+                                let r = ui.selectable_label(row == *idx, job);
+                                if row == *idx && (up || down) {
+                                    r.scroll_to_me(None);
+                                }
+                                if r.clicked() {
+                                    chosen = Some(it.target.clone());
+                                }
+                            }
+                        });
+                    if let Some(m) = matches.get(*idx) {
+                        cols[1].monospace(preview(&palette.items[m.index], tasks, &self.vault));
+                    }
+                });
+            });
+        if enter {
+            chosen = matches
+                .get(*idx)
+                .map(|m| palette.items[m.index].target.clone());
+        }
+        if !open || esc || chosen.is_some() {
+            self.palette = None;
+        }
+        if let Some(target) = chosen {
+            self.choose_palette(target, tasks);
+        }
+    }
+
+    fn project_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .vault
+            .list_projects()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.key)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// next_project / prev_project (`]` / `[`)
+    fn cycle_project(&mut self, forward: bool) {
+        let keys = self.project_keys();
+        if keys.is_empty() {
+            self.project_filter = None;
+            return;
+        }
+        let n = keys.len();
+        let idx = self
+            .project_filter
+            .as_ref()
+            .and_then(|k| keys.iter().position(|p| p == k))
+            .map(|i| {
+                if forward {
+                    (i + 1) % n
+                } else {
+                    (i + n - 1) % n
+                }
+            })
+            .unwrap_or(0);
+        self.project_filter = Some(keys[idx].clone());
+        self.selected = 0;
+    }
+
+    fn list_ui(&mut self, ui: &mut egui::Ui, tasks: &[tm_core::Task], query: &Query) {
+        ui.heading("Tasks");
+        // hand-typed statuses are shown as they are, and flagged
+        let unknown = tm_core::unknown_statuses(tasks);
+        if !unknown.is_empty() {
+            ui.label(theme::rich(
+                &self.theme,
+                Role::Error,
+                format!("unknown status: {} (is:unknown)", unknown.join(", ")),
+            ));
+        }
+        ui.separator();
+        let today = date::today();
+        // project filter + view / filter query
+        let visible: Vec<usize> = tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| {
+                self.project_filter
+                    .as_ref()
+                    .map(|pk| t.project == *pk)
+                    .unwrap_or(true)
+            })
+            .filter(|(_, t)| query.matches(t, today))
+            .map(|(i, _)| i)
+            .collect();
+        // (group header: key, label, count, collapsed) or a task
+        let rows: Vec<Result<usize, (String, String, usize, bool)>> = match self.layout.group {
+            GroupBy::None => visible.into_iter().map(Ok).collect(),
+            by => group_tasks(tasks, &visible, by, today)
+                .into_iter()
+                .flat_map(|g| {
+                    let collapsed = self.collapsed.contains(&g.key);
+                    let header = Err((g.key, g.label, g.tasks.len(), collapsed));
+                    let items = if collapsed { vec![] } else { g.tasks };
+                    std::iter::once(header).chain(items.into_iter().map(Ok))
+                })
+                .collect(),
+        };
+
+        let columns = self.layout.columns.clone();
+        let sort = self.current_sort();
+        let char_w = ui.fonts(|f| f.glyph_width(&egui::TextStyle::Body.resolve(ui.style()), '0'));
+        let label_col = columns
+            .iter()
+            .position(|c| c.column == tm_core::Column::Title)
+            .unwrap_or(0);
+        let mut table = TableBuilder::new(ui)
+            .striped(true)
+            .cell_layout(egui::Layout::left_to_right(Align::Center));
+        for c in &columns {
+            table = table.column(match (c.width, c.column) {
+                (Some(w), _) => TableColumn::exact(w as f32 * char_w),
+                (None, tm_core::Column::Title) => TableColumn::remainder().at_least(120.0),
+                (None, _) => TableColumn::auto().resizable(true),
+            });
+        }
+        if let Some(align) = self.scroll_to.take() {
+            if let Some(row) = rows.iter().position(|r| *r == Ok(self.selected)) {
+                table = table.scroll_to_row(row, Some(align));
+            }
+        }
+
+        let mut chosen = None;
+        let mut clicked_sort = None;
+        let mut toggled = None;
+        table
+            .header(22.0, |mut header| {
+                for c in &columns {
+                    header.col(|ui| {
+                        let pos = c
+                            .column
+                            .sort_key()
+                            .and_then(|k| sort.iter().position(|s| s.key == k));
+                        let label = match pos {
+                            Some(i) => format!(
+                                "{} {}",
+                                c.column.title(),
+                                if sort[i].desc { "▼" } else { "▲" }
+                            ),
+                            None => c.column.title().to_string(),
+                        };
+                        let resp = ui.add(
+                            egui::Button::new(theme::rich(&self.theme, Role::Header, label))
+                                .frame(false),
+                        );
+                        if let Some(key) = c.column.sort_key() {
+                            // click sorts by the column (again: reverse), shift-click adds it
+                            if resp
+                                .on_hover_text("click: sort · shift-click: add to sort")
+                                .clicked()
+                            {
+                                clicked_sort = Some((key, ui.input(|i| i.modifiers.shift)));
+                            }
+                        }
+                    });
+                }
+            })
+            .body(|mut body| {
+                for r in &rows {
+                    body.row(20.0, |mut row| match r {
+                        Err((key, label, count, collapsed)) => {
+                            for ci in 0..columns.len() {
+                                row.col(|ui| {
+                                    let marker = if *collapsed { "▸" } else { "▾" };
+                                    let text = match (ci == 0, ci == label_col) {
+                                        (true, true) => format!("{marker} {label} ({count})"),
+                                        (true, false) => marker.to_string(),
+                                        (false, true) => format!("{label} ({count})"),
+                                        (false, false) => return,
+                                    };
+                                    let text = theme::rich(&self.theme, Role::Group, text);
+                                    if ui
+                                        .add(egui::Label::new(text).sense(egui::Sense::click()))
+                                        .clicked()
+                                    {
+                                        toggled = Some(key.clone());
+                                    }
+                                });
+                            }
+                        }
+                        Ok(i) => {
+                            let t = &tasks[*i];
+                            let selected = *i == self.selected;
+                            for c in &columns {
+                                row.col(|ui| {
+                                    // done tasks fade as a whole
+                                    let role = match c.column.role(t, today) {
+                                        _ if t.is_closed() => Some(Role::StatusDone),
+                                        r => r,
+                                    };
+                                    let text = c.column.cell(t, &self.vault.cfg);
+                                    let text = match role {
+                                        Some(r) => theme::rich(&self.theme, r, text),
+                                        None => RichText::new(text),
+                                    };
+                                    let resp = ui.selectable_label(selected, text);
+                                    if resp.clicked() || resp.secondary_clicked() {
+                                        self.selected = *i;
+                                    }
+                                    // right click: the same quick actions as <leader>a
+                                    resp.context_menu(|ui| {
+                                        if let Some(qa) = self.quick_menu_ui(ui, Some(t)) {
+                                            chosen = Some(qa);
+                                        }
+                                    });
+                                });
+                            }
+                        }
+                    });
+                }
+            });
+        if let Some((key, add)) = clicked_sort {
+            let mut spec = sort;
+            toggle_sort(&mut spec, key, add);
+            self.sort_override = Some(spec);
+        }
+        if let Some(key) = toggled {
+            if !self.collapsed.remove(&key) {
+                self.collapsed.insert(key);
+            }
+        }
+        if let Some(qa) = chosen {
+            self.run_quick(&qa, tasks);
+        }
+    }
+
+    /// Tick or untick checklist item `idx` of a task and say which
+    fn toggle_check(&mut self, id: &str, idx: usize) {
+        self.status = Some(match self.vault.toggle_check(id, idx) {
+            Ok(item) => (
+                false,
+                format!("{} {}", if item.done { "✔" } else { "☐" }, item.text),
+            ),
+            Err(e) => (true, e.to_string()),
+        });
+        self.check_idx = idx;
+    }
+
+    fn detail_ui(&mut self, ui: &mut egui::Ui, tasks: &[tm_core::Task]) {
+        ui.heading("Detail");
+        ui.separator();
+        if tasks.is_empty() {
+            ui.label("No tasks yet. Use the Add button or CLI.");
+        } else {
+            let idx = self.selected.min(tasks.len().saturating_sub(1));
+            let t = &tasks[idx];
+            ui.monospace(format!("id:      {}", t.id));
+            ui.monospace(format!("title:   {}", t.title));
+            ui.monospace(format!("status:  {}", t.status));
+            ui.monospace(format!("project: {}", t.project));
+            if let Some(note) = &t.note {
+                ui.monospace(format!("note:    {}", tm_core::notes::location(note)));
+            }
+            if let Some(due) = &t.due {
+                ui.monospace(format!("due:     {}", self.vault.cfg.display_date(due)));
+            }
+            if let Some(start) = &t.start {
+                ui.monospace(format!("start:   {}", self.vault.cfg.display_date(start)));
+            }
+            if let Some(done) = &t.done_at {
+                ui.monospace(format!("done at: {}", self.vault.cfg.display_date(done)));
+            }
+            ui.monospace(format!("updated: {}", t.updated));
+            if t.estimate.is_some() || !t.time.is_empty() {
+                let est = t
+                    .estimate
+                    .map(format_duration)
+                    .unwrap_or_else(|| "-".into());
+                ui.monospace(format!("time:    {} of {est}", format_duration(t.actual)));
+            }
+            // dependencies, by title when the task is in the list
+            let name = |id: &str| match tasks.iter().find(|x| x.id == id) {
+                Some(x) => format!("{} [{}]", x.title, x.status),
+                None => id.chars().take(8).collect(),
+            };
+            for d in &t.depends_on {
+                let role = if t.blocked {
+                    Role::Blocked
+                } else {
+                    Role::Muted
+                };
+                ui.label(theme::rich(
+                    &self.theme,
+                    role,
+                    format!("waits for: {}", name(d)),
+                ));
+            }
+            for x in tm_core::deps::dependents(tasks, t) {
+                ui.label(theme::rich(
+                    &self.theme,
+                    Role::Muted,
+                    format!("blocks:    {}", name(&x.id)),
+                ));
+            }
+            if !t.checklist.is_empty() {
+                let (done, total) = tm_core::checklist::progress(&t.checklist);
+                ui.add_space(4.0);
+                let hint = if self.check_focus {
+                    " (Tab: back)"
+                } else {
+                    " (Tab, Space)"
+                };
+                ui.horizontal(|ui| {
+                    ui.strong(format!("Checklist {done}/{total}"));
+                    ui.label(theme::rich(&self.theme, Role::Hint, hint));
+                });
+                let mut clicked = None;
+                for (i, c) in t.checklist.iter().enumerate() {
+                    let mut checked = c.done;
+                    let cursor = if self.check_focus && i == self.check_idx {
+                        "➤ "
+                    } else {
+                        ""
+                    };
+                    let role = if c.done { Role::Muted } else { Role::Text };
+                    let text = theme::rich(&self.theme, role, format!("{cursor}{}", c.text));
+                    if ui.checkbox(&mut checked, text).clicked() {
+                        clicked = Some(i);
+                    }
+                }
+                if let Some(i) = clicked {
+                    self.check_idx = i;
+                    self.toggle_check(&t.id, i);
+                }
+            }
+            ui.add_space(8.0);
+            self.edit_ui(ui, t);
+            let label = if t.timer_running() {
+                "⏱ Stop timer"
+            } else {
+                "⏱ Start timer"
+            };
+            if ui.button(label).clicked() {
+                self.status = Some(match self.vault.toggle_timer(&t.id) {
+                    Ok(change) => change.message(),
+                    Err(e) => (true, e.to_string()),
+                });
+            }
+            // one button per workflow status
+            ui.horizontal_wrapped(|ui| {
+                for name in self.vault.cfg.workflow.names() {
+                    let role = Role::for_status(self.vault.cfg.workflow.category(&name));
+                    let current = t.status == name;
+                    if ui
+                        .selectable_label(current, theme::rich(&self.theme, role, &name))
+                        .clicked()
+                        && !current
+                    {
+                        self.status = Some(match self.vault.set_status(&t.id, &name) {
+                            Ok(change) => change.message(),
+                            Err(e) => (true, e.to_string()),
+                        });
+                    }
+                }
+            });
+        }
+    }
 }
 
+/// Key tokens (same names as the TUI keymap: "j", "G", "Ctrl-d", "Down", "Space") for the
+/// input events of one frame. Printable keys come from text events so layouts and Shift work.
+fn egui_events_to_tokens(events: &[Event]) -> Vec<String> {
+    let mut out = Vec::new();
+    for ev in events {
+        match ev {
+            Event::Text(text) => {
+                for c in text.chars() {
+                    out.push(if c == ' ' {
+                        "Space".into()
+                    } else {
+                        c.to_string()
+                    });
+                }
+            }
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => {
+                if let Some(tok) = egui_key_to_token(*key, *modifiers) {
+                    out.push(tok);
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Keys that don't produce text: named keys and Ctrl/Alt chords
 fn egui_key_to_token(key: Key, mods: Modifiers) -> Option<String> {
-    // Normalize to the same tokens as TUI keymap: "j", "k", "Ctrl-d", "G", "/", "1", etc.
     use Key::*;
-    let ctrl = mods.ctrl;
-    let shift = mods.shift;
-
-    let base = match key {
-        ArrowDown => "Down".to_string(),
-        ArrowUp => "Up".to_string(),
-        End => "End".to_string(),
-        G => "g".to_string(),
-        J => "j".to_string(),
-        K => "k".to_string(),
-        D => "d".to_string(),
-        U => "u".to_string(),
-        X => "x".to_string(),
-        Num1 => "1".to_string(),
-        Num2 => "2".to_string(),
-        Num3 => "3".to_string(),
-        Slash => "/".to_string(),
-        Q => "q".to_string(),
-        _ => return None,
+    let named = match key {
+        ArrowDown => "Down",
+        ArrowUp => "Up",
+        ArrowLeft => "Left",
+        ArrowRight => "Right",
+        Home => "Home",
+        End => "End",
+        PageUp => "PageUp",
+        PageDown => "PageDown",
+        Escape => "Esc",
+        Enter => "Enter",
+        Tab if mods.shift => "BackTab",
+        Tab => "Tab",
+        Backspace => "Backspace",
+        Delete => "Delete",
+        _ => "",
     };
-
-    Some(if ctrl {
-        format!("Ctrl-{}", base)
-    } else if shift && base.len() == 1 {
-        base.to_uppercase()
-    } else {
-        base
-    })
+    if !named.is_empty() {
+        return Some(named.to_string());
+    }
+    let name = key.name();
+    if (mods.ctrl || mods.command || mods.alt) && name.chars().count() == 1 {
+        let c = name.to_lowercase();
+        return Some(if mods.alt {
+            format!("Alt-{c}")
+        } else {
+            format!("Ctrl-{c}")
+        });
+    }
+    None
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let tasks = self.vault.list_tasks(None).unwrap_or_default();
+        // poll for config.lua / tm-lock.json edits even when there is no input
+        ctx.request_repaint_after(std::time::Duration::from_millis(500));
+        if self.watcher.as_ref().map(|w| w.changed()).unwrap_or(false) {
+            self.reload_config();
+        }
+        let visuals = theme::visuals(&self.theme);
+        if ctx.style().visuals != visuals {
+            ctx.set_visuals(visuals);
+        }
+        if let Some(n) = self.plugins.take_notices().pop() {
+            self.status = Some((false, n));
+        }
+        self.poll_mirror();
+
+        let mut tasks = self.vault.list_tasks(None).unwrap_or_default();
+        let (query, query_error) = match self.query() {
+            Ok(q) => (q, None),
+            Err(e) => (Query::default(), Some(e)),
+        };
+        self.layout = ListLayout::resolve(&self.vault.cfg, self.active_view(), &query);
+        if let Some(s) = &self.sort_override {
+            self.layout.sort = Some(s.clone());
+        }
+        if let Some(g) = self.group_override {
+            self.layout.group = g;
+        }
+        if let Some(spec) = &self.layout.sort {
+            sort_tasks(&mut tasks, spec);
+        }
         let len = tasks.len();
 
         // --- key handling (global) ---
         let input_snapshot = ctx.input(|i| i.clone());
-        let mut action: Option<Action> = None;
-
-        // support gg and G (use key_pressed)
-        if input_snapshot.key_pressed(Key::G) {
-            if input_snapshot.modifiers.shift {
-                action = Some(Action::GoBottom); // Shift+G
-            } else if self.last_key_g {
-                action = Some(Action::GoTop); // gg
-                self.last_key_g = false;
-            } else {
-                self.last_key_g = true;
-            }
-        } else {
-            self.last_key_g = false;
-
-            // Find the first Key press event this frame and feed to keymap
-            let mut first_key: Option<(Key, Modifiers)> = None;
-            for ev in &input_snapshot.events {
-                if let Event::Key {
-                    key,
-                    pressed: true,
-                    modifiers,
-                    ..
-                } = ev
-                {
-                    first_key = Some((*key, *modifiers));
-                    break;
-                }
-            }
-            if let Some((k, mods)) = first_key {
-                if let Some(tok) = egui_key_to_token(k, mods) {
-                    action = self.keymap.lookup(&tok);
+        let now = Instant::now();
+        let mut dispatches: Vec<Dispatch> = Vec::new();
+        if let Some(KeyOutcome::Action(d)) = self.keys.tick(&self.keymap, now) {
+            dispatches.push(d);
+        }
+        // typing into a text field must not trigger bindings; egui's text fields do their own
+        // line editing, so only `keymaps.normal` applies here
+        if !ctx.wants_keyboard_input() {
+            for tok in egui_events_to_tokens(&input_snapshot.events) {
+                for out in self.keys.feed(&self.keymap, Mode::Normal, &tok, now) {
+                    if let KeyOutcome::Action(d) = out {
+                        dispatches.push(d);
+                    }
                 }
             }
         }
+        if let Some(deadline) = self.keys.deadline() {
+            ctx.request_repaint_after(deadline.saturating_duration_since(now));
+        }
+        dispatches.extend(self.queued.drain(..).map(|action| Dispatch {
+            action,
+            count: None,
+        }));
+        let focus_filter = dispatches.iter().any(|d| d.action == Action::FocusFilter);
 
-        // Extra GUI-only shortcuts
-        if input_snapshot.key_pressed(Key::P) && input_snapshot.modifiers.shift {
-            // focus "New project" field
-            self.focus_new_project = true;
+        let selected = tasks.get(self.selected);
+        if selected.map(|t| &t.id) != self.check_task.as_ref() {
+            self.check_task = selected.map(|t| t.id.clone());
+            self.check_idx = 0;
+            self.check_focus = false;
         }
+        let checks = selected.map(|t| t.checklist.len()).unwrap_or(0);
+        self.check_idx = self.check_idx.min(checks.saturating_sub(1));
+        self.check_focus &= checks > 0;
 
-        if let Some(act) = action {
-            match act {
-                // nav cases...
-                Action::MoveDown => {
-                    if self.selected + 1 < len {
-                        self.selected += 1;
+        for d in dispatches {
+            if self.check_focus {
+                if let Some(target) = motion_target(d, self.check_idx, checks, checks) {
+                    self.check_idx = target;
+                    continue;
+                }
+            }
+            if let Some(target) = motion_target(d, self.selected, len, len) {
+                self.selected = target;
+                continue;
+            }
+            match d.action {
+                Action::MoveDown
+                | Action::MoveUp
+                | Action::HalfPageDown
+                | Action::HalfPageUp
+                | Action::GoTop
+                | Action::GoBottom => {}
+                Action::ScrollTop => self.scroll_to = Some(Align::Min),
+                Action::ScrollCenter => self.scroll_to = Some(Align::Center),
+                Action::ScrollBottom => self.scroll_to = Some(Align::Max),
+                Action::FocusFilter => { /* handled by focusing the filter input below */ }
+                Action::Quit => { /* TUI only */ }
+                Action::EnterCommand => self.focus_command = true,
+                Action::OpenPalette => self.open_palette(&tasks),
+                Action::QuickActions => self.quick_open = true,
+                Action::OpenInEditor => {
+                    if let Some(t) = tasks.get(self.selected) {
+                        let spawned = self.vault.task_path(&t.id).and_then(|p| {
+                            self.vault
+                                .cfg
+                                .editor_command(&p)
+                                .spawn()
+                                .map_err(anyhow::Error::from)
+                        });
+                        if let Err(e) = spawned {
+                            self.status = Some((true, format!("could not open editor: {e:#}")));
+                        }
+                    }
+                }
+                Action::ToggleTimer => {
+                    if let Some(t) = tasks.get(self.selected) {
+                        self.status = Some(match self.vault.toggle_timer(&t.id) {
+                            Ok(change) => change.message(),
+                            Err(e) => (true, e.to_string()),
+                        });
+                    }
+                }
+                Action::FocusDetail => match tasks.get(self.selected) {
+                    Some(t) if !t.checklist.is_empty() => self.check_focus = !self.check_focus,
+                    Some(_) => {
+                        self.status =
+                            Some((false, r#"no checklist (:check.add "text" adds one)"#.into()))
+                    }
+                    None => {}
+                },
+                Action::ToggleCheck if self.check_focus => {
+                    if let Some(t) = tasks.get(self.selected) {
+                        // `3 Space` ticks the third item
+                        let idx = d
+                            .count
+                            .map(|c| (c as usize).saturating_sub(1))
+                            .unwrap_or(self.check_idx);
+                        self.toggle_check(&t.id, idx);
                     }
                 }
-                Action::MoveUp => {
-                    if self.selected > 0 {
-                        self.selected -= 1;
+                Action::PickProject => self.picking_project = true,
+                Action::NextProject => self.cycle_project(true),
+                Action::PrevProject => self.cycle_project(false),
+                Action::NextView => self.cycle_view(true),
+                Action::PrevView => self.cycle_view(false),
+                Action::SortNext | Action::SortReverse => {
+                    let mut spec = self.current_sort();
+                    if d.action == Action::SortNext {
+                        spec = cycle_sort(&spec);
+                    } else if let Some(first) = spec.first_mut() {
+                        first.desc = !first.desc;
                     }
+                    self.status = Some((false, format!("sort: {}", format_sort(&spec))));
+                    self.sort_override = Some(spec);
                 }
-                Action::HalfPageDown => {
-                    let jump = (len.max(1) / 2).max(1);
-                    self.selected = (self.selected + jump).min(len.saturating_sub(1));
+                Action::ToggleFold => {
+                    let groups = group_tasks(
+                        &tasks,
+                        &[self.selected.min(len.saturating_sub(1))],
+                        self.layout.group,
+                        date::today(),
+                    );
+                    if let Some(g) = groups
+                        .into_iter()
+                        .next()
+                        .filter(|_| len > 0 && self.layout.group != GroupBy::None)
+                    {
+                        if !self.collapsed.remove(&g.key) {
+                            self.collapsed.insert(g.key);
+                        }
+                    }
                 }
-                Action::HalfPageUp => {
-                    let jump = (len.max(1) / 2).max(1);
-                    self.selected = self.selected.saturating_sub(jump);
+                Action::OpenFolds => self.collapsed.clear(),
+                Action::CloseFolds => {
+                    let all: Vec<usize> = (0..len).collect();
+                    self.collapsed = group_tasks(&tasks, &all, self.layout.group, date::today())
+                        .into_iter()
+                        .map(|g| g.key)
+                        .collect();
                 }
-                Action::GoTop => self.selected = 0,
-                Action::GoBottom => {
-                    if len > 0 {
-                        self.selected = len - 1;
+                // focus "New project" field
+                Action::NewProject => self.focus_new_project = true,
+                Action::EditDue | Action::EditTitle | Action::EditTags | Action::Snooze => {
+                    if let Some(t) = tasks.get(self.selected) {
+                        let text = match d.action {
+                            Action::EditDue => t.due.clone().unwrap_or_default(),
+                            Action::Snooze => String::new(),
+                            Action::EditTitle => t.title.clone(),
+                            _ => t.tags.join(", "),
+                        };
+                        self.editing = Some((d.action, text));
                     }
                 }
-                Action::FocusFilter => { /* handled by focusing the filter input below */ }
-                Action::Quit => { /* GUI ignores */ }
-
-                Action::StatusNext | Action::StatusPrev | Action::SetTodo | Action::SetDoing | Action::SetDone => {
+                Action::StatusNext
+                | Action::StatusPrev
+                | Action::SetTodo
+                | Action::SetDoing
+                | Action::SetDone
+                | Action::SetCancelled => {
                     if let Some(t) = tasks.get(self.selected) {
                         let id = &t.id;
-                        let _: anyhow::Result<Status> = match act {
-                            Action::StatusNext => self.vault.cycle_status(id, 1),
-                            Action::StatusPrev => self.vault.cycle_status(id, -1),
-                            Action::SetTodo => self.vault.set_status(id, Status::Todo).map(|_| Status::Todo),
-                            Action::SetDoing => self.vault.set_status(id, Status::Doing).map(|_| Status::Doing),
-                            Action::SetDone => self.vault.set_status(id, Status::Done).map(|_| Status::Done),
+                        let res: anyhow::Result<tm_core::StatusChange> = match d.action {
+                            // 3x cycles three steps
+                            Action::StatusNext | Action::StatusPrev => {
+                                let dir = if d.action == Action::StatusNext {
+                                    1
+                                } else {
+                                    -1
+                                };
+                                (1..d.times()).fold(self.vault.cycle_status(id, dir), |s, _| {
+                                    s.and_then(|_| self.vault.cycle_status(id, dir))
+                                })
+                            }
+                            Action::SetTodo => self.vault.set_status_category(id, Category::Open),
+                            Action::SetDoing => {
+                                self.vault.set_status_category(id, Category::Active)
+                            }
+                            Action::SetDone => self.vault.set_status_category(id, Category::Closed),
+                            Action::SetCancelled => self
+                                .vault
+                                .cfg
+                                .workflow
+                                .cancel_status()
+                                .and_then(|s| self.vault.set_status(id, s)),
                             _ => unreachable!(),
                         };
+                        self.status = Some(match res {
+                            Ok(change) => change.message(),
+                            Err(e) => (true, e.to_string()),
+                        });
                     }
                 }
+                // line editing inside inputs is handled by egui
+                _ => {}
             }
         }
 
@@ -174,17 +1222,14 @@ impl eframe::App for App {
                 ui.separator();
 
                 // Project dropdown
-                let mut keys: Vec<String> = self
-                    .vault
-                    .list_projects()
-                    .unwrap_or_default()
-                    .into_iter()
-                    .map(|p| p.key)
-                    .collect();
-                keys.sort();
+                let keys = self.project_keys();
 
                 egui::ComboBox::from_label("Project")
-                    .selected_text(self.project_filter.clone().unwrap_or_else(|| "(all)".into()))
+                    .selected_text(
+                        self.project_filter
+                            .clone()
+                            .unwrap_or_else(|| "(all)".into()),
+                    )
                     .show_ui(ui, |ui| {
                         ui.selectable_value(&mut self.project_filter, None, "(all)".to_string());
                         for k in &keys {
@@ -194,8 +1239,8 @@ impl eframe::App for App {
 
                 ui.separator();
                 ui.label("New:");
-                let new_proj_widget =
-                    egui::TextEdit::singleline(&mut self.new_project_title).id_source("new_proj_input");
+                let new_proj_widget = egui::TextEdit::singleline(&mut self.new_project_title)
+                    .id_source("new_proj_input");
                 let resp = ui.add(new_proj_widget);
                 if self.focus_new_project {
                     resp.request_focus();
@@ -203,7 +1248,8 @@ impl eframe::App for App {
                 }
 
                 let create_clicked = ui.button("Create").clicked();
-                let enter_on_field = ui.input(|i| i.key_pressed(Key::Enter)) && ui.memory(|m| m.has_focus(resp.id));
+                let enter_on_field =
+                    ui.input(|i| i.key_pressed(Key::Enter)) && ui.memory(|m| m.has_focus(resp.id));
 
                 if create_clicked || enter_on_field {
                     let title = self.new_project_title.trim();
@@ -219,79 +1265,163 @@ impl eframe::App for App {
 
                 ui.separator();
                 ui.label("Filter:");
-                let filter_widget = egui::TextEdit::singleline(&mut self.filter).id_source("filter_input");
+                let filter_widget =
+                    egui::TextEdit::singleline(&mut self.filter).id_source("filter_input");
                 let resp_filter = ui.add(filter_widget);
                 // If user pressed key bound to FocusFilter this frame, focus the filter input
-                if matches!(action, Some(Action::FocusFilter)) {
+                if focus_filter {
                     resp_filter.request_focus();
                 }
-            });
-        });
 
-        egui::SidePanel::left("left").resizable(true).default_width(420.0).show(ctx, |ui| {
-            ui.heading("Tasks");
-            ui.separator();
-            ScrollArea::vertical().show(ui, |ui| {
-                for (i, t) in tasks.iter().enumerate() {
-                    // project filter + text filter
-                    if let Some(pk) = &self.project_filter {
-                        if &t.project != pk {
-                            continue;
-                        }
+                ui.separator();
+                ui.label(":");
+                let resp_cmd = ui.add(
+                    egui::TextEdit::singleline(&mut self.command)
+                        .id_source("command_input")
+                        .hint_text("new \"title\" project:<project>"),
+                );
+                if self.focus_command {
+                    resp_cmd.request_focus();
+                    self.focus_command = false;
+                }
+                if resp_cmd.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    let line = std::mem::take(&mut self.command);
+                    let selected = tasks.get(self.selected).map(|t| t.id.clone());
+                    self.run_command(line.trim().trim_start_matches(':'), selected.as_deref());
+                }
+
+                ui.separator();
+                if ui.button("Reload config").clicked() {
+                    self.reload_config();
+                }
+                if let Some(t) = tasks.iter().find(|t| t.timer_running()) {
+                    ui.separator();
+                    ui.label(theme::rich(
+                        &self.theme,
+                        Role::Success,
+                        format!("⏱ {} {}", t.title, format_duration(t.actual)),
+                    ));
+                    if ui.small_button("Stop").clicked() {
+                        self.status = Some(match self.vault.stop_timer(Some(&t.id)) {
+                            Ok(change) => change.message(),
+                            Err(e) => (true, e.to_string()),
+                        });
                     }
-                    if !self.filter.is_empty() {
-                        let hay = format!("[{}] {} {}", t.status, t.title, t.project).to_lowercase();
-                        if !hay.contains(&self.filter.to_lowercase()) {
-                            continue;
-                        }
+                }
+            });
+            // view tabs
+            ui.horizontal(|ui| {
+                let mut pick = None;
+                if ui
+                    .selectable_label(self.cur_view.is_none(), "All")
+                    .clicked()
+                {
+                    pick = Some(None);
+                }
+                for v in &self.views {
+                    let resp = ui.selectable_label(
+                        self.cur_view.as_deref() == Some(v.name.as_str()),
+                        &v.name,
+                    );
+                    if resp.on_hover_text(&v.query).clicked() {
+                        pick = Some(Some(v.name.clone()));
                     }
+                }
+                if let Some(name) = pick {
+                    self.open_view(name);
+                }
+                if let Some(e) = &query_error {
+                    ui.separator();
+                    ui.label(theme::rich(&self.theme, Role::Error, e));
+                }
+            });
+        });
 
-                    let selected = i == self.selected;
-                    let text = format!("[{}] {}  · {}", t.status, t.title, t.project);
-                    if ui.selectable_label(selected, text).clicked() {
-                        self.selected = i;
-                    }
+        egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
+            ui.vertical(|ui| {
+                if let Some((is_err, msg)) = &self.status {
+                    let role = if *is_err { Role::Error } else { Role::Success };
+                    ui.label(theme::rich(&self.theme, role, msg));
                 }
+                if self.keys.is_pending() {
+                    ui.monospace(format!("keys: {}", self.keys.pending_display()));
+                }
+                ui.label("Navigation: j/k, gg/G, Ctrl-d/u, zt/zz/zb · counts: 5j, 3x, 10G");
+                ui.label("Filter/Projects: / focus filter · O pick · ]/[ cycle · P focus 'New project'");
+                ui.label("Views: gt/gT next/prev · :view <name> · :view.save <name> · query: +tag due<=today sort:p");
+                ui.label("List: click a header to sort (shift: add key) · o/~ sort · :group project|status|due|tag · za/zR/zM folds");
+                ui.label("Status: x next · X cancel · 1/2/3 set todo/doing/done · workflow in config");
+                ui.label("Edits: D due · R rename · T tags (inline in the Detail panel; Enter/Esc)");
+                ui.label("Palette: <leader>p fuzzy over tasks, @projects, #tags, >commands · : command field");
+                ui.label("Actions: <leader>a or right click a task · E open in editor");
+                ui.label("Config: ~/.config/tm/config.lua — reloads on save or with 'Reload config'");
             });
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Detail");
-            ui.separator();
-            if tasks.is_empty() {
-                ui.label("No tasks yet. Use the Add button or CLI.");
-            } else {
-                let idx = self.selected.min(tasks.len().saturating_sub(1));
-                let t = &tasks[idx];
-                ui.monospace(format!("id:      {}", t.id));
-                ui.monospace(format!("title:   {}", t.title));
-                ui.monospace(format!("status:  {}", t.status));
-                ui.monospace(format!("project: {}", t.project));
-                ui.monospace(format!("updated: {}", t.updated));
-                ui.add_space(8.0);
-                ui.horizontal(|ui| {
-                    if ui.button("Todo (1)").clicked() {
-                        let _ = self.vault.set_status(&t.id, Status::Todo);
-                    }
-                    if ui.button("In-Progress (2)").clicked() {
-                        let _ = self.vault.set_status(&t.id, Status::Doing);
+        self.palette_ui(ctx, &tasks);
+
+        if self.quick_open {
+            let mut open = true;
+            let task = tasks.get(self.selected);
+            let title = task
+                .map(|t| format!("Actions: {}", t.title))
+                .unwrap_or_else(|| "Actions".into());
+            let mut chosen = None;
+            egui::Window::new(title)
+                .id(egui::Id::new("quick_actions"))
+                .open(&mut open)
+                .collapsible(false)
+                .show(ctx, |ui| chosen = self.quick_menu_ui(ui, task));
+            if !open || chosen.is_some() || ctx.input(|i| i.key_pressed(Key::Escape)) {
+                self.quick_open = false;
+            }
+            if let Some(qa) = chosen {
+                self.run_quick(&qa, &tasks);
+            }
+        }
+
+        if self.picking_project {
+            let mut open = true;
+            egui::Window::new("Pick project")
+                .open(&mut open)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    if ui.button("(all)").clicked() {
+                        self.project_filter = None;
+                        self.picking_project = false;
                     }
-                    if ui.button("Done (3)").clicked() {
-                        let _ = self.vault.set_status(&t.id, Status::Done);
+                    for k in self.project_keys() {
+                        if ui.button(&k).clicked() {
+                            self.project_filter = Some(k);
+                            self.selected = 0;
+                            self.picking_project = false;
+                        }
                     }
                 });
+            if !open || ctx.input(|i| i.key_pressed(Key::Escape)) {
+                self.picking_project = false;
             }
-        });
+        }
 
-        egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
-            ui.vertical(|ui| {
-                ui.label("Navigation: j/k, gg/G, Ctrl-d/u");
-                ui.label("Filter/Projects: / focus filter · Project dropdown · Shift+P focus 'New project'");
-                ui.label("Status: x next · X prev · 1/2/3 set todo/doing/done");
-                ui.label("Edits: inline fields in the Detail panel (title/due/tags)");
-                ui.label("Config: ~/.config/tm/config.lua (Lua keymaps); restart to reload");
-            });
-        });
+        // Detail pane placement follows `layout` in config (applied live on reload)
+        let layout = self.vault.cfg.layout.clone();
+        let screen = ctx.screen_rect();
+        match layout.pane {
+            PanePosition::Right => {
+                egui::SidePanel::right("detail")
+                    .resizable(true)
+                    .default_width(screen.width() * layout.pane_size as f32 / 100.0)
+                    .show(ctx, |ui| self.detail_ui(ui, &tasks));
+            }
+            PanePosition::Bottom => {
+                egui::TopBottomPanel::bottom("detail")
+                    .resizable(true)
+                    .default_height(screen.height() * layout.pane_size as f32 / 100.0)
+                    .show(ctx, |ui| self.detail_ui(ui, &tasks));
+            }
+            PanePosition::Hidden => {}
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| self.list_ui(ui, &tasks, &query));
     }
 }
-
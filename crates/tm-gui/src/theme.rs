@@ -0,0 +1,80 @@
+//! The config theme as egui visuals and text styles
+
+use eframe::egui::{self, text::TextFormat, Color32, RichText, Stroke, Visuals};
+use tm_core::{Rgb, Role, Theme};
+
+fn color(c: Rgb) -> Color32 {
+    Color32::from_rgb(c.0, c.1, c.2)
+}
+
+/// Dark or light egui visuals with the theme's background, text, selection and border
+pub(crate) fn visuals(theme: &Theme) -> Visuals {
+    let text = theme.get(Role::Text);
+    let dark = text
+        .bg
+        .map(|Rgb(r, g, b)| (r as u32 + g as u32 + b as u32) < 384)
+        .unwrap_or(true);
+    let mut v = if dark {
+        Visuals::dark()
+    } else {
+        Visuals::light()
+    };
+    if let Some(bg) = text.bg.map(color) {
+        v.panel_fill = bg;
+        v.window_fill = bg;
+    }
+    v.override_text_color = text.fg.map(color);
+    if let Some(sel) = theme.get(Role::Selection).bg.map(color) {
+        v.selection.bg_fill = sel;
+        v.faint_bg_color = sel.gamma_multiply(0.4);
+    }
+    if let Some(border) = theme.get(Role::Border).fg.map(color) {
+        v.widgets.noninteractive.bg_stroke = Stroke::new(1.0, border);
+        v.window_stroke = Stroke::new(1.0, border);
+    }
+    if let Some(err) = theme.get(Role::Error).fg.map(color) {
+        v.error_fg_color = err;
+    }
+    v
+}
+
+/// `s` in a role's colour and attributes
+pub(crate) fn rich(theme: &Theme, role: Role, s: impl Into<String>) -> RichText {
+    let st = theme.get(role);
+    let mut t = RichText::new(s);
+    if let Some(c) = st.fg.map(color) {
+        t = t.color(c);
+    }
+    if let Some(c) = st.bg.map(color) {
+        t = t.background_color(c);
+    }
+    if st.bold {
+        t = t.strong();
+    }
+    if st.dim {
+        t = t.weak();
+    }
+    if st.italic {
+        t = t.italics();
+    }
+    if st.underline {
+        t = t.underline();
+    }
+    t
+}
+
+/// A role as a `LayoutJob` section format (colour and underline only)
+pub(crate) fn format(theme: &Theme, role: Role, ui: &egui::Ui) -> TextFormat {
+    let st = theme.get(role);
+    let mut f = TextFormat {
+        color: ui.visuals().text_color(),
+        ..Default::default()
+    };
+    if let Some(c) = st.fg.map(color) {
+        f.color = c;
+    }
+    if st.underline {
+        f.underline = Stroke::new(1.0, f.color);
+    }
+    f
+}
@@ -0,0 +1,365 @@
+//! The host functions that reach outside the Lua state: `host.http` (needs the `network`
+//! permission), `host.secrets` (`secrets:<key>`), `host.tasks`, `host.projects` and
+//! `host.state`
+//!
+//! ```lua
+//! local token = host.secrets.get('todoist')          -- $TM_SECRET_TODOIST, or secrets.json
+//! local res = host.http.post_json(url, { sync_token = '*' }, {
+//!   form = true, headers = { Authorization = 'Bearer ' .. token },
+//! })
+//! local t = host.tasks.upsert({ title = 'From Todoist', project = 'work', todoist_id = '123' })
+//! host.state.set('sync_token', res.sync_token)       -- kept in the vault's .tm/plugins/
+//! ```
+//!
+//! JSON `null` arrives as `nil`; `host.null` sends one (clearing a field in `upsert`) and
+//! `host.array(t)` makes an empty table go out as `[]` rather than `{}`.
+
+use std::{collections::BTreeMap, env, fs, io::Write, path::Path, time::Duration};
+
+use anyhow::Result;
+use mlua::{Lua, LuaSerdeExt, SerializeOptions, Table, Value};
+use serde_json::{Map, Value as Json};
+use tm_core::{export::TaskRecord, plugin, Config, Query, Vault};
+
+use crate::manifest::Manifest;
+
+/// `$TM_SECRET_<KEY>` is used over the stored secret (`TM_SECRET_TODOIST`)
+pub const ENV_SECRET_PREFIX: &str = "TM_SECRET_";
+
+fn runtime(msg: impl ToString) -> mlua::Error {
+    mlua::Error::runtime(msg.to_string())
+}
+
+fn anyhow_err(e: anyhow::Error) -> mlua::Error {
+    runtime(format!("{e:#}"))
+}
+
+/// JSON as Lua, `null` as `nil`
+fn to_lua(lua: &Lua, v: &Json) -> mlua::Result<Value> {
+    lua.to_value_with(
+        v,
+        SerializeOptions::new()
+            .serialize_none_to_null(false)
+            .serialize_unit_to_null(false),
+    )
+}
+
+fn request(
+    lua: &Lua,
+    agent: &ureq::Agent,
+    method: &str,
+    url: &str,
+    body: Option<Json>,
+    opts: Option<Table>,
+) -> mlua::Result<Value> {
+    let mut req = agent.request(method, url).set("Accept", "application/json");
+    let mut form = false;
+    if let Some(opts) = &opts {
+        if let Some(headers) = opts.get::<Option<Table>>("headers")? {
+            for pair in headers.pairs::<String, String>() {
+                let (k, v) = pair?;
+                req = req.set(&k, &v);
+            }
+        }
+        if let Some(query) = opts.get::<Option<Table>>("query")? {
+            for pair in query.pairs::<String, String>() {
+                let (k, v) = pair?;
+                req = req.query(&k, &v);
+            }
+        }
+        form = opts.get::<Option<bool>>("form")?.unwrap_or(false);
+    }
+    let resp = match body {
+        None => req.call(),
+        // form fields: strings as they are, anything else as JSON (Todoist's `commands`)
+        Some(Json::Object(fields)) if form => {
+            let pairs: Vec<(String, String)> = fields
+                .into_iter()
+                .map(|(k, v)| match v {
+                    Json::String(s) => (k, s),
+                    v => (k, v.to_string()),
+                })
+                .collect();
+            req.send_form(
+                &pairs
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+                    .collect::<Vec<_>>(),
+            )
+        }
+        Some(_) if form => return Err(runtime("post_json: a form body must be a table of fields")),
+        Some(b) => req
+            .set("Content-Type", "application/json")
+            .send_string(&b.to_string()),
+    };
+    let resp = match resp {
+        Ok(r) => r,
+        Err(ureq::Error::Status(code, r)) => {
+            let text: String = r
+                .into_string()
+                .unwrap_or_default()
+                .chars()
+                .take(200)
+                .collect();
+            return Err(runtime(format!("{method} {url}: HTTP {code}: {text}")));
+        }
+        Err(e) => return Err(runtime(format!("{method} {url}: {e}"))),
+    };
+    let text = resp.into_string().map_err(runtime)?;
+    if text.trim().is_empty() {
+        return Ok(Value::Nil);
+    }
+    let json: Json = serde_json::from_str(&text)
+        .map_err(|e| runtime(format!("{method} {url}: the response is not JSON: {e}")))?;
+    to_lua(lua, &json)
+}
+
+/// `host.http`: `get_json(url, opts)`, `post_json(url, body, opts)`; `opts` has `headers`,
+/// `query` and `form` (send `body` form-encoded)
+pub(crate) fn http_table(lua: &Lua, manifest: &Manifest) -> mlua::Result<Table> {
+    let t = lua.create_table()?;
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .build();
+    let allowed = manifest.permissions.iter().any(|p| p == "network");
+    let denied = format!(
+        "{}: host.http needs the \"network\" permission in plugin.toml",
+        manifest.name
+    );
+
+    let (a, d) = (agent.clone(), denied.clone());
+    let get = lua.create_function(move |lua, (url, opts): (String, Option<Table>)| {
+        if !allowed {
+            return Err(runtime(&d));
+        }
+        request(lua, &a, "GET", &url, None, opts)
+    })?;
+    t.set("get_json", get)?;
+    let post = lua.create_function(
+        move |lua, (url, body, opts): (String, Value, Option<Table>)| {
+            if !allowed {
+                return Err(runtime(&denied));
+            }
+            request(lua, &agent, "POST", &url, Some(lua.from_value(body)?), opts)
+        },
+    )?;
+    t.set("post_json", post)?;
+    Ok(t)
+}
+
+fn secret_var(key: &str) -> String {
+    let key: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{ENV_SECRET_PREFIX}{key}")
+}
+
+fn read_secrets(path: &Path) -> BTreeMap<String, String> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Written to a temporary file created with mode 0600 and renamed over the old one, so
+/// the secrets are never readable by others, not even half written
+fn write_secrets(path: &Path, secrets: &BTreeMap<String, String>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    // a leftover from a crash may have other permissions: start from a new file
+    let _ = fs::remove_file(&tmp);
+    let mut opts = fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let mut file = opts.open(&tmp)?;
+    file.write_all(serde_json::to_string_pretty(secrets)?.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// `host.secrets`: `get(key)` / `set(key, value)` for the keys the manifest grants
+pub(crate) fn secrets_table(lua: &Lua, cfg: &Config, manifest: &Manifest) -> mlua::Result<Table> {
+    let t = lua.create_table()?;
+    let path = plugin::secrets_path(&cfg.config_path);
+    let granted = manifest.permissions.clone();
+    let name = manifest.name.clone();
+    let check = move |key: &str| {
+        if granted
+            .iter()
+            .any(|p| p.strip_prefix("secrets:") == Some(key))
+        {
+            Ok(())
+        } else {
+            Err(runtime(format!(
+                "{name}: host.secrets needs the \"secrets:{key}\" permission in plugin.toml"
+            )))
+        }
+    };
+
+    let (p, c) = (path.clone(), check.clone());
+    let get = lua.create_function(move |_, key: String| {
+        c(&key)?;
+        Ok(env::var(secret_var(&key))
+            .ok()
+            .or_else(|| read_secrets(&p).remove(&key)))
+    })?;
+    t.set("get", get)?;
+    let set = lua.create_function(move |_, (key, value): (String, Option<String>)| {
+        check(&key)?;
+        let mut secrets = read_secrets(&path);
+        match value {
+            Some(v) => secrets.insert(key, v),
+            None => secrets.remove(&key),
+        };
+        write_secrets(&path, &secrets).map_err(anyhow_err)
+    })?;
+    t.set("set", set)?;
+    Ok(t)
+}
+
+/// A task as plugins see it: its export record plus the status `category`
+fn task_value(lua: &Lua, vault: &Vault, r: &TaskRecord) -> mlua::Result<Value> {
+    let mut v = serde_json::to_value(r).map_err(runtime)?;
+    let category = vault
+        .cfg
+        .workflow
+        .category(&r.frontmatter.status)
+        .map(|c| c.as_str());
+    v["category"] = category.into();
+    to_lua(lua, &v)
+}
+
+/// `host.tasks`: `list(query)`, `upsert(fields)` (see `Vault::upsert_task`),
+/// `deleted()`, the tombstones of tasks deleted with `tm rm` (id → when), and
+/// `forget_deleted(id)` once the deletion reached the remote side
+pub(crate) fn tasks_table(lua: &Lua, vault: &Vault) -> mlua::Result<Table> {
+    let t = lua.create_table()?;
+    let v = vault.clone();
+    let list = lua.create_function(move |lua, query: Option<String>| {
+        let query = Query::parse(query.as_deref().unwrap_or_default()).map_err(anyhow_err)?;
+        let tasks = v.export_json(&query).map_err(anyhow_err)?.tasks;
+        let out = lua.create_table()?;
+        for r in &tasks {
+            out.push(task_value(lua, &v, r)?)?;
+        }
+        Ok(out)
+    })?;
+    t.set("list", list)?;
+    let v = vault.clone();
+    let upsert = lua.create_function(move |lua, fields: Value| {
+        let Json::Object(patch) = lua.from_value::<Json>(fields)? else {
+            return Err(runtime("tasks.upsert: expected a table of task fields"));
+        };
+        let r = v.upsert_task(&patch).map_err(anyhow_err)?;
+        task_value(lua, &v, &r)
+    })?;
+    t.set("upsert", upsert)?;
+    let v = vault.clone();
+    let deleted = lua.create_function(move |lua, ()| {
+        to_lua(
+            lua,
+            &serde_json::to_value(v.deleted_tasks().map_err(anyhow_err)?).map_err(runtime)?,
+        )
+    })?;
+    t.set("deleted", deleted)?;
+    let v = vault.clone();
+    let forget =
+        lua.create_function(move |_, id: String| v.forget_deleted(&id).map_err(anyhow_err))?;
+    t.set("forget_deleted", forget)?;
+    Ok(t)
+}
+
+/// `host.projects`: `list()` and `ensure(key, title)`
+pub(crate) fn projects_table(lua: &Lua, vault: &Vault) -> mlua::Result<Table> {
+    let t = lua.create_table()?;
+    let v = vault.clone();
+    let list = lua.create_function(move |lua, ()| {
+        let projects = v
+            .export_json(&Query::default())
+            .map_err(anyhow_err)?
+            .projects;
+        to_lua(lua, &serde_json::to_value(projects).map_err(runtime)?)
+    })?;
+    t.set("list", list)?;
+    let v = vault.clone();
+    let ensure = lua.create_function(move |_, (key, title): (String, Option<String>)| {
+        v.ensure_project(&key, title.as_deref().unwrap_or(&key))
+            .map_err(anyhow_err)
+    })?;
+    t.set("ensure", ensure)?;
+    Ok(t)
+}
+
+/// `host.state`: `get(key)` / `set(key, value)`, kept per plugin in `.tm/plugins/<name>.json`
+pub(crate) fn state_table(lua: &Lua, vault: &Vault, name: &str) -> mlua::Result<Table> {
+    let t = lua.create_table()?;
+    let path = vault
+        .state_dir()
+        .join("plugins")
+        .join(format!("{name}.json"));
+    let read = |path: &Path| -> Map<String, Json> {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    };
+
+    let p = path.clone();
+    let get = lua.create_function(move |lua, key: String| match read(&p).get(&key) {
+        Some(v) => to_lua(lua, v),
+        None => Ok(Value::Nil),
+    })?;
+    t.set("get", get)?;
+    let set = lua.create_function(move |lua, (key, value): (String, Value)| {
+        let mut state = read(&path);
+        match value {
+            Value::Nil => state.remove(&key),
+            v => state.insert(key, lua.from_value(v)?),
+        };
+        let write = || -> Result<()> {
+            fs::create_dir_all(path.parent().expect("under .tm"))?;
+            fs::write(&path, serde_json::to_string_pretty(&state)?)?;
+            Ok(())
+        };
+        write().map_err(anyhow_err)
+    })?;
+    t.set("set", set)?;
+    Ok(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_replaced_whole_and_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let one = BTreeMap::from([("todoist".to_string(), "a".to_string())]);
+        write_secrets(&path, &one).unwrap();
+        let two = BTreeMap::from([("github".to_string(), "b".to_string())]);
+        write_secrets(&path, &two).unwrap();
+        assert_eq!(read_secrets(&path), two);
+        assert!(!path.with_extension("json.tmp").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,545 @@
+//! Plugins shipped inside tm (sources under `plugins/` in the repo). They need no install:
+//! `use({ 'todoist', opts = { ... } })` in config.lua enables one; a `version` constraint
+//! is checked against the bundled version. An installed plugin of the same name wins.
+
+use anyhow::{Context, Result};
+
+use crate::manifest::Manifest;
+
+pub struct Bundled {
+    pub name: &'static str,
+    manifest: &'static str,
+    /// Path of the entry inside the plugin's source dir
+    pub entry_path: &'static str,
+    pub entry: &'static str,
+}
+
+const BUNDLED: &[Bundled] = &[Bundled {
+    name: "todoist",
+    manifest: include_str!("../../../plugins/todoist/plugin.toml"),
+    entry_path: "init.lua",
+    entry: include_str!("../../../plugins/todoist/init.lua"),
+}];
+
+/// The bundled plugin a `use()` source names, if it is a bare bundled name
+pub fn find(source: &str) -> Option<&'static Bundled> {
+    BUNDLED.iter().find(|b| b.name == source.trim())
+}
+
+impl Bundled {
+    pub fn manifest(&self) -> Result<Manifest> {
+        toml::from_str(self.manifest)
+            .with_context(|| format!("bundled plugin '{}': invalid plugin.toml", self.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        fs,
+        path::Path,
+        sync::{Arc, Mutex},
+        thread::JoinHandle,
+    };
+
+    use serde_json::{json, Map, Value as Json};
+    use tm_core::{
+        export::TaskRecord, plugin, Category, Config, PluginSpec, Query, TaskNew, Vault,
+    };
+
+    use super::*;
+    use crate::PluginHost;
+
+    #[test]
+    fn bundled_manifests_are_valid() {
+        for b in BUNDLED {
+            b.manifest()
+                .unwrap()
+                .validate(|entry| entry == Path::new(b.entry_path))
+                .unwrap();
+        }
+    }
+
+    /// What the fake Todoist holds. Items carry the `rev` they last changed at, and a read
+    /// returns those changed since its `sync_token`; what commands change is not read back.
+    #[derive(Default)]
+    struct Remote {
+        projects: Vec<Json>,
+        items: BTreeMap<String, Json>,
+        rev: u64,
+        /// the commands received, in order
+        commands: Vec<Json>,
+        tokens: Vec<Option<String>>,
+        /// answer command batches with HTTP 500
+        fail_commands: bool,
+    }
+
+    impl Remote {
+        /// Add or replace an item as if it was edited in Todoist
+        fn put(&mut self, mut item: Json) {
+            self.rev += 1;
+            item["rev"] = self.rev.into();
+            self.items
+                .insert(item["id"].as_str().unwrap().to_string(), item);
+        }
+
+        fn sync(&mut self, form: &BTreeMap<String, String>) -> Json {
+            let Some(commands) = form.get("commands") else {
+                let since = form["sync_token"].parse().unwrap_or(0);
+                let items: Vec<&Json> = self
+                    .items
+                    .values()
+                    .filter(|i| i["rev"].as_u64().unwrap_or(0) > since)
+                    .collect();
+                return json!({ "sync_token": self.rev.to_string(), "projects": self.projects, "sections": [], "items": items });
+            };
+            let (mut status, mut mapping) = (Map::new(), Map::new());
+            for c in serde_json::from_str::<Vec<Json>>(commands).unwrap() {
+                let args = &c["args"];
+                let id = match c["type"].as_str().unwrap() {
+                    "item_add" => {
+                        let id = format!("{}", 900 + self.items.len());
+                        mapping.insert(c["temp_id"].as_str().unwrap().into(), id.clone().into());
+                        let mut item = args.clone();
+                        item["id"] = id.clone().into();
+                        item["checked"] = false.into();
+                        self.items.insert(id.clone(), item);
+                        id
+                    }
+                    _ => args["id"].as_str().unwrap().to_string(),
+                };
+                let item = self.items.get_mut(&id).unwrap();
+                match c["type"].as_str().unwrap() {
+                    "item_update" => {
+                        for (k, v) in args.as_object().unwrap() {
+                            item[k] = v.clone();
+                        }
+                    }
+                    "item_close" => item["checked"] = true.into(),
+                    "item_uncomplete" => item["checked"] = false.into(),
+                    "item_move" => item["project_id"] = args["project_id"].clone(),
+                    "item_delete" => item["is_deleted"] = true.into(),
+                    _ => {}
+                }
+                status.insert(c["uuid"].as_str().unwrap().into(), "ok".into());
+                self.commands.push(c);
+            }
+            json!({ "sync_status": status, "temp_id_mapping": mapping })
+        }
+
+        fn command_types(&self) -> Vec<&str> {
+            self.commands
+                .iter()
+                .map(|c| c["type"].as_str().unwrap())
+                .collect()
+        }
+    }
+
+    /// A local stand-in for Todoist's `POST /sync`; stops when dropped
+    struct FakeTodoist {
+        url: String,
+        remote: Arc<Mutex<Remote>>,
+        server: Arc<tiny_http::Server>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl FakeTodoist {
+        fn start(remote: Remote) -> Self {
+            let server = Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+            let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+            let remote = Arc::new(Mutex::new(remote));
+            let (incoming, state) = (server.clone(), remote.clone());
+            let thread = std::thread::spawn(move || {
+                for mut req in incoming.incoming_requests() {
+                    let mut body = String::new();
+                    req.as_reader().read_to_string(&mut body).unwrap();
+                    let auth = req
+                        .headers()
+                        .iter()
+                        .find(|h| h.field.equiv("Authorization"))
+                        .map(|h| h.value.to_string());
+                    let mut remote = state.lock().unwrap();
+                    remote.tokens.push(auth);
+                    let form = form(&body);
+                    if remote.fail_commands && form.contains_key("commands") {
+                        let _ = req.respond(tiny_http::Response::empty(500));
+                        continue;
+                    }
+                    let reply = if req.url() == "/sync" {
+                        remote.sync(&form).to_string()
+                    } else {
+                        String::new()
+                    };
+                    let _ = req.respond(tiny_http::Response::from_string(reply));
+                }
+            });
+            Self {
+                url,
+                remote,
+                server,
+                thread: Some(thread),
+            }
+        }
+
+        fn remote(&self) -> std::sync::MutexGuard<'_, Remote> {
+            self.remote.lock().unwrap()
+        }
+    }
+
+    impl Drop for FakeTodoist {
+        fn drop(&mut self) {
+            self.server.unblock();
+            if let Some(t) = self.thread.take() {
+                let _ = t.join();
+            }
+        }
+    }
+
+    fn form(body: &str) -> BTreeMap<String, String> {
+        let decode = |s: &str| {
+            let (b, mut out, mut i) = (s.as_bytes(), Vec::new(), 0);
+            while i < b.len() {
+                match b[i] {
+                    b'+' => out.push(b' '),
+                    b'%' => {
+                        out.push(u8::from_str_radix(&s[i + 1..i + 3], 16).unwrap());
+                        i += 2;
+                    }
+                    c => out.push(c),
+                }
+                i += 1;
+            }
+            String::from_utf8(out).unwrap()
+        };
+        body.split('&')
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| (decode(k), decode(v)))
+            .collect()
+    }
+
+    fn work() -> Remote {
+        Remote {
+            projects: vec![json!({ "id": "p1", "name": "Work" })],
+            ..Remote::default()
+        }
+    }
+
+    fn item(id: &str, content: &str, updated_at: &str) -> Json {
+        json!({
+            "id": id, "project_id": "p1", "content": content, "description": "", "priority": 4,
+            "labels": ["urgent"], "due": { "date": "2030-05-01" }, "checked": false, "updated_at": updated_at,
+        })
+    }
+
+    /// A vault with the bundled plugin pointed at `api_url` and a stored token
+    fn setup(dir: &Path, api_url: &str, conflict: &str) -> (PluginHost, Vault) {
+        let spec = PluginSpec {
+            source: "todoist".into(),
+            version: None,
+            opts: Some(json!({ "api_url": api_url, "conflict": conflict })),
+        };
+        let cfg = Config {
+            vault_path: dir.join("vault"),
+            config_path: dir.join("config.lua"),
+            plugins: vec![spec],
+            ..Config::defaults()
+        };
+        fs::write(
+            plugin::secrets_path(&cfg.config_path),
+            r#"{ "todoist": "secret" }"#,
+        )
+        .unwrap();
+        let vault = Vault::new(cfg.clone()).unwrap();
+        vault.init_dirs().unwrap();
+        let (host, errors) = PluginHost::load(&cfg);
+        assert!(errors.is_empty(), "{errors:?}");
+        (host, vault)
+    }
+
+    fn sync(host: &PluginHost) -> String {
+        host.run_command("todoist.sync", &[]).unwrap().unwrap()
+    }
+
+    fn records(vault: &Vault) -> Vec<TaskRecord> {
+        vault.export_json(&Query::default()).unwrap().tasks
+    }
+
+    fn todoist_id(r: &TaskRecord) -> Option<&str> {
+        r.frontmatter
+            .extra
+            .get("todoist_id")
+            .and_then(|v| v.as_str())
+    }
+
+    #[test]
+    fn pull_maps_items_to_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = work();
+        remote.put(item("r1", "Write report", "2030-01-01T00:00:00Z"));
+        let server = FakeTodoist::start(remote);
+        let (host, vault) = setup(dir.path(), &server.url, "newest-wins");
+
+        assert!(sync(&host).starts_with("todoist: 1 pulled, 0 closed, 0 pushed"));
+        let [r] = &records(&vault)[..] else {
+            panic!("expected one task")
+        };
+        let fm = &r.frontmatter;
+        assert_eq!(
+            (fm.title.as_str(), fm.project.as_str(), fm.priority.as_str()),
+            ("Write report", "work", "high")
+        );
+        assert_eq!(
+            (fm.tags.clone(), fm.due.as_deref(), todoist_id(r)),
+            (vec!["urgent".to_string()], Some("2030-05-01"), Some("r1"))
+        );
+        assert!(server
+            .remote()
+            .tokens
+            .iter()
+            .all(|t| t.as_deref() == Some("Bearer secret")));
+
+        // deleted in Todoist: closed here
+        let mut gone = item("r1", "Write report", "2030-01-02T00:00:00Z");
+        gone["is_deleted"] = true.into();
+        server.remote().put(gone);
+        assert!(sync(&host).contains("1 closed"));
+        assert_eq!(
+            vault
+                .cfg
+                .workflow
+                .category(&records(&vault)[0].frontmatter.status),
+            Some(Category::Closed)
+        );
+    }
+
+    #[test]
+    fn push_adds_new_tasks_under_their_mapped_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = FakeTodoist::start(work());
+        let (host, vault) = setup(dir.path(), &server.url, "newest-wins");
+        sync(&host);
+
+        let t = TaskNew {
+            title: "From tm".into(),
+            project: "work".into(),
+            due: None,
+            tags: vec![],
+            parent: None,
+        };
+        vault.create_task(t).unwrap();
+        assert!(sync(&host).contains("1 pushed"));
+        let add = server.remote().commands[0].clone();
+        assert_eq!(
+            (
+                add["type"].as_str(),
+                add["args"]["content"].as_str(),
+                add["args"]["project_id"].as_str()
+            ),
+            (Some("item_add"), Some("From tm"), Some("p1"))
+        );
+        let real = server.remote().items.keys().next().unwrap().clone();
+        assert_eq!(todoist_id(&records(&vault)[0]), Some(real.as_str()));
+
+        // mapped: the next sync adds nothing again
+        assert!(sync(&host).contains("0 pushed"));
+        assert_eq!(server.remote().command_types(), ["item_add"]);
+    }
+
+    #[test]
+    fn new_sub_tasks_are_added_after_their_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = FakeTodoist::start(work());
+        let (host, vault) = setup(dir.path(), &server.url, "newest-wins");
+        sync(&host);
+
+        let new = |title: &str, parent: Option<String>| TaskNew {
+            title: title.into(),
+            project: "work".into(),
+            due: None,
+            tags: vec![],
+            parent,
+        };
+        // the list puts newer tasks first: both children come before their parent
+        let parent = vault.create_task(new("m parent", None)).unwrap();
+        vault
+            .create_task(new("a child", Some(parent.clone())))
+            .unwrap();
+        vault.create_task(new("z child", Some(parent))).unwrap();
+        assert!(sync(&host).contains("3 pushed"));
+
+        let remote = server.remote();
+        let content = |c: &Json| c["args"]["content"].as_str().unwrap().to_string();
+        assert_eq!(content(&remote.commands[0]), "m parent");
+        let temp = remote.commands[0]["temp_id"].clone();
+        for c in &remote.commands[1..] {
+            assert_eq!(c["args"]["parent_id"], temp, "{}", content(c));
+        }
+        assert!(remote.commands[0]["args"].get("parent_id").is_none());
+    }
+
+    #[test]
+    fn the_token_command_needs_a_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let (host, vault) = setup(dir.path(), "http://127.0.0.1:9", "newest-wins");
+        let secrets = plugin::secrets_path(&vault.cfg.config_path);
+        for args in [vec![], vec![" ".to_string()]] {
+            let err = host.run_command("todoist.token", &args).unwrap_err();
+            assert!(
+                err.to_string().contains("usage: :todoist.token <token>"),
+                "{err}"
+            );
+        }
+        assert!(fs::read_to_string(&secrets).unwrap().contains("secret"));
+
+        host.run_command("todoist.token", &["new-token".into()])
+            .unwrap();
+        assert!(fs::read_to_string(&secrets).unwrap().contains("new-token"));
+    }
+
+    #[test]
+    fn conflicts_keep_the_newest_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = work();
+        remote.put(item("r1", "Old", "2020-01-01T00:00:00Z"));
+        remote.put(item("r2", "Old too", "2020-01-01T00:00:00Z"));
+        let server = FakeTodoist::start(remote);
+        let (host, vault) = setup(dir.path(), &server.url, "newest-wins");
+        sync(&host);
+
+        let id_of = |rid: &str| {
+            records(&vault)
+                .into_iter()
+                .find(|r| todoist_id(r) == Some(rid))
+                .unwrap()
+                .frontmatter
+                .id
+        };
+        vault.rename_title(&id_of("r1"), "Edited in tm").unwrap();
+        vault
+            .rename_title(&id_of("r2"), "Edited in tm too")
+            .unwrap();
+        // r1 was edited in Todoist before the tm edit, r2 after it
+        server
+            .remote()
+            .put(item("r1", "Edited in Todoist", "2020-06-01T00:00:00Z"));
+        server
+            .remote()
+            .put(item("r2", "Edited in Todoist", "2999-01-01T00:00:00Z"));
+        assert!(sync(&host).contains("2 conflict(s)"));
+
+        let title = |rid: &str| {
+            records(&vault)
+                .into_iter()
+                .find(|r| todoist_id(r) == Some(rid))
+                .unwrap()
+                .frontmatter
+                .title
+        };
+        assert_eq!(
+            (title("r1").as_str(), title("r2").as_str()),
+            ("Edited in tm", "Edited in Todoist")
+        );
+        let remote = server.remote();
+        assert_eq!(remote.command_types(), ["item_update"]);
+        assert_eq!(
+            (
+                remote.commands[0]["args"]["id"].as_str(),
+                remote.items["r1"]["content"].as_str()
+            ),
+            (Some("r1"), Some("Edited in tm"))
+        );
+    }
+
+    #[test]
+    fn host_wins_keeps_the_tm_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = work();
+        remote.put(item("r1", "Old", "2020-01-01T00:00:00Z"));
+        let server = FakeTodoist::start(remote);
+        let (host, vault) = setup(dir.path(), &server.url, "host-wins");
+        sync(&host);
+
+        vault
+            .rename_title(&records(&vault)[0].frontmatter.id, "Edited in tm")
+            .unwrap();
+        server
+            .remote()
+            .put(item("r1", "Edited in Todoist", "2999-01-01T00:00:00Z"));
+        assert!(sync(&host).contains("1 conflict(s)"));
+        assert_eq!(records(&vault)[0].frontmatter.title, "Edited in tm");
+        assert_eq!(server.remote().items["r1"]["content"], "Edited in tm");
+    }
+
+    #[test]
+    fn only_tombstoned_tasks_are_deleted_remotely() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = work();
+        remote.put(item("r1", "Deleted with tm rm", "2020-01-01T00:00:00Z"));
+        remote.put(item("r2", "File removed by hand", "2020-01-01T00:00:00Z"));
+        let server = FakeTodoist::start(remote);
+        let (host, vault) = setup(dir.path(), &server.url, "newest-wins");
+        sync(&host);
+
+        let id_of = |rid: &str| {
+            records(&vault)
+                .into_iter()
+                .find(|r| todoist_id(r) == Some(rid))
+                .unwrap()
+                .frontmatter
+                .id
+        };
+        let (rm, by_hand) = (id_of("r1"), id_of("r2"));
+        // titles are kept to report deletions once the task file is gone
+        let state: Json = serde_json::from_str(
+            &fs::read_to_string(vault.state_dir().join("plugins").join("todoist.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(state["items"]["r1"]["title"], "Deleted with tm rm");
+        vault.delete_task(&rm).unwrap();
+        fs::remove_file(vault.task_path(&by_hand).unwrap()).unwrap();
+        assert!(sync(&host).contains("1 deleted remotely"));
+
+        let remote = server.remote();
+        assert_eq!(remote.command_types(), ["item_delete"]);
+        assert_eq!(remote.items["r1"]["is_deleted"], true);
+        assert!(remote.items["r2"].get("is_deleted").is_none());
+        // the deletion reached Todoist, so its tombstone goes
+        assert!(vault.deleted_tasks().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_failed_push_keeps_the_pull() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut remote = work();
+        remote.put(item("r1", "From Todoist", "2020-01-01T00:00:00Z"));
+        remote.fail_commands = true;
+        let server = FakeTodoist::start(remote);
+        let (host, vault) = setup(dir.path(), &server.url, "newest-wins");
+        let t = TaskNew {
+            title: "From tm".into(),
+            project: "work".into(),
+            due: None,
+            tags: vec![],
+            parent: None,
+        };
+        vault.create_task(t).unwrap();
+        assert!(host.run_command("todoist.sync", &[]).is_err());
+
+        let state: Json = serde_json::from_str(
+            &fs::read_to_string(vault.state_dir().join("plugins").join("todoist.json")).unwrap(),
+        )
+        .unwrap();
+        assert!(state["sync_token"].is_string());
+        assert!(state["items"]["r1"].is_object());
+
+        // the pulled item is known, not a conflict with itself
+        server.remote().fail_commands = false;
+        let out = sync(&host);
+        assert!(
+            out.contains("0 pulled") && out.contains("1 pushed"),
+            "{out}"
+        );
+        assert!(out.contains("0 conflict(s)"), "{out}");
+    }
+}
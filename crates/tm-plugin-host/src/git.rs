@@ -0,0 +1,79 @@
+//! Thin wrapper over the local `git` binary (works with URLs and local/bare repo paths)
+
+use std::{path::Path, process::Command};
+
+use anyhow::{bail, Context, Result};
+
+fn git(dir: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut cmd = Command::new("git");
+    if let Some(d) = dir {
+        cmd.arg("-C").arg(d);
+    }
+    let out = cmd
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .context("failed to run git (is it installed?)")?;
+    if !out.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+pub fn clone(url: &str, dest: &Path) -> Result<()> {
+    let dest = dest.to_string_lossy();
+    git(None, &["clone", "--quiet", url, &dest])?;
+    Ok(())
+}
+
+pub fn fetch(dir: &Path) -> Result<()> {
+    git(
+        Some(dir),
+        &["fetch", "--quiet", "--tags", "--force", "origin"],
+    )?;
+    Ok(())
+}
+
+pub fn checkout(dir: &Path, rev: &str) -> Result<()> {
+    git(
+        Some(dir),
+        &[
+            "-c",
+            "advice.detachedHead=false",
+            "checkout",
+            "--quiet",
+            rev,
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn head(dir: &Path) -> Result<String> {
+    git(Some(dir), &["rev-parse", "HEAD"])
+}
+
+/// Resolve a ref to a full commit hash; `None` when it does not exist
+pub fn rev_parse(dir: &Path, rev: &str) -> Option<String> {
+    git(
+        Some(dir),
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ],
+    )
+    .ok()
+    .filter(|s| !s.is_empty())
+}
+
+pub fn tags(dir: &Path) -> Result<Vec<String>> {
+    Ok(git(Some(dir), &["tag", "--list"])?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
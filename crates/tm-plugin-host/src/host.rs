@@ -0,0 +1,732 @@
+//! Runs installed Lua plugins: `M.setup(host)` on load, `M.teardown()` before unload/reload
+//!
+//! ```lua
+//! local M = {}
+//! function M.setup(host) host.notify('hello from ' .. host.name) end
+//! function M.teardown() --[[ stop timers, close files ]] end
+//! return M
+//! ```
+//!
+//! Plugins add ex commands with `host.register_command`; the signature drives completion
+//! and the inline hint in the `:` bar:
+//!
+//! ```lua
+//! host.register_command({
+//!   signature = ':todoist.sync [project:<project>]',
+//!   summary = 'pull and push Todoist tasks',
+//!   run = function(args) return 'synced ' .. #args .. ' args' end,
+//! })
+//! ```
+//!
+//! and palette sources with `host.register_palette_source`; `items` runs each time the
+//! palette opens and every entry runs its ex `command` when chosen:
+//!
+//! ```lua
+//! host.register_palette_source({
+//!   name = 'todoist', prefix = '!',
+//!   items = function()
+//!     return { { label = 'Sync now', detail = 'todoist', preview = '...', command = 'todoist.sync' } }
+//!   end,
+//! })
+//! ```
+//!
+//! Quick actions (`<leader>a`) run an ex `command` (`{id}` / `{project}` are filled in; with
+//! `prompt = true` the ex bar opens with it instead). `when(ctx)` decides if the entry is
+//! listed; `ctx.task` is the selected task (or nil) and `ctx.project` the viewed project.
+//!
+//! ```lua
+//! host.register_quick_action({
+//!   id = 'todoist.push', label = 'Push to Todoist', command = 'todoist.push {id}',
+//!   when = function(ctx) return ctx.task ~= nil and ctx.task.category ~= 'closed' end,
+//! })
+//! ```
+//!
+//! `host.opts` is the `opts` table of the plugin's `use({ ..., opts = { ... } })`. HTTP,
+//! secrets, task and state access are in [`crate::api`].
+
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+use anyhow::{anyhow, Context, Result};
+use mlua::{Function, Lua, LuaSerdeExt, Table, Value};
+use serde_json::Value as Json;
+use tm_core::{
+    builtin_commands, plugin, CommandDef, Config, Palette, PaletteItem, PaletteSource,
+    PaletteTarget, QuickAction, QuickActions, QuickContext, QuickRun, Vault, When,
+};
+
+use crate::{api, bundled, lockfile::Lockfile, manager::check_constraint, manifest::Manifest};
+
+pub struct LoadedPlugin {
+    pub manifest: Manifest,
+    module: Option<Table>,
+}
+
+/// One Lua state shared by all active plugins: the ones config.lua lists, installed
+/// (`tm-lock.json`) or bundled. The state is thrown away and rebuilt on reload.
+pub struct PluginHost {
+    lua: Lua,
+    plugins: Vec<LoadedPlugin>,
+    notices: Rc<RefCell<Vec<String>>>,
+    commands: Rc<RefCell<Vec<CommandDef>>>,
+    palette_sources: Rc<RefCell<Vec<PaletteSource>>>,
+    /// quick actions without their predicates (those live in the `tm.quick` registry table)
+    quick_actions: Rc<RefCell<Vec<QuickAction>>>,
+}
+
+/// Registry table holding the `run` functions of plugin commands, by name
+const COMMANDS_KEY: &str = "tm.commands";
+/// Registry table holding the `items` functions of plugin palette sources, by name
+const PALETTE_KEY: &str = "tm.palette";
+/// Registry table holding the `when` predicates of plugin quick actions, by id
+const QUICK_KEY: &str = "tm.quick";
+
+/// `ctx` for quick action predicates
+fn quick_ctx_table(lua: &Lua, ctx: &QuickContext) -> mlua::Result<Table> {
+    let t = lua.create_table()?;
+    if let Some(task) = ctx.task {
+        let tt = lua.create_table()?;
+        tt.set("id", task.id.as_str())?;
+        tt.set("title", task.title.as_str())?;
+        tt.set("status", task.status.as_str())?;
+        // open, active, closed (nil if the workflow doesn't know the status)
+        tt.set("category", task.category.map(|c| c.as_str()))?;
+        tt.set("project", task.project.as_str())?;
+        tt.set("priority", task.priority.as_str())?;
+        tt.set("due", task.due.clone())?;
+        tt.set("tags", task.tags.clone())?;
+        // the timer runs; seconds tracked so far
+        tt.set("tracking", task.timer_running())?;
+        tt.set("actual", task.actual)?;
+        t.set("task", tt)?;
+    }
+    t.set("project", ctx.project)?;
+    Ok(t)
+}
+
+/// The named registry table `key`, created on first use
+/// How much plugins had registered before a `setup` ran
+#[derive(Clone, Copy)]
+struct Mark {
+    commands: usize,
+    palette_sources: usize,
+    quick_actions: usize,
+}
+
+fn registry_table(lua: &Lua, key: &str) -> mlua::Result<Table> {
+    match lua.named_registry_value::<Option<Table>>(key)? {
+        Some(t) => Ok(t),
+        None => {
+            let t = lua.create_table()?;
+            lua.set_named_registry_value(key, &t)?;
+            Ok(t)
+        }
+    }
+}
+
+impl PluginHost {
+    /// A host with no plugins (e.g. before the first load)
+    pub fn empty() -> Self {
+        Self {
+            lua: Lua::new(),
+            plugins: vec![],
+            notices: Rc::default(),
+            commands: Rc::default(),
+            palette_sources: Rc::default(),
+            quick_actions: Rc::default(),
+        }
+    }
+
+    /// Load the installed plugins config.lua lists. Plugins that fail validation or `setup`
+    /// are skipped; their errors are returned so the UI can show them.
+    pub fn load(cfg: &Config) -> (Self, Vec<String>) {
+        let mut host = Self::empty();
+        let errors = host.load_all(cfg);
+        (host, errors)
+    }
+
+    /// `M.teardown()` on every plugin, then a fresh Lua state with the set from `cfg`
+    pub fn reload(&mut self, cfg: &Config) -> Vec<String> {
+        let mut errors = self.teardown();
+        *self = Self::empty();
+        errors.extend(self.load_all(cfg));
+        errors
+    }
+
+    /// Call `M.teardown()` on every plugin (reverse load order) and forget them
+    pub fn teardown(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for p in self.plugins.drain(..).rev() {
+            let Some(module) = p.module else { continue };
+            if let Ok(Value::Function(f)) = module.get::<Value>("teardown") {
+                if let Err(e) = f.call::<()>(()) {
+                    errors.push(format!("{}: teardown failed: {e}", p.manifest.name));
+                }
+            }
+        }
+        errors
+    }
+
+    pub fn plugins(&self) -> &[LoadedPlugin] {
+        &self.plugins
+    }
+
+    /// Ex commands registered by plugins
+    pub fn commands(&self) -> Vec<CommandDef> {
+        self.commands.borrow().clone()
+    }
+
+    /// Run a plugin command with its (already split) arguments. `Ok(None)` if no plugin
+    /// registered `name`; otherwise the string the command returned, if any.
+    pub fn run_command(&self, name: &str, args: &[String]) -> Result<Option<String>> {
+        if !self.commands.borrow().iter().any(|c| c.name == name) {
+            return Ok(None);
+        }
+        let err = |e: mlua::Error| anyhow!(e.to_string());
+        let table: Table = self.lua.named_registry_value(COMMANDS_KEY).map_err(err)?;
+        let run: Function = table.get(name).map_err(err)?;
+        let ret: Value = run.call(args.to_vec()).map_err(|e| {
+            // the message is what the user needs; the Lua traceback is noise in a status line
+            let msg = e.to_string();
+            anyhow!(
+                ":{name} failed: {}",
+                msg.split("\nstack traceback:").next().unwrap_or(&msg)
+            )
+        })?;
+        Ok(Some(match ret {
+            Value::Nil => String::new(),
+            Value::String(s) => s.to_string_lossy().to_string(),
+            other => format!("{other:?}"),
+        }))
+    }
+
+    /// Add the entries of every plugin palette source; sources that fail are skipped and
+    /// their errors returned
+    pub fn extend_palette(&self, palette: &mut Palette) -> Vec<String> {
+        let sources = self.palette_sources.borrow().clone();
+        let mut errors = Vec::new();
+        for src in sources {
+            palette.add_source(&src.name, src.prefix);
+            match self.palette_items(&src.name) {
+                Ok(items) => palette.items.extend(items),
+                Err(e) => errors.push(format!("palette source {}: {e:#}", src.name)),
+            }
+        }
+        errors
+    }
+
+    fn palette_items(&self, name: &str) -> Result<Vec<PaletteItem>> {
+        let err = |e: mlua::Error| anyhow!(e.to_string());
+        let table: Table = self.lua.named_registry_value(PALETTE_KEY).map_err(err)?;
+        let items: Function = table.get(name).map_err(err)?;
+        let list: Table = items.call(()).map_err(err)?;
+        list.sequence_values::<Table>()
+            .map(|entry| {
+                let entry = entry.map_err(err)?;
+                let label: String = entry.get("label").map_err(err)?;
+                let command: String = entry.get("command").map_err(err)?;
+                Ok(PaletteItem {
+                    source: name.to_string(),
+                    label,
+                    detail: entry
+                        .get::<Option<String>>("detail")
+                        .map_err(err)?
+                        .unwrap_or_default(),
+                    preview: entry.get("preview").map_err(err)?,
+                    target: PaletteTarget::Run(command.trim_start_matches(':').to_string()),
+                })
+            })
+            .collect()
+    }
+
+    /// Add the plugin quick actions; a predicate that errors hides its entry
+    pub fn extend_quick_actions(&self, reg: &mut QuickActions) {
+        let preds: Option<Table> = self.lua.named_registry_value(QUICK_KEY).ok().flatten();
+        for qa in self.quick_actions.borrow().iter() {
+            let mut qa = qa.clone();
+            if let Some(when) = preds
+                .as_ref()
+                .and_then(|t| t.get::<Option<Function>>(qa.id.as_str()).ok().flatten())
+            {
+                let lua = self.lua.clone();
+                qa.when = When::Custom(Rc::new(move |ctx: &QuickContext| {
+                    quick_ctx_table(&lua, ctx)
+                        .and_then(|t| when.call::<bool>(t))
+                        .unwrap_or(false)
+                }));
+            }
+            reg.register(qa);
+        }
+    }
+
+    /// Messages plugins sent with `host.notify(msg)` since the last call
+    pub fn take_notices(&self) -> Vec<String> {
+        std::mem::take(&mut *self.notices.borrow_mut())
+    }
+
+    fn load_all(&mut self, cfg: &Config) -> Vec<String> {
+        let lock = match Lockfile::load(&plugin::lockfile_path(&cfg.config_path)) {
+            Ok(l) => l,
+            Err(e) => return vec![format!("{e:#}")],
+        };
+        let dir = plugin::plugins_dir(&cfg.config_path);
+        let mut errors = Vec::new();
+        for (name, entry) in &lock.plugins {
+            // removed from config.lua but not yet `tm plugin sync`ed: installed, not active
+            let Some(spec) = cfg.plugins.iter().find(|s| s.url() == entry.source) else {
+                continue;
+            };
+            if let Err(e) = self.load_one(cfg, &dir.join(name), spec.opts.as_ref()) {
+                errors.push(format!("{name}: {e:#}"));
+            }
+        }
+        for spec in &cfg.plugins {
+            let Some(b) = bundled::find(&spec.source) else {
+                continue;
+            };
+            if lock.plugins.contains_key(b.name) {
+                continue;
+            }
+            if let Err(e) = self.load_bundled(cfg, b, spec.version.as_deref(), spec.opts.as_ref()) {
+                errors.push(format!("{}: {e:#}", b.name));
+            }
+        }
+        errors
+    }
+
+    fn load_one(&mut self, cfg: &Config, dir: &Path, opts: Option<&Json>) -> Result<()> {
+        let manifest = Manifest::load(dir)?;
+        let entry = dir.join(&manifest.entry);
+        let src =
+            fs::read_to_string(&entry).with_context(|| format!("reading {}", entry.display()))?;
+        self.extend_package_path(dir)?;
+        self.activate(cfg, manifest, &src, &entry.to_string_lossy(), opts)
+    }
+
+    fn load_bundled(
+        &mut self,
+        cfg: &Config,
+        b: &bundled::Bundled,
+        constraint: Option<&str>,
+        opts: Option<&Json>,
+    ) -> Result<()> {
+        let manifest = b.manifest()?;
+        manifest.validate(|entry| entry == Path::new(b.entry_path))?;
+        check_constraint(&manifest, constraint)?;
+        self.activate(
+            cfg,
+            manifest,
+            b.entry,
+            &format!("bundled:{}/{}", b.name, b.entry_path),
+            opts,
+        )
+    }
+
+    /// Run a plugin's entry and its `M.setup(host)`
+    fn activate(
+        &mut self,
+        cfg: &Config,
+        manifest: Manifest,
+        src: &str,
+        chunk: &str,
+        opts: Option<&Json>,
+    ) -> Result<()> {
+        let ret = self
+            .lua
+            .load(src)
+            .set_name(chunk)
+            .eval::<Value>()
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let module = match ret {
+            Value::Table(t) => Some(t),
+            _ => None,
+        };
+
+        if let Some(m) = &module {
+            if let Ok(Value::Function(setup)) = m.get::<Value>("setup") {
+                let host = self.host_table(cfg, &manifest, opts)?;
+                let mark = self.mark();
+                if let Err(e) = setup.call::<()>(host) {
+                    self.rollback(mark);
+                    return Err(anyhow!("setup failed: {e}"));
+                }
+            }
+        }
+        self.plugins.push(LoadedPlugin { manifest, module });
+        Ok(())
+    }
+
+    fn mark(&self) -> Mark {
+        Mark {
+            commands: self.commands.borrow().len(),
+            palette_sources: self.palette_sources.borrow().len(),
+            quick_actions: self.quick_actions.borrow().len(),
+        }
+    }
+
+    /// Drop whatever was registered since `mark` (a plugin whose setup failed)
+    fn rollback(&self, mark: Mark) {
+        let commands: Vec<CommandDef> = self.commands.borrow_mut().drain(mark.commands..).collect();
+        let sources: Vec<PaletteSource> = self
+            .palette_sources
+            .borrow_mut()
+            .drain(mark.palette_sources..)
+            .collect();
+        if let Ok(table) = self.lua.named_registry_value::<Table>(COMMANDS_KEY) {
+            for c in commands {
+                let _ = table.set(c.name, Value::Nil);
+            }
+        }
+        if let Ok(table) = self.lua.named_registry_value::<Table>(PALETTE_KEY) {
+            for s in sources {
+                let _ = table.set(s.name, Value::Nil);
+            }
+        }
+        let quick: Vec<QuickAction> = self
+            .quick_actions
+            .borrow_mut()
+            .drain(mark.quick_actions..)
+            .collect();
+        if let Ok(table) = self.lua.named_registry_value::<Table>(QUICK_KEY) {
+            for q in quick {
+                let _ = table.set(q.id, Value::Nil);
+            }
+        }
+    }
+
+    /// `require('x')` inside a plugin resolves against its own directory
+    fn extend_package_path(&self, dir: &Path) -> Result<()> {
+        let package: Table = self
+            .lua
+            .globals()
+            .get("package")
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let cur: String = package.get("path").map_err(|e| anyhow!(e.to_string()))?;
+        let d = dir.to_string_lossy();
+        package
+            .set("path", format!("{d}/?.lua;{d}/lua/?.lua;{cur}"))
+            .map_err(|e| anyhow!(e.to_string()))
+    }
+
+    /// The `host` table passed to `M.setup(host)`
+    fn host_table(&self, cfg: &Config, manifest: &Manifest, opts: Option<&Json>) -> Result<Table> {
+        let lua = &self.lua;
+        let err = |e: mlua::Error| anyhow!(e.to_string());
+        let host = lua.create_table().map_err(err)?;
+        host.set("name", manifest.name.clone()).map_err(err)?;
+        host.set("version", manifest.version.clone()).map_err(err)?;
+        host.set("config", lua.to_value(cfg).map_err(err)?)
+            .map_err(err)?;
+        let opts = match opts {
+            Some(o) => lua.to_value(o).map_err(err)?,
+            None => Value::Table(lua.create_table().map_err(err)?),
+        };
+        host.set("opts", opts).map_err(err)?;
+        host.set("null", lua.null()).map_err(err)?;
+        let array_mt = lua.array_metatable();
+        let array: Function = lua
+            .create_function(move |_, t: Table| {
+                t.set_metatable(Some(array_mt.clone()));
+                Ok(t)
+            })
+            .map_err(err)?;
+        host.set("array", array).map_err(err)?;
+
+        let vault = Vault::new(cfg.clone()).map_err(|e| anyhow!("{e:#}"))?;
+        host.set("http", api::http_table(lua, manifest).map_err(err)?)
+            .map_err(err)?;
+        host.set(
+            "secrets",
+            api::secrets_table(lua, cfg, manifest).map_err(err)?,
+        )
+        .map_err(err)?;
+        host.set("tasks", api::tasks_table(lua, &vault).map_err(err)?)
+            .map_err(err)?;
+        host.set("projects", api::projects_table(lua, &vault).map_err(err)?)
+            .map_err(err)?;
+        host.set(
+            "state",
+            api::state_table(lua, &vault, &manifest.name).map_err(err)?,
+        )
+        .map_err(err)?;
+
+        let notices = self.notices.clone();
+        let name = manifest.name.clone();
+        let notify: Function = lua
+            .create_function(move |_, msg: String| {
+                notices.borrow_mut().push(format!("[{name}] {msg}"));
+                Ok(())
+            })
+            .map_err(err)?;
+        host.set("notify", notify).map_err(err)?;
+
+        let commands = self.commands.clone();
+        let plugin = manifest.name.clone();
+        let register: Function = lua
+            .create_function(move |lua, spec: Table| {
+                let run: Function = spec.get("run")?;
+                let summary: Option<String> = spec.get("summary")?;
+                let signature = match (
+                    spec.get::<Option<String>>("signature")?,
+                    spec.get::<Option<String>>("name")?,
+                ) {
+                    (Some(sig), _) => sig,
+                    (None, Some(name)) => format!(":{name}"),
+                    (None, None) => {
+                        return Err(mlua::Error::runtime(
+                            "register_command: signature or name required",
+                        ))
+                    }
+                };
+                let def = CommandDef::new(
+                    &signature,
+                    &summary.unwrap_or_else(|| format!("from {plugin}")),
+                );
+                if def.name.is_empty() {
+                    return Err(mlua::Error::runtime("register_command: empty command name"));
+                }
+                let taken = builtin_commands().iter().any(|c| c.name == def.name)
+                    || commands.borrow().iter().any(|c| c.name == def.name);
+                if taken {
+                    return Err(mlua::Error::runtime(format!(
+                        ":{} is already defined",
+                        def.name
+                    )));
+                }
+                registry_table(lua, COMMANDS_KEY)?.set(def.name.clone(), run)?;
+                commands.borrow_mut().push(def);
+                Ok(())
+            })
+            .map_err(err)?;
+        host.set("register_command", register).map_err(err)?;
+
+        let sources = self.palette_sources.clone();
+        let register: Function = lua
+            .create_function(move |lua, spec: Table| {
+                let name: String = spec.get("name")?;
+                let items: Function = spec.get("items")?;
+                let prefix = spec
+                    .get::<Option<String>>("prefix")?
+                    .and_then(|p| p.chars().next());
+                if sources.borrow().iter().any(|s| s.name == name) {
+                    return Err(mlua::Error::runtime(format!(
+                        "palette source {name} is already defined"
+                    )));
+                }
+                registry_table(lua, PALETTE_KEY)?.set(name.clone(), items)?;
+                sources.borrow_mut().push(PaletteSource { name, prefix });
+                Ok(())
+            })
+            .map_err(err)?;
+        host.set("register_palette_source", register).map_err(err)?;
+
+        let quick = self.quick_actions.clone();
+        let register: Function = lua
+            .create_function(move |lua, spec: Table| {
+                let label: String = spec.get("label")?;
+                let id = spec
+                    .get::<Option<String>>("id")?
+                    .unwrap_or_else(|| label.clone());
+                let command: String = spec.get("command")?;
+                let command = command.trim_start_matches(':').to_string();
+                let run = if spec.get::<Option<bool>>("prompt")?.unwrap_or(false) {
+                    QuickRun::Prompt(command)
+                } else {
+                    QuickRun::Ex(command)
+                };
+                if QuickActions::builtin().get(&id).is_some()
+                    || quick.borrow().iter().any(|q| q.id == id)
+                {
+                    return Err(mlua::Error::runtime(format!(
+                        "quick action {id} is already defined"
+                    )));
+                }
+                if let Some(when) = spec.get::<Option<Function>>("when")? {
+                    registry_table(lua, QUICK_KEY)?.set(id.as_str(), when)?;
+                }
+                quick
+                    .borrow_mut()
+                    .push(QuickAction::new(&id, &label, When::Always, run));
+                Ok(())
+            })
+            .map_err(err)?;
+        host.set("register_quick_action", register).map_err(err)?;
+        Ok(host)
+    }
+}
+
+impl Drop for PluginHost {
+    fn drop(&mut self) {
+        let _ = self.teardown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tm_core::Origin;
+
+    use super::*;
+    use crate::lockfile::LockEntry;
+
+    /// Install `name` with `init.lua` = `src` under `root/plugins` and pin it in the lockfile;
+    /// returns the source path config.lua lists it by
+    fn install(root: &Path, name: &str, src: &str) -> PathBuf {
+        let source = root.join(format!("{name}-src"));
+        fs::create_dir_all(&source).unwrap();
+        let source = fs::canonicalize(source).unwrap();
+        let dir = root.join("plugins").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(crate::manifest::MANIFEST_FILE),
+            format!("name = \"{name}\"\nid = \"test.{name}\"\nversion = \"1.0.0\"\n"),
+        )
+        .unwrap();
+        fs::write(dir.join("init.lua"), src).unwrap();
+        let lock_path = root.join("tm-lock.json");
+        let mut lock = Lockfile::load(&lock_path).unwrap();
+        lock.plugins.insert(
+            name.into(),
+            LockEntry {
+                source: source.to_string_lossy().into_owned(),
+                constraint: None,
+                commit: "0".repeat(40),
+                version: "1.0.0".into(),
+            },
+        );
+        lock.save(&lock_path).unwrap();
+        source
+    }
+
+    fn config(root: &Path, uses: &[&Path]) -> Config {
+        let list: Vec<String> = uses
+            .iter()
+            .map(|p| format!("{:?}", p.display().to_string()))
+            .collect();
+        let path = root.join("config.lua");
+        fs::write(
+            &path,
+            format!("return {{ plugins = {{ {} }} }}", list.join(", ")),
+        )
+        .unwrap();
+        Config::load_from(&path, Origin::Default).unwrap()
+    }
+
+    fn names(host: &PluginHost) -> Vec<&str> {
+        host.plugins()
+            .iter()
+            .map(|p| p.manifest.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn only_plugins_config_lists_are_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = install(dir.path(), "a", "return {}");
+        let b = install(dir.path(), "b", "return {}");
+
+        let (mut host, errors) = PluginHost::load(&config(dir.path(), &[&a]));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(names(&host), ["a"]);
+
+        let errors = host.reload(&config(dir.path(), &[&a, &b]));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(names(&host), ["a", "b"]);
+
+        assert!(host.reload(&config(dir.path(), &[])).is_empty());
+        assert!(host.plugins().is_empty());
+    }
+
+    #[test]
+    fn reload_tears_plugins_down_before_setting_them_up_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("torn-down");
+        let a = install(
+            dir.path(),
+            "a",
+            &format!(
+                r#"
+local marker = {marker:?}
+local M = {{}}
+function M.setup(host)
+  local f = io.open(marker)
+  if f then f:close(); host.notify('set up after teardown') else host.notify('set up') end
+end
+function M.teardown() io.open(marker, 'w'):close() end
+return M
+"#,
+                marker = marker.display().to_string()
+            ),
+        );
+        let cfg = config(dir.path(), &[&a]);
+
+        let (mut host, errors) = PluginHost::load(&cfg);
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(host.take_notices(), ["[a] set up"]);
+
+        assert!(host.reload(&cfg).is_empty());
+        assert_eq!(host.take_notices(), ["[a] set up after teardown"]);
+    }
+
+    #[test]
+    fn a_failing_setup_skips_the_plugin_and_reports_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = install(
+            dir.path(),
+            "a",
+            "return { setup = function() error('boom') end }",
+        );
+        let b = install(dir.path(), "b", "return {}");
+
+        let (host, errors) = PluginHost::load(&config(dir.path(), &[&a, &b]));
+        assert_eq!(names(&host), ["b"]);
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("a: setup failed") && errors[0].contains("boom"),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn a_failing_setup_leaves_nothing_registered() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = install(
+            dir.path(),
+            "a",
+            r#"return { setup = function(host)
+  host.register_command { name = 'hello', run = function() return 'from a' end }
+  host.register_palette_source { name = 'a', items = function() return {} end }
+  host.register_quick_action { label = 'From a', command = 'hello', when = function() return true end }
+  error('boom')
+end }"#,
+        );
+        let b = install(
+            dir.path(),
+            "b",
+            r#"return { setup = function(host)
+  host.register_command { name = 'hello', run = function() return 'from b' end }
+end }"#,
+        );
+
+        let (host, errors) = PluginHost::load(&config(dir.path(), &[&a, &b]));
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(names(&host), ["b"]);
+        let commands: Vec<String> = host.commands().into_iter().map(|c| c.name).collect();
+        assert_eq!(commands, ["hello"]);
+        assert_eq!(
+            host.run_command("hello", &[]).unwrap().as_deref(),
+            Some("from b")
+        );
+
+        let mut palette = Palette::default();
+        assert!(host.extend_palette(&mut palette).is_empty());
+        assert!(palette.sources.iter().all(|s| s.name != "a"));
+        let mut quick = QuickActions::builtin();
+        host.extend_quick_actions(&mut quick);
+        assert!(quick.get("From a").is_none());
+
+        let (host, _) = PluginHost::load(&config(dir.path(), &[&a]));
+        assert!(host.commands().is_empty());
+        assert_eq!(host.run_command("hello", &[]).unwrap(), None);
+    }
+}
@@ -1,13 +1,28 @@
-//! Minimal Lua host (skeleton)
+//! Minimal Lua host (skeleton) + git-based plugin manager
 use mlua::{Lua, Result as LuaResult}; // note: use mlua::Result
 
+pub mod api;
+pub mod bundled;
+mod git;
+pub mod host;
+pub mod lockfile;
+pub mod manager;
+pub mod manifest;
+
+pub use host::{LoadedPlugin, PluginHost};
+pub use lockfile::{LockEntry, Lockfile};
+pub use manager::{Change, PluginManager};
+pub use manifest::Manifest;
+
 pub fn init_lua() -> LuaResult<Lua> {
     let lua = Lua::new();
     let globals = lua.globals();
-    globals.set("print_host", lua.create_function(|_, msg: String| {
-        println!("[host] {}", msg);
-        Ok(())
-    })?)?;
+    globals.set(
+        "print_host",
+        lua.create_function(|_, msg: String| {
+            println!("[host] {}", msg);
+            Ok(())
+        })?,
+    )?;
     Ok(lua)
 }
-
@@ -0,0 +1,62 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+pub const LOCKFILE_VERSION: u32 = 1;
+
+/// `tm-lock.json`: exact commits for every installed plugin, keyed by plugin name
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    #[serde(default)]
+    pub plugins: BTreeMap<String, LockEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockEntry {
+    /// clone URL or path
+    pub source: String,
+    /// constraint or ref the plugin was installed with (re-resolved on update)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constraint: Option<String>,
+    /// pinned commit
+    pub commit: String,
+    /// manifest version at that commit
+    pub version: String,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Self {
+            version: LOCKFILE_VERSION,
+            plugins: BTreeMap::new(),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Missing file → empty lockfile
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let s = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        serde_json::from_str(&s).with_context(|| format!("invalid lockfile {}", path.display()))
+    }
+
+    /// Pretty, sorted JSON so the lockfile diffs cleanly in dotfile repos
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)? + "\n")?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn find_by_source(&self, source: &str) -> Option<(&String, &LockEntry)> {
+        self.plugins.iter().find(|(_, e)| e.source == source)
+    }
+}
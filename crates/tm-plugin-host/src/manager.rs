@@ -0,0 +1,601 @@
+//! Git-based plugin manager: install/update/remove/sync with `tm-lock.json` pins
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use semver::{Version, VersionReq};
+use tm_core::PluginSpec;
+
+use crate::{
+    bundled, git,
+    lockfile::{LockEntry, Lockfile},
+    manifest::{self, Manifest},
+};
+
+/// Owns the plugins directory and its lockfile. Paths are passed in so tests and
+/// alternative layouts (e.g. a dotfiles checkout) can point it anywhere.
+#[derive(Clone, Debug)]
+pub struct PluginManager {
+    pub plugins_dir: PathBuf,
+    pub lock_path: PathBuf,
+}
+
+/// What a manager operation did to one plugin
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Installed {
+        name: String,
+        version: String,
+        commit: String,
+    },
+    Updated {
+        name: String,
+        from: String,
+        to: String,
+    },
+    UpToDate {
+        name: String,
+    },
+    Removed {
+        name: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Installed {
+                name,
+                version,
+                commit,
+            } => {
+                write!(f, "installed {name} {version} ({})", short(commit))
+            }
+            Change::Updated { name, from, to } => {
+                write!(f, "updated {name} {} -> {}", short(from), short(to))
+            }
+            Change::UpToDate { name } => write!(f, "{name} is up to date"),
+            Change::Removed { name } => write!(f, "removed {name}"),
+        }
+    }
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(8)]
+}
+
+impl PluginManager {
+    pub fn new(plugins_dir: PathBuf, lock_path: PathBuf) -> Self {
+        Self {
+            plugins_dir,
+            lock_path,
+        }
+    }
+
+    pub fn lockfile(&self) -> Result<Lockfile> {
+        Lockfile::load(&self.lock_path)
+    }
+
+    /// `tm plugin install <git-url|path>[@version]`
+    pub fn install(&self, spec: &PluginSpec) -> Result<Change> {
+        let mut lock = self.lockfile()?;
+        let change = self.install_into(&mut lock, spec)?;
+        lock.save(&self.lock_path)?;
+        Ok(change)
+    }
+
+    /// `tm plugin update [name]`: re-resolve constraints (config.lua wins over the lockfile)
+    /// and move to the newest matching commit. A plugin whose new manifest fails
+    /// validation is rolled back to its locked commit. The lockfile is saved after each
+    /// plugin, so an error part-way leaves it matching what is checked out.
+    pub fn update(&self, name: Option<&str>, specs: &[PluginSpec]) -> Result<Vec<Change>> {
+        let mut lock = self.lockfile()?;
+        if let Some(n) = name {
+            if !lock.plugins.contains_key(n) {
+                bail!("plugin '{n}' is not installed");
+            }
+        }
+        let mut changes = Vec::new();
+        let names: Vec<String> = lock
+            .plugins
+            .keys()
+            .filter(|k| name.map(|n| n == k.as_str()).unwrap_or(true))
+            .cloned()
+            .collect();
+
+        for n in names {
+            let entry = lock.plugins[&n].clone();
+            let dir = self.plugins_dir.join(&n);
+            if !dir.exists() {
+                bail!("plugin '{n}' is locked but not on disk; run `tm plugin sync`");
+            }
+            let constraint = specs
+                .iter()
+                .find(|s| s.url() == entry.source)
+                .map(|s| s.version.clone())
+                .unwrap_or(entry.constraint.clone());
+
+            git::fetch(&dir)?;
+            let commit = resolve(&dir, constraint.as_deref())?;
+            if commit == entry.commit {
+                lock.plugins.get_mut(&n).unwrap().constraint = constraint;
+                lock.save(&self.lock_path)?;
+                changes.push(Change::UpToDate { name: n });
+                continue;
+            }
+
+            git::checkout(&dir, &commit)?;
+            let manifest = match validate(&dir, constraint.as_deref()) {
+                Ok(m) => m,
+                Err(e) => {
+                    git::checkout(&dir, &entry.commit)?;
+                    return Err(e.context(format!("update of '{n}' rolled back")));
+                }
+            };
+            lock.plugins.insert(
+                n.clone(),
+                LockEntry {
+                    source: entry.source,
+                    constraint,
+                    commit: commit.clone(),
+                    version: manifest.version,
+                },
+            );
+            lock.save(&self.lock_path)?;
+            changes.push(Change::Updated {
+                name: n,
+                from: entry.commit,
+                to: commit,
+            });
+        }
+        Ok(changes)
+    }
+
+    /// `tm plugin remove <name>`
+    pub fn remove(&self, name: &str) -> Result<Change> {
+        // the name becomes a path under plugins/: no `..`, `/` or empty names
+        if !manifest::valid_name(name) {
+            bail!("'{name}' is not a plugin name (lowercase [a-z0-9_-])");
+        }
+        let mut lock = self.lockfile()?;
+        let dir = self.plugins_dir.join(name);
+        let locked = lock.plugins.remove(name).is_some();
+        if !locked && !dir.exists() {
+            bail!("plugin '{name}' is not installed");
+        }
+        if dir.exists() {
+            fs::remove_dir_all(&dir).with_context(|| format!("removing {}", dir.display()))?;
+        }
+        lock.save(&self.lock_path)?;
+        Ok(Change::Removed {
+            name: name.to_string(),
+        })
+    }
+
+    /// `tm plugin sync`: check out exactly the locked commits (cloning missing plugins),
+    /// then install anything declared in config.lua that is not locked yet. Each install
+    /// is saved to the lockfile as soon as it is activated.
+    pub fn sync(&self, specs: &[PluginSpec]) -> Result<Vec<Change>> {
+        let mut lock = self.lockfile()?;
+        let mut changes = Vec::new();
+
+        for (name, entry) in lock.plugins.clone() {
+            let dir = self.plugins_dir.join(&name);
+            let constraint = specs
+                .iter()
+                .find(|s| s.url() == entry.source)
+                .and_then(|s| s.version.clone());
+
+            if !dir.exists() {
+                let staging = self.staging_dir(&name);
+                let manifest = self.fresh_checkout(
+                    &entry.source,
+                    &staging,
+                    |d| {
+                        git::rev_parse(d, &entry.commit).ok_or_else(|| {
+                            anyhow!(
+                                "locked commit {} not found in {}",
+                                entry.commit,
+                                entry.source
+                            )
+                        })
+                    },
+                    constraint.as_deref(),
+                )?;
+                if manifest.name != name {
+                    fs::remove_dir_all(&staging).ok();
+                    bail!(
+                        "lockfile entry '{name}' resolves to plugin '{}'",
+                        manifest.name
+                    );
+                }
+                self.activate(&staging, &name)?;
+                changes.push(Change::Installed {
+                    name,
+                    version: manifest.version,
+                    commit: entry.commit,
+                });
+                continue;
+            }
+
+            let head = git::head(&dir)?;
+            if head == entry.commit {
+                validate(&dir, constraint.as_deref())?;
+                changes.push(Change::UpToDate { name });
+                continue;
+            }
+            if git::rev_parse(&dir, &entry.commit).is_none() {
+                git::fetch(&dir)?;
+            }
+            git::checkout(&dir, &entry.commit)?;
+            validate(&dir, constraint.as_deref())
+                .with_context(|| format!("locked commit of '{name}' failed validation"))?;
+            changes.push(Change::Updated {
+                name,
+                from: head,
+                to: entry.commit,
+            });
+        }
+
+        for spec in specs.iter().filter(|s| bundled::find(&s.source).is_none()) {
+            if lock.find_by_source(&spec.url()).is_none() {
+                changes.push(self.install_into(&mut lock, spec)?);
+                lock.save(&self.lock_path)?;
+            }
+        }
+        Ok(changes)
+    }
+
+    fn install_into(&self, lock: &mut Lockfile, spec: &PluginSpec) -> Result<Change> {
+        if let Some(b) = bundled::find(&spec.source) {
+            bail!(
+                "{0} comes with tm: enable it with use({{ '{0}' }}) in config.lua",
+                b.name
+            );
+        }
+        let url = spec.url();
+        if let Some((name, _)) = lock.find_by_source(&url) {
+            bail!("{name} is already installed (use `tm plugin update {name}`)");
+        }
+        let staging = self.staging_dir(&spec.name());
+        let constraint = spec.version.as_deref();
+        let manifest =
+            self.fresh_checkout(&url, &staging, |d| resolve(d, constraint), constraint)?;
+        let commit = git::head(&staging)?;
+        self.activate(&staging, &manifest.name)?;
+
+        lock.plugins.insert(
+            manifest.name.clone(),
+            LockEntry {
+                source: url,
+                constraint: spec.version.clone(),
+                commit: commit.clone(),
+                version: manifest.version.clone(),
+            },
+        );
+        Ok(Change::Installed {
+            name: manifest.name,
+            version: manifest.version,
+            commit,
+        })
+    }
+
+    /// Clone into a staging dir, check out the chosen commit and validate the manifest.
+    /// The staging dir is removed on failure, so nothing half-installed is ever activated.
+    fn fresh_checkout(
+        &self,
+        url: &str,
+        staging: &Path,
+        pick: impl FnOnce(&Path) -> Result<String>,
+        constraint: Option<&str>,
+    ) -> Result<Manifest> {
+        fs::create_dir_all(&self.plugins_dir)?;
+        if staging.exists() {
+            fs::remove_dir_all(staging)?;
+        }
+        let res = (|| {
+            git::clone(url, staging)?;
+            let commit = pick(staging)?;
+            git::checkout(staging, &commit)?;
+            validate(staging, constraint)
+        })();
+        if res.is_err() {
+            fs::remove_dir_all(staging).ok();
+        }
+        res
+    }
+
+    fn activate(&self, staging: &Path, name: &str) -> Result<()> {
+        let dest = self.plugins_dir.join(name);
+        if dest.exists() {
+            fs::remove_dir_all(staging).ok();
+            bail!("{} already exists", dest.display());
+        }
+        fs::rename(staging, &dest)?;
+        Ok(())
+    }
+
+    fn staging_dir(&self, name: &str) -> PathBuf {
+        self.plugins_dir.join(format!(".staging-{name}"))
+    }
+}
+
+/// Constraint → semver requirement. A bare version (`1.2.0`) means exactly that version;
+/// operators (`>=1.0.0`, `^1.2`, `~0.3`) and partial versions (`1.2`) are ranges.
+/// Anything else (branch, tag, commit) is a git ref with no requirement.
+fn semver_req(constraint: &str) -> Option<VersionReq> {
+    let c = constraint.trim();
+    if let Ok(v) = Version::parse(c.trim_start_matches('v')) {
+        return VersionReq::parse(&format!("={v}")).ok();
+    }
+    let ranged = c.starts_with(['>', '<', '=', '^', '~', '*'])
+        || (c.contains('.') && c.chars().all(|ch| ch.is_ascii_digit() || ch == '.'));
+    if ranged {
+        VersionReq::parse(c).ok()
+    } else {
+        None
+    }
+}
+
+fn validate(dir: &Path, constraint: Option<&str>) -> Result<Manifest> {
+    let manifest = Manifest::load(dir)?;
+    check_constraint(&manifest, constraint)?;
+    Ok(manifest)
+}
+
+/// Check a manifest against a `use()` constraint; git refs constrain nothing
+pub(crate) fn check_constraint(manifest: &Manifest, constraint: Option<&str>) -> Result<()> {
+    match constraint.and_then(semver_req) {
+        Some(req) => manifest.check_constraint(&req),
+        None => Ok(()),
+    }
+}
+
+/// Pick the commit to check out: newest semver tag matching the constraint,
+/// else the constraint as a branch/tag/commit, else the remote default branch.
+fn resolve(dir: &Path, constraint: Option<&str>) -> Result<String> {
+    let Some(c) = constraint else {
+        return git::rev_parse(dir, "origin/HEAD")
+            .or_else(|| git::rev_parse(dir, "HEAD"))
+            .ok_or_else(|| anyhow!("repository has no commits"));
+    };
+
+    if let Some(req) = semver_req(c) {
+        let best = git::tags(dir)?
+            .into_iter()
+            .filter_map(|t| {
+                Version::parse(t.trim_start_matches('v'))
+                    .ok()
+                    .map(|v| (v, t))
+            })
+            .filter(|(v, _)| req.matches(v))
+            .max_by(|a, b| a.0.cmp(&b.0));
+        if let Some((_, tag)) = best {
+            return git::rev_parse(dir, &format!("refs/tags/{tag}"))
+                .ok_or_else(|| anyhow!("tag {tag} has no commit"));
+        }
+        // fall through: "1.0" might also be a branch name
+    }
+
+    git::rev_parse(dir, &format!("origin/{c}"))
+        .or_else(|| git::rev_parse(dir, c))
+        .ok_or_else(|| anyhow!("no tag, branch or commit matches '{c}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    fn run(dir: &Path, args: &[&str]) -> String {
+        let out = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "git {args:?}: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    }
+
+    /// A plugin's working repo with a bare `origin` next to it
+    struct Repo {
+        work: PathBuf,
+        bare: PathBuf,
+    }
+
+    impl Repo {
+        fn new(root: &Path, name: &str) -> Self {
+            let work = root.join(format!("{name}-src"));
+            let bare = root.join(format!("{name}.git"));
+            fs::create_dir_all(&work).unwrap();
+            run(&work, &["init", "-q", "-b", "main"]);
+            run(root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+            run(&work, &["remote", "add", "origin", bare.to_str().unwrap()]);
+            Self { work, bare }
+        }
+
+        /// Commit `manifest` as plugin.toml, tag `v<tag>` and push; returns the commit
+        fn release(&self, tag: &str, manifest: &str) -> String {
+            fs::write(self.work.join(crate::manifest::MANIFEST_FILE), manifest).unwrap();
+            fs::write(self.work.join("init.lua"), "return {}\n").unwrap();
+            run(&self.work, &["add", "-A"]);
+            run(&self.work, &["commit", "-q", "-m", tag]);
+            run(&self.work, &["tag", &format!("v{tag}")]);
+            run(&self.work, &["push", "-q", "origin", "main", "--tags"]);
+            run(&self.work, &["rev-parse", "HEAD"])
+        }
+
+        fn spec(&self, version: &str) -> PluginSpec {
+            PluginSpec::parse(&format!("{}@{version}", self.bare.display()))
+        }
+    }
+
+    fn manifest(name: &str, version: &str) -> String {
+        format!("name = \"{name}\"\nid = \"test.{name}\"\nversion = \"{version}\"\n")
+    }
+
+    fn manager(root: &Path) -> PluginManager {
+        PluginManager::new(root.join("plugins"), root.join("tm-lock.json"))
+    }
+
+    #[test]
+    fn install_pins_the_newest_matching_tag() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = Repo::new(tmp.path(), "hello");
+        let v1 = repo.release("1.0.0", &manifest("hello", "1.0.0"));
+        let v11 = repo.release("1.1.0", &manifest("hello", "1.1.0"));
+        repo.release("2.0.0", &manifest("hello", "2.0.0"));
+        let m = manager(tmp.path());
+
+        let change = m.install(&repo.spec("^1.0")).unwrap();
+        assert_eq!(
+            change,
+            Change::Installed {
+                name: "hello".into(),
+                version: "1.1.0".into(),
+                commit: v11.clone()
+            }
+        );
+        assert_eq!(git::head(&m.plugins_dir.join("hello")).unwrap(), v11);
+        let entry = &m.lockfile().unwrap().plugins["hello"];
+        assert_eq!(
+            (entry.commit.as_str(), entry.constraint.as_deref()),
+            (v11.as_str(), Some("^1.0"))
+        );
+
+        m.remove("hello").unwrap();
+        assert!(!m.plugins_dir.join("hello").exists());
+        let change = m.install(&repo.spec("1.0.0")).unwrap();
+        assert!(matches!(change, Change::Installed { commit, .. } if commit == v1));
+        assert!(m.install(&repo.spec("1.0.0")).is_err(), "installed twice");
+    }
+
+    #[test]
+    fn remove_refuses_names_that_leave_the_plugins_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let m = manager(tmp.path());
+        fs::create_dir_all(m.plugins_dir.join("kept")).unwrap();
+        let outside = tmp.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        for name in ["..", "", "../outside", "kept/..", outside.to_str().unwrap()] {
+            assert!(m.remove(name).is_err(), "{name:?}");
+        }
+        assert!(outside.exists() && m.plugins_dir.join("kept").exists());
+        assert!(tmp.path().exists());
+    }
+
+    #[test]
+    fn update_moves_to_a_new_release_and_rolls_back_an_invalid_one() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = Repo::new(tmp.path(), "hello");
+        let v1 = repo.release("1.0.0", &manifest("hello", "1.0.0"));
+        let m = manager(tmp.path());
+        m.install(&repo.spec("^1")).unwrap();
+        assert_eq!(
+            m.update(None, &[]).unwrap(),
+            vec![Change::UpToDate {
+                name: "hello".into()
+            }]
+        );
+
+        let v12 = repo.release("1.2.0", &manifest("hello", "1.2.0"));
+        assert_eq!(
+            m.update(Some("hello"), &[]).unwrap(),
+            vec![Change::Updated {
+                name: "hello".into(),
+                from: v1,
+                to: v12.clone()
+            }]
+        );
+        assert_eq!(m.lockfile().unwrap().plugins["hello"].version, "1.2.0");
+
+        // tagged 1.3.0, but the manifest says otherwise
+        repo.release("1.3.0", &manifest("hello", "9.0.0"));
+        assert!(m.update(None, &[]).is_err());
+        assert_eq!(git::head(&m.plugins_dir.join("hello")).unwrap(), v12);
+        assert_eq!(m.lockfile().unwrap().plugins["hello"].commit, v12);
+    }
+
+    #[test]
+    fn update_saves_the_lock_for_plugins_done_before_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b) = (
+            Repo::new(tmp.path(), "alpha"),
+            Repo::new(tmp.path(), "beta"),
+        );
+        a.release("1.0.0", &manifest("alpha", "1.0.0"));
+        b.release("1.0.0", &manifest("beta", "1.0.0"));
+        let m = manager(tmp.path());
+        m.install(&a.spec("^1")).unwrap();
+        m.install(&b.spec("^1")).unwrap();
+
+        let a11 = a.release("1.1.0", &manifest("alpha", "1.1.0"));
+        fs::remove_dir_all(m.plugins_dir.join("beta")).unwrap();
+        assert!(m.update(None, &[]).is_err());
+        let lock = m.lockfile().unwrap();
+        assert_eq!(lock.plugins["alpha"].commit, a11);
+        assert_eq!(git::head(&m.plugins_dir.join("alpha")).unwrap(), a11);
+    }
+
+    #[test]
+    fn sync_checks_out_locked_commits_and_installs_declared_plugins() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b) = (
+            Repo::new(tmp.path(), "alpha"),
+            Repo::new(tmp.path(), "beta"),
+        );
+        let a1 = a.release("1.0.0", &manifest("alpha", "1.0.0"));
+        let b1 = b.release("1.0.0", &manifest("beta", "1.0.0"));
+        let m = manager(tmp.path());
+        m.install(&a.spec("1.0.0")).unwrap();
+        a.release("1.1.0", &manifest("alpha", "1.1.0"));
+
+        // a missing checkout is cloned at the locked commit, not the newest
+        fs::remove_dir_all(m.plugins_dir.join("alpha")).unwrap();
+        let changes = m.sync(&[a.spec("1.0.0"), b.spec("^1")]).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Installed {
+                    name: "alpha".into(),
+                    version: "1.0.0".into(),
+                    commit: a1.clone()
+                },
+                Change::Installed {
+                    name: "beta".into(),
+                    version: "1.0.0".into(),
+                    commit: b1
+                },
+            ]
+        );
+        assert_eq!(m.lockfile().unwrap().plugins.len(), 2);
+
+        // a checkout moved by hand goes back to the lock
+        let alpha = m.plugins_dir.join("alpha");
+        git::fetch(&alpha).unwrap();
+        git::checkout(&alpha, "v1.1.0").unwrap();
+        assert!(matches!(&m.sync(&[]).unwrap()[0], Change::Updated { to, .. } if *to == a1));
+        assert_eq!(git::head(&alpha).unwrap(), a1);
+    }
+
+    #[test]
+    fn sync_saves_each_install_before_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let a = Repo::new(tmp.path(), "alpha");
+        a.release("1.0.0", &manifest("alpha", "1.0.0"));
+        let m = manager(tmp.path());
+        let missing = PluginSpec::parse(&tmp.path().join("nowhere.git").display().to_string());
+        assert!(m.sync(&[a.spec("^1"), missing]).is_err());
+        assert!(m.lockfile().unwrap().plugins.contains_key("alpha"));
+    }
+}
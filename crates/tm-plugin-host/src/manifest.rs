@@ -0,0 +1,106 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+
+/// Plugin manifest (`plugin.toml` at the plugin root)
+///
+/// ```toml
+/// name = "todoist"
+/// id = "tm.todoist"
+/// version = "1.0.0"
+/// entry = "init.lua"
+/// permissions = ["network", "secrets:todoist"]
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    pub id: String,
+    pub version: String,
+    #[serde(default = "default_entry")]
+    pub entry: String,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Plugin names are lowercase `[a-z0-9_-]`: safe as a directory name under `plugins/`
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+fn default_entry() -> String {
+    "init.lua".into()
+}
+
+pub const MANIFEST_FILE: &str = "plugin.toml";
+
+impl Manifest {
+    /// Read and validate `<dir>/plugin.toml`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let src =
+            fs::read_to_string(&path).with_context(|| format!("missing {}", path.display()))?;
+        let m: Manifest =
+            toml::from_str(&src).with_context(|| format!("invalid {}", path.display()))?;
+        m.validate(|entry| dir.join(entry).is_file())?;
+        Ok(m)
+    }
+
+    /// Structural checks run before a plugin is activated; `has_entry` says whether the
+    /// plugin ships the (relative) entry file
+    pub fn validate(&self, has_entry: impl FnOnce(&Path) -> bool) -> Result<()> {
+        if !valid_name(&self.name) {
+            bail!("plugin name '{}' must be lowercase [a-z0-9_-]", self.name);
+        }
+        if self.id.trim().is_empty() {
+            bail!("plugin '{}': empty id", self.name);
+        }
+        self.semver()?;
+
+        let entry = Path::new(&self.entry);
+        if entry.is_absolute() || entry.components().any(|c| c.as_os_str() == "..") {
+            bail!(
+                "plugin '{}': entry must be a path inside the plugin",
+                self.name
+            );
+        }
+        if !has_entry(entry) {
+            bail!("plugin '{}': entry '{}' not found", self.name, self.entry);
+        }
+
+        for p in &self.permissions {
+            let known = matches!(p.as_str(), "network" | "fs") || p.starts_with("secrets:");
+            if !known {
+                bail!("plugin '{}': unknown permission '{p}'", self.name);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn semver(&self) -> Result<Version> {
+        Version::parse(self.version.trim_start_matches('v')).with_context(|| {
+            format!(
+                "plugin '{}': version '{}' is not semver",
+                self.name, self.version
+            )
+        })
+    }
+
+    /// Check the manifest version against a `use({ version = ... })` constraint
+    pub fn check_constraint(&self, req: &VersionReq) -> Result<()> {
+        let v = self.semver()?;
+        if !req.matches(&v) {
+            bail!(
+                "plugin '{}' is {v}, which does not satisfy '{req}'",
+                self.name
+            );
+        }
+        Ok(())
+    }
+}
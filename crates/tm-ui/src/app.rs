@@ -0,0 +1,1774 @@
+//! TUI state and input handling. Every key goes through the keymap of the current mode
+//! (`keymaps.normal`, `.insert`, `.command`, `.search`, `.picker`); unbound printable keys
+//! are typed into the active input.
+
+use std::{collections::HashSet, path::PathBuf, time::Instant};
+
+use ratatui::{prelude::*, widgets::*};
+use tm_core::{
+    apply_sort_arg, builtin_commands, checklist, complete, cycle_sort, date, ex::tokenize,
+    format_duration, fuzzy_match, group_tasks, motion_target, parse_ex, parse_sort, preview,
+    run_ex, signature_hint, sort::sort_tasks, unknown_statuses, Action, Category, Column,
+    CommandDef, Completion, CompletionData, Dispatch, ExEffect, GroupBy, History, KeyDispatcher,
+    KeyOutcome, Keymap, LineEdit, ListLayout, MirrorTrigger, Mode, Palette, PaletteMatch,
+    PaletteTarget, PanePosition, Query, QuickAction, QuickActions, QuickContext, QuickRun, Role,
+    SortSpec, StatusChange, Task, Vault, View,
+};
+use tm_plugin_host::PluginHost;
+
+use crate::theme::Styles;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InputMode {
+    None,
+    Ex,
+    Filter,
+    EditDue,
+    EditTitle,
+    EditTags,
+    Snooze,
+    PickProject,
+    NewProject,
+    Palette,
+    Quick,
+}
+
+impl InputMode {
+    fn keymap_mode(self) -> Mode {
+        match self {
+            InputMode::None => Mode::Normal,
+            InputMode::Ex => Mode::Command,
+            InputMode::Filter => Mode::Search,
+            InputMode::PickProject | InputMode::Palette | InputMode::Quick => Mode::Picker,
+            InputMode::EditDue
+            | InputMode::EditTitle
+            | InputMode::EditTags
+            | InputMode::Snooze
+            | InputMode::NewProject => Mode::Insert,
+        }
+    }
+}
+
+/// A line of the task table: a group header or a task
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ListRow {
+    Group {
+        key: String,
+        label: String,
+        count: usize,
+        collapsed: bool,
+    },
+    Task(usize),
+}
+
+pub(crate) struct App {
+    pub vault: Vault,
+    keymap: Keymap,
+    styles: Styles, // `theme` at the terminal's colour depth
+    plugins: PluginHost,
+    // multi-key sequences, counts and the pending-keys indicator
+    keys: KeyDispatcher,
+
+    tasks: Vec<Task>,
+    rows: Vec<ListRow>, // tasks (indexes into `tasks`) by project, view and filter query, grouped
+    selected: usize,    // index in `rows`
+    list_state: TableState,
+    list_rows: usize, // visible table rows, for Ctrl-d/u and zt/zz/zb
+    // columns, sort and grouping in effect (config ← view ← query ← overrides below)
+    layout: ListLayout,
+    // `:sort`, `o`, `~` and `:group` for this session; cleared when the view changes
+    sort_override: Option<Vec<SortSpec>>,
+    group_override: Option<GroupBy>,
+    collapsed: HashSet<String>, // group keys folded with za/zM
+    // the selected task's checklist in the side pane: Tab focuses it (j/k move `check_idx`),
+    // Space ticks; both reset when another task is selected
+    check_focus: bool,
+    check_idx: usize,
+    check_task: Option<String>,
+
+    input_mode: InputMode,
+    filter: LineEdit,
+    input: LineEdit, // edit panels and the project picker query
+    ex: LineEdit,
+    ex_history: History, // persisted in <vault>/.tm/history
+    filter_history: History,
+    // candidates of the last Tab and the one shown; repeated Tab cycles
+    ex_completion: Option<(Completion, usize)>,
+    // Result area (displayed even after ex-mode closes). is_error=false => green, true => red.
+    ex_result: Option<(bool, String)>,
+
+    // named views (config + .tm/views.json) shown as tabs; None is "All"
+    views: Vec<View>,
+    cur_view: Option<String>,
+    // why the view or `/` query doesn't parse (shown in the list title)
+    query_error: Option<String>,
+
+    projects: Vec<String>,
+    cur_project: Option<String>,
+    project_pick_idx: usize,
+
+    // <leader>p: entries collected on open, ranked for the query in `input`
+    palette: Palette,
+    palette_matches: Vec<PaletteMatch>,
+    palette_idx: usize,
+    // <leader>a: the registry and the entries that applied when the menu opened
+    quick: QuickActions,
+    quick_menu: Vec<QuickAction>,
+    quick_idx: usize,
+
+    pub reload_requested: bool,
+    /// when to sync `todotxt_mirror` and rewrite the `ics` feed
+    mirror: MirrorTrigger,
+    /// open_in_editor: the run loop suspends the TUI and runs `editor` on this file
+    pub editor_request: Option<PathBuf>,
+    pub quit: bool,
+}
+
+fn project_keys(vault: &Vault) -> Vec<String> {
+    let mut keys: Vec<String> = vault
+        .list_projects()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| p.key)
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// A key token that types a character in text modes
+fn typed_char(tok: &str) -> Option<char> {
+    if tok == "Space" {
+        return Some(' ');
+    }
+    let mut chars = tok.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+impl App {
+    pub fn new(vault: Vault) -> Self {
+        // Keymap from config.lua (`keymaps.<mode>` over the defaults)
+        let keymap = Keymap::from_config(&vault.cfg);
+        let (plugins, plugin_errors) = PluginHost::load(&vault.cfg);
+        let ex_result = (!plugin_errors.is_empty())
+            .then(|| (true, format!("plugin errors: {}", plugin_errors.join("; "))));
+        let projects = project_keys(&vault);
+        let ex_history = History::load(&vault.state_dir().join("history"));
+        let mirror = MirrorTrigger::new(&vault.cfg.vault_path);
+        let layout = ListLayout::resolve(&vault.cfg, None, &Query::default());
+        let styles = Styles::from_config(&vault.cfg);
+        let (views, ex_result) = match vault.views() {
+            Ok(v) => (v, ex_result),
+            Err(e) => (vec![], Some((true, format!("{e:#}")))),
+        };
+        Self {
+            vault,
+            keymap,
+            styles,
+            plugins,
+            keys: KeyDispatcher::new(),
+            tasks: vec![],
+            rows: vec![],
+            selected: 0,
+            list_state: TableState::default(),
+            list_rows: 10,
+            layout,
+            sort_override: None,
+            group_override: None,
+            collapsed: HashSet::new(),
+            check_focus: false,
+            check_idx: 0,
+            check_task: None,
+            input_mode: InputMode::None,
+            filter: LineEdit::new(),
+            input: LineEdit::new(),
+            ex: LineEdit::new(),
+            ex_history,
+            filter_history: History::new(),
+            ex_completion: None,
+            ex_result,
+            views,
+            cur_view: None,
+            query_error: None,
+            projects,
+            cur_project: None,
+            project_pick_idx: 0,
+            palette: Palette::default(),
+            palette_matches: vec![],
+            palette_idx: 0,
+            quick: QuickActions::builtin(),
+            quick_menu: vec![],
+            quick_idx: 0,
+            reload_requested: false,
+            mirror,
+            editor_request: None,
+            quit: false,
+        }
+    }
+
+    /// Re-read config.lua and swap in the new vault, keymap and plugin set.
+    /// If the new config does not validate, everything keeps running on the old one.
+    pub fn reload_config(&mut self) {
+        let cfg = match self.vault.cfg.reload() {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                self.ex_result = Some((true, format!("config not reloaded: {e:#}")));
+                return;
+            }
+        };
+        let vault_changed = cfg.vault_path != self.vault.cfg.vault_path;
+        self.keymap = Keymap::from_config(&cfg);
+        self.styles = Styles::from_config(&cfg);
+        self.keys.reset();
+        let errors = self.plugins.reload(&cfg);
+        self.vault.cfg = cfg;
+        self.mirror = MirrorTrigger::new(&self.vault.cfg.vault_path);
+        if vault_changed {
+            self.projects = project_keys(&self.vault);
+            self.ex_history = History::load(&self.vault.state_dir().join("history"));
+            self.cur_project = None;
+            self.selected = 0;
+        }
+        self.ex_result = Some(if errors.is_empty() {
+            (
+                false,
+                format!("config reloaded ({} plugins)", self.plugins.plugins().len()),
+            )
+        } else {
+            (
+                true,
+                format!("config reloaded; plugin errors: {}", errors.join("; ")),
+            )
+        });
+        // a bad view is reported over the summary
+        self.load_views();
+    }
+
+    /// Report how `editor` exited (after open_in_editor)
+    pub fn editor_finished(&mut self, status: std::io::Result<std::process::ExitStatus>) {
+        self.ex_result = match status {
+            Ok(st) if st.success() => None,
+            Ok(st) => Some((true, format!("{} exited with {st}", self.vault.cfg.editor))),
+            Err(e) => Some((
+                true,
+                format!("could not run {}: {e}", self.vault.cfg.editor),
+            )),
+        };
+    }
+
+    /// Show the latest `host.notify` message
+    pub fn poll_plugins(&mut self) {
+        if let Some(n) = self.plugins.take_notices().pop() {
+            self.ex_result = Some((false, n));
+        }
+    }
+
+    /// Sync `todotxt_mirror` and rewrite the `ics` feed when task files change, and read
+    /// back the todo.txt when it was edited outside tm; only edits that came from the
+    /// todo.txt (and errors) are reported
+    pub fn poll_mirror(&mut self) {
+        let cfg = &self.vault.cfg;
+        if cfg.todotxt_mirror.is_none() && cfg.ics.is_none() {
+            return;
+        }
+        let (tasks_changed, edited) = self.mirror.poll(&self.vault, cfg.todotxt_mirror.as_deref());
+        if !(tasks_changed || edited) {
+            return;
+        }
+        if let Some(path) = self.vault.cfg.todotxt_mirror.clone() {
+            match self.vault.sync_todotxt(&path) {
+                Ok(r) if r.changed_vault() => self.ex_result = Some((false, r.summary())),
+                Ok(_) => {}
+                Err(e) => self.ex_result = Some((true, format!("todo.txt mirror: {e:#}"))),
+            }
+        }
+        if let Some(feed) = self.vault.cfg.ics.as_ref().filter(|_| tasks_changed) {
+            if let Err(e) = self.vault.write_ics_feed(feed) {
+                self.ex_result = Some((true, format!("ics feed: {e:#}")));
+            }
+        }
+    }
+
+    /// Re-read the views; a view that no longer exists falls back to "All"
+    fn load_views(&mut self) {
+        match self.vault.views() {
+            Ok(v) => self.views = v,
+            Err(e) => self.ex_result = Some((true, format!("{e:#}"))),
+        }
+        if self.active_view().is_none() {
+            self.cur_view = None;
+        }
+    }
+
+    fn active_view(&self) -> Option<&View> {
+        let name = self.cur_view.as_ref()?;
+        self.views.iter().find(|v| v.name == *name)
+    }
+
+    fn open_view(&mut self, name: Option<String>) {
+        self.cur_view = name;
+        self.filter.clear();
+        self.sort_override = None;
+        self.group_override = None;
+        self.selected = 0;
+    }
+
+    /// gt / gT: step through the tabs, "All" included
+    fn cycle_view(&mut self, forward: bool) {
+        let n = self.views.len() + 1;
+        let cur = self
+            .cur_view
+            .as_ref()
+            .and_then(|name| self.views.iter().position(|v| v.name == *name))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let next = if forward {
+            (cur + 1) % n
+        } else {
+            (cur + n - 1) % n
+        };
+        self.open_view(next.checked_sub(1).map(|i| self.views[i].name.clone()));
+    }
+
+    /// The active view's query narrowed by the `/` filter
+    fn query(&self) -> Result<Query, String> {
+        let view = match self.active_view() {
+            Some(v) => v.parsed().map_err(|e| format!("view {}: {e:#}", v.name))?,
+            None => Query::default(),
+        };
+        let filter = Query::parse(self.filter.text()).map_err(|e| format!("{e:#}"))?;
+        Ok(view.and(filter))
+    }
+
+    /// The order in effect (the configured `sort` when nothing overrides it)
+    fn current_sort(&self) -> Vec<SortSpec> {
+        self.layout
+            .sort
+            .clone()
+            .unwrap_or_else(|| parse_sort(&self.vault.cfg.sort).unwrap_or_default())
+    }
+
+    /// The group the selected row belongs to (its header, or the nearest header above it)
+    fn current_group(&self) -> Option<String> {
+        self.rows[..(self.selected + 1).min(self.rows.len())]
+            .iter()
+            .rev()
+            .find_map(|r| match r {
+                ListRow::Group { key, .. } => Some(key.clone()),
+                ListRow::Task(_) => None,
+            })
+    }
+
+    /// za: fold or unfold the current group and keep the cursor on its header
+    fn toggle_fold(&mut self) {
+        let Some(key) = self.current_group() else {
+            return;
+        };
+        if !self.collapsed.remove(&key) {
+            self.collapsed.insert(key.clone());
+        }
+        self.refresh();
+        if let Some(i) = self
+            .rows
+            .iter()
+            .position(|r| matches!(r, ListRow::Group { key: k, .. } if *k == key))
+        {
+            self.selected = i;
+            self.list_state.select(Some(i));
+        }
+    }
+
+    /// Re-read tasks and recompute the visible rows
+    pub fn refresh(&mut self) {
+        self.tasks = self.vault.list_tasks(None).unwrap_or_default();
+        let query = match self.query() {
+            Ok(q) => {
+                self.query_error = None;
+                q
+            }
+            Err(e) => {
+                self.query_error = Some(e);
+                Query::default()
+            }
+        };
+        self.layout = ListLayout::resolve(&self.vault.cfg, self.active_view(), &query);
+        if let Some(s) = &self.sort_override {
+            self.layout.sort = Some(s.clone());
+        }
+        if let Some(g) = self.group_override {
+            self.layout.group = g;
+        }
+        if let Some(spec) = &self.layout.sort {
+            sort_tasks(&mut self.tasks, spec);
+        }
+        let today = date::today();
+        let visible: Vec<usize> = self
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| {
+                let in_project = self
+                    .cur_project
+                    .as_ref()
+                    .map(|pk| t.project == *pk)
+                    .unwrap_or(true);
+                in_project && query.matches(t, today)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.rows = match self.layout.group {
+            GroupBy::None => visible.into_iter().map(ListRow::Task).collect(),
+            by => {
+                let mut rows = vec![];
+                for g in group_tasks(&self.tasks, &visible, by, today) {
+                    let collapsed = self.collapsed.contains(&g.key);
+                    let count = g.tasks.len();
+                    rows.push(ListRow::Group {
+                        key: g.key,
+                        label: g.label,
+                        count,
+                        collapsed,
+                    });
+                    if !collapsed {
+                        rows.extend(g.tasks.into_iter().map(ListRow::Task));
+                    }
+                }
+                rows
+            }
+        };
+        let len = self.rows.len();
+        if len == 0 {
+            self.selected = 0;
+        } else if self.selected >= len {
+            self.selected = len - 1;
+        }
+        self.list_state.select(Some(self.selected));
+
+        let task = self.selected_task();
+        let id = task.as_ref().map(|t| t.id.clone());
+        if id != self.check_task {
+            self.check_task = id;
+            self.check_idx = 0;
+            self.check_focus = false;
+        }
+        let n = task.map(|t| t.checklist.len()).unwrap_or(0);
+        self.check_idx = self.check_idx.min(n.saturating_sub(1));
+        self.check_focus &= n > 0;
+    }
+
+    fn mode(&self) -> Mode {
+        self.input_mode.keymap_mode()
+    }
+
+    fn selected_id(&self) -> Option<String> {
+        self.selected_task().map(|t| t.id)
+    }
+
+    /// Projects matching the picker query
+    fn picker_items(&self) -> Vec<&String> {
+        let q = self.input.text().to_lowercase();
+        self.projects
+            .iter()
+            .filter(|p| p.to_lowercase().contains(&q))
+            .collect()
+    }
+
+    /// An ambiguous sequence (`g` of `gg`, `3` of `3x`) runs its own binding on timeout
+    pub fn tick(&mut self) {
+        let mode = self.mode();
+        if let Some(out) = self.keys.tick(&self.keymap, Instant::now()) {
+            self.handle_outcome(mode, out);
+        }
+    }
+
+    pub fn on_key(&mut self, tok: &str) {
+        let mode = self.mode();
+        for out in self.keys.feed(&self.keymap, mode, tok, Instant::now()) {
+            self.handle_outcome(mode, out);
+        }
+    }
+
+    fn handle_outcome(&mut self, mode: Mode, out: KeyOutcome) {
+        match out {
+            KeyOutcome::Action(d) => self.dispatch(d),
+            // typing: unbound keys go into the active input
+            KeyOutcome::Unbound(ks) if mode.is_text() && self.mode() == mode => {
+                for c in ks.iter().filter_map(|k| typed_char(k)) {
+                    self.active_input().insert(c);
+                }
+                self.after_edit();
+            }
+            _ => {}
+        }
+    }
+
+    fn active_input(&mut self) -> &mut LineEdit {
+        match self.input_mode {
+            InputMode::Ex => &mut self.ex,
+            InputMode::Filter => &mut self.filter,
+            _ => &mut self.input,
+        }
+    }
+
+    fn after_edit(&mut self) {
+        self.ex_completion = None;
+        match self.input_mode {
+            InputMode::PickProject => self.project_pick_idx = 0,
+            InputMode::Palette => {
+                self.palette_matches = self.palette.filter(self.input.text());
+                self.palette_idx = 0;
+            }
+            InputMode::Quick => self.quick_idx = 0,
+            _ => {}
+        }
+    }
+
+    /// `<leader>p`: collect entries from every source (plugins included)
+    fn open_palette(&mut self) {
+        let mut filters: Vec<(String, String)> = Vec::new();
+        for q in self.filter_history.entries().iter().rev() {
+            if !filters.iter().any(|(_, f)| f == q) {
+                filters.push((format!("/{q}"), q.clone()));
+            }
+        }
+        self.palette = Palette::new(
+            &self.tasks,
+            &self.projects,
+            &self.command_defs(),
+            &self.views,
+            &filters,
+        );
+        self.load_quick_actions();
+        let task = self.selected_task();
+        let ctx = QuickContext {
+            task: task.as_ref(),
+            project: self.cur_project.as_deref(),
+        };
+        self.palette.add_quick_actions(&self.quick.applicable(&ctx));
+        let errors = self.plugins.extend_palette(&mut self.palette);
+        if !errors.is_empty() {
+            self.ex_result = Some((true, errors.join("; ")));
+        }
+        self.open_input(InputMode::Palette);
+        self.after_edit();
+    }
+
+    /// Jump to / run the highlighted palette entry
+    fn choose_palette(&mut self) {
+        let Some(m) = self.palette_matches.get(self.palette_idx) else {
+            return;
+        };
+        match self.palette.items[m.index].target.clone() {
+            PaletteTarget::Task(id) => {
+                self.cur_project = None;
+                self.filter.clear();
+                self.collapsed.clear();
+                self.refresh();
+                let row = self
+                    .rows
+                    .iter()
+                    .position(|r| matches!(r, ListRow::Task(i) if self.tasks[*i].id == id));
+                if let Some(i) = row {
+                    self.selected = i;
+                    self.list_state.select(Some(i));
+                }
+            }
+            PaletteTarget::Project(k) => {
+                self.cur_project = Some(k);
+                self.selected = 0;
+            }
+            PaletteTarget::Tag(tag) => {
+                self.cur_project = None;
+                self.filter.set(format!("+{tag}"));
+                self.selected = 0;
+            }
+            PaletteTarget::Filter(q) => {
+                self.filter.set(q);
+                self.selected = 0;
+            }
+            PaletteTarget::View(name) => self.open_view(Some(name)),
+            PaletteTarget::Command(name) => {
+                let needs_args = self
+                    .command_defs()
+                    .iter()
+                    .find(|c| c.name == name)
+                    .map(|c| c.params().iter().any(|p| !p.optional))
+                    .unwrap_or(false);
+                if needs_args {
+                    self.input_mode = InputMode::Ex;
+                    self.ex.set(format!("{name} "));
+                } else {
+                    self.run_ex(&name);
+                }
+            }
+            PaletteTarget::Run(line) => self.run_ex(&line),
+            PaletteTarget::Quick(id) => {
+                if let Some(qa) = self.quick.get(&id).cloned() {
+                    self.run_quick(&qa);
+                }
+            }
+        }
+    }
+
+    /// Built-in quick actions plus plugin ones
+    fn load_quick_actions(&mut self) {
+        self.quick = QuickActions::builtin();
+        self.plugins.extend_quick_actions(&mut self.quick);
+    }
+
+    fn selected_task(&self) -> Option<Task> {
+        match self.rows.get(self.selected) {
+            Some(ListRow::Task(i)) => Some(self.tasks[*i].clone()),
+            _ => None,
+        }
+    }
+
+    /// `<leader>a`: the verbs for the selected task / current project
+    fn open_quick_actions(&mut self) {
+        self.load_quick_actions();
+        let task = self.selected_task();
+        let ctx = QuickContext {
+            task: task.as_ref(),
+            project: self.cur_project.as_deref(),
+        };
+        self.quick_menu = self.quick.applicable(&ctx).into_iter().cloned().collect();
+        self.open_input(InputMode::Quick);
+        self.quick_idx = 0;
+    }
+
+    /// Quick actions matching the menu's query, best first
+    fn quick_items(&self) -> Vec<(&QuickAction, Vec<usize>)> {
+        let mut scored: Vec<(i64, &QuickAction, Vec<usize>)> = self
+            .quick_menu
+            .iter()
+            .filter_map(|qa| fuzzy_match(self.input.text(), &qa.label).map(|(s, pos)| (s, qa, pos)))
+            .collect();
+        if !self.input.is_empty() {
+            scored.sort_by_key(|(s, _, _)| -s);
+        }
+        scored.into_iter().map(|(_, qa, pos)| (qa, pos)).collect()
+    }
+
+    fn run_quick(&mut self, qa: &QuickAction) {
+        let task = self.selected_task();
+        let ctx = QuickContext {
+            task: task.as_ref(),
+            project: self.cur_project.as_deref(),
+        };
+        match qa.resolve(&ctx) {
+            QuickRun::Action(action) => self.normal_action(Dispatch {
+                action,
+                count: None,
+            }),
+            QuickRun::Ex(line) => self.run_ex(&line),
+            QuickRun::Prompt(text) => {
+                self.input_mode = InputMode::Ex;
+                self.ex.set(text);
+            }
+        }
+    }
+
+    fn dispatch(&mut self, d: Dispatch) {
+        match self.mode() {
+            Mode::Normal => self.normal_action(d),
+            _ => self.input_action(d),
+        }
+    }
+
+    fn open_input(&mut self, mode: InputMode) {
+        self.input_mode = mode;
+        self.input.clear();
+    }
+
+    fn cycle_project(&mut self, forward: bool) {
+        if self.projects.is_empty() {
+            self.cur_project = None;
+            return;
+        }
+        let n = self.projects.len();
+        let idx = self
+            .cur_project
+            .as_ref()
+            .and_then(|k| self.projects.iter().position(|p| p == k))
+            .map(|i| {
+                if forward {
+                    (i + 1) % n
+                } else {
+                    (i + n - 1) % n
+                }
+            })
+            .unwrap_or(0);
+        self.cur_project = Some(self.projects[idx].clone());
+        self.selected = 0;
+    }
+
+    fn normal_action(&mut self, d: Dispatch) {
+        if self.check_focus {
+            let n = self.selected_task().map(|t| t.checklist.len()).unwrap_or(0);
+            if let Some(target) = motion_target(d, self.check_idx, n, n) {
+                self.check_idx = target;
+                return;
+            }
+        }
+        if let Some(target) = motion_target(d, self.selected, self.rows.len(), self.list_rows) {
+            self.selected = target;
+            self.list_state.select(Some(target));
+            return;
+        }
+        let rows = self.list_rows;
+        match d.action {
+            // keep the selection, move the viewport around it
+            Action::ScrollTop => *self.list_state.offset_mut() = self.selected,
+            Action::ScrollCenter => {
+                *self.list_state.offset_mut() = self.selected.saturating_sub(rows / 2)
+            }
+            Action::ScrollBottom => {
+                *self.list_state.offset_mut() = self.selected.saturating_sub(rows.saturating_sub(1))
+            }
+            Action::FocusFilter => self.input_mode = InputMode::Filter,
+            Action::EnterCommand => {
+                self.input_mode = InputMode::Ex;
+                self.ex.clear();
+                // keep last ex_result shown until replaced
+            }
+            Action::Quit => self.quit = true,
+
+            Action::StatusNext
+            | Action::StatusPrev
+            | Action::SetTodo
+            | Action::SetDoing
+            | Action::SetDone
+            | Action::SetCancelled => {
+                let Some(id) = self.selected_id() else { return };
+                let vault = &self.vault;
+                let res: anyhow::Result<StatusChange> = match d.action {
+                    // 3x cycles three steps
+                    Action::StatusNext | Action::StatusPrev => {
+                        let dir = if d.action == Action::StatusNext {
+                            1
+                        } else {
+                            -1
+                        };
+                        (1..d.times()).fold(vault.cycle_status(&id, dir), |s, _| {
+                            s.and_then(|_| vault.cycle_status(&id, dir))
+                        })
+                    }
+                    Action::SetTodo => vault.set_status_category(&id, Category::Open),
+                    Action::SetDoing => vault.set_status_category(&id, Category::Active),
+                    Action::SetDone => vault.set_status_category(&id, Category::Closed),
+                    Action::SetCancelled => vault
+                        .cfg
+                        .workflow
+                        .cancel_status()
+                        .and_then(|s| vault.set_status(&id, s)),
+                    _ => unreachable!(),
+                };
+                self.ex_result = Some(match res {
+                    Ok(change) => change.message(),
+                    Err(e) => (true, e.to_string()),
+                });
+            }
+
+            Action::PickProject => {
+                self.projects = project_keys(&self.vault);
+                self.project_pick_idx = 0;
+                self.open_input(InputMode::PickProject);
+            }
+            Action::NextProject => self.cycle_project(true),
+            Action::PrevProject => self.cycle_project(false),
+            Action::NewProject => self.open_input(InputMode::NewProject),
+            Action::NextView => self.cycle_view(true),
+            Action::PrevView => self.cycle_view(false),
+            Action::SortNext | Action::SortReverse => {
+                let mut spec = self.current_sort();
+                if d.action == Action::SortNext {
+                    spec = cycle_sort(&spec);
+                } else if let Some(first) = spec.first_mut() {
+                    first.desc = !first.desc;
+                }
+                self.ex_result = Some((false, format!("sort: {}", tm_core::format_sort(&spec))));
+                self.sort_override = Some(spec);
+            }
+            Action::ToggleFold => self.toggle_fold(),
+            Action::OpenFolds => self.collapsed.clear(),
+            Action::CloseFolds => {
+                self.collapsed = self
+                    .rows
+                    .iter()
+                    .filter_map(|r| match r {
+                        ListRow::Group { key, .. } => Some(key.clone()),
+                        ListRow::Task(_) => None,
+                    })
+                    .collect();
+                self.selected = 0;
+            }
+            Action::EditDue => self.open_input(InputMode::EditDue),
+            Action::EditTitle => self.open_input(InputMode::EditTitle),
+            Action::EditTags => self.open_input(InputMode::EditTags),
+            Action::Snooze => self.open_input(InputMode::Snooze),
+            Action::OpenPalette => self.open_palette(),
+            Action::QuickActions => self.open_quick_actions(),
+            Action::ToggleTimer => {
+                let Some(id) = self.selected_id() else { return };
+                self.ex_result = Some(match self.vault.toggle_timer(&id) {
+                    Ok(change) => change.message(),
+                    Err(e) => (true, e.to_string()),
+                });
+            }
+            Action::FocusDetail => match self.selected_task() {
+                Some(t) if !t.checklist.is_empty() => self.check_focus = !self.check_focus,
+                Some(_) => {
+                    self.ex_result =
+                        Some((false, r#"no checklist (:check.add "text" adds one)"#.into()))
+                }
+                None => {}
+            },
+            Action::ToggleCheck => {
+                // only the focused checklist: a stray Space must not tick anything
+                if !self.check_focus {
+                    return;
+                }
+                let Some(id) = self.selected_id() else { return };
+                // `3 Space` ticks the third item
+                let idx = d
+                    .count
+                    .map(|c| (c as usize).saturating_sub(1))
+                    .unwrap_or(self.check_idx);
+                self.ex_result = Some(match self.vault.toggle_check(&id, idx) {
+                    Ok(item) => (
+                        false,
+                        format!("{} {}", if item.done { "✔" } else { "☐" }, item.text),
+                    ),
+                    Err(e) => (true, e.to_string()),
+                });
+                self.check_idx = idx;
+            }
+            Action::OpenInEditor => match self.selected_id().map(|id| self.vault.task_path(&id)) {
+                Some(Ok(path)) => self.editor_request = Some(path),
+                Some(Err(e)) => self.ex_result = Some((true, e.to_string())),
+                None => {}
+            },
+
+            // motions are handled above; input editing only applies in text modes
+            _ => {}
+        }
+    }
+
+    fn input_action(&mut self, d: Dispatch) {
+        if self.active_input().apply(d.action) {
+            self.after_edit();
+            return;
+        }
+        match d.action {
+            Action::Submit => self.submit(),
+            Action::Cancel => self.cancel(),
+            Action::HistoryPrev | Action::HistoryNext => {
+                let prev = d.action == Action::HistoryPrev;
+                let (hist, line) = match self.input_mode {
+                    InputMode::Ex => (&mut self.ex_history, &mut self.ex),
+                    InputMode::Filter => (&mut self.filter_history, &mut self.filter),
+                    _ => return,
+                };
+                let entry = if prev {
+                    hist.older(line.text())
+                } else {
+                    hist.newer()
+                };
+                if let Some(e) = entry.map(str::to_string) {
+                    line.set(e);
+                }
+                self.ex_completion = None;
+            }
+            Action::Complete if self.input_mode == InputMode::Ex => self.complete_ex(),
+            Action::MoveDown
+                if self.input_mode == InputMode::PickProject
+                    && self.project_pick_idx + 1 < self.picker_items().len() =>
+            {
+                self.project_pick_idx += 1;
+            }
+            Action::MoveUp if self.input_mode == InputMode::PickProject => {
+                self.project_pick_idx = self.project_pick_idx.saturating_sub(1);
+            }
+            Action::MoveDown
+                if self.input_mode == InputMode::Palette
+                    && self.palette_idx + 1 < self.palette_matches.len() =>
+            {
+                self.palette_idx += 1;
+            }
+            Action::MoveUp if self.input_mode == InputMode::Palette => {
+                self.palette_idx = self.palette_idx.saturating_sub(1);
+            }
+            Action::MoveDown
+                if self.input_mode == InputMode::Quick
+                    && self.quick_idx + 1 < self.quick_items().len() =>
+            {
+                self.quick_idx += 1;
+            }
+            Action::MoveUp if self.input_mode == InputMode::Quick => {
+                self.quick_idx = self.quick_idx.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    /// Built-in ex commands plus those registered by plugins
+    fn command_defs(&self) -> Vec<CommandDef> {
+        let mut defs = builtin_commands();
+        defs.extend(self.plugins.commands());
+        defs
+    }
+
+    fn completion_data(&self) -> CompletionData {
+        let mut tags: Vec<String> = self
+            .tasks
+            .iter()
+            .flat_map(|t| t.tags.iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        CompletionData {
+            projects: self.projects.clone(),
+            tags,
+            tasks: self.tasks.iter().map(|t| t.id.clone()).collect(),
+            views: self.views.iter().map(|v| v.name.clone()).collect(),
+            statuses: self.vault.cfg.workflow.names(),
+        }
+    }
+
+    /// Tab in the ex bar: complete the word under the cursor from the command signatures.
+    /// One candidate is taken as is; several insert their common prefix and list them,
+    /// and further Tabs cycle through them.
+    fn complete_ex(&mut self) {
+        if let Some((c, i)) = &mut self.ex_completion {
+            let shown = c.candidates[*i].len();
+            *i = (*i + 1) % c.candidates.len();
+            let (start, next) = (c.start, c.candidates[*i].clone());
+            self.ex.replace_range(start, start + shown, &next);
+            return;
+        }
+        let c = complete(
+            self.ex.text(),
+            self.ex.cursor(),
+            &self.command_defs(),
+            &self.completion_data(),
+        );
+        match c.candidates.as_slice() {
+            [] => {}
+            [one] => {
+                // keep typing right after `project:` / `+`, otherwise move on to the next word
+                let sep = if one.ends_with(':') || one.ends_with('+') {
+                    ""
+                } else {
+                    " "
+                };
+                self.ex
+                    .replace_range(c.start, c.end, &format!("{one}{sep}"));
+            }
+            many => {
+                self.ex_result = Some((false, many.join("  ")));
+                let common = c.common_prefix();
+                if common.len() > c.end - c.start {
+                    self.ex.replace_range(c.start, c.end, &common);
+                } else {
+                    self.ex.replace_range(c.start, c.end, &many[0]);
+                    self.ex_completion = Some((c, 0));
+                }
+            }
+        }
+    }
+
+    fn cancel(&mut self) {
+        match self.input_mode {
+            InputMode::Ex => {
+                self.ex.clear();
+                self.ex_history.reset();
+                self.ex_completion = None;
+                // keep last result displayed
+            }
+            InputMode::Filter => self.filter_history.reset(),
+            _ => self.input.clear(),
+        }
+        self.input_mode = InputMode::None;
+    }
+
+    fn submit(&mut self) {
+        let mode = std::mem::replace(&mut self.input_mode, InputMode::None);
+        match mode {
+            InputMode::None => {}
+            InputMode::Ex => {
+                let line = self.ex.text().trim().trim_start_matches(':').to_string();
+                self.ex.clear();
+                self.ex_completion = None;
+                self.run_ex(&line);
+                if let Err(e) = self.ex_history.push(&line) {
+                    self.ex_result = Some((true, format!("{e:#}")));
+                }
+            }
+            InputMode::Filter => {
+                let _ = self.filter_history.push(self.filter.text());
+            }
+            InputMode::EditDue | InputMode::EditTitle | InputMode::EditTags => {
+                if let Some(id) = self.selected_id() {
+                    let text = self.input.text();
+                    let res = match mode {
+                        InputMode::EditDue => self.vault.set_due(&id, text),
+                        InputMode::EditTitle => self.vault.rename_title(&id, text),
+                        _ => self.vault.set_tags_csv(&id, text),
+                    };
+                    self.ex_result = Some(match res {
+                        Ok(_) => (false, "saved".into()),
+                        Err(e) => (true, e.to_string()),
+                    });
+                }
+                self.input.clear();
+            }
+            InputMode::Snooze => {
+                if let Some(id) = self.selected_id() {
+                    self.ex_result = Some(match self.vault.snooze(&id, self.input.text()) {
+                        Ok(Some(d)) => (
+                            false,
+                            format!("snoozed until {}", self.vault.cfg.display_date(&d)),
+                        ),
+                        Ok(None) => (false, "no longer deferred".into()),
+                        Err(e) => (true, e.to_string()),
+                    });
+                }
+                self.input.clear();
+            }
+            InputMode::PickProject => {
+                let items = self.picker_items();
+                if items.is_empty() {
+                    self.cur_project = None;
+                } else {
+                    let idx = self.project_pick_idx.min(items.len() - 1);
+                    self.cur_project = Some(items[idx].clone());
+                    self.selected = 0;
+                }
+                self.input.clear();
+            }
+            InputMode::NewProject => {
+                let title = self.input.text().trim().to_string();
+                if !title.is_empty() {
+                    self.ex_result = Some(self.create_project(&title, vec![]));
+                }
+                self.input.clear();
+            }
+            InputMode::Palette => {
+                self.choose_palette();
+                self.input.clear();
+            }
+            InputMode::Quick => {
+                let chosen = self
+                    .quick_items()
+                    .get(self.quick_idx)
+                    .map(|(qa, _)| (*qa).clone());
+                self.input.clear();
+                if let Some(qa) = chosen {
+                    self.run_quick(&qa);
+                }
+            }
+        }
+    }
+
+    fn create_project(&mut self, title: &str, tags: Vec<String>) -> (bool, String) {
+        match self.vault.create_project(tm_core::ProjectNew {
+            title: title.to_string(),
+            tags,
+        }) {
+            Ok(k) => (false, self.show_project(k)),
+            Err(e) => (true, e.to_string()),
+        }
+    }
+
+    /// Refresh projects and jump into a newly created one
+    fn show_project(&mut self, key: String) -> String {
+        self.projects = project_keys(&self.vault);
+        let msg = format!("created project {key}");
+        self.cur_project = Some(key);
+        self.selected = 0;
+        msg
+    }
+
+    /// Run + display result
+    fn run_ex(&mut self, line: &str) {
+        let mut args = tokenize(line);
+        if !args.is_empty() {
+            let name = args.remove(0);
+            match self.plugins.run_command(&name, &args) {
+                Ok(None) => {}
+                Ok(Some(msg)) => {
+                    self.ex_result = Some((
+                        false,
+                        if msg.is_empty() {
+                            format!(":{name} done")
+                        } else {
+                            msg
+                        },
+                    ));
+                    return;
+                }
+                Err(e) => {
+                    self.ex_result = Some((true, format!("{e:#}")));
+                    return;
+                }
+            }
+        }
+        let selected = self.selected_id();
+        let effect = parse_ex(line).and_then(|cmd| run_ex(&self.vault, cmd, selected.as_deref()));
+        let msg = match effect {
+            Ok(ExEffect::ReloadConfig) => {
+                self.reload_requested = true;
+                return;
+            }
+            Ok(ExEffect::Done(m)) => m,
+            Ok(ExEffect::Warning(m)) => {
+                self.ex_result = Some((true, m));
+                return;
+            }
+            Ok(ExEffect::OpenProject(key)) => {
+                self.selected = 0;
+                let msg = match &key {
+                    Some(k) => format!("opened project {k}"),
+                    None => "opened all projects".into(),
+                };
+                self.cur_project = key;
+                msg
+            }
+            Ok(ExEffect::ProjectCreated(key)) => self.show_project(key),
+            Ok(ExEffect::Sort(arg)) => match arg {
+                None => {
+                    self.sort_override = None;
+                    "sort reset".into()
+                }
+                Some(a) => match apply_sort_arg(&self.current_sort(), &a) {
+                    Ok(spec) => {
+                        let msg = format!("sort: {}", tm_core::format_sort(&spec));
+                        self.sort_override = Some(spec);
+                        msg
+                    }
+                    Err(e) => {
+                        self.ex_result = Some((true, format!("{e:#}")));
+                        return;
+                    }
+                },
+            },
+            Ok(ExEffect::Group(by)) => {
+                self.group_override = by;
+                self.selected = 0;
+                format!("group: {}", by.unwrap_or(self.layout.group).as_str())
+            }
+            Ok(ExEffect::OpenView(name)) => {
+                let msg = match &name {
+                    Some(n) => format!("opened view {n}"),
+                    None => "opened all tasks".into(),
+                };
+                self.load_views();
+                self.open_view(name);
+                msg
+            }
+            Ok(ExEffect::SaveView(name)) => {
+                let view =
+                    View::save_current(&name, self.active_view(), self.filter.text(), &self.layout);
+                if let Err(e) = self.vault.save_view(&view) {
+                    self.ex_result = Some((true, format!("{e:#}")));
+                    return;
+                }
+                self.load_views();
+                self.open_view(Some(name.clone()));
+                format!("saved view {name}")
+            }
+            Ok(ExEffect::ViewsChanged(msg)) => {
+                self.load_views();
+                msg
+            }
+            Err(e) => {
+                self.ex_result = Some((true, e.to_string()));
+                return;
+            }
+        };
+        self.ex_result = Some((false, msg));
+    }
+
+    // ---------- Draw ----------
+    pub fn draw(&mut self, f: &mut Frame) {
+        let area = f.area();
+        f.render_widget(Block::default().style(self.styles.get(Role::Text)), area);
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Min(1),
+                Constraint::Length(3),
+            ])
+            .split(area);
+
+        // Header: project, then the view tabs (gt/gT)
+        let mut hdr = vec![Span::raw(format!(
+            "Project: {}  │ ",
+            self.cur_project.as_deref().unwrap_or("(all)")
+        ))];
+        let tabs = std::iter::once(None).chain(self.views.iter().map(|v| Some(v.name.as_str())));
+        for (i, name) in tabs.enumerate() {
+            if i > 0 {
+                hdr.push(Span::raw(" "));
+            }
+            let label = format!(" {} ", name.unwrap_or("All"));
+            if name == self.cur_view.as_deref() {
+                hdr.push(Span::styled(label, self.styles.get(Role::TabActive)));
+            } else {
+                hdr.push(Span::raw(label));
+            }
+        }
+        f.render_widget(Paragraph::new(Line::from(hdr)), rows[0]);
+
+        // Main area: task list + side pane (position/size from `layout` in config)
+        let layout = &self.vault.cfg.layout;
+        let pane = layout.pane_size.min(90);
+        let cols = match layout.pane {
+            PanePosition::Right | PanePosition::Bottom => Layout::default()
+                .direction(if layout.pane == PanePosition::Right {
+                    Direction::Horizontal
+                } else {
+                    Direction::Vertical
+                })
+                .constraints([
+                    Constraint::Percentage(100 - pane),
+                    Constraint::Percentage(pane),
+                ])
+                .split(rows[1]),
+            PanePosition::Hidden => Layout::default()
+                .constraints([Constraint::Percentage(100)])
+                .split(rows[1]),
+        };
+
+        // Left: tasks
+        let mut block = self.block("Tasks");
+        if let Some(e) = &self.query_error {
+            block = block.title(Span::styled(format!(" {e} "), self.styles.get(Role::Error)));
+        }
+        // hand-typed statuses are shown as they are, and flagged
+        let unknown = unknown_statuses(&self.tasks);
+        if !unknown.is_empty() {
+            let msg = format!(" unknown status: {} (is:unknown) ", unknown.join(", "));
+            block = block.title(Span::styled(msg, self.styles.get(Role::Error)));
+        }
+        if let Some(t) = self.tasks.iter().find(|t| t.timer_running()) {
+            let msg = format!(" ⏱ {} {} ", t.title, format_duration(t.actual));
+            block = block.title(
+                Line::from(Span::styled(msg, self.styles.get(Role::Success))).right_aligned(),
+            );
+        }
+        if self.keys.is_pending() {
+            block = block
+                .title(Line::from(format!(" {} ", self.keys.pending_display())).right_aligned());
+        }
+        // borders and the header line
+        self.list_rows = cols[0].height.saturating_sub(3).max(1) as usize;
+        let table = self.task_table().block(block);
+        f.render_stateful_widget(table, cols[0], &mut self.list_state);
+
+        // Right: HELP (multiline)
+        let help_text = [
+            "Navigation:",
+            "  j/k, gg/G, Ctrl-d/u, zt/zz/zb, q (quit)",
+            "  counts: 5j · 3x · 10G · Esc cancels pending keys",
+            "",
+            "Filtering & Projects:",
+            "  / filter · O pick project · ]/[ next/prev project · P new project",
+            "  query: +tag status:!done due<=today p>=med is:overdue sort:p,due",
+            "  gt/gT next/prev view · :view <name> · :view.save <name>",
+            "  o next sort key · ~ reverse · :sort due,!p · :sort +title (add key)",
+            "  :group (project|status|due|tag) · za fold · zR/zM open/close all",
+            "",
+            "Status:",
+            "  x next · X cancel · 1 todo · 2 doing · 3 done (workflow in config)",
+            "",
+            "Edits:",
+            "  D due · R rename · T tags",
+            "",
+            "Inputs:",
+            "  ←/→ Ctrl-a/e Alt-b/f move · Ctrl-w word · Ctrl-u/k line",
+            "  ↑/↓ history (prefix search) · Tab complete · Enter/Esc",
+            "",
+            "Palette:",
+            "  <leader>p fuzzy: tasks · @projects · #tags · >commands · filters",
+            "  <leader>a quick actions for the selected task · E open in $EDITOR",
+            "",
+            "Ex commands:",
+            "  :new \"Title\" project:<slug> +tag due:YYYY-MM-DD",
+            "  :status [<id>] (<status>|next|prev)",
+            "  :open project:<slug>",
+            "  :move project:<slug> · :priority (none|low|med|high)",
+            "  t timer start/stop · :estimate 1h30m · :timer stop",
+            "  s snooze (1d, mon, next week) · is:deferred",
+            "  :dep (add|rm) <id> — selected waits for <id> (⊘ blocked)",
+            "  :promote — note checkbox → task file",
+            "  :check.add \"text\" — checklist item · Tab focus checklist · Space tick",
+            "  :project.new \"Title\" +tag",
+            "  :config.reload",
+            "  plugins add more (host.register_command)",
+            "",
+            "Config:",
+            "  ~/.config/tm/config.lua — reloads on save or :config.reload",
+            "  keymaps.<normal|insert|command|search|picker>",
+        ]
+        .join("\n");
+
+        if layout.pane != PanePosition::Hidden {
+            let mut help_area = cols[1];
+            if let Some(t) = self.selected_task().filter(|t| !t.checklist.is_empty()) {
+                let height = (t.checklist.len() as u16 + 2).min(cols[1].height / 2);
+                let split = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(height), Constraint::Min(0)])
+                    .split(cols[1]);
+                self.draw_checklist(f, split[0], &t);
+                help_area = split[1];
+            }
+            let right = Paragraph::new(help_text)
+                .block(self.block("Help"))
+                .wrap(Wrap { trim: false });
+            f.render_widget(right, help_area);
+        }
+
+        // Bottom: the active input, else the last result
+        let (prompt, title, line) = match self.input_mode {
+            InputMode::Ex => (":", "command", &self.ex),
+            InputMode::Filter => ("/", "Filter", &self.filter),
+            InputMode::EditDue => ("due> ", "Set Due (Enter/Esc)", &self.input),
+            InputMode::EditTitle => ("title> ", "Rename (Enter/Esc)", &self.input),
+            InputMode::EditTags => ("tags> ", "Set Tags (Enter/Esc)", &self.input),
+            InputMode::Snooze => (
+                "until> ",
+                "Snooze: 1d, mon, next week, none (Enter/Esc)",
+                &self.input,
+            ),
+            InputMode::NewProject => ("project> ", "New Project Title (Enter/Esc)", &self.input),
+            InputMode::PickProject => {
+                self.draw_picker(f, rows[1]);
+                ("project> ", "Pick project (↑/↓, Enter, Esc)", &self.input)
+            }
+            InputMode::Quick => {
+                self.draw_quick(f, cols[0]);
+                ("action> ", "Quick actions (↑/↓, Enter, Esc)", &self.input)
+            }
+            InputMode::Palette => {
+                self.draw_palette(f, rows[1]);
+                (
+                    "> ",
+                    "Palette (↑/↓, Enter, Esc · >commands #tags @projects)",
+                    &self.input,
+                )
+            }
+            InputMode::None => {
+                let bottom = match &self.ex_result {
+                    // If we have a recent result, show it here (colored).
+                    Some((is_err, msg)) => {
+                        let style =
+                            self.styles
+                                .get(if *is_err { Role::Error } else { Role::Success });
+                        Paragraph::new(Span::styled(msg.clone(), style)).block(self.block("result"))
+                    }
+                    None => Paragraph::new("").block(self.block("Command")),
+                };
+                f.render_widget(bottom, rows[2]);
+                return;
+            }
+        };
+        let mut spans = vec![Span::raw(prompt), Span::raw(line.text())];
+        if self.input_mode == InputMode::Ex {
+            if let Some(hint) = signature_hint(line.text(), &self.command_defs()) {
+                spans.push(Span::styled(
+                    format!("   {hint}"),
+                    self.styles.get(Role::Hint),
+                ));
+            }
+        }
+        let p = Paragraph::new(Line::from(spans)).block(self.block(title));
+        f.render_widget(p, rows[2]);
+        let x = rows[2].x + 1 + (prompt.chars().count() + line.cursor_chars()) as u16;
+        f.set_cursor_position((x.min(rows[2].right().saturating_sub(2)), rows[2].y + 1));
+    }
+
+    /// The selected task's checklist above the help; the cursor shows while it has focus
+    fn draw_checklist(&self, f: &mut Frame, area: Rect, t: &Task) {
+        let (done, total) = checklist::progress(&t.checklist);
+        let items: Vec<ListItem> = t
+            .checklist
+            .iter()
+            .map(|c| {
+                let (mark, role) = if c.done {
+                    ("[x] ", Role::Muted)
+                } else {
+                    ("[ ] ", Role::Text)
+                };
+                ListItem::new(Span::styled(
+                    format!("{mark}{}", c.text),
+                    self.styles.get(role),
+                ))
+            })
+            .collect();
+        let title = if self.check_focus {
+            format!("Checklist {done}/{total} (Tab: back)")
+        } else {
+            format!("Checklist {done}/{total}")
+        };
+        let mut list = List::new(items).block(self.block(title));
+        let mut state = ListState::default();
+        if self.check_focus {
+            list = list
+                .highlight_symbol("➤ ")
+                .highlight_style(self.styles.get(Role::Selection));
+            state.select(Some(self.check_idx));
+        }
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// A bordered pane in the theme's border and title styles
+    fn block<'a>(&self, title: impl Into<Line<'a>>) -> Block<'a> {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(self.styles.get(Role::Border))
+            .title_style(self.styles.get(Role::Title))
+            .title(title)
+    }
+
+    /// The task list as a table: a header with sort markers, group header rows, and column
+    /// widths from the layout (unsized columns fit their content, the title takes the rest)
+    fn task_table(&self) -> Table<'static> {
+        let cfg = &self.vault.cfg;
+        let today = date::today();
+        let columns = &self.layout.columns;
+        let sort = self.current_sort();
+        let header: Vec<String> = columns
+            .iter()
+            .map(|c| {
+                let pos = c
+                    .column
+                    .sort_key()
+                    .and_then(|k| sort.iter().position(|s| s.key == k));
+                match pos {
+                    Some(i) => {
+                        let arrow = if sort[i].desc { "▼" } else { "▲" };
+                        if sort.len() > 1 {
+                            format!("{}{arrow}{}", c.column.title(), i + 1)
+                        } else {
+                            format!("{}{arrow}", c.column.title())
+                        }
+                    }
+                    None => c.column.title().to_string(),
+                }
+            })
+            .collect();
+
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|r| match r {
+                ListRow::Task(i) => columns
+                    .iter()
+                    .map(|c| c.column.cell(&self.tasks[*i], cfg))
+                    .collect(),
+                ListRow::Group { .. } => vec![],
+            })
+            .collect();
+        let widths: Vec<Constraint> = columns
+            .iter()
+            .enumerate()
+            .map(|(ci, c)| match (c.width, c.column) {
+                (Some(w), _) => Constraint::Length(w),
+                (None, Column::Title) => Constraint::Fill(1),
+                (None, _) => {
+                    let content = cells
+                        .iter()
+                        .filter_map(|row| row.get(ci))
+                        .map(|s| s.chars().count());
+                    let w = content
+                        .chain([header[ci].chars().count()])
+                        .max()
+                        .unwrap_or(0);
+                    Constraint::Length(w.min(30) as u16)
+                }
+            })
+            .collect();
+
+        // group headers go in the title column (the first one if there is none)
+        let label_col = columns
+            .iter()
+            .position(|c| c.column == Column::Title)
+            .unwrap_or(0);
+        let rows: Vec<Row> = self
+            .rows
+            .iter()
+            .zip(cells)
+            .map(|(r, task_cells)| match r {
+                ListRow::Task(i) => {
+                    let t = &self.tasks[*i];
+                    let styled = columns.iter().zip(task_cells).map(|(c, text)| {
+                        let style = c
+                            .column
+                            .role(t, today)
+                            .map(|r| self.styles.get(r))
+                            .unwrap_or_default();
+                        Cell::from(text).style(style)
+                    });
+                    // done tasks fade as a whole
+                    let row = Row::new(styled.collect::<Vec<_>>());
+                    if t.is_closed() {
+                        row.style(self.styles.get(Role::StatusDone))
+                    } else {
+                        row
+                    }
+                }
+                ListRow::Group {
+                    label,
+                    count,
+                    collapsed,
+                    ..
+                } => {
+                    let marker = if *collapsed { "▸" } else { "▾" };
+                    let mut group_cells = vec![String::new(); columns.len()];
+                    if label_col == 0 {
+                        group_cells[0] = format!("{marker} {label} ({count})");
+                    } else {
+                        group_cells[0] = marker.to_string();
+                        group_cells[label_col] = format!("{label} ({count})");
+                    }
+                    Row::new(group_cells).style(self.styles.get(Role::Group))
+                }
+            })
+            .collect();
+        Table::new(rows, widths)
+            .header(Row::new(header).style(self.styles.get(Role::Header)))
+            .highlight_style(self.styles.get(Role::Selection))
+            .highlight_symbol("➤ ")
+            .highlight_spacing(HighlightSpacing::Always)
+    }
+
+    /// Palette popup over the main area: ranked entries (matched chars highlighted) and a
+    /// preview of the highlighted one
+    fn draw_palette(&self, f: &mut Frame, area: Rect) {
+        let popup = Rect {
+            x: area.x + area.width / 10,
+            y: area.y + 1,
+            width: area.width - area.width / 5,
+            height: area.height.saturating_sub(2).max(3),
+        };
+        let cols = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+            .split(popup);
+        let hl = self.styles.get(Role::Match);
+        let items: Vec<ListItem> = self
+            .palette_matches
+            .iter()
+            .map(|m| {
+                let it = &self.palette.items[m.index];
+                let mut spans: Vec<Span> = it
+                    .label
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        let style = if m.positions.contains(&i) {
+                            hl
+                        } else {
+                            Style::default()
+                        };
+                        Span::styled(c.to_string(), style)
+                    })
+                    .collect();
+                spans.push(Span::styled(
+                    format!("  {}", it.detail),
+                    self.styles.get(Role::Muted),
+                ));
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let title = format!(
+            "Palette {}/{}",
+            self.palette_matches.len(),
+            self.palette.items.len()
+        );
+        let mut st = ListState::default();
+        st.select((!items.is_empty()).then_some(self.palette_idx));
+        let list = List::new(items)
+            .highlight_symbol("➤ ")
+            .highlight_style(self.styles.get(Role::Selection))
+            .block(self.block(title));
+        let text = self
+            .palette_matches
+            .get(self.palette_idx)
+            .map(|m| {
+                let it = &self.palette.items[m.index];
+                preview(it, &self.tasks, &self.vault)
+            })
+            .unwrap_or_default();
+        let pv = Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(self.block("Preview"));
+        f.render_widget(Clear, popup);
+        f.render_widget(Block::default().style(self.styles.get(Role::Text)), popup);
+        f.render_stateful_widget(list, cols[0], &mut st);
+        f.render_widget(pv, cols[1]);
+    }
+
+    /// Quick actions menu as a popup at the bottom of the task list
+    fn draw_quick(&self, f: &mut Frame, area: Rect) {
+        let hl = self.styles.get(Role::Match);
+        let items: Vec<ListItem> = self
+            .quick_items()
+            .into_iter()
+            .map(|(qa, pos)| {
+                let spans: Vec<Span> = qa
+                    .label
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        Span::styled(
+                            c.to_string(),
+                            if pos.contains(&i) {
+                                hl
+                            } else {
+                                Style::default()
+                            },
+                        )
+                    })
+                    .collect();
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let height = (items.len() as u16 + 2).clamp(3, area.height);
+        let popup = Rect {
+            x: area.x + 2,
+            y: area.bottom() - height,
+            width: area.width.saturating_sub(4).min(40),
+            height,
+        };
+        let title = match self.selected_task() {
+            Some(t) => format!("Actions: {}", t.title),
+            None => "Actions".to_string(),
+        };
+        let mut st = ListState::default();
+        st.select((!items.is_empty()).then_some(self.quick_idx));
+        let list = List::new(items)
+            .highlight_symbol("➤ ")
+            .highlight_style(self.styles.get(Role::Selection))
+            .block(self.block(title));
+        f.render_widget(Clear, popup);
+        f.render_widget(Block::default().style(self.styles.get(Role::Text)), popup);
+        f.render_stateful_widget(list, popup, &mut st);
+    }
+
+    /// Project picker as a popup over the bottom of the main area
+    fn draw_picker(&self, f: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .picker_items()
+            .iter()
+            .map(|k| ListItem::new(k.to_string()))
+            .collect();
+        let height = (items.len() as u16 + 2).clamp(3, area.height);
+        let popup = Rect {
+            x: area.x,
+            y: area.bottom() - height,
+            width: area.width.min(50),
+            height,
+        };
+        let mut st = ListState::default();
+        st.select(Some(
+            self.project_pick_idx.min(items.len().saturating_sub(1)),
+        ));
+        let list = List::new(items)
+            .highlight_symbol("➤ ")
+            .highlight_style(self.styles.get(Role::Selection))
+            .block(self.block("Projects"));
+        f.render_widget(Clear, popup);
+        f.render_widget(Block::default().style(self.styles.get(Role::Text)), popup);
+        f.render_stateful_widget(list, popup, &mut st);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tm_core::{keymap::Lookup, Config, Origin};
+
+    use super::*;
+
+    fn q_is_go_top(app: &App) -> bool {
+        matches!(
+            app.keymap.lookup(Mode::Normal, &["Q".to_string()]),
+            Lookup::Exact(Action::GoTop)
+        )
+    }
+
+    #[test]
+    fn a_broken_config_keeps_the_running_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.lua");
+        fs::write(
+            &path,
+            "return { date_format = 'DD.MM.YYYY', keymaps = { normal = { Q = 'go_top' } } }",
+        )
+        .unwrap();
+        let cfg = Config::load_from(&path, Origin::Default).unwrap();
+        let mut app = App::new(Vault::new(cfg).unwrap());
+        assert!(q_is_go_top(&app));
+
+        fs::write(&path, "return { date_format = 'DD.MM' }").unwrap();
+        app.reload_config();
+        let (is_err, msg) = app.ex_result.clone().unwrap();
+        assert!(is_err && msg.starts_with("config not reloaded"), "{msg}");
+        assert_eq!(app.vault.cfg.date_format, "DD.MM.YYYY");
+        assert!(q_is_go_top(&app));
+
+        fs::write(&path, "return { date_format = 'YYYY-MM-DD' }").unwrap();
+        app.reload_config();
+        let (is_err, msg) = app.ex_result.clone().unwrap();
+        assert!(!is_err, "{msg}");
+        assert_eq!(app.vault.cfg.date_format, "YYYY-MM-DD");
+        assert!(!q_is_go_top(&app));
+    }
+
+    #[test]
+    fn space_ticks_checklist_items_only_while_the_checklist_has_focus() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.lua");
+        fs::write(&path, "return {}").unwrap();
+        let mut cfg = Config::load_from(&path, Origin::Default).unwrap();
+        cfg.override_vault(dir.path().join("vault"));
+        let vault = Vault::new(cfg).unwrap();
+        let id = vault
+            .create_task(tm_core::TaskNew {
+                title: "pack".into(),
+                project: "inbox".into(),
+                due: None,
+                tags: vec![],
+                parent: None,
+            })
+            .unwrap();
+        vault.add_check(&id, "socks").unwrap();
+        vault.add_check(&id, "tent").unwrap();
+        let done = |v: &Vault| -> Vec<bool> {
+            v.list_tasks(None).unwrap()[0]
+                .checklist
+                .iter()
+                .map(|c| c.done)
+                .collect()
+        };
+
+        let mut app = App::new(vault);
+        app.refresh();
+        app.on_key("Space");
+        app.on_key("2");
+        app.on_key("Space");
+        assert_eq!(done(&app.vault), [false, false]);
+
+        app.on_key("Tab");
+        app.on_key("Space");
+        app.refresh();
+        app.on_key("2");
+        app.on_key("Space");
+        assert_eq!(done(&app.vault), [true, true]);
+    }
+}
@@ -0,0 +1,161 @@
+//! The config theme as ratatui styles, degraded to what the terminal can show
+
+use ratatui::style::{Color, Modifier, Style};
+use tm_core::{Config, Rgb, Role, Theme};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    /// attributes only (`NO_COLOR` or `colors = 'none'`)
+    None,
+}
+
+impl ColorDepth {
+    /// `colors` from config; `auto` looks at `NO_COLOR`, `COLORTERM` and `TERM`
+    pub fn detect(setting: &str) -> Self {
+        let env = |k: &str| std::env::var(k).unwrap_or_default();
+        match setting {
+            "truecolor" => ColorDepth::TrueColor,
+            "256" => ColorDepth::Ansi256,
+            "16" => ColorDepth::Ansi16,
+            "none" => ColorDepth::None,
+            _ if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) => ColorDepth::None,
+            _ if matches!(env("COLORTERM").as_str(), "truecolor" | "24bit") => {
+                ColorDepth::TrueColor
+            }
+            _ if env("TERM").contains("256color") => ColorDepth::Ansi256,
+            _ => ColorDepth::Ansi16,
+        }
+    }
+
+    fn color(self, c: Rgb) -> Option<Color> {
+        const ANSI: [Color; 16] = [
+            Color::Black,
+            Color::Red,
+            Color::Green,
+            Color::Yellow,
+            Color::Blue,
+            Color::Magenta,
+            Color::Cyan,
+            Color::Gray,
+            Color::DarkGray,
+            Color::LightRed,
+            Color::LightGreen,
+            Color::LightYellow,
+            Color::LightBlue,
+            Color::LightMagenta,
+            Color::LightCyan,
+            Color::White,
+        ];
+        match self {
+            ColorDepth::TrueColor => Some(Color::Rgb(c.0, c.1, c.2)),
+            ColorDepth::Ansi256 => Some(Color::Indexed(c.to_ansi256())),
+            ColorDepth::Ansi16 => Some(ANSI[c.to_ansi16() as usize]),
+            ColorDepth::None => None,
+        }
+    }
+}
+
+pub(crate) struct Styles {
+    theme: Theme,
+    depth: ColorDepth,
+}
+
+impl Styles {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            theme: cfg.theme(),
+            depth: ColorDepth::detect(&cfg.colors),
+        }
+    }
+
+    pub fn get(&self, role: Role) -> Style {
+        let s = self.theme.get(role);
+        let mut out = Style::default();
+        if let Some(c) = s.fg.and_then(|c| self.depth.color(c)) {
+            out = out.fg(c);
+        }
+        match s.bg.map(|c| self.depth.color(c)) {
+            // 16 colours can't tell the dark background shades apart: keep the terminal's
+            // own background, and show highlights reversed instead of black on black
+            Some(None | Some(Color::Black)) if role == Role::Text => {}
+            Some(None | Some(Color::Black)) => out = out.add_modifier(Modifier::REVERSED),
+            Some(Some(c)) => out = out.bg(c),
+            None => {}
+        }
+        for (on, m) in [
+            (s.bold, Modifier::BOLD),
+            (s.dim, Modifier::DIM),
+            (s.italic, Modifier::ITALIC),
+            (s.underline, Modifier::UNDERLINED),
+            (s.reverse, Modifier::REVERSED),
+        ] {
+            if on {
+                out = out.add_modifier(m);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styles(depth: ColorDepth) -> Styles {
+        Styles {
+            theme: Theme::default(),
+            depth,
+        }
+    }
+
+    #[test]
+    fn explicit_colour_settings_skip_detection() {
+        assert_eq!(ColorDepth::detect("truecolor"), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::detect("256"), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::detect("16"), ColorDepth::Ansi16);
+        assert_eq!(ColorDepth::detect("none"), ColorDepth::None);
+    }
+
+    #[test]
+    fn colours_follow_the_terminal_depth() {
+        let title = |d| styles(d).get(Role::Title);
+        assert_eq!(
+            title(ColorDepth::TrueColor).fg,
+            Some(Color::Rgb(0xfa, 0xbd, 0x2f))
+        );
+        assert!(matches!(
+            title(ColorDepth::Ansi256).fg,
+            Some(Color::Indexed(_))
+        ));
+        assert_eq!(title(ColorDepth::Ansi16).fg, Some(Color::Yellow));
+        // without colour only the attributes are left
+        assert_eq!(
+            title(ColorDepth::None),
+            Style::default().add_modifier(Modifier::BOLD)
+        );
+    }
+
+    #[test]
+    fn dark_backgrounds_fall_back_to_the_terminal_or_reverse_video() {
+        let s = styles(ColorDepth::Ansi16);
+        assert_eq!(s.get(Role::Text).bg, None);
+        let sel = s.get(Role::Selection);
+        assert_eq!(sel.bg, None);
+        assert!(sel
+            .add_modifier
+            .contains(Modifier::REVERSED | Modifier::BOLD));
+        // brighter backgrounds survive the degrade
+        assert_eq!(s.get(Role::TabActive).bg, Some(Color::Yellow));
+
+        let s = styles(ColorDepth::Ansi256);
+        assert!(matches!(s.get(Role::Selection).bg, Some(Color::Indexed(_))));
+        let s = styles(ColorDepth::None);
+        assert!(s
+            .get(Role::Selection)
+            .add_modifier
+            .contains(Modifier::REVERSED));
+    }
+}
@@ -0,0 +1,361 @@
+-- Todoist two-way sync, bundled with tm. Enable it in config.lua:
+--
+--   use({ 'todoist', opts = {
+--     projects = { Inbox = 'inbox', Work = 'work' },  -- Todoist project → tm project (default: all, by name)
+--     conflict = 'newest-wins',                        -- or 'host-wins': tm's version always wins
+--   } })
+--
+-- then store an API token once with `:todoist.token <token>` (or set $TM_SECRET_TODOIST).
+-- `:todoist.sync` (`tm sync todoist`) pulls what changed since the last sync token, then
+-- pushes what was edited in tm since the last sync; `--full` pulls everything again.
+--
+-- Projects map to projects, labels to tags, priorities p1..p4 to high/med/low/none, the due
+-- date to `due`, sub-tasks to `parent` and the section to a `section` key. Each task keeps
+-- its Todoist id as `todoist_id` in its frontmatter. A task completed on one side is
+-- completed on the other; one deleted in Todoist is closed here, one deleted with `tm rm`
+-- is deleted in Todoist (a task file removed by hand is not). New tasks in a synced project
+-- are added.
+
+local M = {}
+local host
+
+local API = 'https://api.todoist.com/api/v1'
+
+-- Todoist's priority 4 is p1, the most urgent
+local FROM_PRIORITY = { [4] = 'high', [3] = 'med', [2] = 'low', [1] = 'none' }
+local TO_PRIORITY = { high = 4, med = 3, low = 2, none = 1 }
+
+local function opts()
+  return host.opts or {}
+end
+
+local function token()
+  local t = host.secrets.get('todoist')
+  if not t or t == '' then
+    error('no Todoist token: run :todoist.token <token> or set $TM_SECRET_TODOIST')
+  end
+  return t
+end
+
+-- POST /sync; tables in `fields` go out as JSON
+local function post(fields)
+  return host.http.post_json((opts().api_url or API) .. '/sync', fields, {
+    form = true,
+    headers = { Authorization = 'Bearer ' .. token() },
+  })
+end
+
+local function uuid()
+  return (string.gsub('xxxxxxxx-xxxx-4xxx-yxxx-xxxxxxxxxxxx', '[xy]', function(c)
+    return string.format('%x', c == 'x' and math.random(0, 15) or math.random(8, 11))
+  end))
+end
+
+local function trim(s)
+  return ((s or ''):gsub('^%s+', ''):gsub('%s+$', ''))
+end
+
+local function slug(name)
+  local s = name:lower():gsub('[^%w]+', '-'):gsub('^%-+', ''):gsub('%-+$', '')
+  return s ~= '' and s or 'todoist'
+end
+
+-- a date as `YYYY-MM-DD`, a UTC datetime as it is; floating times keep only the day
+local function due_from(due)
+  if type(due) ~= 'table' or not due.date then
+    return nil
+  end
+  if #due.date > 10 and due.date:sub(-1) == 'Z' then
+    return due.date
+  end
+  return due.date:sub(1, 10)
+end
+
+-- RFC 3339 UTC timestamps compare as strings once cut to the second
+local function ts(s)
+  return (s or ''):sub(1, 19)
+end
+
+local function project_key(p)
+  if p.is_deleted or p.is_archived then
+    return nil
+  end
+  local map = opts().projects
+  if map then
+    return map[p.name]
+  end
+  if p.inbox_project then
+    return host.config.default_project
+  end
+  return slug(p.name)
+end
+
+-- what the last sync left: the sync token, Todoist project id → tm project, section id →
+-- name, and per Todoist item the tm id plus what both sides agreed on (and its title, for
+-- reporting a deletion after the task file is gone)
+local function load_state()
+  return {
+    token = host.state.get('sync_token'),
+    projects = host.state.get('projects') or {},
+    sections = host.state.get('sections') or {},
+    items = host.state.get('items') or {},
+  }
+end
+
+local function save_state(st)
+  host.state.set('sync_token', st.token)
+  host.state.set('projects', st.projects)
+  host.state.set('sections', st.sections)
+  host.state.set('items', st.items)
+end
+
+local function pull(st, full, report)
+  local conflict = opts().conflict or 'newest-wins'
+  local res = post({
+    sync_token = (full or not st.token) and '*' or st.token,
+    resource_types = { 'projects', 'sections', 'items' },
+  })
+  for _, p in ipairs(res.projects or {}) do
+    local key = project_key(p)
+    if key then
+      host.projects.ensure(key, p.name)
+    end
+    st.projects[p.id] = key
+  end
+  for _, s in ipairs(res.sections or {}) do
+    st.sections[s.id] = not s.is_deleted and s.name or nil
+  end
+
+  local by_remote = {}
+  for _, t in ipairs(host.tasks.list()) do
+    if t.todoist_id then
+      by_remote[t.todoist_id] = t
+    end
+  end
+  local deleted = host.tasks.deleted()
+  local parents = {}
+  for _, it in ipairs(res.items or {}) do
+    local task, known = by_remote[it.id], st.items[it.id]
+    local project = st.projects[it.project_id]
+    if it.is_deleted then
+      if task and task.category ~= 'closed' then
+        host.tasks.upsert({ id = task.id, category = 'closed' })
+        table.insert(report.closed, task.title)
+      end
+      st.items[it.id] = nil
+    elseif known and deleted[known.id] then
+      -- deleted here with `tm rm`: the push deletes it in Todoist
+    elseif project then
+      local keep = false
+      if task and (not known or known.updated ~= task.updated) then
+        keep = conflict == 'host-wins' or ts(task.updated) >= ts(it.updated_at)
+        table.insert(report.conflicts, task.title .. (keep and ': kept the tm version' or ': took the Todoist version'))
+      end
+      if keep then
+        -- what Todoist has now, so the push below sends the differences
+        st.items[it.id] = {
+          id = task.id, title = task.title, closed = it.checked or false, due = due_from(it.due), project = project,
+        }
+      else
+        local fields = {
+          id = task and task.id,
+          title = it.content,
+          project = project,
+          priority = FROM_PRIORITY[it.priority] or 'none',
+          due = due_from(it.due) or host.null,
+          tags = host.array(it.labels or {}),
+          section = it.section_id and st.sections[it.section_id] or host.null,
+          todoist_id = it.id,
+        }
+        if it.checked then
+          fields.category = 'closed'
+          if it.completed_at and not (task and task.category == 'closed') then
+            fields.done_at = it.completed_at
+          end
+        elseif task and task.category == 'closed' then
+          fields.category = 'open'
+        end
+        local desc = trim(it.description)
+        if not task or desc ~= trim(task.body) then
+          fields.body = desc == '' and '' or '\n' .. desc .. '\n'
+        end
+        if it.parent_id then
+          table.insert(parents, { it.id, it.parent_id })
+        else
+          fields.parent = host.null
+        end
+        local t = host.tasks.upsert(fields)
+        if not task or t.updated ~= task.updated then
+          table.insert(report.pulled, t.title)
+        end
+        st.items[it.id] = {
+          id = t.id, title = t.title, updated = t.updated, closed = it.checked or false, due = due_from(it.due), project = project,
+        }
+      end
+    end
+  end
+  -- sub-tasks once every parent has a tm id
+  for _, link in ipairs(parents) do
+    local child, parent = st.items[link[1]], st.items[link[2]]
+    if child and parent and child.updated then
+      child.updated = host.tasks.upsert({ id = child.id, parent = parent.id }).updated
+    end
+  end
+  st.token = res.sync_token
+end
+
+local function push(st, report)
+  local linked = {}
+  for pid, key in pairs(st.projects) do
+    linked[key] = linked[key] or pid
+  end
+  local remote_of = {}
+  for rid, known in pairs(st.items) do
+    remote_of[known.id] = rid
+  end
+  local cmds, done = {}, {}
+  local function command(type, args, temp_id, after)
+    local id = uuid()
+    table.insert(cmds, { type = type, uuid = id, temp_id = temp_id, args = args })
+    done[id] = after
+  end
+
+  local seen, adds, adding = {}, {}, {}
+  for _, t in ipairs(host.tasks.list()) do
+    seen[t.id] = true
+    local rid = t.todoist_id or remote_of[t.id]
+    local known = rid and st.items[rid]
+    local pid = linked[t.project]
+    local closed = t.category == 'closed'
+    local fields = {
+      content = t.title,
+      description = trim(t.body),
+      priority = TO_PRIORITY[t.priority] or 1,
+      labels = host.array(t.tags or {}),
+    }
+    if known and known.updated ~= t.updated then
+      fields.id = rid
+      -- only a changed date is sent, so a recurring due date stays recurring
+      if t.due ~= known.due then
+        fields.due = t.due and { date = t.due } or host.null
+      end
+      command('item_update', fields, nil, function()
+        known.updated, known.closed, known.due, known.project = t.updated, closed, t.due, t.project
+        known.title = t.title
+      end)
+      if pid and t.project ~= known.project then
+        command('item_move', { id = rid, project_id = pid })
+      end
+      if closed ~= known.closed then
+        command(closed and 'item_close' or 'item_uncomplete', { id = rid })
+      end
+      table.insert(report.pushed, t.title)
+    elseif not known and not t.todoist_id and pid and not closed then
+      fields.project_id = pid
+      fields.due = t.due and { date = t.due } or nil
+      remote_of[t.id] = uuid()
+      adding[t.id] = { task = t, fields = fields }
+      table.insert(adds, adding[t.id])
+    end
+  end
+  -- new tasks go out parents first, so a sub-task's `parent_id` can be its parent's temp id
+  local function add(a)
+    if a.queued then
+      return
+    end
+    a.queued = true
+    local t, temp = a.task, remote_of[a.task.id]
+    if t.parent and adding[t.parent] then
+      add(adding[t.parent])
+    end
+    a.fields.parent_id = t.parent and remote_of[t.parent] or nil
+    command('item_add', a.fields, temp, function(res)
+      local real = res.temp_id_mapping and res.temp_id_mapping[temp]
+      if real then
+        local saved = host.tasks.upsert({ id = t.id, todoist_id = real })
+        st.items[real] = { id = t.id, title = t.title, updated = saved.updated, closed = false, due = t.due, project = t.project }
+      end
+    end)
+    table.insert(report.pushed, t.title)
+  end
+  for _, a in ipairs(adds) do
+    add(a)
+  end
+  -- only a tombstone deletes: a task that is just missing from the list stays in Todoist
+  local deleted = host.tasks.deleted()
+  for rid, known in pairs(st.items) do
+    if deleted[known.id] and not seen[known.id] then
+      command('item_delete', { id = rid }, nil, function()
+        st.items[rid] = nil
+        host.tasks.forget_deleted(known.id)
+      end)
+      table.insert(report.deleted, known.title or rid)
+    end
+  end
+  if #cmds == 0 then
+    return
+  end
+
+  local res = post({ commands = cmds })
+  for _, c in ipairs(cmds) do
+    local status = (res.sync_status or {})[c.uuid]
+    if status == 'ok' then
+      if done[c.uuid] then
+        done[c.uuid](res)
+      end
+    else
+      local why = type(status) == 'table' and status.error or 'no status'
+      table.insert(report.errors, c.type .. ' ' .. (c.args.content or c.args.id or '') .. ': ' .. tostring(why))
+    end
+  end
+  -- what Todoist accepted, before anything else can fail
+  save_state(st)
+end
+
+function M.sync(full)
+  local st = load_state()
+  local report = { pulled = {}, closed = {}, pushed = {}, deleted = {}, conflicts = {}, errors = {} }
+  pull(st, full, report)
+  -- a push that fails doesn't lose the pull: the next sync reads on from its token
+  save_state(st)
+  push(st, report)
+  for _, c in ipairs(report.conflicts) do
+    host.notify('conflict: ' .. c)
+  end
+  for _, e in ipairs(report.errors) do
+    host.notify('error: ' .. e)
+  end
+  return string.format(
+    'todoist: %d pulled, %d closed, %d pushed, %d deleted remotely, %d conflict(s), %d error(s)',
+    #report.pulled, #report.closed, #report.pushed, #report.deleted, #report.conflicts, #report.errors
+  )
+end
+
+function M.setup(h)
+  host = h
+  local conflict = opts().conflict
+  if conflict and conflict ~= 'newest-wins' and conflict ~= 'host-wins' then
+    error("todoist: conflict must be 'newest-wins' or 'host-wins'")
+  end
+  host.register_command({
+    signature = ':todoist.sync [--full]',
+    summary = 'pull Todoist changes, then push tasks edited here',
+    run = function(args)
+      return M.sync(args[1] == '--full')
+    end,
+  })
+  host.register_command({
+    signature = ':todoist.token <token>',
+    summary = 'store the Todoist API token',
+    run = function(args)
+      local t = trim(args[1])
+      if t == '' then
+        error('usage: :todoist.token <token>')
+      end
+      host.secrets.set('todoist', t)
+      return 'Todoist token saved'
+    end,
+  })
+end
+
+return M
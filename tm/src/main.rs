@@ -1,7 +1,13 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use tm_core::{Config, TaskNew, Vault};
 use std::path::PathBuf;
+use tm_core::{
+    date, deps, format_duration, format_row, group_tasks, notes, plugin, sort::sort_tasks,
+    taskwarrior, track, unknown_statuses, Config, Conflict, GroupBy, ImportReport, ListLayout,
+    MappingReport, MirrorTrigger, PluginSpec, Query, ReportBy, ReportRow, StatusChange, Task,
+    TaskNew, TimerChange, Vault, VaultExport, BLOCKED_MARKER,
+};
+use tm_plugin_host::{PluginHost, PluginManager};
 
 #[derive(Parser)]
 #[command(version, about)]
@@ -17,34 +23,312 @@ struct Cli {
 enum Cmd {
     Tui,
     Gui,
-    Ls { #[arg(short, long)] project: Option<String> },
-    Add { title: String, #[arg(long)] project: Option<String>, #[arg(long)] due: Option<String>, #[arg(long, value_delimiter=',')] tags: Option<Vec<String>> },
+    /// List tasks: `tm ls @today`, `tm ls +work due<=fri sort:p`
+    Ls {
+        #[arg(short, long)]
+        project: Option<String>,
+        /// `@view` and/or query terms
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        query: Vec<String>,
+    },
+    Add {
+        title: String,
+        #[arg(long)]
+        project: Option<String>,
+        #[arg(long)]
+        due: Option<String>,
+        #[arg(long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+    },
     Init,
-    /// Set status: todo|doing|done
-    Status { id: String, value: String },
-    /// Shortcut: set status to 'doing'
-    Start { id: String },
+    /// Set a workflow status: todo|doing|done|cancelled (or as configured)
+    Status {
+        id: String,
+        value: String,
+    },
+    /// Start the task's timer (stopping any other) and set the first active status
+    Start {
+        id: String,
+    },
+    /// Stop the running timer
+    Stop {
+        id: Option<String>,
+    },
+    /// Delete a task; syncs delete it remotely too
+    Rm {
+        id: String,
+    },
+    /// Hide a task until a date: `tm snooze <task> next week` (`none` shows it again)
+    Snooze {
+        id: String,
+        #[arg(trailing_var_arg = true, required = true)]
+        when: Vec<String>,
+    },
+    /// Planned effort: `tm estimate <task> 1h30m` (`none` clears)
+    Estimate {
+        id: String,
+        value: String,
+    },
+    /// Reports: `tm report time --from mon --by tag --csv`
+    Report {
+        #[command(subcommand)]
+        cmd: ReportCmd,
+    },
+    /// Task dependencies: `tm dep add <task> <on>` (task waits for on)
+    Dep {
+        #[command(subcommand)]
+        cmd: DepCmd,
+    },
+    /// Checkboxes in the `notes` folders: `tm notes ls`, `tm notes promote <task>`
+    Notes {
+        #[command(subcommand)]
+        cmd: NotesCmd,
+    },
+    /// Print the dependency graph: `tm graph --format mermaid`
+    Graph {
+        #[arg(long, default_value = "dot")]
+        format: String,
+    },
+    /// Write tasks and projects as JSON: `tm export -o vault.json +work` (`--to taskwarrior`
+    /// for `task import`, `--to todotxt`, `--to org`, `--to ics`)
+    Export {
+        #[arg(long, alias = "to", default_value = "json")]
+        format: String,
+        /// same as --format json
+        #[arg(long)]
+        json: bool,
+        /// ics: an all-day event for each due date as well
+        #[arg(long)]
+        events: bool,
+        /// file to write (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// only tasks matching this query
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        query: Vec<String>,
+    },
+    /// Upsert tasks and projects from an export (`-` reads stdin); `--from taskwarrior`
+    /// reads `task export` output, `--from todotxt` a todo.txt, `--from org` an org file
+    Import {
+        file: PathBuf,
+        #[arg(long, alias = "from", default_value = "json")]
+        format: String,
+        /// report what would change without writing
+        #[arg(long)]
+        dry_run: bool,
+        /// when a record differs: skip, overwrite or newest-wins
+        #[arg(long, default_value = "newest-wins")]
+        on_conflict: String,
+    },
+    /// Sync the todo.txt mirror with the vault both ways (`todotxt_mirror` in the config)
+    Mirror {
+        /// the todo.txt to sync (default: `todotxt_mirror`)
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// keep syncing as tasks or the file change until interrupted
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Write the iCalendar feed (`ics` in the config) if the vault changed since last time
+    Feed {
+        /// keep rewriting it whenever the vault changes until interrupted
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Two-way sync with a remote service
+    Sync {
+        #[command(subcommand)]
+        cmd: SyncCmd,
+    },
+    /// Manage git-installed plugins (pinned in tm-lock.json)
+    Plugin {
+        #[command(subcommand)]
+        cmd: PluginCmd,
+    },
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        cmd: ConfigCmd,
+    },
+}
+
+#[derive(Subcommand)]
+enum SyncCmd {
+    /// Projects ↔ CalDAV calendars, tasks ↔ VTODOs (`caldav` in the config)
+    Caldav,
+    /// Repository issues ↔ project tasks (`issues` in the config)
+    Issues {
+        /// read every open issue again, not just those updated since the last sync
+        #[arg(long)]
+        full: bool,
+    },
+    /// `tm sync todoist [--full]`: run a plugin's `:<name>.sync`
+    #[command(external_subcommand)]
+    Plugin(Vec<String>),
+}
+
+#[derive(Subcommand)]
+enum ConfigCmd {
+    /// Print every setting with the file, env var or default it came from
+    Show,
+}
+
+#[derive(Subcommand)]
+enum ReportCmd {
+    /// Tracked time per project or tag (default: this week)
+    Time {
+        /// first day: a date, `mon`, `-7d`, ... (default: this week's Monday)
+        #[arg(long, allow_hyphen_values = true)]
+        from: Option<String>,
+        /// last day, included (default: today)
+        #[arg(long, allow_hyphen_values = true)]
+        to: Option<String>,
+        #[arg(long, default_value = "project")]
+        by: String,
+        /// print CSV (group,id,title,hours) instead of a table
+        #[arg(long)]
+        csv: bool,
+        /// write the CSV to a file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DepCmd {
+    /// <task> can't start before <on> is closed
+    Add {
+        task: String,
+        on: String,
+    },
+    Rm {
+        task: String,
+        on: String,
+    },
+    /// What a task waits for and what waits for it
+    Ls {
+        task: String,
+    },
+    /// Report cycles and dependencies on missing tasks
+    Check,
+}
+
+#[derive(Subcommand)]
+enum NotesCmd {
+    /// Every checkbox found, with where it is
+    Ls,
+    /// Make task files of note checkboxes (the lines get `🆔 <id>`)
+    Promote {
+        tasks: Vec<String>,
+        /// every open checkbox
+        #[arg(long, conflicts_with = "tasks")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PluginCmd {
+    /// Install from a git URL, local path or user/repo: <source>[@version]
+    Install {
+        source: String,
+    },
+    /// Move plugins to the newest commit matching their constraint
+    Update {
+        name: Option<String>,
+    },
+    Remove {
+        name: String,
+    },
+    /// Check out the locked commits and install plugins declared with use({...})
+    Sync,
+    Ls,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
     let mut cfg = Config::load_default()?;
-    if let Some(v) = cli.vault { cfg.vault_path = v; }
+    if let Some(v) = cli.vault {
+        cfg.override_vault(v);
+    }
     let vault = Vault::new(cfg.clone())?;
 
     match cli.command.unwrap_or(Cmd::Tui) {
         Cmd::Tui => tm_ui::run_tui(vault)?,
         Cmd::Gui => tm_gui::run_gui(vault)?,
-        Cmd::Ls { project } => {
-            let tasks = vault.list_tasks(project.as_deref())?;
-            for t in tasks { println!("{} [{}] {}", t.id, t.status, t.title); }
+        Cmd::Ls { project, query } => {
+            let mut view = None;
+            let mut words = Vec::new();
+            for w in query {
+                match w.strip_prefix('@') {
+                    Some(name) if view.is_none() => view = Some(vault.view(name)?),
+                    Some(_) => anyhow::bail!("only one @view at a time"),
+                    None => words.push(w),
+                }
+            }
+            let q = match &view {
+                Some(v) => v.parsed()?,
+                None => Query::default(),
+            };
+            let q = q.and(Query::parse(&words.join(" "))?);
+            let layout = ListLayout::resolve(&cfg, view.as_ref(), &q);
+            let mut tasks = vault.list_tasks(project.as_deref())?;
+            if let Some(spec) = &layout.sort {
+                sort_tasks(&mut tasks, spec);
+            }
+            let today = date::today();
+            // the classic line unless columns are configured
+            let custom =
+                view.as_ref().and_then(|v| v.columns.as_ref()).is_some() || !cfg.columns.is_empty();
+            let marker = |t: &Task| {
+                if t.blocked {
+                    format!("{BLOCKED_MARKER} ")
+                } else {
+                    String::new()
+                }
+            };
+            let print = |t: &Task| match (&t.due, custom) {
+                (_, true) => println!("{} {}", t.id, format_row(t, &layout.columns, &cfg)),
+                (Some(d), false) => println!(
+                    "{} [{}] {}{}  (due {})",
+                    t.id,
+                    t.status,
+                    marker(t),
+                    t.title,
+                    cfg.display_date(d)
+                ),
+                (None, false) => println!("{} [{}] {}{}", t.id, t.status, marker(t), t.title),
+            };
+            let unknown = unknown_statuses(&tasks);
+            if !unknown.is_empty() {
+                eprintln!(
+                    "warning: unknown status: {} (not in the workflow; list them with is:unknown)",
+                    unknown.join(", ")
+                );
+            }
+            let visible: Vec<usize> = (0..tasks.len())
+                .filter(|&i| q.matches(&tasks[i], today))
+                .collect();
+            if layout.group == GroupBy::None {
+                visible.iter().for_each(|&i| print(&tasks[i]));
+            } else {
+                for g in group_tasks(&tasks, &visible, layout.group, today) {
+                    println!("## {} ({})", g.label, g.tasks.len());
+                    g.tasks.iter().for_each(|&i| print(&tasks[i]));
+                }
+            }
         }
-        Cmd::Add { title, project, due, tags } => {
+        Cmd::Add {
+            title,
+            project,
+            due,
+            tags,
+        } => {
             let id = vault.create_task(TaskNew {
                 title,
-                project: project.unwrap_or_else(|| "inbox".into() ),
+                project: project.unwrap_or_else(|| cfg.default_project.clone()),
                 due,
                 tags: tags.unwrap_or_default(),
+                parent: None,
             })?;
             println!("Created task {id}");
         }
@@ -54,17 +338,397 @@ fn main() -> Result<()> {
         }
 
         Cmd::Status { id, value } => {
-        let st = match value.as_str() {
-            "todo" => tm_core::Status::Todo,
-            "doing" | "in-progress" => tm_core::Status::Doing,
-            "done" => tm_core::Status::Done,
-            other => anyhow::bail!("unknown status: {other} (use: todo|doing|done)"),
+            print_change(vault.set_status(&vault.resolve_task(&id)?, &value)?)
+        }
+        Cmd::Start { id } => print_timer(vault.start_timer(&vault.resolve_task(&id)?)?),
+        Cmd::Stop { id } => {
+            let id = id.map(|h| vault.resolve_task(&h)).transpose()?;
+            print_timer(vault.stop_timer(id.as_deref())?);
+        }
+        Cmd::Rm { id } => println!("deleted: {}", vault.delete_task(&vault.resolve_task(&id)?)?),
+        Cmd::Snooze { id, when } => {
+            match vault.snooze(&vault.resolve_task(&id)?, &when.join(" "))? {
+                Some(d) => println!("snoozed until {}", cfg.display_date(&d)),
+                None => println!("no longer deferred"),
+            }
+        }
+        Cmd::Estimate { id, value } => {
+            match vault.set_estimate(&vault.resolve_task(&id)?, &value)? {
+                Some(e) => println!("estimate -> {e}"),
+                None => println!("estimate cleared"),
+            }
+        }
+        Cmd::Report {
+            cmd:
+                ReportCmd::Time {
+                    from,
+                    to,
+                    by,
+                    csv,
+                    output,
+                },
+        } => {
+            let today = date::today();
+            let day = |s: Option<String>, default| match s {
+                Some(s) => date::parse_relative(&s, today)
+                    .ok_or_else(|| anyhow::anyhow!("not a date: {s}")),
+                None => Ok(default),
+            };
+            let (from, to) = (day(from, date::week_start(today))?, day(to, today)?);
+            let rows =
+                track::time_report(&vault.list_tasks(None)?, from, to, ReportBy::parse(&by)?);
+            if let Some(path) = output {
+                std::fs::write(&path, track::report_csv(&rows))?;
+                println!("wrote {} rows to {}", rows.len(), path.display());
+            } else if csv {
+                print!("{}", track::report_csv(&rows));
+            } else {
+                let span = |d| date::format_date(&cfg.date_format, d);
+                print_time_report(&rows, &span(from), &span(to));
+            }
+        }
+        Cmd::Dep { cmd } => run_dep_cmd(&vault, cmd)?,
+        Cmd::Notes { cmd } => run_notes_cmd(&vault, cmd)?,
+        Cmd::Export {
+            format,
+            json,
+            events,
+            output,
+            query,
+        } => {
+            let format = if json { "json".to_string() } else { format };
+            let query = Query::parse(&query.join(" "))?;
+            let text = match format.as_str() {
+                "json" => vault.export_json(&query)?.to_json()?,
+                "taskwarrior" => {
+                    let (text, mapping) =
+                        taskwarrior::to_taskwarrior(&vault.export_json(&query)?, &cfg.workflow)?;
+                    print_mapping(&mapping);
+                    text
+                }
+                "todotxt" => vault.export_todotxt(&query)?,
+                "org" => vault.export_org(&query)?,
+                "ics" => vault.export_ics(&query, events)?,
+                other => anyhow::bail!(
+                    "unknown export format: {other} (use: json|taskwarrior|todotxt|org|ics)"
+                ),
+            };
+            match output {
+                Some(path) => std::fs::write(&path, text)?,
+                None => print!("{text}"),
+            }
+        }
+        Cmd::Import {
+            file,
+            format,
+            dry_run,
+            on_conflict,
+        } => {
+            let text = if file.as_os_str() == "-" {
+                std::io::read_to_string(std::io::stdin())?
+            } else {
+                std::fs::read_to_string(&file)?
+            };
+            let strategy = Conflict::parse(&on_conflict)?;
+            let report = match format.as_str() {
+                "json" => vault.import_json(&VaultExport::from_json(&text)?, strategy, dry_run)?,
+                "taskwarrior" => {
+                    let known: Vec<String> =
+                        vault.list_projects()?.into_iter().map(|p| p.key).collect();
+                    let (data, mapping) =
+                        taskwarrior::from_taskwarrior(&text, &cfg.workflow, &known)?;
+                    print_mapping(&mapping);
+                    vault.import_json(&data, strategy, dry_run)?
+                }
+                "todotxt" => vault.import_todotxt(&text, strategy, dry_run)?,
+                "org" => vault.import_org(&text, strategy, dry_run)?,
+                other => anyhow::bail!(
+                    "unknown import format: {other} (use: json|taskwarrior|todotxt|org)"
+                ),
+            };
+            print_import(&report, dry_run);
+        }
+        Cmd::Mirror { file, watch } => {
+            let Some(path) = file.or_else(|| cfg.todotxt_mirror.clone()) else {
+                anyhow::bail!(
+                    "no todo.txt to mirror: set todotxt_mirror in the config or pass --file"
+                );
+            };
+            // with --watch: whenever a task file changes or the todo.txt is edited
+            let mut trigger = MirrorTrigger::new(&cfg.vault_path);
+            loop {
+                let (tasks_changed, edited) = trigger.poll(&vault, Some(&path));
+                if tasks_changed || edited {
+                    let report = vault.sync_todotxt(&path)?;
+                    if !watch || !report.is_empty() {
+                        println!("{}", report.summary());
+                    }
+                }
+                if !watch {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(250));
+            }
+        }
+        Cmd::Feed { watch } => {
+            let Some(feed) = cfg.ics.clone() else {
+                anyhow::bail!(
+                    "no feed configured: set ics = {{ path = '…/tm.ics' }} in the config"
+                );
+            };
+            loop {
+                if vault.write_ics_feed(&feed)? {
+                    println!("wrote {}", feed.path.display());
+                } else if !watch {
+                    println!("{} is up to date", feed.path.display());
+                }
+                if !watch {
+                    break;
+                }
+                std::thread::sleep(tm_core::ics::FEED_INTERVAL);
+            }
+        }
+        Cmd::Sync {
+            cmd: SyncCmd::Caldav,
+        } => {
+            let report = vault.sync_caldav()?;
+            println!("{}", report.summary("caldav"));
+            for c in &report.conflicts {
+                println!("  conflict: {c}");
+            }
+        }
+        Cmd::Sync {
+            cmd: SyncCmd::Issues { full },
+        } => {
+            let report = vault.sync_issues(full)?;
+            println!("{}", report.summary("issues"));
+        }
+        Cmd::Sync {
+            cmd: SyncCmd::Plugin(args),
+        } => {
+            let (name, args) = args.split_first().expect("clap passes the subcommand name");
+            let (host, errors) = PluginHost::load(&cfg);
+            for e in errors {
+                eprintln!("plugin error: {e}");
+            }
+            let Some(out) = host.run_command(&format!("{name}.sync"), args)? else {
+                anyhow::bail!("nothing syncs with '{name}': use caldav or issues, or enable a plugin with a :{name}.sync command");
+            };
+            for n in host.take_notices() {
+                println!("{n}");
+            }
+            if !out.is_empty() {
+                println!("{out}");
+            }
+        }
+        Cmd::Graph { format } => {
+            let tasks = vault.list_tasks(None)?;
+            match format.as_str() {
+                "dot" => print!("{}", deps::to_dot(&tasks)),
+                "mermaid" => print!("{}", deps::to_mermaid(&tasks)),
+                other => anyhow::bail!("unknown graph format: {other} (use: dot|mermaid)"),
+            }
+        }
+        Cmd::Plugin { cmd } => run_plugin_cmd(&cfg, cmd)?,
+        Cmd::Config {
+            cmd: ConfigCmd::Show,
+        } => {
+            for (key, value, origin) in cfg.entries() {
+                println!("{key:<16} {value:<40} # {origin}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_timer(change: TimerChange) {
+    match change.message() {
+        (true, msg) => eprintln!("{msg}"),
+        (false, msg) => println!("{msg}"),
+    }
+}
+
+/// Groups with their total, tasks indented below
+fn print_time_report(rows: &[ReportRow], from: &str, to: &str) {
+    println!("time from {from} to {to}");
+    let mut i = 0;
+    while i < rows.len() {
+        let group = &rows[i].group;
+        let members: Vec<&ReportRow> = rows[i..].iter().take_while(|r| &r.group == group).collect();
+        let total: i64 = members.iter().map(|r| r.seconds).sum();
+        println!("{group:<30} {:>8}", format_duration(total));
+        for r in &members {
+            println!(
+                "  {} {} {:>8}",
+                &r.id[..8.min(r.id.len())],
+                tm_core::fit(&r.title, 19),
+                format_duration(r.seconds)
+            );
+        }
+        i += members.len();
+    }
+    let total: i64 = rows.iter().map(|r| r.seconds).sum();
+    println!("{:<30} {:>8}", "total", format_duration(total));
+}
+
+/// What changed (each id on a dry run), then the counts
+fn print_import(report: &ImportReport, dry_run: bool) {
+    if dry_run {
+        for (mark, ids) in [
+            ("+", &report.created),
+            ("~", &report.updated),
+            ("=", &report.skipped),
+        ] {
+            for id in ids {
+                println!("{mark} {id}");
+            }
+        }
+    }
+    for e in &report.errors {
+        eprintln!("error: {e}");
+    }
+    println!(
+        "{}{}",
+        if dry_run { "dry run: " } else { "" },
+        report.summary()
+    );
+}
+
+/// Fields a Taskwarrior import/export couldn't carry over, on stderr
+fn print_mapping(mapping: &MappingReport) {
+    for line in mapping.lines() {
+        eprintln!("mapping: {line}");
+    }
+}
+
+fn print_change(change: StatusChange) {
+    match change.message() {
+        (true, msg) => eprintln!("{msg}"),
+        (false, msg) => println!("{msg}"),
+    }
+}
+
+fn run_notes_cmd(vault: &Vault, cmd: NotesCmd) -> Result<()> {
+    if vault.cfg.notes.folders.is_empty() {
+        anyhow::bail!("no note folders: set notes = {{ folders = {{ '~/Notes' }} }} in the config");
+    }
+    let found: Vec<Task> = vault
+        .list_tasks(None)?
+        .into_iter()
+        .filter(|t| t.note.is_some())
+        .collect();
+    match cmd {
+        NotesCmd::Ls => {
+            for t in &found {
+                let at = t.note.as_ref().map(notes::location).unwrap_or_default();
+                println!("{} [{}] {}  {at}", t.id, t.status, t.title);
+            }
+        }
+        NotesCmd::Promote { tasks, all } => {
+            let ids: Vec<String> = if all {
+                found
+                    .iter()
+                    .filter(|t| !t.is_closed())
+                    .map(|t| t.id.clone())
+                    .collect()
+            } else {
+                tasks
+                    .iter()
+                    .map(|h| vault.resolve_task(h))
+                    .collect::<Result<_>>()?
+            };
+            for id in ids {
+                let new_id = vault.promote_note_task(&id)?;
+                println!("{id} → {new_id}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_dep_cmd(vault: &Vault, cmd: DepCmd) -> Result<()> {
+    let short = |id: &str| id.chars().take(8).collect::<String>();
+    match cmd {
+        DepCmd::Add { task, on } => {
+            let (id, on) = vault.add_dependency(&task, &on)?;
+            println!("{} waits for {}", short(&id), short(&on));
+        }
+        DepCmd::Rm { task, on } => {
+            let (id, on) = vault.remove_dependency(&task, &on)?;
+            println!("{} no longer waits for {on}", short(&id));
+        }
+        DepCmd::Ls { task } => {
+            let id = vault.resolve_task(&task)?;
+            let tasks = vault.list_tasks(None)?;
+            let Some(t) = tasks.iter().find(|t| t.id == id) else {
+                anyhow::bail!("task {id} not found")
             };
-        vault.set_status(&id, st)?;
+            println!(
+                "{} [{}] {}{}",
+                t.id,
+                t.status,
+                t.title,
+                if t.blocked { "  (blocked)" } else { "" }
+            );
+            for d in &t.depends_on {
+                match tasks.iter().find(|x| &x.id == d) {
+                    Some(x) => println!("  waits for {} [{}] {}", x.id, x.status, x.title),
+                    None => println!("  waits for {d} (missing)"),
+                }
+            }
+            for x in deps::dependents(&tasks, t) {
+                println!("  blocks    {} [{}] {}", x.id, x.status, x.title);
+            }
+        }
+        DepCmd::Check => {
+            let tasks = vault.list_tasks(None)?;
+            let cycles = deps::find_cycles(&tasks);
+            let dangling = deps::dangling(&tasks);
+            for c in &cycles {
+                println!(
+                    "cycle: {}",
+                    c.iter().map(|i| short(i)).collect::<Vec<_>>().join(" → ")
+                );
+            }
+            for (id, d) in &dangling {
+                println!("missing: {} depends on {d}", short(id));
+            }
+            if cycles.is_empty() && dangling.is_empty() {
+                println!("dependencies ok");
+            }
         }
-    Cmd::Start { id } => {
-        vault.set_status(&id, tm_core::Status::Doing)?;
     }
+    Ok(())
 }
+
+fn run_plugin_cmd(cfg: &Config, cmd: PluginCmd) -> Result<()> {
+    let mgr = PluginManager::new(
+        plugin::plugins_dir(&cfg.config_path),
+        plugin::lockfile_path(&cfg.config_path),
+    );
+    match cmd {
+        PluginCmd::Install { source } => println!("{}", mgr.install(&PluginSpec::parse(&source))?),
+        PluginCmd::Update { name } => {
+            for c in mgr.update(name.as_deref(), &cfg.plugins)? {
+                println!("{c}");
+            }
+        }
+        PluginCmd::Remove { name } => println!("{}", mgr.remove(&name)?),
+        PluginCmd::Sync => {
+            for c in mgr.sync(&cfg.plugins)? {
+                println!("{c}");
+            }
+        }
+        PluginCmd::Ls => {
+            for (name, e) in mgr.lockfile()?.plugins {
+                println!(
+                    "{name} {} {} {}",
+                    e.version,
+                    &e.commit[..e.commit.len().min(8)],
+                    e.source
+                );
+            }
+        }
+    }
     Ok(())
 }